@@ -34,6 +34,7 @@ pub async fn create_channel(
     let mut config =
         PlayoutConfig::new(Some("/usr/share/ffplayout/ffplayout.yml.orig".to_string()));
 
+    config.general.channel = target_channel.name.clone();
     config.general.stat_file = format!(".ffp_{channel_name}",);
 
     config.logging.path = Path::new(&config.logging.path)
@@ -42,6 +43,8 @@ pub async fn create_channel(
         .to_string();
 
     config.rpc_server.address = format!("127.0.0.1:70{:7>2}", channel_num);
+    config.metrics.address = format!("127.0.0.1:95{:9>2}", channel_num);
+    config.control.address = format!("127.0.0.1:96{:0>2}", channel_num);
 
     config.playlist.path = Path::new(&config.playlist.path)
         .join(channel_name)
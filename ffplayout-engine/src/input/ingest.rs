@@ -1,40 +1,184 @@
 use std::{
-    io::{BufRead, BufReader, Error, Read},
-    process::{exit, ChildStderr, Command, Stdio},
-    sync::atomic::Ordering,
+    io::{BufRead, BufReader, Error, ErrorKind, Read},
+    process::{ChildStderr, Stdio},
+    sync::{atomic::Ordering, Arc, Mutex},
     thread,
+    time::{Duration, Instant},
 };
 
-use crossbeam_channel::Sender;
+use crossbeam_channel::{Receiver, SendTimeoutError, Sender, TrySendError};
 use simplelog::*;
 
-use crate::utils::{log_line, valid_stream};
-use ffplayout_lib::{
-    utils::{
-        controller::ProcessUnit::*, test_tcp_port, Media, PlayoutConfig, ProcessControl,
-        FFMPEG_IGNORE_ERRORS, FFMPEG_UNRECOVERABLE_ERRORS,
-    },
-    vec_strings,
+use crate::{
+    output::ingest_cmd,
+    utils::{log_line, webhook::ingest_hook, ClientCheck, ClientGate},
 };
+use ffplayout_lib::utils::{
+    controller::ProcessUnit::*, failure_alert, ffmpeg_command, hide_secrets, spawn_tagged,
+    test_tcp_port, Backpressure, IngestMode, IngestPackage, PlayoutConfig, ProcessControl,
+    TsPackets, FFMPEG_IGNORE_ERRORS, FFMPEG_UNRECOVERABLE_ERRORS,
+};
+
+/// Max time to wait on shutdown, until the consumer has read all ingest packages.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connections which send data for longer, reset the failure counter.
+const RECONNECT_RESET: Duration = Duration::from_secs(10);
+
+/// Time for the server to open the input of a client, before the client gets rejected.
+const CLIENT_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Empty reads in a row, before a still running server counts as disconnected.
+const MAX_EMPTY_READS: usize = 5;
+
+/// Pause between empty reads.
+const EMPTY_READ_DELAY: Duration = Duration::from_millis(20);
+
+/// Read from ingest, but tolerate empty reads under load.
+///
+/// Returns `None` on end of stream, which is when the server process is gone
+/// or when too many empty reads comes in a row.
+pub fn read_ingest<R: Read, F: FnMut() -> bool>(
+    reader: &mut R,
+    buffer: &mut [u8],
+    mut is_closed: F,
+) -> Result<Option<usize>, Error> {
+    let mut empty_reads = 0;
+
+    loop {
+        let bytes_len = reader.read(buffer)?;
+
+        if bytes_len > 0 {
+            return Ok(Some(bytes_len));
+        }
+
+        empty_reads += 1;
+
+        if is_closed() || empty_reads >= MAX_EMPTY_READS {
+            return Ok(None);
+        }
+
+        debug!("Empty ingest read <yellow>{empty_reads}</>, try again");
+        thread::sleep(EMPTY_READ_DELAY);
+    }
+}
+
+/// Idle state of the ingest server.
+///
+/// The timer starts on a disconnect, or when the server gets started again over the control API,
+/// and stops with the first bytes. At startup the server waits without a timeout.
+#[derive(Debug, Clone)]
+pub struct IngestIdle {
+    timeout: Duration,
+    since: Option<Instant>,
+}
+
+impl IngestIdle {
+    pub fn new(config: &PlayoutConfig) -> Self {
+        Self {
+            timeout: Duration::from_secs(config.ingest.idle_timeout_seconds),
+            since: None,
+        }
+    }
+
+    /// Bytes came in, so the server is not idle.
+    pub fn connected(&mut self) {
+        self.since = None;
+    }
+
+    /// Client is gone, from here on the server waits for a new one.
+    pub fn disconnected(&mut self, now: Instant) {
+        self.since = Some(now);
+    }
+
+    /// The server waits longer than the timeout, a timeout of 0 never gets idle.
+    pub fn is_idle(&self, now: Instant) -> bool {
+        !self.timeout.is_zero()
+            && self
+                .since
+                .is_some_and(|since| now.saturating_duration_since(since) >= self.timeout)
+    }
+}
+
+/// Stop the ingest server, when it waits too long for a new stream.
+///
+/// The server stays stopped, until the control API clears the idle flag.
+fn idle_watcher(idle: Arc<Mutex<IngestIdle>>, proc_control: ProcessControl) {
+    while !proc_control.is_terminated.load(Ordering::SeqCst) {
+        if !proc_control.ingest_idle.load(Ordering::SeqCst)
+            && idle.lock().unwrap().is_idle(Instant::now())
+        {
+            info!("No ingest stream since the idle timeout, stop server");
+            proc_control.ingest_idle.store(true, Ordering::SeqCst);
+
+            if let Err(e) = proc_control.stop(Ingest) {
+                error!("{e}")
+            }
+        }
+
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+/// Check if the server process has exited, so a empty read is a real end of stream.
+fn server_closed(proc_control: &ProcessControl) -> bool {
+    match proc_control.server_term.lock().unwrap().as_mut() {
+        Some(proc) => !matches!(proc.try_wait(), Ok(None)),
+        None => true,
+    }
+}
+
+/// Wait for the client check, the stream data is held back until the client is accepted.
+fn client_checked(gate: &Mutex<ClientGate>) -> bool {
+    let start = Instant::now();
+
+    loop {
+        let mut gate = gate.lock().unwrap();
+
+        match gate.state {
+            ClientCheck::Accepted => return true,
+            ClientCheck::Rejected => return false,
+            ClientCheck::Pending if start.elapsed() >= CLIENT_CHECK_TIMEOUT => {
+                gate.timeout();
+                return false;
+            }
+            ClientCheck::Pending => (),
+        }
+
+        drop(gate);
+        thread::sleep(EMPTY_READ_DELAY);
+    }
+}
 
 fn server_monitor(
-    level: &str,
+    config: &PlayoutConfig,
     buffer: BufReader<ChildStderr>,
+    gate: Arc<Mutex<ClientGate>>,
     proc_ctl: ProcessControl,
 ) -> Result<(), Error> {
+    let level = config.logging.ingest_level.clone().unwrap();
+
     for line in buffer.lines() {
         let line = line?;
 
         if !FFMPEG_IGNORE_ERRORS.iter().any(|i| line.contains(*i)) {
-            log_line(&line, level);
+            log_line(&line, &level);
+            proc_ctl.push_line(Ingest, &line);
         }
 
-        if line.contains("rtmp") && line.contains("Unexpected stream") && !valid_stream(&line) {
+        let mut gate = gate.lock().unwrap();
+
+        // refuse the feed, by stopping the session, the server starts again for the next client
+        if gate.state == ClientCheck::Pending
+            && gate.check_line(config, &line) == ClientCheck::Rejected
+        {
             if let Err(e) = proc_ctl.stop(Ingest) {
                 error!("{e}");
             };
         }
 
+        drop(gate);
+
         if FFMPEG_UNRECOVERABLE_ERRORS
             .iter()
             .any(|i| line.contains(*i))
@@ -46,101 +190,244 @@ fn server_monitor(
     Ok(())
 }
 
+/// Create the read buffer for the ingest server, with the size from config.
+pub fn ingest_buffer(config: &PlayoutConfig) -> Vec<u8> {
+    vec![0; config.ingest.buffer_size]
+}
+
+/// Why a package could not be queued.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SendError {
+    /// The consumer reads nothing since the backpressure timeout.
+    Timeout,
+    Disconnected,
+}
+
+/// Queue a ingest package, a full channel is handled by the backpressure policy.
+///
+/// `block` waits until the consumer reads, `drop_oldest` never waits and drops the package
+/// on a full channel, the consumer removes then the stale queued packages with [`drop_stale`].
+/// `terminate` gives up after the timeout. It returns the number of dropped packages.
+pub fn send_package(
+    config: &PlayoutConfig,
+    sender: &Sender<IngestPackage>,
+    package: IngestPackage,
+) -> Result<usize, SendError> {
+    match config.ingest.backpressure {
+        Backpressure::Block => sender
+            .send(package)
+            .map(|_| 0)
+            .map_err(|_| SendError::Disconnected),
+        Backpressure::DropOldest => match sender.try_send(package) {
+            Ok(_) => Ok(0),
+            Err(TrySendError::Full(_)) => Ok(1),
+            Err(TrySendError::Disconnected(_)) => Err(SendError::Disconnected),
+        },
+        Backpressure::Terminate => {
+            let timeout = Duration::from_millis(config.ingest.backpressure_timeout_ms);
+
+            sender
+                .send_timeout(package, timeout)
+                .map(|_| 0)
+                .map_err(|e| match e {
+                    SendTimeoutError::Timeout(_) => SendError::Timeout,
+                    SendTimeoutError::Disconnected(_) => SendError::Disconnected,
+                })
+        }
+    }
+}
+
+/// Remove the oldest half of a full ingest channel, so the encoder gets the newer data.
+///
+/// The consumer calls it for the `drop_oldest` policy, the producer holds no receiver,
+/// so the channel disconnects when the consumer is gone. The packages contain whole mpegts
+/// packets, dropping them keeps the stream in sync. A close message at the end is kept.
+/// It returns the number of dropped packages.
+pub fn drop_stale(receiver: &Receiver<IngestPackage>) -> usize {
+    if !receiver.is_full() {
+        return 0;
+    }
+
+    let keep = receiver.capacity().unwrap_or_default() / 2;
+    let mut dropped = 0;
+
+    while receiver.len() > keep.max(1) && receiver.try_recv().is_ok() {
+        dropped += 1;
+    }
+
+    dropped
+}
+
+/// Delay before the next server spawn, it doubles with every failure in a row.
+pub fn reconnect_delay(config: &PlayoutConfig, failures: u32) -> Duration {
+    if failures == 0 {
+        return Duration::ZERO;
+    }
+
+    let factor = 2_u64.saturating_pow(failures - 1);
+    let delay = config.ingest.reconnect_base_ms.saturating_mul(factor);
+
+    Duration::from_millis(delay.min(config.ingest.reconnect_max_ms))
+}
+
 /// ffmpeg Ingest Server
 ///
 /// Start ffmpeg in listen mode, and wait for input.
 pub fn ingest_server(
     config: PlayoutConfig,
-    ingest_sender: Sender<(usize, [u8; 65088])>,
+    ingest_sender: Sender<IngestPackage>,
     proc_control: ProcessControl,
 ) -> Result<(), Error> {
-    let mut buffer: [u8; 65088] = [0; 65088];
-    let mut server_cmd = vec_strings!["-hide_banner", "-nostats", "-v", "level+info"];
+    let mut buffer = ingest_buffer(&config);
     let stream_input = config.ingest.input_cmd.clone().unwrap();
-    let mut dummy_media = Media::new(0, "Live Stream", false);
-    dummy_media.unit = Ingest;
-    dummy_media.add_filter(&config, &None);
 
-    server_cmd.append(&mut stream_input.clone());
+    let mut is_running;
+    let mut failures = 0;
+    let idle = Arc::new(Mutex::new(IngestIdle::new(&config)));
 
-    if let Some(mut filter) = dummy_media.filter {
-        server_cmd.append(&mut filter.cmd());
-        server_cmd.append(&mut filter.map());
-    }
+    if config.ingest.idle_timeout_seconds > 0 {
+        let idle_state = idle.clone();
+        let proc_ctl = proc_control.clone();
 
-    if let Some(mut cmd) = config.processing.cmd {
-        server_cmd.append(&mut cmd);
+        spawn_tagged(move || idle_watcher(idle_state, proc_ctl));
     }
 
-    let mut is_running;
+    let audio_input = config.ingest.audio_input_cmd.clone().unwrap_or_default();
 
-    if let Some(url) = stream_input.iter().find(|s| s.contains("://")) {
-        if !test_tcp_port(url) {
+    // the separate audio feed listens on its own address
+    for url in [&stream_input, &audio_input]
+        .into_iter()
+        .filter_map(|cmd| cmd.iter().find(|s| s.contains("://")))
+    {
+        if matches!(config.ingest.mode, IngestMode::Rtmp | IngestMode::Rtsp) && !test_tcp_port(url)
+        {
+            // only this channel stops, the other ones keep running
             proc_control.stop_all();
-            exit(1);
-        }
 
-        info!("Start ingest server, listening on: <b><magenta>{url}</></b>",);
-    };
+            return Err(Error::new(ErrorKind::AddrInUse, "ingest address in use"));
+        }
 
-    debug!(
-        "Server CMD: <bright-blue>\"ffmpeg {}\"</>",
-        server_cmd.join(" ")
-    );
+        info!(
+            "Start ingest server, listening on: <b><magenta>{}</></b>",
+            hide_secrets(url)
+        );
+    }
 
     while !proc_control.is_terminated.load(Ordering::SeqCst) {
+        // every instance gets the filters from the current live settings
+        let server_cmd = ingest_cmd(&config, &None);
+
+        debug!(
+            "Server CMD: <bright-blue>\"ffmpeg {}\"</>",
+            hide_secrets(&server_cmd.join(" "))
+        );
+
         let proc_ctl = proc_control.clone();
-        let level = config.logging.ingest_level.clone().unwrap();
-        let mut server_proc = match Command::new("ffmpeg")
-            .args(server_cmd.clone())
+        let monitor_config = config.clone();
+        let mut server_proc = match ffmpeg_command(&config)
+            .args(server_cmd)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
         {
             Err(e) => {
-                error!("couldn't spawn ingest server: {e}");
-                panic!("couldn't spawn ingest server: {e}")
+                proc_control.spawn_failed(Ingest, &config.processing.ffmpeg_path, &e);
+
+                return Ok(());
             }
             Ok(proc) => proc,
         };
         let mut ingest_reader = BufReader::new(server_proc.stdout.take().unwrap());
         let server_err = BufReader::new(server_proc.stderr.take().unwrap());
-        let error_reader_thread =
-            thread::spawn(move || server_monitor(&level, server_err, proc_ctl));
+        let gate = Arc::new(Mutex::new(ClientGate::new(&config)));
+        let monitor_gate = gate.clone();
+        let error_reader_thread = spawn_tagged(move || {
+            server_monitor(&monitor_config, server_err, monitor_gate, proc_ctl)
+        });
 
-        *proc_control.server_term.lock().unwrap() = Some(server_proc);
+        proc_control.set_child(Ingest, server_proc);
         is_running = false;
+        let mut dropping = false;
+        let mut packets = TsPackets::new();
+        // the time of the first package, a server which waits long for a client is not alive
+        let mut connected: Option<Instant> = None;
 
-        loop {
-            let bytes_len = match ingest_reader.read(&mut buffer[..]) {
-                Ok(length) => length,
+        'ingest_iter: loop {
+            // stop reading on termination and give the consumer the chance to write the rest
+            if proc_control.is_terminated.load(Ordering::SeqCst) {
+                proc_control.drain_and_terminate(&ingest_sender, DRAIN_TIMEOUT);
+                break 'ingest_iter;
+            }
+
+            let bytes_len = match read_ingest(&mut ingest_reader, &mut buffer[..], || {
+                server_closed(&proc_control)
+            }) {
+                Ok(Some(length)) => length,
+                Ok(None) => break 'ingest_iter,
                 Err(e) => {
                     debug!("Ingest server read {e:?}");
-                    break;
+                    break 'ingest_iter;
                 }
             };
 
+            if !is_running && !client_checked(&gate) {
+                if let Err(e) = proc_control.stop(Ingest) {
+                    error!("{e}");
+                }
+
+                break 'ingest_iter;
+            }
+
             if !is_running {
-                proc_control.server_is_running.store(true, Ordering::SeqCst);
+                connected = Some(Instant::now());
+                idle.lock().unwrap().connected();
+                proc_control.set_ingest_active(true);
                 is_running = true;
+                ingest_hook(&config, true);
             }
 
-            if bytes_len > 0 {
-                if let Err(e) = ingest_sender.send((bytes_len, buffer)) {
-                    error!("Ingest server write error: {e:?}");
+            // only whole mpegts packets get queued, so a dropped package cuts none
+            let data = packets.push(&buffer[..bytes_len]);
+
+            if data.is_empty() {
+                continue;
+            }
+
+            let package = (data.len(), data, Instant::now());
+
+            match send_package(&config, &ingest_sender, package) {
+                Ok(0) => dropping = false,
+                Ok(dropped) => {
+                    if !dropping {
+                        warn!("Encoder reads too slow, drop ingest packages");
+                        dropping = true;
+                    }
+
+                    debug!("Dropped <yellow>{dropped}</> ingest package(s)");
+                }
+                Err(SendError::Timeout) => {
+                    error!(
+                        "Encoder reads nothing since <yellow>{}</> ms, stop ingest",
+                        config.ingest.backpressure_timeout_ms
+                    );
+
+                    if let Err(e) = proc_control.stop(Ingest) {
+                        error!("{e}");
+                    }
+
+                    break 'ingest_iter;
+                }
+                Err(SendError::Disconnected) => {
+                    error!("Ingest server write error: channel is disconnected");
 
                     proc_control.is_terminated.store(true, Ordering::SeqCst);
-                    break;
+                    break 'ingest_iter;
                 }
-            } else {
-                break;
             }
         }
 
         drop(ingest_reader);
-        proc_control
-            .server_is_running
-            .store(false, Ordering::SeqCst);
+        proc_control.set_ingest_active(false);
 
         if let Err(e) = proc_control.wait(Ingest) {
             error!("{e}")
@@ -149,6 +436,42 @@ pub fn ingest_server(
         if let Err(e) = error_reader_thread.join() {
             error!("{e:?}");
         };
+
+        // a clean client disconnect is no failure, only a server without any data
+        if is_running {
+            idle.lock().unwrap().disconnected(Instant::now());
+            ingest_hook(&config, false);
+            proc_control.clear_lines(Ingest);
+
+            if connected.is_some_and(|since| since.elapsed() >= RECONNECT_RESET) {
+                failures = 0;
+            }
+        } else if gate.lock().unwrap().state == ClientCheck::Rejected {
+            // a refused client is no failure of the server, so no alert and no backoff
+            proc_control.clear_lines(Ingest);
+        } else if proc_control.ingest_idle.load(Ordering::SeqCst) {
+            info!("Ingest server is idle, wait for start over the control API");
+
+            while proc_control.ingest_idle.load(Ordering::SeqCst)
+                && !proc_control.is_terminated.load(Ordering::SeqCst)
+            {
+                thread::sleep(Duration::from_secs(1));
+            }
+
+            idle.lock().unwrap().disconnected(Instant::now());
+            failures = 0;
+        } else if !proc_control.is_terminated.load(Ordering::SeqCst) {
+            failures += 1;
+            let delay = reconnect_delay(&config, failures);
+            failure_alert(&config, &proc_control, Ingest);
+
+            warn!(
+                "Ingest server stopped without data, restart in <yellow>{}ms</>",
+                delay.as_millis()
+            );
+
+            thread::sleep(delay);
+        }
     }
 
     Ok(())
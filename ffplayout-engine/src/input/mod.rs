@@ -1,26 +1,29 @@
 use std::{
+    path::Path,
     sync::{
         atomic::{AtomicBool, AtomicUsize},
         Arc, Mutex,
     },
-    thread,
 };
 
 use simplelog::*;
 
-use ffplayout_lib::utils::{Media, PlayoutConfig, PlayoutStatus, ProcessMode::*};
+use ffplayout_lib::utils::{spawn_tagged, Media, PlayoutConfig, PlayoutStatus, ProcessMode::*};
 
 pub mod folder;
 pub mod ingest;
 pub mod playlist;
 
 pub use folder::watchman;
-pub use ingest::ingest_server;
+pub use ingest::{
+    drop_stale, ingest_buffer, ingest_server, read_ingest, reconnect_delay, send_package,
+    IngestIdle, SendError,
+};
 pub use playlist::CurrentProgram;
 
 use ffplayout_lib::utils::folder::FolderSource;
 
-/// Create a source iterator from playlist, or from folder.
+/// Create a source iterator from playlist, or from folder and loop source.
 pub fn source_generator(
     config: PlayoutConfig,
     current_list: Arc<Mutex<Vec<Media>>>,
@@ -29,19 +32,22 @@ pub fn source_generator(
     is_terminated: Arc<AtomicBool>,
 ) -> Box<dyn Iterator<Item = Media>> {
     match config.processing.mode {
-        Folder => {
-            info!("Playout in folder mode");
-            debug!(
-                "Monitor folder: <b><magenta>{}</></b>",
-                &config.storage.path
-            );
+        Folder | Loop => {
+            info!("Playout in {} mode", config.processing.mode);
 
             let config_clone = config.clone();
             let folder_source = FolderSource::new(&config, playout_stat.chain, current_list, index);
             let node_clone = folder_source.nodes.clone();
 
-            // Spawn a thread to monitor folder for file changes.
-            thread::spawn(move || watchman(config_clone, is_terminated.clone(), node_clone));
+            // Spawn a thread to monitor folder for file changes, a single file in loop mode needs no watcher.
+            if Path::new(&config.storage.path).is_dir() {
+                debug!(
+                    "Monitor folder: <b><magenta>{}</></b>",
+                    &config.storage.path
+                );
+
+                spawn_tagged(move || watchman(config_clone, is_terminated.clone(), node_clone));
+            }
 
             Box::new(folder_source) as Box<dyn Iterator<Item = Media>>
         }
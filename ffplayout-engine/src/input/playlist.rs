@@ -10,10 +10,13 @@ use std::{
 use serde_json::json;
 use simplelog::*;
 
+use crate::utils::resume::read_resume;
+use ffplayout_lib::filter::measure_ahead;
 use ffplayout_lib::utils::{
-    check_sync, gen_dummy, get_delta, get_sec, is_close, is_remote, json_serializer::read_json,
-    loop_filler, loop_image, modified_time, seek_and_length, valid_source, Media, MediaProbe,
-    PlayoutConfig, PlayoutStatus, DUMMY_LEN, IMAGE_FORMAT,
+    check_sync, gen_dummy, get_delta, get_sec, is_close, is_remote,
+    json_serializer::{is_stdin_playlist, read_json},
+    loop_filler, loop_image, modified_time, resolve_source, seek_and_length, valid_source,
+    write_status, Media, MediaProbe, PlayoutConfig, PlayoutStatus, DUMMY_LEN, IMAGE_FORMAT,
 };
 
 /// Struct for current playlist.
@@ -28,9 +31,11 @@ pub struct CurrentProgram {
     json_date: String,
     pub nodes: Arc<Mutex<Vec<Media>>>,
     current_node: Media,
+    filler: Option<Media>,
     index: Arc<AtomicUsize>,
     is_terminated: Arc<AtomicBool>,
     playout_stat: PlayoutStatus,
+    resume: bool,
 }
 
 impl CurrentProgram {
@@ -70,9 +75,11 @@ impl CurrentProgram {
             json_date: json.date,
             nodes: current_list,
             current_node: Media::new(0, "", false),
+            filler: None,
             index: global_index,
             is_terminated,
             playout_stat,
+            resume: config.general.resume_on_restart,
         }
     }
 
@@ -88,12 +95,18 @@ impl CurrentProgram {
             self.json_path = json.current_file;
             self.json_mod = json.modified;
             *self.nodes.lock().unwrap() = json.program;
+        } else if is_stdin_playlist(self.json_path.as_deref().unwrap()) {
+            if self.playout_stat.reload.swap(false, Ordering::SeqCst) {
+                warn!("Playlist from stdin can't be read again, ignore reload");
+            }
         } else if Path::new(&self.json_path.clone().unwrap()).is_file()
             || is_remote(&self.json_path.clone().unwrap())
         {
             let mod_time = modified_time(&self.json_path.clone().unwrap());
+            let reload = self.playout_stat.reload.swap(false, Ordering::SeqCst);
 
-            if self.json_mod != mod_time {
+            // a unreachable remote playlist has no modification time, keep the current one
+            if (mod_time.is_some() && self.json_mod != mod_time) || reload {
                 // when playlist has changed, reload it
                 info!(
                     "Reload playlist <b><magenta>{}</></b>",
@@ -208,6 +221,15 @@ impl CurrentProgram {
         }
     }
 
+    // Measure the loudness of the next clip, while the current one plays.
+    fn measure_next(&self) {
+        let index = self.index.load(Ordering::SeqCst);
+
+        if let Some(node) = self.nodes.lock().unwrap().get(index) {
+            measure_ahead(&self.config, node);
+        }
+    }
+
     // Get current time and when we are before start time,
     // we add full seconds of a day to it.
     fn get_current_time(&mut self) -> f64 {
@@ -233,18 +255,81 @@ impl CurrentProgram {
             time_sec += *shift;
         }
 
-        for (i, item) in self.nodes.lock().unwrap().iter_mut().enumerate() {
-            if item.begin.unwrap() + item.out - item.seek > time_sec {
-                self.playout_stat.list_init.store(false, Ordering::SeqCst);
-                self.index.store(i, Ordering::SeqCst);
+        if let Some((i, _)) = realtime_position(&self.nodes.lock().unwrap(), time_sec) {
+            self.playout_stat.list_init.store(false, Ordering::SeqCst);
+            self.index.store(i, Ordering::SeqCst);
+        }
+    }
+
+    // On startup continue the clip from before the restart, at the saved position.
+    fn resume_clip(&mut self) -> bool {
+        self.resume = false;
+
+        let state = match read_resume(&self.config) {
+            Some(s) => s,
+            None => return false,
+        };
+
+        let node = self
+            .nodes
+            .lock()
+            .unwrap()
+            .get(state.index)
+            .filter(|n| n.source == state.source)
+            .cloned();
+
+        let mut node_clone = match node {
+            Some(n) if state.elapsed < n.out - n.seek => n,
+            _ => {
+                info!("Saved clip is not in playlist, start at scheduled position");
+                return false;
+            }
+        };
+
+        let (delta, _) = get_delta(&self.config, &(node_clone.begin.unwrap() + state.elapsed));
+        let current_date = self.playout_stat.current_date.lock().unwrap().clone();
+        *self.playout_stat.time_shift.lock().unwrap() = delta;
+        *self.playout_stat.date.lock().unwrap() = current_date.clone();
+        write_status(&self.config, &current_date, delta);
+
+        info!(
+            "Resume <b><magenta>{}</></b> at <yellow>{:.3}</> seconds",
+            node_clone.source, state.elapsed
+        );
+
+        node_clone = self.seek_clip(node_clone, state.elapsed);
+        self.index.store(state.index + 1, Ordering::SeqCst);
+        self.playout_stat.list_init.store(false, Ordering::SeqCst);
+        self.current_node = handle_list_init(&self.config, node_clone, &self.playout_stat.chain);
+
+        true
+    }
+
+    // Start a clip at the elapsed time, when it is to short and the time is
+    // already in its gap, the filler starts instead.
+    fn seek_clip(&mut self, mut node: Media, elapsed: f64) -> Media {
+        let filler = fill_gap(&self.config, &mut node);
+        let length = node.out - node.seek;
 
-                break;
+        match filler {
+            Some(mut filler) if elapsed >= length => {
+                filler.seek += elapsed - length;
+                filler
+            }
+            filler => {
+                self.filler = filler;
+                node.seek += elapsed;
+                node
             }
         }
     }
 
     // Prepare init clip.
     fn init_clip(&mut self) {
+        if self.resume && self.resume_clip() {
+            return;
+        }
+
         self.get_current_clip();
 
         if !self.playout_stat.list_init.load(Ordering::SeqCst) {
@@ -253,8 +338,9 @@ impl CurrentProgram {
 
             // de-instance node to preserve original values in list
             let mut node_clone = self.nodes.lock().unwrap()[index].clone();
+            let elapsed = (time_sec - node_clone.begin.unwrap()).max(0.0);
 
-            node_clone.seek = time_sec - node_clone.begin.unwrap();
+            node_clone = self.seek_clip(node_clone, elapsed);
             self.current_node =
                 handle_list_init(&self.config, node_clone, &self.playout_stat.chain);
         }
@@ -269,6 +355,8 @@ impl Iterator for CurrentProgram {
         self.check_update(self.playout_stat.list_init.load(Ordering::SeqCst));
 
         if self.playout_stat.list_init.load(Ordering::SeqCst) {
+            self.filler = None;
+
             if self.json_path.is_some() {
                 self.init_clip();
             }
@@ -317,30 +405,34 @@ impl Iterator for CurrentProgram {
             }
 
             self.last_next_ad();
+            self.measure_next();
+
+            return Some(self.current_node.clone());
+        }
+
+        // the filler of a to short clip is not in the playlist, it plays before the next clip
+        if let Some(filler) = self.filler.take() {
+            let is_last = self.index.load(Ordering::SeqCst) >= self.nodes.lock().unwrap().len();
+            self.current_node = timed_source(filler, &self.config, is_last, &self.playout_stat);
 
             return Some(self.current_node.clone());
         }
 
         if self.index.load(Ordering::SeqCst) < self.nodes.lock().unwrap().len() {
             self.check_for_next_playlist();
-            let mut is_last = false;
             let index = self.index.load(Ordering::SeqCst);
-            let nodes = self.nodes.lock().unwrap();
+            let nodes_len = self.nodes.lock().unwrap().len();
+            let mut node = self.nodes.lock().unwrap()[index].clone();
 
-            if index == nodes.len() - 1 {
-                is_last = true
-            }
+            // probe outside of the lock, the control server reads the list meanwhile
+            self.filler = fill_gap(&self.config, &mut node);
+            let is_last = index == nodes_len - 1 && self.filler.is_none();
 
-            self.current_node = timed_source(
-                nodes[index].clone(),
-                &self.config,
-                is_last,
-                &self.playout_stat,
-            );
+            self.current_node = timed_source(node, &self.config, is_last, &self.playout_stat);
 
-            drop(nodes);
             self.last_next_ad();
             self.index.fetch_add(1, Ordering::SeqCst);
+            self.measure_next();
 
             Some(self.current_node.clone())
         } else {
@@ -394,12 +486,77 @@ impl Iterator for CurrentProgram {
             self.current_node.last_ad = last_ad;
 
             self.index.store(1, Ordering::SeqCst);
+            self.measure_next();
 
             Some(self.current_node.clone())
         }
     }
 }
 
+/// Cut a clip, which is shorter then scheduled, to its real length
+/// and create a filler clip for the remaining time until the next clip starts.
+pub fn fill_gap(config: &PlayoutConfig, node: &mut Media) -> Option<Media> {
+    let is_image = node
+        .source
+        .rsplit_once('.')
+        .map(|(_, e)| e.to_lowercase())
+        .filter(|c| IMAGE_FORMAT.contains(&c.as_str()))
+        .is_some();
+
+    if is_image
+        || !Path::new(&node.source).is_file()
+        || !Path::new(&config.storage.filler_clip).is_file()
+    {
+        return None;
+    }
+
+    node.add_probe();
+
+    let gap = node.out - node.duration;
+
+    if gap <= 1.0 {
+        return None;
+    }
+
+    warn!(
+        "Clip <b><magenta>{}</></b> is <yellow>{gap:.2}</> seconds to short, fill the gap with filler",
+        node.source
+    );
+
+    node.out = node.duration;
+
+    let mut filler = Media::new(
+        node.index.unwrap_or_default(),
+        &config.storage.filler_clip,
+        false,
+    );
+    filler.begin = node.begin.map(|b| b + node.out - node.seek);
+    filler.duration = gap;
+    filler.out = gap;
+    filler.process = Some(true);
+
+    Some(filler)
+}
+
+/// Find the clip and the offset in it, which belongs to the given time.
+///
+/// When the playlist is to short for this time, `None` is returned and the gap gets filled.
+/// A time before the first clip, like from a negative time shift, starts the first clip.
+pub fn realtime_position(nodes: &[Media], time_sec: f64) -> Option<(usize, f64)> {
+    if nodes
+        .first()
+        .is_some_and(|n| time_sec < n.begin.unwrap_or_default())
+    {
+        return Some((0, 0.0));
+    }
+
+    nodes
+        .iter()
+        .enumerate()
+        .find(|(_, n)| n.begin.unwrap_or_default() + n.out - n.seek > time_sec)
+        .map(|(i, n)| (i, (time_sec - n.begin.unwrap_or_default()).max(0.0)))
+}
+
 /// Prepare input clip:
 ///
 /// - check begin and length from clip
@@ -461,8 +618,18 @@ pub fn gen_source(
 ) -> Media {
     let duration = node.out - node.seek;
 
-    if valid_source(&node.source) {
-        node.add_probe();
+    match resolve_source(config, &node.source) {
+        Ok(source) => node.source = source,
+        Err(e) => error!("{e}"),
+    }
+
+    // ffprobe can't send the request headers, so authenticated sources get trusted
+    let authenticated = is_remote(&node.source) && !node.source_headers.trim().is_empty();
+
+    if authenticated || valid_source(&node.source) {
+        if !authenticated {
+            node.add_probe();
+        }
 
         if node
             .source
@@ -473,10 +640,14 @@ pub fn gen_source(
         {
             node.cmd = Some(loop_image(&node));
         } else {
-            node.cmd = Some(seek_and_length(&node));
+            node.cmd = Some(seek_and_length(config, &node));
         }
     } else {
-        error!("Source not found: <b><magenta>\"{}\"</></b>", node.source);
+        error!(
+            "Source not found: <b><magenta>\"{}\"</></b>, at position <yellow>{}</>",
+            node.source,
+            node.index.unwrap_or_default()
+        );
         warn!("Generate filler with <yellow>{duration:.2}</> seconds length!");
 
         let probe = MediaProbe::new(&config.storage.filler_clip);
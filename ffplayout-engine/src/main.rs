@@ -1,74 +1,135 @@
 use std::{
-    fs::{self, File},
-    path::{Path, PathBuf},
+    fs,
+    path::Path,
     process::exit,
-    sync::{Arc, Mutex},
-    thread,
+    sync::{atomic::AtomicBool, Arc, Mutex},
 };
 
 #[cfg(debug_assertions)]
 use chrono::prelude::*;
-use serde::{Deserialize, Serialize};
-use serde_json::json;
 use simplelog::*;
 
-use ffplayout::{
-    output::{player, write_hls},
-    rpc::json_rpc_server,
-    utils::{arg_parse::get_args, get_config},
+use ffplayout::output::print_commands;
+use ffplayout::utils::{
+    arg_parse::get_args,
+    channel::{run_playout, set_shutdown_handler, spawn_channel, Channel},
+    get_config, Args, Command,
 };
 
 use ffplayout_lib::utils::{
-    generate_playlist, import::import_file, init_logging, send_mail, validate_ffmpeg,
-    OutputMode::*, PlayerControl, PlayoutStatus, ProcessControl,
+    analyze_playlist, eight_bit_encoder, generate_playlist, gop_size, hls_time,
+    import::import_file, init_logging, keyframe_interval, playlist_errors, read_json, send_mail,
+    set_log_channel, validate_ffmpeg, FpsConvert, PlayerControl, PlayoutConfig, PlayoutStatus,
+    ProcessControl, ProcessMode::Playlist,
 };
 
-#[cfg(debug_assertions)]
-use ffplayout::utils::Args;
-
 #[cfg(debug_assertions)]
 use ffplayout_lib::utils::{mock_time, time_now};
 
-const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Validate ffmpeg and config parameters, exit on errors.
+fn check_config(config: &mut PlayoutConfig) {
+    if let Err(errors) = config.validate() {
+        for e in errors {
+            error!("Config error {e}");
+        }
+
+        exit(1);
+    }
+
+    if let Err(e) = validate_ffmpeg(config) {
+        error!("{e}");
+        exit(1);
+    };
+
+    if ![2, 4, 6, 8].contains(&config.processing.audio_channels) {
+        error!(
+            "Encoding {} channel(s) is not allowed. Only 2, 4, 6 and 8 channels are supported!",
+            config.processing.audio_channels
+        );
+        exit(1);
+    }
+
+    if let Some(cmd) = config.out.output_cmd.as_ref() {
+        let extra = &config.out.extra_args;
 
-#[derive(Serialize, Deserialize)]
-struct StatusData {
-    time_shift: f64,
-    date: String,
+        if !extra.is_empty() {
+            let pos = cmd.len().saturating_sub(extra.len() + 1);
+
+            info!(
+                "Insert extra output args <yellow>{}</> at position <yellow>{pos}</>, before: <b><magenta>{}</></b>",
+                extra.join(" "),
+                cmd[pos + extra.len()..].join(" ")
+            );
+        }
+    }
+
+    if config.processing.fps_convert == FpsConvert::Interpolate {
+        warn!("Frame rate conversion with motion interpolation is cpu heavy, check that encoding stays realtime");
+    }
+
+    if let Some(encoder) = eight_bit_encoder(config) {
+        warn!(
+            "Encoder <b><magenta>{encoder}</></b> supports only 8 bit, pix_fmt <yellow>{}</> gets reduced or fails",
+            config.processing.pix_fmt
+        );
+    }
+
+    if let Some(cmd) = config.out.output_cmd.as_ref() {
+        let frames = hls_time(cmd).map(|s| gop_size(config.processing.fps, s));
+        let gop = keyframe_interval(cmd).unwrap_or_default();
+
+        if let Some(frames) = frames.filter(|f| gop > 0 && f % gop != 0) {
+            warn!("Segment length of <yellow>{frames}</> frames is not a multiple of gop size <yellow>{gop}</>, segments will be uneven");
+        }
+    }
 }
 
-/// Here we create a status file in temp folder.
-/// We need this for reading/saving program status.
-/// For example when we skip a playing file,
-/// we save the time difference, so we stay in sync.
+/// Playlist of the `--date` argument, in a playlist folder.
+fn date_playlist(config: &PlayoutConfig, date: &Option<String>) -> Option<String> {
+    let date = date.as_ref()?;
+    let d: Vec<&str> = date.split('-').collect();
+
+    if d.len() == 3 && Path::new(&config.playlist.path).is_dir() {
+        let playlist_path = Path::new(&config.playlist.path)
+            .join(d[0])
+            .join(d[1])
+            .join(date)
+            .with_extension("json");
+
+        return Some(playlist_path.display().to_string());
+    }
+
+    None
+}
+
+/// Validate the playlist, the config is already checked. It returns the exit code.
 ///
-/// When file not exists we create it, and when it exists we get its values.
-fn status_file(stat_file: &str, playout_stat: &PlayoutStatus) {
-    debug!("Start ffplayout v{VERSION}, status file path: <b><magenta>{stat_file}</></b>");
-
-    if !PathBuf::from(stat_file).exists() {
-        let data = json!({
-            "time_shift": 0.0,
-            "date": String::new(),
-        });
-
-        let json: String = serde_json::to_string(&data).expect("Serialize status data failed");
-        if let Err(e) = fs::write(stat_file, json) {
-            error!("Unable to write to status file <b><magenta>{stat_file}</></b>: {e}");
-        };
-    } else {
-        let stat_file = File::options()
-            .read(true)
-            .write(false)
-            .open(stat_file)
-            .expect("Could not open status file");
+/// Unlike the dry run, nothing gets decoded, but the local sources must exist.
+fn validate(config: &PlayoutConfig, args: &Args) -> i32 {
+    if config.processing.mode == Playlist {
+        let path = date_playlist(config, &args.date);
+        // terminated flag prevents the validation thread from read_json
+        let playlist = read_json(config, path, Arc::new(AtomicBool::new(true)), false, 0.0);
+
+        if playlist.current_file.is_none() {
+            error!("No playlist found for validation!");
+            return 1;
+        }
 
-        let data: StatusData =
-            serde_json::from_reader(stat_file).expect("Could not read status file.");
+        let errors = playlist_errors(&playlist, true);
 
-        *playout_stat.time_shift.lock().unwrap() = data.time_shift;
-        *playout_stat.date.lock().unwrap() = data.date;
+        for e in &errors {
+            error!("Playlist error {e}");
+        }
+
+        if !errors.is_empty() {
+            return 1;
+        }
     }
+
+    info!("Config and playlist are valid");
+
+    0
 }
 
 #[cfg(debug_assertions)]
@@ -89,13 +150,8 @@ fn main() {
     fake_time(&args);
 
     let mut config = get_config(args.clone());
-    let play_control = PlayerControl::new();
-    let playout_stat = PlayoutStatus::new();
     let proc_control = ProcessControl::new();
-    let play_ctl = play_control.clone();
-    let play_stat = playout_stat.clone();
     let proc_ctl1 = proc_control.clone();
-    let proc_ctl2 = proc_control.clone();
     let messages = Arc::new(Mutex::new(Vec::new()));
 
     // try to create logging folder, if not exist
@@ -110,19 +166,10 @@ fn main() {
     let logging = init_logging(&config, Some(proc_ctl1), Some(messages.clone()));
     CombinedLogger::init(logging).unwrap();
 
-    if let Err(e) = validate_ffmpeg(&mut config) {
-        error!("{e}");
-        exit(1);
-    };
+    check_config(&mut config);
 
-    let config_clone = config.clone();
-
-    if ![2, 4, 6, 8].contains(&config.processing.audio_channels) {
-        error!(
-            "Encoding {} channel(s) is not allowed. Only 2, 4, 6 and 8 channels are supported!",
-            config.processing.audio_channels
-        );
-        exit(1);
+    if args.command == Some(Command::Validate) {
+        exit(validate(&config, &args));
     }
 
     if config.general.generate.is_some() {
@@ -134,14 +181,14 @@ fn main() {
         exit(0);
     }
 
-    if let Some(path) = args.import {
+    if let Some(path) = args.import.clone() {
         if args.date.is_none() {
             error!("Import needs date parameter!");
             exit(1);
         }
 
         // convert text/m3u file to playlist
-        match import_file(&config, &args.date.unwrap(), None, &path) {
+        match import_file(&config, args.date.as_ref().unwrap(), None, &path) {
             Ok(m) => {
                 info!("{m}");
                 exit(0);
@@ -153,18 +200,70 @@ fn main() {
         }
     }
 
-    if config.rpc_server.enable {
-        // If RPC server is enable we also fire up a JSON RPC server.
-        thread::spawn(move || json_rpc_server(config_clone, play_ctl, play_stat, proc_ctl2));
+    if args.print_commands {
+        for line in print_commands(&config, None) {
+            println!("{line}");
+        }
+
+        exit(0);
+    }
+
+    if args.dry_run {
+        let path = date_playlist(&config, &args.date);
+
+        // terminated flag prevents the validation thread from read_json
+        let playlist = read_json(&config, path, Arc::new(AtomicBool::new(true)), false, 0.0);
+
+        if playlist.current_file.is_none() {
+            error!("No playlist found for dry run!");
+            exit(1);
+        }
+
+        // broken items are already logged by read_json
+        match analyze_playlist(&playlist, config.clone()) + playlist_errors(&playlist, false).len()
+        {
+            0 => exit(0),
+            _ => exit(1),
+        }
     }
 
-    status_file(&config.general.stat_file, &playout_stat);
+    if let Some(names) = args.channels.clone() {
+        // run multiple channels in one process, each with its own pipeline
+        let channels: Vec<Channel> = names
+            .into_iter()
+            .map(|name| {
+                let mut channel_args = args.clone();
+                channel_args.channel = Some(name);
+                let mut channel_config = get_config(channel_args);
+                check_config(&mut channel_config);
 
-    match config.out.mode {
-        // write files/playlist to HLS m3u8 playlist
-        HLS => write_hls(&config, play_control, playout_stat, proc_control),
-        // play on desktop or stream to a remote target
-        _ => player(&config, play_control, playout_stat, proc_control),
+                spawn_channel(channel_config, run_playout)
+            })
+            .collect();
+
+        let controls: Vec<ProcessControl> =
+            channels.iter().map(|c| c.proc_control.clone()).collect();
+
+        // On SIGINT/SIGTERM a running ingest get drained first, a second signal stops everything.
+        set_shutdown_handler(controls);
+
+        for channel in channels {
+            channel.join();
+        }
+
+        proc_control.stop_all();
+    } else {
+        // On SIGINT/SIGTERM a running ingest get drained first, a second signal stops everything.
+        set_shutdown_handler(vec![proc_control.clone()]);
+
+        set_log_channel(&config.general.channel);
+
+        run_playout(
+            config.clone(),
+            PlayerControl::new(),
+            PlayoutStatus::new(),
+            proc_control,
+        );
     }
 
     info!("Playout done...");
@@ -176,4 +275,5 @@ fn main() {
     }
 
     drop(msg);
+    log::logger().flush();
 }
@@ -0,0 +1,309 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize},
+    Arc, Mutex,
+};
+
+use super::{
+    desktop,
+    hls::{discontinuity_cmd, hls_flag_cmd},
+    watchdog::progress_cmd,
+};
+use crate::utils::prepare_output_cmd;
+use ffplayout_lib::{
+    filter::Filters,
+    utils::{
+        folder::FolderSource, hide_secrets, hw_input_cmd, input_flags_cmd,
+        json_serializer::read_json, loop_image, quote_cmd, reconnect_cmd, seek_and_length,
+        threads_cmd, timestamp_cmd, Media, OutputMode::*, PlayoutConfig, ProcessMode::*,
+        ProcessUnit::*, IMAGE_FORMAT,
+    },
+    vec_strings,
+};
+
+/// Log level for the ffmpeg instances.
+pub fn log_format(config: &PlayoutConfig) -> String {
+    format!("level+{}", config.logging.ffmpeg_level.to_lowercase())
+}
+
+/// Metadata for the output, with the program title and the next clip.
+///
+/// Without a clip, like for the long running encoder, only the channel is set.
+/// Container metadata can't change while ffmpeg runs, so the title per clip
+/// exists only in hls mode, where every clip has its own writer.
+pub fn metadata_cmd(
+    config: &PlayoutConfig,
+    node: Option<&Media>,
+    next: Option<&Media>,
+) -> Vec<String> {
+    let metadata = &config.out.metadata;
+
+    if !metadata.enable {
+        return vec![];
+    }
+
+    let channel = &config.general.channel;
+    let service = match config.out.service_name.is_empty() {
+        true => channel,
+        false => &config.out.service_name,
+    };
+    let mut cmd = vec_strings!["-metadata", format!("service_name={service}")];
+
+    if let Some(node) = node {
+        let next_title = next.map(|n| n.display_title()).unwrap_or_default();
+        let title = metadata
+            .title
+            .replace("{title}", &node.display_title())
+            .replace("{next}", &next_title)
+            .replace("{channel}", channel);
+
+        cmd.append(&mut vec_strings!["-metadata", format!("title={title}")]);
+
+        if !next_title.is_empty() {
+            cmd.append(&mut vec_strings![
+                "-metadata",
+                format!("comment=Next: {next_title}")
+            ]);
+        }
+    }
+
+    cmd
+}
+
+/// Add output parameters before the target of every output.
+///
+/// A target is found like in `prepare_output_cmd`, it follows a value and is not the
+/// value of a flag. The last parameter is always a target.
+fn insert_output_args(cmd: Vec<String>, args: Vec<String>) -> Vec<String> {
+    if args.is_empty() {
+        return cmd;
+    }
+
+    let last = cmd.len().saturating_sub(1);
+    let mut new_cmd = vec![];
+
+    for (i, param) in cmd.iter().enumerate() {
+        if i == last || (i > 0 && !param.starts_with('-') && !cmd[i - 1].starts_with('-')) {
+            new_cmd.extend(args.iter().cloned());
+        }
+
+        new_cmd.push(param.clone());
+    }
+
+    new_cmd
+}
+
+/// Decoder for one clip, its output goes to the encoder.
+pub fn decoder_cmd(
+    config: &PlayoutConfig,
+    log_format: &str,
+    input: Vec<String>,
+    filter: Option<Filters>,
+) -> Vec<String> {
+    let mut dec_cmd = vec_strings!["-hide_banner", "-nostats", "-v", log_format];
+    dec_cmd.append(&mut threads_cmd(config));
+    dec_cmd.append(&mut hw_input_cmd(config));
+    dec_cmd.append(&mut timestamp_cmd(config));
+    dec_cmd.append(&mut input_flags_cmd(config, input));
+
+    if let Some(mut filter) = filter {
+        dec_cmd.append(&mut filter.cmd());
+        dec_cmd.append(&mut filter.map());
+    }
+
+    if let Some(mut cmd) = config.processing.cmd.clone() {
+        dec_cmd.append(&mut cmd);
+    }
+
+    dec_cmd
+}
+
+/// Encoder, which reads from the decoder and ingest. The desktop mode uses ffplay.
+pub fn encoder_cmd<'a>(config: &'a PlayoutConfig, log_format: &str) -> (&'a str, Vec<String>) {
+    if config.out.mode == Desktop {
+        return ("ffplay", desktop::cmd(config, log_format));
+    }
+
+    let mut media = Media::new(0, "", false);
+    media.unit = Encoder;
+    media.add_filter(config, &None);
+
+    let mut enc_prefix = vec_strings!["-hide_banner", "-stats_period", "1", "-v", log_format];
+    enc_prefix.append(&mut progress_cmd(config));
+    enc_prefix.append(&mut vec_strings!["-re", "-i", "pipe:0"]);
+    enc_prefix.append(&mut threads_cmd(config));
+
+    let enc_cmd = prepare_output_cmd(config, enc_prefix, &media.filter);
+
+    (
+        &config.processing.ffmpeg_path,
+        insert_output_args(enc_cmd, metadata_cmd(config, None, None)),
+    )
+}
+
+/// Put the reconnect options for a remote feed before the `-i` of a server input.
+fn reconnect_input(config: &PlayoutConfig, mut cmd: Vec<String>) -> Vec<String> {
+    if let Some(pos) = cmd.iter().position(|a| a == "-i") {
+        let reconnect = cmd
+            .get(pos + 1)
+            .map(|url| reconnect_cmd(config, url))
+            .unwrap_or_default();
+
+        cmd.splice(pos..pos, reconnect);
+    }
+
+    cmd
+}
+
+/// Ingest server, in hls mode it writes direct to the playlist.
+pub fn ingest_cmd(
+    config: &PlayoutConfig,
+    filter_chain: &Option<Arc<Mutex<Vec<String>>>>,
+) -> Vec<String> {
+    let mut server_cmd = vec_strings!["-hide_banner", "-nostats", "-v", "level+info"];
+    let mut dummy_media = Media::new(0, "Live Stream", false);
+    dummy_media.unit = Ingest;
+    dummy_media.add_filter(config, filter_chain);
+
+    server_cmd.append(&mut threads_cmd(config));
+    server_cmd.append(&mut hw_input_cmd(config));
+    let mut input_cmd = config.ingest.input_cmd.clone().unwrap_or_default();
+
+    if let Some(audio_cmd) = config.ingest.audio_input_cmd.clone() {
+        // both feeds get padded on their own, so one which disconnects doesn't end the stream
+        input_cmd = reconnect_input(config, input_cmd);
+        input_cmd.append(&mut reconnect_input(config, audio_cmd));
+    }
+
+    server_cmd.append(&mut input_cmd);
+
+    if config.out.mode == HLS {
+        // without takeover the stream is only received, the playlist keeps the hls output
+        if !config.ingest.takeover {
+            server_cmd.append(&mut vec_strings!["-c", "copy", "-f", "null", "-"]);
+
+            return server_cmd;
+        }
+
+        return prepare_output_cmd(config, server_cmd, &dummy_media.filter);
+    }
+
+    if let Some(mut filter) = dummy_media.filter {
+        server_cmd.append(&mut filter.cmd());
+        server_cmd.append(&mut filter.map());
+    }
+
+    if let Some(mut cmd) = config.processing.cmd.clone() {
+        server_cmd.append(&mut cmd);
+    }
+
+    server_cmd
+}
+
+/// HLS writer for one clip, it decodes and encodes in one instance.
+pub fn hls_writer_cmd(
+    config: &PlayoutConfig,
+    node: &Media,
+    next: Option<&Media>,
+    input: Vec<String>,
+    discontinuity: bool,
+) -> Vec<String> {
+    let mut enc_prefix = vec_strings!["-hide_banner", "-nostats", "-v", log_format(config)];
+    enc_prefix.append(&mut threads_cmd(config));
+    enc_prefix.append(&mut hw_input_cmd(config));
+    enc_prefix.append(&mut timestamp_cmd(config));
+    enc_prefix.append(&mut input_flags_cmd(config, input));
+    let mut enc_cmd = prepare_output_cmd(config, enc_prefix, &node.filter);
+    enc_cmd = insert_output_args(enc_cmd, metadata_cmd(config, Some(node), next));
+
+    if config.out.metadata.enable && config.out.metadata.program_date_time {
+        enc_cmd = hls_flag_cmd(enc_cmd, "program_date_time");
+    }
+
+    match discontinuity {
+        true => discontinuity_cmd(enc_cmd),
+        false => enc_cmd,
+    }
+}
+
+/// First clip of the playlist or folder, like the playout would start it.
+///
+/// The source is not resolved and not probed, printing the commands should not wait on
+/// remote hosts or read the media files. So the filters don't know the streams of the clip.
+fn first_clip(config: &PlayoutConfig) -> Option<Media> {
+    let mut node = match config.processing.mode {
+        Playlist => {
+            // terminated flag prevents the validation thread from read_json
+            let playlist = read_json(config, None, Arc::new(AtomicBool::new(true)), false, 0.0);
+
+            playlist.program.first().cloned()?
+        }
+        Folder | Loop => FolderSource::new(
+            config,
+            None,
+            Arc::new(Mutex::new(vec![])),
+            Arc::new(AtomicUsize::new(0)),
+        )
+        .nodes
+        .lock()
+        .unwrap()
+        .first()
+        .cloned()?,
+    };
+
+    let image = node
+        .source
+        .rsplit_once('.')
+        .is_some_and(|(_, e)| IMAGE_FORMAT.contains(&e.to_lowercase().as_str()));
+
+    node.cmd = match image {
+        true => Some(loop_image(&node)),
+        false => Some(seek_and_length(config, &node)),
+    };
+    node.add_filter(config, &None);
+
+    Some(node)
+}
+
+/// All commands, like they get spawned, quoted for copy and paste.
+///
+/// The decoder is shown for the first clip.
+pub fn print_commands(config: &PlayoutConfig, node: Option<Media>) -> Vec<String> {
+    let log_format = log_format(config);
+    let mut lines = vec![];
+
+    if config.ingest.enable {
+        let cmd = ingest_cmd(config, &None);
+        lines.push(format!(
+            "Ingest:  {}",
+            hide_secrets(&quote_cmd(&config.processing.ffmpeg_path, &cmd))
+        ));
+    }
+
+    if let Some(mut node) = node.or_else(|| first_clip(config)) {
+        let input = node.cmd.take().unwrap_or_default();
+
+        if config.out.mode == HLS {
+            let cmd = hls_writer_cmd(config, &node, None, input, false);
+            lines.push(format!(
+                "Writer:  {}",
+                hide_secrets(&quote_cmd(&config.processing.ffmpeg_path, &cmd))
+            ));
+        } else {
+            let cmd = decoder_cmd(config, &log_format, input, node.filter.take());
+            lines.push(format!(
+                "Decoder: {}",
+                hide_secrets(&quote_cmd(&config.processing.ffmpeg_path, &cmd))
+            ));
+        }
+    }
+
+    if config.out.mode != HLS {
+        let (program, cmd) = encoder_cmd(config, &log_format);
+        lines.push(format!(
+            "Encoder: {}",
+            hide_secrets(&quote_cmd(program, &cmd))
+        ));
+    }
+
+    lines
+}
@@ -0,0 +1,50 @@
+use std::{
+    io::{BufReader, Error},
+    process::{Child, ChildStdout},
+    thread::JoinHandle,
+};
+
+use simplelog::*;
+
+use ffplayout_lib::utils::{Media, MediaClock};
+
+/// Media seconds before the current clip ends, in which the decoder for the next clip starts.
+pub const CONCAT_LEAD: f64 = 2.0;
+
+/// Check if the decoder for the next clip has to start.
+///
+/// It goes by the media time, which the current decoder delivered, so a decoder which
+/// runs faster or slower then realtime starts the next one always at the same place.
+pub fn next_due(node: &Media, clock: &MediaClock) -> bool {
+    clock.elapsed() >= node.out - node.seek - CONCAT_LEAD
+}
+
+/// Decoder for the next clip, which runs ahead in the continuous mode.
+///
+/// Every clip has its own decoder with its own filters. This one opens its input already,
+/// while the current clip plays, and then waits on its full pipe. So the switch to the next
+/// clip needs no start time of a new decoder.
+pub struct NextDecoder {
+    pub node: Media,
+    pub child: Child,
+    pub reader: BufReader<ChildStdout>,
+    pub error_thread: JoinHandle<Result<(), Error>>,
+}
+
+impl NextDecoder {
+    /// Stop the decoder, when its clip will not come, like after a playlist change.
+    pub fn discard(mut self) {
+        debug!(
+            "Stop the prepared decoder for: <b><magenta>{}</></b>",
+            self.node.source
+        );
+
+        if let Err(e) = self.child.kill().and_then(|_| self.child.wait()) {
+            error!("Decoder: {e}");
+        }
+
+        if let Err(e) = self.error_thread.join() {
+            error!("{e:?}");
+        }
+    }
+}
@@ -1,15 +1,19 @@
-use std::process::{self, Command, Stdio};
+use std::{
+    io::{self, Write},
+    process::{self, Command, Stdio},
+};
 
+use crossbeam_channel::{bounded, Sender};
 use simplelog::*;
 
 use ffplayout_lib::filter::v_drawtext;
-use ffplayout_lib::utils::PlayoutConfig;
+use ffplayout_lib::utils::{
+    hide_secrets, spawn_tagged, PlayoutConfig, ProcessControl, TsPackets, TS_PACKET_SIZE,
+};
 use ffplayout_lib::vec_strings;
 
-/// Desktop Output
-///
-/// Instead of streaming, we run a ffplay instance and play on desktop.
-pub fn output(config: &PlayoutConfig, log_format: &str) -> process::Child {
+/// ffplay command for the desktop output.
+pub fn cmd(config: &PlayoutConfig, log_format: &str) -> Vec<String> {
     let mut enc_filter: Vec<String> = vec![];
 
     let mut enc_cmd = vec_strings![
@@ -60,23 +64,126 @@ pub fn output(config: &PlayoutConfig, log_format: &str) -> process::Child {
 
     enc_cmd.append(&mut enc_filter);
 
+    enc_cmd
+}
+
+/// Desktop Output
+///
+/// Instead of streaming, we run a ffplay instance and play on desktop.
+pub fn output(config: &PlayoutConfig, log_format: &str) -> io::Result<process::Child> {
+    let enc_cmd = cmd(config, log_format);
+
     debug!(
         "Encoder CMD: <bright-blue>\"ffplay {}\"</>",
-        enc_cmd.join(" ")
+        hide_secrets(&enc_cmd.join(" "))
     );
 
-    let enc_proc = match Command::new("ffplay")
+    Command::new("ffplay")
         .args(enc_cmd)
         .stdin(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
+}
+
+/// Copy of the encoder data for the preview window.
+///
+/// Only whole mpegts packets are sent, when ffplay is too slow they get dropped
+/// and the count is logged, when the preview takes data again.
+pub struct PreviewFeed {
+    sender: Sender<Vec<u8>>,
+    packets: TsPackets,
+    dropped: usize,
+}
+
+impl PreviewFeed {
+    pub fn new(sender: Sender<Vec<u8>>) -> Self {
+        Self {
+            sender,
+            packets: TsPackets::new(),
+            dropped: 0,
+        }
+    }
+
+    pub fn send(&mut self, data: &[u8]) {
+        let packets = self.packets.push(data);
+
+        if packets.is_empty() {
+            return;
+        }
+
+        let count = packets.len() / TS_PACKET_SIZE;
+
+        match self.sender.try_send(packets) {
+            Ok(_) if self.dropped > 0 => {
+                warn!(
+                    "Preview is too slow, dropped <yellow>{}</> mpegts packets",
+                    self.dropped
+                );
+                self.dropped = 0;
+            }
+            Ok(_) => {}
+            Err(_) => self.dropped += count,
+        }
+    }
+
+    /// A new source starts, the unfinished packet of the old one is not sent.
+    pub fn reset(&mut self) {
+        self.packets.reset();
+    }
+}
+
+/// Desktop Preview
+///
+/// Run a ffplay window beside the stream output. It gets a copy of the same data as the encoder,
+/// so logo and subtitles looks like in the stream. Packets are dropped, when ffplay is too slow.
+pub fn preview(
+    config: &PlayoutConfig,
+    log_format: &str,
+    proc_control: &ProcessControl,
+) -> Option<PreviewFeed> {
+    let preview_cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        log_format,
+        "-autoexit",
+        "-i",
+        "pipe:0",
+        "-window_title",
+        format!("ffplayout preview - {}", config.general.channel)
+    ];
+
+    debug!(
+        "Preview CMD: <bright-blue>\"ffplay {}\"</>",
+        preview_cmd.join(" ")
+    );
+
+    let mut preview_proc = match Command::new("ffplay")
+        .args(preview_cmd)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
     {
         Err(e) => {
-            error!("couldn't spawn encoder process: {e}");
-            panic!("couldn't spawn encoder process: {e}")
+            error!("couldn't spawn preview process: {e}");
+            return None;
         }
         Ok(proc) => proc,
     };
 
-    enc_proc
+    let mut preview_writer = preview_proc.stdin.take().unwrap();
+    *proc_control.preview_term.lock().unwrap() = Some(preview_proc);
+
+    let (sender, receiver) = bounded::<Vec<u8>>(96);
+
+    spawn_tagged(move || {
+        for data in receiver {
+            if let Err(e) = preview_writer.write_all(&data) {
+                warn!("Preview closed: {}", e.kind());
+                break;
+            }
+        }
+    });
+
+    Some(PreviewFeed::new(sender))
 }
@@ -0,0 +1,132 @@
+use std::{
+    sync::atomic::Ordering,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use simplelog::*;
+
+use ffplayout_lib::utils::{EncoderStats, PlayoutConfig, ProcessControl, ProcessUnit::*};
+
+/// Change of the encoder settings, with the new ladder level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FallbackAction {
+    Downgrade(usize),
+    Upgrade(usize),
+}
+
+/// Decides from the encoding speed, when the encoder needs other settings.
+///
+/// Level 0 is the configured output, every higher level is one step of the ladder.
+pub struct FallbackLadder {
+    steps: usize,
+    level: usize,
+    slow_after: Duration,
+    cooldown: Duration,
+    slow_since: Option<Instant>,
+    fast_since: Option<Instant>,
+}
+
+impl FallbackLadder {
+    pub fn new(config: &PlayoutConfig) -> Self {
+        let fallback = &config.out.fallback;
+
+        Self {
+            steps: fallback.ladder.len(),
+            level: 0,
+            slow_after: Duration::from_secs(fallback.slow_seconds),
+            cooldown: Duration::from_secs(fallback.cooldown_seconds),
+            slow_since: None,
+            fast_since: None,
+        }
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// Register a speed sample, a speed of 0 is unknown and gets ignored.
+    pub fn update(&mut self, speed: f64, now: Instant) -> Option<FallbackAction> {
+        if speed <= 0.0 {
+            return None;
+        }
+
+        if speed < 1.0 {
+            self.fast_since = None;
+            let since = *self.slow_since.get_or_insert(now);
+
+            if self.level < self.steps && now.duration_since(since) >= self.slow_after {
+                self.level += 1;
+                self.slow_since = None;
+
+                return Some(FallbackAction::Downgrade(self.level));
+            }
+        } else {
+            self.slow_since = None;
+            let since = *self.fast_since.get_or_insert(now);
+
+            if self.level > 0 && now.duration_since(since) >= self.cooldown {
+                self.level -= 1;
+                self.fast_since = None;
+
+                return Some(FallbackAction::Upgrade(self.level));
+            }
+        }
+
+        None
+    }
+}
+
+/// Config for the encoder on the given ladder level.
+pub fn fallback_config(config: &PlayoutConfig, level: usize) -> PlayoutConfig {
+    let mut config = config.clone();
+
+    if let Some(step) = level
+        .checked_sub(1)
+        .and_then(|i| config.out.fallback.ladder.get(i))
+    {
+        config.out.output_cmd = config.out.output_cmd.map(|cmd| step.output_cmd(cmd));
+    }
+
+    config
+}
+
+/// Watch the encoding speed and restart the encoder on a new ladder level.
+///
+/// The player respawns the stopped encoder, like after a stall.
+pub fn fallback_watcher(config: PlayoutConfig, proc_control: ProcessControl) {
+    let mut ladder = FallbackLadder::new(&config);
+
+    while !proc_control.is_terminated.load(Ordering::SeqCst) {
+        let speed = proc_control.encoder_stats.lock().unwrap().speed;
+
+        if let Some(action) = ladder.update(speed, Instant::now()) {
+            match action {
+                FallbackAction::Downgrade(level) => {
+                    let step = &config.out.fallback.ladder[level - 1];
+
+                    warn!(
+                        "Encoding is too slow, restart encoder on fallback level <yellow>{level}</>, size: <yellow>{}</> preset: <yellow>{}</>",
+                        step.size, step.preset
+                    )
+                }
+                FallbackAction::Upgrade(level) => info!(
+                    "Encoding is fast again, restart encoder on fallback level <yellow>{level}</>"
+                ),
+            }
+
+            proc_control
+                .encoder_level
+                .store(ladder.level(), Ordering::SeqCst);
+            // the old stats are from the stopped encoder
+            *proc_control.encoder_stats.lock().unwrap() = EncoderStats::default();
+            proc_control.encoder_stalled.store(true, Ordering::SeqCst);
+
+            if let Err(e) = proc_control.stop(Encoder) {
+                error!("{e}")
+            }
+        }
+
+        sleep(Duration::from_secs(1));
+    }
+}
@@ -18,23 +18,24 @@ out:
 */
 
 use std::{
-    io::{BufRead, BufReader, Error},
-    process::{exit, Command, Stdio},
-    sync::atomic::Ordering,
-    thread::{self, sleep},
+    fs,
+    io::{BufRead, BufReader, Error, ErrorKind},
+    process::Stdio,
+    sync::{atomic::Ordering, Arc, Mutex},
+    thread::sleep,
     time::Duration,
 };
 
 use simplelog::*;
 
-use crate::input::source_generator;
-use crate::utils::{log_line, prepare_output_cmd, valid_stream};
-use ffplayout_lib::{
-    utils::{
-        controller::ProcessUnit::*, sec_to_time, stderr_reader, test_tcp_port, Media,
-        PlayerControl, PlayoutConfig, PlayoutStatus, ProcessControl,
-    },
-    vec_strings,
+use super::commands::{hls_writer_cmd, ingest_cmd};
+use crate::input::{playlist::gen_source, source_generator};
+use crate::utils::{log_line, ClientCheck, ClientGate};
+use ffplayout_lib::filter::report_loudness;
+use ffplayout_lib::utils::{
+    controller::ProcessUnit::*, failure_alert, ffmpeg_command, get_sec, hide_secrets, hls_time,
+    sec_to_time, spawn_tagged, stderr_reader, test_tcp_port, time_now, write_status, IngestMode,
+    Media, PlayerControl, PlayoutConfig, PlayoutStatus, ProcessControl,
 };
 
 /// Ingest Server for HLS
@@ -46,60 +47,76 @@ fn ingest_to_hls_server(
     let playlist_init = playout_stat.list_init;
     let level = config.logging.ffmpeg_level.clone();
 
-    let mut server_prefix = vec_strings!["-hide_banner", "-nostats", "-v", "level+info"];
     let stream_input = config.ingest.input_cmd.clone().unwrap();
-    server_prefix.append(&mut stream_input.clone());
-    let mut dummy_media = Media::new(0, "Live Stream", false);
-    dummy_media.unit = Ingest;
-
     let mut is_running;
 
-    if let Some(url) = stream_input.iter().find(|s| s.contains("://")) {
-        if !test_tcp_port(url) {
+    let audio_input = config.ingest.audio_input_cmd.clone().unwrap_or_default();
+
+    // the separate audio feed listens on its own address
+    for url in [&stream_input, &audio_input]
+        .into_iter()
+        .filter_map(|cmd| cmd.iter().find(|s| s.contains("://")))
+    {
+        if matches!(config.ingest.mode, IngestMode::Rtmp | IngestMode::Rtsp) && !test_tcp_port(url)
+        {
+            // only this channel stops, the other ones keep running
             proc_control.stop_all();
-            exit(1);
+
+            return Err(Error::new(ErrorKind::AddrInUse, "ingest address in use"));
         }
 
-        info!("Start ingest server, listening on: <b><magenta>{url}</></b>");
-    };
+        info!(
+            "Start ingest server, listening on: <b><magenta>{}</></b>",
+            hide_secrets(url)
+        );
+    }
 
     loop {
-        dummy_media.add_filter(&config, &playout_stat.chain);
-        let server_cmd = prepare_output_cmd(&config, server_prefix.clone(), &dummy_media.filter);
+        let server_cmd = ingest_cmd(&config, &playout_stat.chain);
 
         debug!(
             "Server CMD: <bright-blue>\"ffmpeg {}\"</>",
-            server_cmd.join(" ")
+            hide_secrets(&server_cmd.join(" "))
         );
 
         let proc_ctl = proc_control.clone();
-        let mut server_proc = match Command::new("ffmpeg")
+        let mut server_proc = match ffmpeg_command(&config)
             .args(server_cmd.clone())
             .stderr(Stdio::piped())
             .spawn()
         {
             Err(e) => {
-                error!("couldn't spawn ingest server: {e}");
-                panic!("couldn't spawn ingest server: {e}");
+                proc_control.spawn_failed(Ingest, &config.processing.ffmpeg_path, &e);
+
+                return Ok(());
             }
             Ok(proc) => proc,
         };
 
         let server_err = BufReader::new(server_proc.stderr.take().unwrap());
-        *proc_control.server_term.lock().unwrap() = Some(server_proc);
+        proc_control.set_child(Ingest, server_proc);
         is_running = false;
+        // the server writes the playlist itself, so a refused client gets stopped at once
+        let mut gate = ClientGate::new(&config);
 
         for line in server_err.lines() {
             let line = line?;
 
-            if line.contains("rtmp") && line.contains("Unexpected stream") && !valid_stream(&line) {
+            if gate.state == ClientCheck::Pending
+                && gate.check_line(&config, &line) == ClientCheck::Rejected
+            {
                 if let Err(e) = proc_ctl.stop(Ingest) {
                     error!("{e}");
                 };
             }
 
+            if !is_running && !config.ingest.takeover {
+                info!("Live ingest connected, without takeover the playlist continues");
+                is_running = true;
+            }
+
             if !is_running {
-                proc_control.server_is_running.store(true, Ordering::SeqCst);
+                proc_control.set_ingest_active(true);
                 playlist_init.store(true, Ordering::SeqCst);
                 is_running = true;
 
@@ -117,9 +134,7 @@ fn ingest_to_hls_server(
             info!("Switch from live ingest to {}", config.processing.mode);
         }
 
-        proc_control
-            .server_is_running
-            .store(false, Ordering::SeqCst);
+        proc_control.set_ingest_active(false);
 
         if let Err(e) = proc_control.wait(Ingest) {
             error!("{e}")
@@ -133,9 +148,198 @@ fn ingest_to_hls_server(
     Ok(())
 }
 
+/// Add a flag to the `-hls_flags` of the output, or add them after the format.
+pub fn hls_flag_cmd(mut cmd: Vec<String>, flag: &str) -> Vec<String> {
+    if let Some(i) = cmd.iter().position(|c| c == "-hls_flags") {
+        if let Some(flags) = cmd.get_mut(i + 1) {
+            if !flags.split('+').any(|f| f == flag) {
+                flags.push('+');
+                flags.push_str(flag);
+            }
+        }
+    } else if let Some(i) = cmd
+        .iter()
+        .rposition(|c| c == "-f")
+        .filter(|i| i + 2 <= cmd.len())
+    {
+        cmd.insert(i + 2, "-hls_flags".to_string());
+        cmd.insert(i + 3, flag.to_string());
+    }
+
+    cmd
+}
+
+/// Add the discontinuity flag to the hls output, because the timestamps starts new.
+pub fn discontinuity_cmd(cmd: Vec<String>) -> Vec<String> {
+    hls_flag_cmd(cmd, "discont_start")
+}
+
+/// Length of the ad break, rounded up to full segments, so the break ends on a segment boundary.
+pub fn ad_break_length(config: &PlayoutConfig, duration: f64) -> f64 {
+    match config.out.output_cmd.as_ref().and_then(|c| hls_time(c)) {
+        Some(segment) if segment > 0.0 => (duration / segment).ceil() * segment,
+        _ => duration,
+    }
+}
+
+/// Create a filler clip for the ad break.
+fn ad_break_node(config: &PlayoutConfig, playout_stat: &PlayoutStatus, duration: f64) -> Media {
+    let mut node = Media::new(0, &config.storage.filler_clip, false);
+    node.begin = Some(get_sec());
+    node.category = "advertisement".to_string();
+    node.duration = duration;
+    node.out = duration;
+
+    gen_source(config, node, &playout_stat.chain)
+}
+
+/// Segments of a ad break in the hls playlist.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CueMark {
+    pub first: String,
+    pub last: String,
+    pub duration: f64,
+}
+
+/// Path of the hls playlist, a variant playlist of the abr output has no single path.
+fn hls_playlist(config: &PlayoutConfig) -> Option<String> {
+    config
+        .out
+        .output_cmd
+        .as_ref()
+        .and_then(|c| c.last())
+        .filter(|p| p.ends_with(".m3u8") && !p.contains("%v"))
+        .cloned()
+}
+
+/// Segment names of the hls playlist.
+fn hls_segments(path: &str) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.to_string())
+        .collect()
+}
+
+/// Add `#EXT-X-CUE-OUT` before the first and `#EXT-X-CUE-IN` after the last segment
+/// of every ad break. `None` is returned, when the playlist has already all tags.
+pub fn cue_playlist(playlist: &str, marks: &[CueMark]) -> Option<String> {
+    let lines: Vec<&str> = playlist.lines().collect();
+    let mut out = vec![];
+    let mut changed = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(mark) = marks.iter().find(|m| m.first == *line) {
+            let extinf = out
+                .iter()
+                .rposition(|l: &String| l.starts_with("#EXTINF"))
+                .unwrap_or(out.len());
+
+            if extinf == 0 || !out[extinf - 1].starts_with("#EXT-X-CUE-OUT") {
+                out.insert(extinf, format!("#EXT-X-CUE-OUT:DURATION={}", mark.duration));
+                changed = true;
+            }
+        }
+
+        out.push(line.to_string());
+
+        if marks.iter().any(|m| m.last == *line) && lines.get(i + 1) != Some(&"#EXT-X-CUE-IN") {
+            out.push("#EXT-X-CUE-IN".to_string());
+            changed = true;
+        }
+    }
+
+    changed.then(|| out.join("\n") + "\n")
+}
+
+/// Keep the cue tags in the playlist, ffmpeg writes the playlist new on every segment
+/// and drops them. A ad break, which is not in the playlist anymore, is forgotten.
+fn cue_writer(path: String, marks: Arc<Mutex<Vec<CueMark>>>, proc_control: ProcessControl) {
+    while !proc_control.is_terminated.load(Ordering::SeqCst) {
+        sleep(Duration::from_millis(500));
+
+        let mut marks = marks.lock().unwrap();
+
+        if marks.is_empty() {
+            continue;
+        }
+
+        let playlist = fs::read_to_string(&path).unwrap_or_default();
+        marks.retain(|m| playlist.lines().any(|l| l == m.last));
+
+        if let Some(tagged) = cue_playlist(&playlist, &marks) {
+            let tmp = format!("{path}.cue.tmp");
+
+            if let Err(e) = fs::write(&tmp, tagged).and_then(|_| fs::rename(&tmp, &path)) {
+                error!("Unable to write cue tags to <b><magenta>{path}</></b>: {e}");
+            }
+        }
+    }
+}
+
+/// Run one ffmpeg instance, which writes the clip to the hls playlist.
+fn encode_node(
+    config: &PlayoutConfig,
+    node: &Media,
+    next: Option<&Media>,
+    cmd: Vec<String>,
+    discontinuity: bool,
+    proc_control: &ProcessControl,
+) {
+    info!(
+        "Play for <yellow>{}</>: <b><magenta>{}</></b>",
+        sec_to_time(node.out - node.seek),
+        node.source
+    );
+
+    let aired = time_now();
+    let enc_cmd = hls_writer_cmd(config, node, next, cmd, discontinuity);
+
+    debug!(
+        "HLS writer CMD: <bright-blue>\"ffmpeg {}\"</>",
+        hide_secrets(&enc_cmd.join(" "))
+    );
+
+    let mut enc_proc = match ffmpeg_command(config)
+        .args(enc_cmd)
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Err(e) => {
+            proc_control.spawn_failed(Encoder, &config.processing.ffmpeg_path, &e);
+
+            return;
+        }
+        Ok(proc) => proc,
+    };
+
+    let enc_err = BufReader::new(enc_proc.stderr.take().unwrap());
+    proc_control.set_child(Encoder, enc_proc);
+
+    if let Err(e) = stderr_reader(enc_err, Encoder, proc_control.clone()) {
+        error!("{e:?}")
+    };
+
+    if let Err(e) = proc_control.wait(Encoder) {
+        error!("{e}");
+    }
+
+    if proc_control.count_restart(Encoder) {
+        failure_alert(config, proc_control, Encoder);
+    }
+
+    report_loudness(config, node, aired);
+}
+
 /// HLS Writer
 ///
 /// Write with single ffmpeg instance directly to a HLS playlist.
+///
+/// A requested ad break plays the filler clip for the break duration, before the next clip.
+/// The break is rounded up to full segments, is marked with cue out and cue in tags and
+/// the segments after the break gets a discontinuity tag. The playlist is shifted for the
+/// break duration, to stay in sync.
 pub fn write_hls(
     config: &PlayoutConfig,
     play_control: PlayerControl,
@@ -143,9 +347,11 @@ pub fn write_hls(
     proc_control: ProcessControl,
 ) {
     let config_clone = config.clone();
-    let ff_log_format = format!("level+{}", config.logging.ffmpeg_level.to_lowercase());
     let play_stat = playout_stat.clone();
     let proc_control_c = proc_control.clone();
+    let status = playout_stat.clone();
+    let cue_marks = Arc::new(Mutex::new(vec![]));
+    let playlist_path = hls_playlist(config);
 
     let get_source = source_generator(
         config.clone(),
@@ -157,13 +363,63 @@ pub fn write_hls(
 
     // spawn a thread for ffmpeg ingest server and create a channel for package sending
     if config.ingest.enable {
-        thread::spawn(move || ingest_to_hls_server(config_clone, play_stat, proc_control_c));
+        spawn_tagged(move || ingest_to_hls_server(config_clone, play_stat, proc_control_c));
+    }
+
+    if let Some(path) = playlist_path.clone() {
+        let marks = cue_marks.clone();
+        let cue_control = proc_control.clone();
+
+        spawn_tagged(move || cue_writer(path, marks, cue_control));
     }
 
     for node in get_source {
+        let mut discontinuity = false;
+        let ad_break = status.ad_break.lock().unwrap().take();
+
+        if let Some(duration) = ad_break.map(|d| ad_break_length(config, d)) {
+            let ad_node = ad_break_node(config, &status, duration);
+            *play_control.current_media.lock().unwrap() = Some(ad_node.clone());
+            proc_control.set_clip(&ad_node);
+
+            info!("Start ad break for <yellow>{duration}</> seconds");
+
+            let before = playlist_path
+                .as_deref()
+                .map(hls_segments)
+                .unwrap_or_default();
+
+            if let Some(cmd) = ad_node.cmd.clone() {
+                encode_node(config, &ad_node, Some(&node), cmd, true, &proc_control);
+            }
+
+            let segments = playlist_path
+                .as_deref()
+                .map(hls_segments)
+                .unwrap_or_default();
+            let mut ad_segments = segments.iter().filter(|s| !before.contains(s));
+
+            if let (Some(first), Some(last)) = (ad_segments.next(), segments.last()) {
+                cue_marks.lock().unwrap().push(CueMark {
+                    first: first.clone(),
+                    last: last.clone(),
+                    duration,
+                });
+            }
+
+            let current_date = status.current_date.lock().unwrap().clone();
+            let mut time_shift = status.time_shift.lock().unwrap();
+            *time_shift -= duration;
+            *status.date.lock().unwrap() = current_date.clone();
+            write_status(config, &current_date, *time_shift);
+
+            discontinuity = true;
+        }
+
         *play_control.current_media.lock().unwrap() = Some(node.clone());
+        proc_control.set_clip(&node);
 
-        let mut cmd = match node.cmd {
+        let cmd = match node.cmd.clone() {
             Some(cmd) => cmd,
             None => break,
         };
@@ -172,42 +428,27 @@ pub fn write_hls(
             continue;
         }
 
-        info!(
-            "Play for <yellow>{}</>: <b><magenta>{}</></b>",
-            sec_to_time(node.out - node.seek),
-            node.source
-        );
-
-        let mut enc_prefix = vec_strings!["-hide_banner", "-nostats", "-v", &ff_log_format];
-        enc_prefix.append(&mut cmd);
-        let enc_cmd = prepare_output_cmd(config, enc_prefix, &node.filter);
-
-        debug!(
-            "HLS writer CMD: <bright-blue>\"ffmpeg {}\"</>",
-            enc_cmd.join(" ")
+        let next = node.index.and_then(|i| {
+            play_control
+                .current_list
+                .lock()
+                .unwrap()
+                .get(i + 1)
+                .cloned()
+        });
+
+        encode_node(
+            config,
+            &node,
+            next.as_ref(),
+            cmd,
+            discontinuity,
+            &proc_control,
         );
 
-        let mut enc_proc = match Command::new("ffmpeg")
-            .args(enc_cmd)
-            .stderr(Stdio::piped())
-            .spawn()
-        {
-            Err(e) => {
-                error!("couldn't spawn encoder process: {e}");
-                panic!("couldn't spawn encoder process: {e}")
-            }
-            Ok(proc) => proc,
-        };
-
-        let enc_err = BufReader::new(enc_proc.stderr.take().unwrap());
-        *proc_control.encoder_term.lock().unwrap() = Some(enc_proc);
-
-        if let Err(e) = stderr_reader(enc_err, Encoder, proc_control.clone()) {
-            error!("{e:?}")
-        };
-
-        if let Err(e) = proc_control.wait(Encoder) {
-            error!("{e}");
+        // the encoder couldn't start, or the playout got stopped
+        if proc_control.is_terminated.load(Ordering::SeqCst) {
+            break;
         }
 
         while proc_control.server_is_running.load(Ordering::SeqCst) {
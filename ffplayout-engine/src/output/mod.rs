@@ -1,27 +1,164 @@
 use std::{
-    io::{prelude::*, BufReader, BufWriter, Read},
-    process::{Command, Stdio},
+    io,
+    io::{prelude::*, BufReader, BufWriter, Error, Read},
+    mem,
+    process::{Child, ChildStdin, ChildStdout, Stdio},
     sync::atomic::Ordering,
-    thread::{self, sleep},
-    time::Duration,
+    thread::{sleep, JoinHandle},
+    time::{Duration, Instant},
 };
 
 use crossbeam_channel::bounded;
 use simplelog::*;
 
+mod commands;
+mod concat;
 mod desktop;
+mod fallback;
 mod hls;
 mod null;
+mod pip;
+mod preroll;
+mod recovery;
 mod stream;
+mod takeover;
+mod watchdog;
 
-pub use hls::write_hls;
-
-use crate::input::{ingest_server, source_generator};
+pub use commands::{
+    decoder_cmd, encoder_cmd, hls_writer_cmd, ingest_cmd, log_format, metadata_cmd, print_commands,
+};
+pub use concat::{next_due, CONCAT_LEAD};
+pub use fallback::{fallback_config, FallbackAction, FallbackLadder};
+pub use hls::{ad_break_length, cue_playlist, discontinuity_cmd, hls_flag_cmd, write_hls, CueMark};
+pub use pip::{pip_active, pip_cmd};
+pub use preroll::{Preroll, PREROLL_MAX_BYTES};
+pub use recovery::{recovery_input_cmd, recovery_node, RECOVERY_FLAGS};
+pub use takeover::{Switch, Takeover};
+pub use watchdog::{check_stall, out_time};
+
+use crate::input::{drop_stale, ingest_server, source_generator};
+use concat::NextDecoder;
+use fallback::fallback_watcher;
+use ffplayout_lib::filter::{report_loudness, Filters};
 use ffplayout_lib::utils::{
-    sec_to_time, stderr_reader, OutputMode::*, PlayerControl, PlayoutConfig, PlayoutStatus,
-    ProcessControl, ProcessUnit::*,
+    failure_alert, ffmpeg_command, hide_secrets, sec_to_time, spawn_tagged, stderr_reader,
+    time_now, Backpressure, DecoderMode, Media, MediaClock, OutputMode::*, PlayerControl,
+    PlayoutConfig, PlayoutStatus, ProcessControl, ProcessUnit::*,
 };
-use ffplayout_lib::vec_strings;
+use pip::PipFeeder;
+use watchdog::{progress_reader, watchdog, watchdog_enabled};
+
+/// Pipe of a running ffmpeg process, with the thread which logs its messages.
+type ProcessPipe<T> = (T, JoinHandle<Result<(), Error>>);
+
+/// Start the ffmpeg output instance, with a thread for logging its messages.
+///
+/// When the process can't start, the playout gets stopped and there is no encoder.
+fn start_encoder(
+    config: &PlayoutConfig,
+    log_format: &str,
+    proc_control: &ProcessControl,
+) -> Option<ProcessPipe<BufWriter<ChildStdin>>> {
+    let (enc_bin, enc_proc) = match config.out.mode {
+        Desktop => ("ffplay", desktop::output(config, log_format)),
+        Null => (
+            config.processing.ffmpeg_path.as_str(),
+            null::output(config, log_format),
+        ),
+        Stream | Dash => (
+            config.processing.ffmpeg_path.as_str(),
+            stream::output(config, log_format),
+        ),
+        _ => panic!("Output mode doesn't exists!"),
+    };
+
+    let mut enc_proc = match enc_proc {
+        Ok(proc) => proc,
+        Err(e) => {
+            proc_control.spawn_failed(Encoder, enc_bin, &e);
+            return None;
+        }
+    };
+
+    let enc_writer = BufWriter::new(enc_proc.stdin.take().unwrap());
+    let enc_err = BufReader::new(enc_proc.stderr.take().unwrap());
+
+    if let Some(enc_out) = enc_proc.stdout.take() {
+        let progress_ctl = proc_control.clone();
+        spawn_tagged(move || progress_reader(BufReader::new(enc_out), progress_ctl));
+    }
+
+    proc_control.set_child(Encoder, enc_proc);
+    let enc_p_ctl = proc_control.clone();
+
+    // spawn a thread to log ffmpeg output error messages
+    let error_encoder_thread = spawn_tagged(move || stderr_reader(enc_err, Encoder, enc_p_ctl));
+
+    Some((enc_writer, error_encoder_thread))
+}
+
+/// Replace a encoder, which was killed by the watchdog or for a fallback level.
+///
+/// It returns false, when the new encoder couldn't start.
+fn restart_encoder(
+    config: &PlayoutConfig,
+    log_format: &str,
+    proc_control: &ProcessControl,
+    enc_writer: &mut BufWriter<ChildStdin>,
+    error_encoder_thread: &mut JoinHandle<Result<(), Error>>,
+) -> bool {
+    let config = fallback_config(config, proc_control.encoder_level.load(Ordering::SeqCst));
+    let Some((writer, error_thread)) = start_encoder(&config, log_format, proc_control) else {
+        return false;
+    };
+    *enc_writer = writer;
+
+    if let Err(e) = mem::replace(error_encoder_thread, error_thread).join() {
+        error!("{e:?}");
+    };
+
+    true
+}
+
+/// Start a decoder for the clip, with a thread for logging its messages.
+///
+/// With `pip_input` the decoder gets a stdin for the live ingest.
+fn spawn_decoder(
+    config: &PlayoutConfig,
+    log_format: &str,
+    proc_control: &ProcessControl,
+    cmd: Vec<String>,
+    filter: Option<Filters>,
+    pip_input: bool,
+) -> io::Result<(Child, ProcessPipe<BufReader<ChildStdout>>)> {
+    let dec_cmd = decoder_cmd(config, log_format, cmd, filter);
+
+    debug!(
+        "Decoder CMD: <bright-blue>\"ffmpeg {}\"</>",
+        hide_secrets(&dec_cmd.join(" "))
+    );
+
+    let mut dec_command = ffmpeg_command(config);
+    dec_command
+        .args(dec_cmd)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if pip_input {
+        dec_command.stdin(Stdio::piped());
+    }
+
+    // create ffmpeg decoder instance, for reading the input files
+    let mut dec_proc = dec_command.spawn()?;
+
+    let dec_reader = BufReader::new(dec_proc.stdout.take().unwrap());
+    let dec_err = BufReader::new(dec_proc.stderr.take().unwrap());
+    let dec_p_ctl = proc_control.clone();
+
+    let error_decoder_thread = spawn_tagged(move || stderr_reader(dec_err, Decoder, dec_p_ctl));
+
+    Ok((dec_proc, (dec_reader, error_decoder_thread)))
+}
 
 /// Player
 ///
@@ -32,6 +169,9 @@ use ffplayout_lib::vec_strings;
 /// for getting live feeds.
 /// When a live ingest arrive, it stops the current playing and switch to the live source.
 /// When ingest stops, it switch back to playlist/folder mode.
+///
+/// With the continuous decoder mode, the decoder for the next clip starts
+/// before the current clip ends, so it is ready at the switch.
 pub fn player(
     config: &PlayoutConfig,
     play_control: PlayerControl,
@@ -39,13 +179,21 @@ pub fn player(
     proc_control: ProcessControl,
 ) {
     let config_clone = config.clone();
-    let ff_log_format = format!("level+{}", config.logging.ffmpeg_level.to_lowercase());
+    let ff_log_format = log_format(config);
     let mut buffer = [0; 65088];
-    let mut live_on = false;
+    let mut takeover = Takeover::new(config);
     let playlist_init = playout_stat.list_init.clone();
+    let pause = playout_stat.pause.clone();
+    let mut preroll = Preroll::new(config.processing.preroll_ms);
+
+    // in continuous mode the next clip and its decoder are ready, before the current clip ends
+    let continuous = config.processing.decoder_mode == DecoderMode::Continuous;
+    let mut next_node: Option<Option<Media>> = None;
+    let mut next_decoder: Option<NextDecoder> = None;
+    let mut clock = MediaClock::new();
 
     // get source iterator
-    let get_source = source_generator(
+    let mut get_source = source_generator(
         config.clone(),
         play_control.current_list.clone(),
         play_control.index.clone(),
@@ -54,21 +202,31 @@ pub fn player(
     );
 
     // get ffmpeg output instance
-    let mut enc_proc = match config.out.mode {
-        Desktop => desktop::output(config, &ff_log_format),
-        Null => null::output(config, &ff_log_format),
-        Stream => stream::output(config, &ff_log_format),
-        _ => panic!("Output mode doesn't exists!"),
+    let Some((mut enc_writer, mut error_encoder_thread)) =
+        start_encoder(config, &ff_log_format, &proc_control)
+    else {
+        return;
     };
 
-    let mut enc_writer = BufWriter::new(enc_proc.stdin.take().unwrap());
-    let enc_err = BufReader::new(enc_proc.stderr.take().unwrap());
+    if watchdog_enabled(config) {
+        let watchdog_config = config.clone();
+        let watchdog_ctl = proc_control.clone();
 
-    *proc_control.encoder_term.lock().unwrap() = Some(enc_proc);
-    let enc_p_ctl = proc_control.clone();
+        spawn_tagged(move || watchdog(watchdog_config, watchdog_ctl));
+    }
 
-    // spawn a thread to log ffmpeg output error messages
-    let error_encoder_thread = thread::spawn(move || stderr_reader(enc_err, Encoder, enc_p_ctl));
+    if config.out.fallback.enable {
+        let fallback_config = config.clone();
+        let fallback_ctl = proc_control.clone();
+
+        spawn_tagged(move || fallback_watcher(fallback_config, fallback_ctl));
+    }
+
+    // copy of the encoder input, for watching the stream on desktop
+    let mut preview = match config.out.preview && config.out.mode == Stream {
+        true => desktop::preview(config, &ff_log_format, &proc_control),
+        false => None,
+    };
 
     let proc_control_c = proc_control.clone();
     let mut ingest_receiver = None;
@@ -77,13 +235,40 @@ pub fn player(
     if config.ingest.enable {
         let (ingest_sender, rx) = bounded(96);
         ingest_receiver = Some(rx);
-        thread::spawn(move || ingest_server(config_clone, ingest_sender, proc_control_c));
+        spawn_tagged(move || ingest_server(config_clone, ingest_sender, proc_control_c));
     }
 
-    'source_iter: for node in get_source {
+    // a failed clip gets one more decoder run, before the next clip comes
+    let mut retry: Option<Media> = None;
+
+    'source_iter: loop {
+        let recovery = retry.is_some();
+
+        // a playlist change or a retry comes before the prepared clip
+        if playlist_init.load(Ordering::SeqCst) || recovery {
+            if playlist_init.load(Ordering::SeqCst) {
+                next_node = None;
+            }
+
+            if let Some(next) = next_decoder.take() {
+                next.discard();
+            }
+        }
+
+        let mut node = match retry
+            .take()
+            .or_else(|| next_node.take().unwrap_or_else(|| get_source.next()))
+        {
+            Some(node) => node,
+            None => break,
+        };
+        let original = node.clone();
+        let prepared = next_decoder.take();
+
         *play_control.current_media.lock().unwrap() = Some(node.clone());
+        proc_control.set_clip(&node);
 
-        let mut cmd = match node.cmd {
+        let mut cmd = match node.cmd.take() {
             Some(cmd) => cmd,
             None => break,
         };
@@ -99,51 +284,66 @@ pub fn player(
             node.audio
         );
 
-        let mut dec_cmd = vec_strings!["-hide_banner", "-nostats", "-v", &ff_log_format];
-        dec_cmd.append(&mut cmd);
+        let aired = time_now();
+        let mut filter = node.filter.take();
+        // the decoder has the live ingest as picture-in-picture input
+        let mut pip_live = false;
+        let mut pip_restart = false;
+        let mut pip_feed = None;
+
+        let mut decoder: ProcessPipe<BufReader<ChildStdout>> = if let Some(next) = prepared {
+            proc_control.set_child(Decoder, next.child);
+
+            (next.reader, next.error_thread)
+        } else {
+            pip_live = pip_active(
+                config,
+                proc_control.server_is_running.load(Ordering::SeqCst),
+            );
+
+            if pip_live {
+                let (pip_input, pip_filter) = pip_cmd(config, cmd, filter);
+                cmd = pip_input;
+                filter = Some(pip_filter);
+            }
 
-        if let Some(mut filter) = node.filter {
-            dec_cmd.append(&mut filter.cmd());
-            dec_cmd.append(&mut filter.map());
-        }
+            let (mut dec_proc, pipe) =
+                match spawn_decoder(config, &ff_log_format, &proc_control, cmd, filter, pip_live) {
+                    Ok(decoder) => decoder,
+                    Err(e) => {
+                        proc_control.spawn_failed(Decoder, &config.processing.ffmpeg_path, &e);
 
-        if let Some(mut cmd) = config.processing.cmd.clone() {
-            dec_cmd.append(&mut cmd);
-        }
+                        break 'source_iter;
+                    }
+                };
 
-        debug!(
-            "Decoder CMD: <bright-blue>\"ffmpeg {}\"</>",
-            dec_cmd.join(" ")
-        );
+            if let Some(dec_writer) = dec_proc.stdin.take() {
+                let pip_receiver = ingest_receiver.clone().unwrap();
+                let pip_ctl = proc_control.clone();
 
-        // create ffmpeg decoder instance, for reading the input files
-        let mut dec_proc = match Command::new("ffmpeg")
-            .args(dec_cmd)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-        {
-            Ok(proc) => proc,
-            Err(e) => {
-                error!("couldn't spawn decoder process: {e}");
-                panic!("couldn't spawn decoder process: {e}")
+                pip_feed = Some(PipFeeder::spawn(dec_writer, pip_receiver, pip_ctl));
             }
+
+            proc_control.set_child(Decoder, dec_proc);
+
+            pipe
         };
 
-        let mut dec_reader = BufReader::new(dec_proc.stdout.take().unwrap());
-        let dec_err = BufReader::new(dec_proc.stderr.take().unwrap());
+        preroll.start();
+        clock.reset();
 
-        *proc_control.decoder_term.lock().unwrap() = Some(dec_proc);
-        let dec_p_ctl = proc_control.clone();
+        if let Some(feed) = preview.as_mut() {
+            feed.reset();
+        }
 
-        let error_decoder_thread =
-            thread::spawn(move || stderr_reader(dec_err, Decoder, dec_p_ctl));
+        let dec_reader = &mut decoder.0;
+        let mut delivered = false;
 
         loop {
-            // when server is running, read from channel
-            if proc_control.server_is_running.load(Ordering::SeqCst) {
-                if !live_on {
+            match takeover.update(proc_control.server_is_running.load(Ordering::SeqCst)) {
+                Some(Switch::ToLive) => {
                     info!("Switch from {} to live ingest", config.processing.mode);
+                    proc_control.watchdog_pause.store(true, Ordering::SeqCst);
 
                     if let Err(e) = enc_writer.flush() {
                         error!("Encoder error: {e}")
@@ -153,27 +353,160 @@ pub fn player(
                         error!("{e}")
                     }
 
-                    live_on = true;
                     playlist_init.store(true, Ordering::SeqCst);
+                    preroll.start();
+
+                    if let Some(feed) = preview.as_mut() {
+                        feed.reset();
+                    }
+                }
+                Some(Switch::ToPlaylist) => {
+                    info!("Switch from live ingest to {}", config.processing.mode);
+                    proc_control.watchdog_pause.store(true, Ordering::SeqCst);
+
+                    if let Err(e) = enc_writer
+                        .write_all(&preroll.flush())
+                        .and_then(|_| enc_writer.flush())
+                    {
+                        error!("Encoder error: {e}")
+                    }
+
+                    if let Some(feed) = preview.as_mut() {
+                        feed.reset();
+                    }
                 }
+                None => {}
+            }
+
+            // when live ingest is on air, read from channel
+            if takeover.is_live() {
+                let receiver = ingest_receiver.as_ref().unwrap();
 
-                for rx in ingest_receiver.as_ref().unwrap().try_iter() {
-                    if let Err(e) = enc_writer.write(&rx.1[..rx.0]) {
-                        error!("Error from Ingest: {:?}", e);
+                if config.ingest.backpressure == Backpressure::DropOldest {
+                    let dropped = drop_stale(receiver);
+
+                    if dropped > 0 {
+                        debug!("Dropped <yellow>{dropped}</> stale ingest package(s)");
+                    }
+                }
+
+                for rx in receiver.try_iter() {
+                    // zero length package is the close message from a draining ingest server
+                    if rx.0 == 0 {
+                        if let Err(e) = enc_writer.flush() {
+                            error!("Encoder error: {e}")
+                        }
 
                         break 'source_iter;
+                    }
+
+                    let Some(data) = preroll.push(&rx.1[..rx.0], Instant::now()) else {
+                        continue;
                     };
+
+                    if let Err(e) = enc_writer.write(data) {
+                        if !proc_control.encoder_stalled.swap(false, Ordering::SeqCst) {
+                            error!("Error from Ingest: {:?}", e);
+
+                            break 'source_iter;
+                        }
+
+                        if !restart_encoder(
+                            config,
+                            &ff_log_format,
+                            &proc_control,
+                            &mut enc_writer,
+                            &mut error_encoder_thread,
+                        ) {
+                            break 'source_iter;
+                        }
+                    };
+
+                    proc_control.watchdog_pause.store(false, Ordering::SeqCst);
+                    // time from the ingest queue until the package is at the encoder
+                    proc_control
+                        .ingest_latency
+                        .lock()
+                        .unwrap()
+                        .push(rx.2.elapsed());
+
+                    if let Some(feed) = preview.as_mut() {
+                        feed.send(data);
+                    }
                 }
             // read from decoder instance
             } else {
-                if live_on {
-                    info!("Switch from live ingest to {}", config.processing.mode);
+                let pip_on = pip_active(
+                    config,
+                    proc_control.server_is_running.load(Ordering::SeqCst),
+                );
+
+                // the picture-in-picture box comes and goes with a new decoder
+                if pip_on != pip_live && !pip_restart {
+                    match pip_on {
+                        true => info!("Live ingest started, restart clip with picture-in-picture"),
+                        false => {
+                            info!("Live ingest stopped, restart clip without picture-in-picture")
+                        }
+                    }
 
-                    if let Err(e) = enc_writer.flush() {
-                        error!("Encoder error: {e}")
+                    pip_restart = true;
+                    playlist_init.store(true, Ordering::SeqCst);
+
+                    if let Err(e) = proc_control.stop(Decoder) {
+                        error!("{e}")
                     }
+                }
+
+                // without takeover the ingest packages are not used, only the picture-in-picture decoder reads them
+                if let Some(rx) = ingest_receiver
+                    .as_ref()
+                    .filter(|_| !config.ingest.takeover && !pip_live)
+                {
+                    rx.try_iter().for_each(drop);
+                }
 
-                    live_on = false;
+                // a paused playout reads nothing, the decoder waits on its full pipe
+                if pause.lock().unwrap().is_some() {
+                    proc_control.watchdog_pause.store(true, Ordering::SeqCst);
+                    sleep(Duration::from_millis(100));
+                    continue;
+                }
+
+                // the next decoder starts at the media time, the current one has delivered
+                if continuous && next_node.is_none() && !pip_live && next_due(&node, &clock) {
+                    let next = get_source.next();
+
+                    if let Some(next) = next.as_ref().filter(|n| n.process == Some(true)) {
+                        match spawn_decoder(
+                            config,
+                            &ff_log_format,
+                            &proc_control,
+                            next.cmd.clone().unwrap_or_default(),
+                            next.filter.clone(),
+                            false,
+                        ) {
+                            Ok((child, (reader, error_thread))) => {
+                                next_decoder = Some(NextDecoder {
+                                    node: next.clone(),
+                                    child,
+                                    reader,
+                                    error_thread,
+                                })
+                            }
+                            Err(e) => {
+                                proc_control.spawn_failed(
+                                    Decoder,
+                                    &config.processing.ffmpeg_path,
+                                    &e,
+                                );
+
+                                break 'source_iter;
+                            }
+                        }
+                    }
+
+                    next_node = Some(next);
                 }
 
                 let dec_bytes_len = match dec_reader.read(&mut buffer[..]) {
@@ -186,26 +519,111 @@ pub fn player(
                 };
 
                 if dec_bytes_len > 0 {
-                    if let Err(e) = enc_writer.write(&buffer[..dec_bytes_len]) {
-                        error!("Encoder write error: {}", e.kind());
+                    delivered = true;
 
-                        break 'source_iter;
+                    if continuous {
+                        clock.push(&buffer[..dec_bytes_len]);
+                    }
+
+                    // the first reads of a new source are held back by the preroll
+                    let Some(data) = preroll.push(&buffer[..dec_bytes_len], Instant::now()) else {
+                        continue;
                     };
+
+                    if let Err(e) = enc_writer.write(data) {
+                        if !proc_control.encoder_stalled.swap(false, Ordering::SeqCst) {
+                            error!("Encoder write error: {}", e.kind());
+                            failure_alert(config, &proc_control, Encoder);
+
+                            break 'source_iter;
+                        }
+
+                        if !restart_encoder(
+                            config,
+                            &ff_log_format,
+                            &proc_control,
+                            &mut enc_writer,
+                            &mut error_encoder_thread,
+                        ) {
+                            break 'source_iter;
+                        }
+                    };
+
+                    proc_control.watchdog_pause.store(false, Ordering::SeqCst);
+
+                    if let Some(feed) = preview.as_mut() {
+                        feed.send(data);
+                    }
                 } else {
+                    // a short clip can end, before the preroll is full
+                    if let Err(e) = enc_writer.write_all(&preroll.flush()) {
+                        error!("Encoder write error: {}", e.kind());
+                    }
+
                     break;
                 }
             }
         }
 
+        let (_, error_decoder_thread) = decoder;
+
         if let Err(e) = proc_control.wait(Decoder) {
             error!("{e}")
         }
 
+        if let Some(feed) = pip_feed.take() {
+            feed.stop();
+        }
+
+        let failed = proc_control.count_restart(Decoder);
+
         if let Err(e) = error_decoder_thread.join() {
             error!("{e:?}");
         };
+
+        // a retry airs the same clip again
+        if !recovery {
+            report_loudness(config, &node, aired);
+        }
+
+        if failed {
+            proc_control.count_clip_failure(&node.source);
+
+            retry = match recovery {
+                true => None,
+                false => recovery_node(&original, failed, delivered),
+            };
+
+            if retry.is_some() {
+                warn!(
+                    "Decoder failed on <b><magenta>{}</></b>, retry with recovery flags",
+                    node.source
+                );
+            } else {
+                if recovery {
+                    error!(
+                        "Recovery of <b><magenta>{}</></b> failed, skip to next clip",
+                        node.source
+                    );
+                }
+
+                failure_alert(config, &proc_control, Decoder);
+            }
+        } else if recovery {
+            info!(
+                "Decoder recovered <b><magenta>{}</></b> with recovery flags",
+                node.source
+            );
+        }
+    }
+
+    if let Some(next) = next_decoder.take() {
+        next.discard();
     }
 
+    // close encoder input, so it can finish the last segment
+    drop(enc_writer);
+    drop(preview);
     sleep(Duration::from_secs(1));
 
     proc_control.stop_all();
@@ -1,50 +1,31 @@
-use std::process::{self, Command, Stdio};
+use std::{
+    io,
+    process::{self, Stdio},
+};
 
 use simplelog::*;
 
-use crate::utils::prepare_output_cmd;
-use ffplayout_lib::{
-    utils::{Media, PlayoutConfig, ProcessUnit::*},
-    vec_strings,
-};
+use super::{commands::encoder_cmd, watchdog::watchdog_enabled};
+use ffplayout_lib::utils::{ffmpeg_command, hide_secrets, PlayoutConfig};
 
 /// Desktop Output
 ///
 /// Instead of streaming, we run a ffplay instance and play on desktop.
-pub fn output(config: &PlayoutConfig, log_format: &str) -> process::Child {
-    let mut media = Media::new(0, "", false);
-    media.unit = Encoder;
-    media.add_filter(config, &None);
-
-    let enc_prefix = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        log_format,
-        "-re",
-        "-i",
-        "pipe:0"
-    ];
-
-    let enc_cmd = prepare_output_cmd(config, enc_prefix, &media.filter);
+pub fn output(config: &PlayoutConfig, log_format: &str) -> io::Result<process::Child> {
+    let (_, enc_cmd) = encoder_cmd(config, log_format);
 
     debug!(
         "Encoder CMD: <bright-blue>\"ffmpeg {}\"</>",
-        enc_cmd.join(" ")
+        hide_secrets(&enc_cmd.join(" "))
     );
 
-    let enc_proc = match Command::new("ffmpeg")
+    ffmpeg_command(config)
         .args(enc_cmd)
         .stdin(Stdio::piped())
+        .stdout(match watchdog_enabled(config) {
+            true => Stdio::piped(),
+            false => Stdio::inherit(),
+        })
         .stderr(Stdio::piped())
         .spawn()
-    {
-        Err(e) => {
-            error!("couldn't spawn encoder process: {e}");
-            panic!("couldn't spawn encoder process: {e}")
-        }
-        Ok(proc) => proc,
-    };
-
-    enc_proc
 }
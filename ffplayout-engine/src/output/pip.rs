@@ -0,0 +1,120 @@
+use std::{
+    io::Write,
+    process::ChildStdin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use simplelog::*;
+
+use ffplayout_lib::{
+    filter::{pip_filter, FilterType::Video, Filters},
+    utils::{spawn_tagged, IngestPackage, PlayoutConfig, ProcessControl},
+    vec_strings,
+};
+
+/// Picture-in-picture is used, when it is enabled and a live stream comes in.
+pub fn pip_active(config: &PlayoutConfig, server_running: bool) -> bool {
+    config.ingest.enable && config.ingest.pip.enable && server_running
+}
+
+/// Add the live ingest as last input of the decoder, with the overlay for its box.
+///
+/// The ingest packages come over stdin of the decoder.
+pub fn pip_cmd(
+    config: &PlayoutConfig,
+    mut input: Vec<String>,
+    filter: Option<Filters>,
+) -> (Vec<String>, Filters) {
+    let pip_input = input.iter().filter(|i| *i == "-i").count();
+    let mut filter = filter.unwrap_or_else(|| Filters::new(config.clone(), 0));
+
+    input.append(&mut vec_strings![
+        "-thread_queue_size",
+        "1024",
+        "-f",
+        "mpegts",
+        "-i",
+        "pipe:0"
+    ]);
+    filter.add_filter(&pip_filter(config, pip_input), 0, Video);
+
+    (input, filter)
+}
+
+/// Feeder of the ingest packages for one picture-in-picture decoder.
+///
+/// It belongs to its decoder, so it must stop with it. Otherwise it would take
+/// the packages away from the feeder of the next decoder.
+pub struct PipFeeder {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PipFeeder {
+    pub fn spawn(
+        dec_writer: ChildStdin,
+        receiver: Receiver<IngestPackage>,
+        proc_control: ProcessControl,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let feeder_stop = stop.clone();
+        let thread =
+            spawn_tagged(move || pip_feeder(dec_writer, receiver, proc_control, feeder_stop));
+
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Stop the feeder and wait for it, after its decoder has ended.
+    pub fn stop(mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+
+        if let Some(thread) = self.thread.take() {
+            if let Err(e) = thread.join() {
+                error!("{e:?}");
+            }
+        }
+    }
+}
+
+impl Drop for PipFeeder {
+    // without a join, the decoder can still run and block the write
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Write the ingest packages to the decoder, until it is gone, the ingest closes or the
+/// feeder gets stopped.
+fn pip_feeder(
+    mut dec_writer: ChildStdin,
+    receiver: Receiver<IngestPackage>,
+    proc_control: ProcessControl,
+    stop: Arc<AtomicBool>,
+) {
+    while !proc_control.is_terminated.load(Ordering::SeqCst) && !stop.load(Ordering::SeqCst) {
+        let package = match receiver.recv_timeout(Duration::from_millis(100)) {
+            Ok(p) => p,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        // zero length package is the close message from a draining ingest server
+        if package.0 == 0 {
+            break;
+        }
+
+        if let Err(e) = dec_writer.write_all(&package.1[..package.0]) {
+            debug!("Picture-in-picture input closed: {}", e.kind());
+            break;
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::{mem, time::Instant};
+
+use ffplayout_lib::utils::MediaClock;
+
+/// Max bytes, which the preroll holds back.
+///
+/// When the encoder stalls, the decoder can't fill the memory up.
+pub const PREROLL_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// Preroll Buffer
+///
+/// Holds back the data of a new source, until it covers the preroll depth in media time.
+/// So a slow first read at a clip switch gets absorbed, before the encoder needs the data.
+///
+/// The encoder reads with `-re` in realtime, so the data is only held back as long as the
+/// encoder has still media from before. Has it nothing left, the data goes out at once.
+pub struct Preroll {
+    depth: f64,
+    max_bytes: usize,
+    filling: bool,
+    held: MediaClock,
+    data: Vec<u8>,
+    ready: Vec<u8>,
+    sent: MediaClock,
+    sent_before: f64,
+    sent_since: Option<Instant>,
+}
+
+impl Preroll {
+    pub fn new(depth_ms: u64) -> Self {
+        Self {
+            depth: depth_ms as f64 / 1000.0,
+            max_bytes: PREROLL_MAX_BYTES,
+            filling: false,
+            held: MediaClock::new(),
+            data: vec![],
+            ready: vec![],
+            sent: MediaClock::new(),
+            sent_before: 0.0,
+            sent_since: None,
+        }
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Start buffering for a new source, a depth of 0 disables it.
+    pub fn start(&mut self) {
+        // the timestamps of the new source start from its own base
+        self.sent_before += self.sent.elapsed();
+        self.sent.reset();
+
+        if self.depth > 0.0 {
+            self.data.clear();
+            self.held.reset();
+            self.filling = true;
+        }
+    }
+
+    pub fn is_filling(&self) -> bool {
+        self.filling
+    }
+
+    /// Media seconds, which the encoder got but has not read yet.
+    ///
+    /// When it is used up, the encoder was waiting and the count starts again from now.
+    fn backlog(&mut self, now: Instant) -> f64 {
+        let since = *self.sent_since.get_or_insert(now);
+        let backlog =
+            self.sent_before + self.sent.elapsed() - now.duration_since(since).as_secs_f64();
+
+        if backlog <= 0.0 {
+            self.sent_before = -self.sent.elapsed();
+            self.sent_since = Some(now);
+        }
+
+        backlog
+    }
+
+    /// Add the data of a read, it returns what can go to the encoder.
+    ///
+    /// While filling nothing is returned, the first return contains the whole preroll.
+    pub fn push<'a>(&'a mut self, data: &'a [u8], now: Instant) -> Option<&'a [u8]> {
+        if !self.filling {
+            self.backlog(now);
+            self.sent.push(data);
+
+            return Some(data);
+        }
+
+        self.data.extend_from_slice(data);
+        self.held.push(data);
+
+        if self.held.elapsed() < self.depth
+            && self.data.len() < self.max_bytes
+            && self.backlog(now) > 0.0
+        {
+            return None;
+        }
+
+        self.filling = false;
+        self.ready = mem::take(&mut self.data);
+        self.sent.push(&self.ready);
+
+        Some(&self.ready)
+    }
+
+    /// Release the held back data, when the source ends before the preroll is full.
+    pub fn flush(&mut self) -> Vec<u8> {
+        self.filling = false;
+        self.sent.push(&self.data);
+
+        mem::take(&mut self.data)
+    }
+}
@@ -0,0 +1,41 @@
+use ffplayout_lib::utils::Media;
+
+/// Input flags for the second decoder run, they skip broken packets and regenerate timestamps.
+pub const RECOVERY_FLAGS: [&str; 4] = [
+    "-err_detect",
+    "ignore_err",
+    "-fflags",
+    "+genpts+discardcorrupt",
+];
+
+/// Add the recovery flags to every input of the command.
+///
+/// They stand right before the `-i`, so they win over the general input flags.
+pub fn recovery_input_cmd(cmd: &[String]) -> Vec<String> {
+    let mut recovery_cmd = vec![];
+
+    for arg in cmd {
+        if arg == "-i" {
+            recovery_cmd.extend(RECOVERY_FLAGS.iter().map(|f| f.to_string()));
+        }
+
+        recovery_cmd.push(arg.clone());
+    }
+
+    recovery_cmd
+}
+
+/// Clip for a second decoder run with the recovery flags.
+///
+/// Only a failed first run, which delivered no data, gets a retry.
+/// After the data is at the encoder, a new run would repeat the clip begin.
+pub fn recovery_node(node: &Media, failed: bool, delivered: bool) -> Option<Media> {
+    if !failed || delivered {
+        return None;
+    }
+
+    let mut retry = node.clone();
+    retry.cmd = node.cmd.as_deref().map(recovery_input_cmd);
+
+    Some(retry)
+}
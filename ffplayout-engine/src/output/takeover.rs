@@ -0,0 +1,47 @@
+use ffplayout_lib::utils::PlayoutConfig;
+
+/// Input change for the encoder.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Switch {
+    ToLive,
+    ToPlaylist,
+}
+
+/// Live override
+///
+/// Decides from the ingest state, whether the encoder gets the live stream or the playlist.
+/// Both are written to the same encoder input, so the output timestamps continue.
+#[derive(Debug, Clone, Copy)]
+pub struct Takeover {
+    enabled: bool,
+    live_on: bool,
+}
+
+impl Takeover {
+    pub fn new(config: &PlayoutConfig) -> Self {
+        Self {
+            enabled: config.ingest.enable && config.ingest.takeover,
+            live_on: false,
+        }
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.live_on
+    }
+
+    /// Update with the current ingest state, returns the switch when the input changes.
+    pub fn update(&mut self, server_running: bool) -> Option<Switch> {
+        let live = self.enabled && server_running;
+
+        if live == self.live_on {
+            return None;
+        }
+
+        self.live_on = live;
+
+        match live {
+            true => Some(Switch::ToLive),
+            false => Some(Switch::ToPlaylist),
+        }
+    }
+}
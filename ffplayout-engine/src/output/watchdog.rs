@@ -0,0 +1,93 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::ChildStdout,
+    sync::atomic::Ordering,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use simplelog::*;
+
+use ffplayout_lib::{
+    utils::{OutputMode::*, PlayoutConfig, ProcessControl, ProcessUnit::*},
+    vec_strings,
+};
+
+/// Watchdog works only with a ffmpeg encoder, which reports its progress.
+pub fn watchdog_enabled(config: &PlayoutConfig) -> bool {
+    config.processing.stall_timeout_seconds > 0 && [Dash, Null, Stream].contains(&config.out.mode)
+}
+
+/// Progress parameter for the encoder, it writes the report to stdout.
+pub fn progress_cmd(config: &PlayoutConfig) -> Vec<String> {
+    match watchdog_enabled(config) {
+        true => vec_strings!["-progress", "pipe:1"],
+        false => vec![],
+    }
+}
+
+/// Get the output time from a progress line, like: `out_time_ms=1200000`
+pub fn out_time(line: &str) -> Option<u64> {
+    line.strip_prefix("out_time_ms=")
+        .and_then(|t| t.trim().parse().ok())
+}
+
+/// Read the progress report from encoder, every advance of the output time gets registered.
+pub fn progress_reader(buffer: BufReader<ChildStdout>, proc_control: ProcessControl) {
+    let mut last_time = 0;
+
+    for line in buffer.lines().map_while(Result::ok) {
+        if let Some(time) = out_time(&line).filter(|t| *t > last_time) {
+            last_time = time;
+            *proc_control.last_progress.lock().unwrap() = Instant::now();
+        }
+    }
+}
+
+/// Check if the encoder has no progress since the stall timeout, then kill it.
+/// The player spawns a new encoder, when it can't write to the killed one.
+///
+/// While the ingest switch is running, the watchdog is paused.
+pub fn check_stall(config: &PlayoutConfig, proc_control: &ProcessControl) -> bool {
+    let timeout = Duration::from_secs(config.processing.stall_timeout_seconds);
+    let mut last_progress = proc_control.last_progress.lock().unwrap();
+
+    if proc_control.watchdog_pause.load(Ordering::SeqCst) {
+        *last_progress = Instant::now();
+        return false;
+    }
+
+    if last_progress.elapsed() < timeout {
+        return false;
+    }
+
+    error!(
+        "Encoder has no progress since <yellow>{}</> seconds, restart it",
+        timeout.as_secs()
+    );
+
+    // give the new encoder the full timeout for starting
+    *last_progress = Instant::now();
+    drop(last_progress);
+
+    proc_control.encoder_stalled.store(true, Ordering::SeqCst);
+
+    if let Err(e) = proc_control.stop(Encoder) {
+        error!("{e}")
+    }
+
+    true
+}
+
+/// Encoder Watchdog
+///
+/// Detects a frozen encoder, which is still alive, but produce no frames.
+pub fn watchdog(config: PlayoutConfig, proc_control: ProcessControl) {
+    *proc_control.last_progress.lock().unwrap() = Instant::now();
+
+    while !proc_control.is_terminated.load(Ordering::SeqCst) {
+        check_stall(&config, &proc_control);
+
+        sleep(Duration::from_secs(1));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{fmt, process::exit, sync::atomic::Ordering};
+use std::{fmt, sync::atomic::Ordering};
 
 mod zmq_cmd;
 
@@ -400,8 +400,6 @@ pub fn json_rpc_server(
         Err(e) => {
             error!("Unable to start RPC server: {e}");
             proc_control.stop_all();
-
-            exit(1);
         }
     };
 }
@@ -0,0 +1,83 @@
+use std::{
+    fs,
+    path::Path,
+    sync::atomic::Ordering,
+    thread::sleep,
+    time::{Duration, Instant, SystemTime},
+};
+
+use simplelog::*;
+
+use ffplayout_lib::utils::{spawn_tagged, PlayoutConfig, ProcessControl};
+
+/// Time between two cleanups of the archive folder.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Delete the recordings in the archive folder, which are older then `max_age`.
+///
+/// Returns the number of deleted files.
+pub fn prune_archive(dir: &Path, max_age: Duration) -> usize {
+    let now = SystemTime::now();
+    let mut count = 0;
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|m| now.duration_since(m).ok())
+            .is_some_and(|age| age > max_age);
+
+        if path.is_file() && expired {
+            match fs::remove_file(&path) {
+                Ok(_) => count += 1,
+                Err(e) => error!("Unable to delete archive file {path:?}: {e}"),
+            }
+        }
+    }
+
+    count
+}
+
+/// Create the archive folder, and when a max age is set, prune old recordings every hour.
+pub fn init_archive(config: &PlayoutConfig, proc_control: &ProcessControl) {
+    let archive = config.out.archive.clone();
+
+    if let Err(e) = fs::create_dir_all(&archive.dir) {
+        error!(
+            "Unable to create archive folder <b><magenta>{}</></b>: {e}",
+            archive.dir
+        );
+        return;
+    }
+
+    if archive.max_age_days == 0 {
+        return;
+    }
+
+    let max_age = Duration::from_secs(archive.max_age_days * 86400);
+    let is_terminated = proc_control.is_terminated.clone();
+
+    spawn_tagged(move || {
+        let mut last_run: Option<Instant> = None;
+
+        while !is_terminated.load(Ordering::SeqCst) {
+            if last_run.is_none_or(|t| t.elapsed() >= PRUNE_INTERVAL) {
+                let count = prune_archive(Path::new(&archive.dir), max_age);
+
+                if count > 0 {
+                    info!("Deleted <yellow>{count}</> old archive files");
+                }
+
+                last_run = Some(Instant::now());
+            }
+
+            sleep(Duration::from_secs(1));
+        }
+    });
+}
@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 use ffplayout_lib::utils::{OutputMode, ProcessMode};
 
@@ -9,12 +9,22 @@ use ffplayout_lib::utils::{OutputMode, ProcessMode};
                       \n    ffplayout (ARGS) [OPTIONS]\n\n    Pass channel name only in multi channel environment!",
     long_about = None)]
 pub struct Args {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
     #[clap(index = 1, value_parser, help = "Channel name")]
     pub channel: Option<String>,
 
-    #[clap(short, long, help = "File path to ffplayout.yml")]
+    #[clap(short, long, global = true, help = "File path to ffplayout.yml")]
     pub config: Option<String>,
 
+    #[clap(
+        long,
+        help = "Run multiple channels in one process, by channel names",
+        multiple_values = true
+    )]
+    pub channels: Option<Vec<String>>,
+
     #[clap(short, long, help = "File path for logging")]
     pub log: Option<String>,
 
@@ -34,7 +44,7 @@ pub struct Args {
     )]
     pub paths: Option<Vec<String>>,
 
-    #[clap(short = 'm', long, help = "Playing mode: folder, playlist")]
+    #[clap(short = 'm', long, help = "Playing mode: folder, playlist, loop")]
     pub play_mode: Option<ProcessMode>,
 
     #[clap(short, long, help = "Play folder content")]
@@ -43,7 +53,8 @@ pub struct Args {
     #[clap(
         short,
         long,
-        help = "Target date (YYYY-MM-DD) for text/m3u to playlist import"
+        global = true,
+        help = "Target date (YYYY-MM-DD) for text/m3u to playlist import, dry run or validate"
     )]
     pub date: Option<String>,
 
@@ -73,9 +84,25 @@ pub struct Args {
     #[clap(short, long, help = "Loop playlist infinitely")]
     pub infinit: bool,
 
-    #[clap(short, long, help = "Set output mode: desktop, hls, null, stream")]
+    #[clap(
+        short,
+        long,
+        help = "Set output mode: desktop, hls, dash, null, stream"
+    )]
     pub output: Option<OutputMode>,
 
+    #[clap(
+        long,
+        help = "Decode and filter playlist to null output, without playout. Exit with 1 on errors"
+    )]
+    pub dry_run: bool,
+
+    #[clap(
+        long,
+        help = "Print the ffmpeg commands for the first clip, without running them"
+    )]
+    pub print_commands: bool,
+
     #[clap(short, long, help = "Set audio volume")]
     pub volume: Option<f64>,
 
@@ -84,6 +111,17 @@ pub struct Args {
     pub fake_time: Option<String>,
 }
 
+/// Operation of the run, without a command the playout runs like with `play`.
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Load and validate config and playlist, exit with 1 on errors
+    Validate,
+    /// Run the playout
+    Play,
+    /// Run the playout, with the control and metrics servers
+    Serve,
+}
+
 /// Get arguments from command line, and return them.
 #[cfg(not(test))]
 pub fn get_args() -> Args {
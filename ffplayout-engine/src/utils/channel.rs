@@ -0,0 +1,249 @@
+use std::{
+    fs::{self, File},
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::{self, JoinHandle},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+#[cfg(unix)]
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+};
+use simplelog::*;
+
+#[cfg(unix)]
+use crate::utils::reload::reload_handler;
+use crate::{
+    output::{player, write_hls},
+    rpc::json_rpc_server,
+    utils::{
+        archive::init_archive,
+        control::control_server,
+        logo::{logo_paths, logo_watchman},
+        metrics::metrics_server,
+        resume::{current_state, resume_writer, write_resume},
+    },
+};
+use ffplayout_lib::utils::{
+    child_reaper, set_ffprobe_path, set_log_channel, spawn_tagged, OutputMode::*, PlayerControl,
+    PlayoutConfig, PlayoutStatus, ProcessControl, ProcessMode::*,
+};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Serialize, Deserialize)]
+struct StatusData {
+    time_shift: f64,
+    date: String,
+}
+
+/// Here we create a status file in temp folder.
+/// We need this for reading/saving program status.
+/// For example when we skip a playing file,
+/// we save the time difference, so we stay in sync.
+///
+/// When file not exists we create it, and when it exists we get its values.
+fn status_file(stat_file: &str, playout_stat: &PlayoutStatus) {
+    debug!("Start ffplayout v{VERSION}, status file path: <b><magenta>{stat_file}</></b>");
+
+    if !PathBuf::from(stat_file).exists() {
+        let data = json!({
+            "time_shift": 0.0,
+            "date": String::new(),
+        });
+
+        let json: String = serde_json::to_string(&data).expect("Serialize status data failed");
+        if let Err(e) = fs::write(stat_file, json) {
+            error!("Unable to write to status file <b><magenta>{stat_file}</></b>: {e}");
+        };
+    } else {
+        let stat_file = File::options()
+            .read(true)
+            .write(false)
+            .open(stat_file)
+            .expect("Could not open status file");
+
+        let data: StatusData =
+            serde_json::from_reader(stat_file).expect("Could not read status file.");
+
+        *playout_stat.time_shift.lock().unwrap() = data.time_shift;
+        *playout_stat.date.lock().unwrap() = data.date;
+    }
+}
+
+/// Start the servers for one channel and run its playout, until it is done.
+pub fn run_playout(
+    config: PlayoutConfig,
+    play_control: PlayerControl,
+    playout_stat: PlayoutStatus,
+    proc_control: ProcessControl,
+) {
+    if config.rpc_server.enable {
+        // If RPC server is enable we also fire up a JSON RPC server.
+        let rpc_config = config.clone();
+        let play_ctl = play_control.clone();
+        let play_stat = playout_stat.clone();
+        let proc_ctl = proc_control.clone();
+
+        spawn_tagged(move || json_rpc_server(rpc_config, play_ctl, play_stat, proc_ctl));
+    }
+
+    if config.metrics.enable {
+        // serve playout state for prometheus, in its own thread
+        let metrics_config = config.clone();
+        let proc_ctl = proc_control.clone();
+
+        spawn_tagged(move || metrics_server(metrics_config, proc_ctl));
+    }
+
+    if config.control.enable {
+        let control_config = config.clone();
+        let play_ctl = play_control.clone();
+        let play_stat = playout_stat.clone();
+        let proc_ctl = proc_control.clone();
+
+        spawn_tagged(move || control_server(control_config, play_ctl, play_stat, proc_ctl));
+    }
+
+    status_file(&config.general.stat_file, &playout_stat);
+
+    let reaper_ctl = proc_control.clone();
+    spawn_tagged(move || child_reaper(reaper_ctl));
+
+    let resume = config.general.resume_on_restart && config.processing.mode == Playlist;
+    let resume_ctl = resume.then(|| (play_control.clone(), playout_stat.clone()));
+
+    if resume {
+        let resume_config = config.clone();
+        let play_ctl = play_control.clone();
+        let play_stat = playout_stat.clone();
+        let proc_ctl = proc_control.clone();
+
+        spawn_tagged(move || resume_writer(resume_config, play_ctl, play_stat, proc_ctl));
+    }
+
+    if !logo_paths(&config).is_empty() {
+        // reload filters, when a logo file changes
+        let logo_config = config.clone();
+        let play_stat = playout_stat.clone();
+        let proc_ctl = proc_control.clone();
+
+        spawn_tagged(move || logo_watchman(logo_config, play_stat, proc_ctl));
+    }
+
+    #[cfg(unix)]
+    reload_handler(config.clone(), playout_stat.clone(), proc_control.clone());
+
+    if config.out.mode == Stream && !config.out.archive.dir.is_empty() {
+        init_archive(&config, &proc_control);
+    }
+
+    match config.out.mode {
+        // write files/playlist to HLS m3u8 playlist
+        HLS => write_hls(&config, play_control, playout_stat, proc_control),
+        // play on desktop or stream to a remote target
+        _ => player(&config, play_control, playout_stat, proc_control),
+    }
+
+    // save the last position, the resume writer gets no chance any more
+    if let Some(state) =
+        resume_ctl.and_then(|(play_ctl, play_stat)| current_state(&config, &play_ctl, &play_stat))
+    {
+        write_resume(&config, &state);
+    }
+}
+
+/// On termination let a running ingest drain first, when it is already terminated stop everything.
+pub fn graceful_stop(proc_control: &ProcessControl) {
+    if proc_control.server_is_running.load(Ordering::SeqCst)
+        && !proc_control.is_terminated.load(Ordering::SeqCst)
+    {
+        proc_control.is_terminated.store(true, Ordering::SeqCst);
+    } else {
+        proc_control.stop_all();
+    }
+}
+
+/// Stop the channels on SIGINT/SIGTERM, see [`graceful_stop`].
+///
+/// Only the first signal stops them, every further signal exits the process at once,
+/// also while a live ingest still drains. The same happens, when all channels are
+/// stopped already and the shutdown hangs. SIGHUP is left for the config reload.
+pub fn set_shutdown_handler(controls: Vec<ProcessControl>) {
+    let received = AtomicUsize::new(0);
+
+    let handler = move || {
+        if received.fetch_add(1, Ordering::SeqCst) > 0
+            || controls.iter().all(|c| !c.is_alive.load(Ordering::SeqCst))
+        {
+            warn!("Shutdown is still running, force exit");
+            log::logger().flush();
+            process::exit(1);
+        }
+
+        controls.iter().for_each(graceful_stop);
+    };
+
+    #[cfg(unix)]
+    match Signals::new([SIGINT, SIGTERM]) {
+        Ok(mut signals) => {
+            thread::spawn(move || signals.forever().for_each(|_| handler()));
+        }
+        Err(e) => error!("Unable to set signal handler: {e}"),
+    }
+
+    #[cfg(windows)]
+    if let Err(e) = ctrlc::set_handler(handler) {
+        error!("Unable to set signal handler: {e}");
+    }
+}
+
+/// Playout channel, which runs in its own thread with its own process control.
+pub struct Channel {
+    pub name: String,
+    pub proc_control: ProcessControl,
+    handle: JoinHandle<()>,
+}
+
+impl Channel {
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Wait until the channel playout is done.
+    pub fn join(self) {
+        if let Err(e) = self.handle.join() {
+            error!("Channel <b><magenta>{}</></b> failed: {e:?}", self.name);
+        }
+    }
+}
+
+/// Spawn a independent channel pipeline, with its own controls.
+///
+/// The runner is normally [`run_playout`].
+pub fn spawn_channel<F>(config: PlayoutConfig, runner: F) -> Channel
+where
+    F: FnOnce(PlayoutConfig, PlayerControl, PlayoutStatus, ProcessControl) + Send + 'static,
+{
+    let name = config.general.channel.clone();
+    let proc_control = ProcessControl::new();
+    let proc_ctl = proc_control.clone();
+
+    info!("Start channel: <b><magenta>{name}</></b>");
+
+    let handle = thread::spawn(move || {
+        set_log_channel(&config.general.channel);
+        set_ffprobe_path(&config.processing.ffprobe_path);
+        runner(config, PlayerControl::new(), PlayoutStatus::new(), proc_ctl)
+    });
+
+    Channel {
+        name,
+        proc_control,
+        handle,
+    }
+}
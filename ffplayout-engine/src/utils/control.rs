@@ -0,0 +1,457 @@
+use std::{
+    io::ErrorKind,
+    net::{TcpListener, TcpStream},
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use simplelog::*;
+
+use crate::input::playlist::realtime_position;
+use crate::utils::{
+    http::{read_request, serve, write_response},
+    logo::reload_filters,
+};
+use ffplayout_lib::utils::{
+    get_delta, parse_playlist, write_playlist, write_status, CensorRegion, Media, OutputMode::*,
+    PlayerControl, PlaylistMode, PlayoutConfig, PlayoutStatus, ProcessControl,
+    ProcessMode::Playlist, ProcessUnit::*,
+};
+
+/// Stop the current decoder, the playout continues with the next clip in sync.
+fn next_clip(
+    config: &PlayoutConfig,
+    play_control: &PlayerControl,
+    playout_stat: &PlayoutStatus,
+    proc_control: &ProcessControl,
+) -> Result<Value, String> {
+    let index = play_control.index.load(Ordering::SeqCst);
+    let current_list = play_control.current_list.lock().unwrap();
+
+    if index >= current_list.len() {
+        return Err("Last clip can not be skipped".to_string());
+    }
+
+    let mut media = current_list[index].clone();
+    drop(current_list);
+
+    proc_control.stop(Decoder)?;
+    info!("Move to next clip");
+
+    media.add_probe();
+
+    let (delta, _) = get_delta(config, &media.begin.unwrap_or(0.0));
+    let current_date = playout_stat.current_date.lock().unwrap().clone();
+    *playout_stat.time_shift.lock().unwrap() = delta;
+    *playout_stat.date.lock().unwrap() = current_date.clone();
+    write_status(config, &current_date, delta);
+
+    Ok(json!({
+        "operation": "move_to_next",
+        "shifted_seconds": delta,
+        "source": media.source,
+    }))
+}
+
+/// Target of a seek request, like `{"index": 5}` or `{"time": "20:00:00"}`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum SeekTarget {
+    Index { index: usize },
+    Time { time: String },
+}
+
+/// Seconds from a strict `HH:MM:SS` time, fractions of seconds are allowed.
+fn day_time(time: &str) -> Option<f64> {
+    let parts: Vec<&str> = time.split(':').collect();
+
+    let [h, m, s] = parts[..] else {
+        return None;
+    };
+    let h = h.parse::<u8>().ok().filter(|h| *h < 24)?;
+    let m = m.parse::<u8>().ok().filter(|m| *m < 60)?;
+    let s = s.parse::<f64>().ok().filter(|s| (0.0..60.0).contains(s))?;
+
+    Some(h as f64 * 3600.0 + m as f64 * 60.0 + s)
+}
+
+/// Clip index and the offset in it, for the seek target.
+///
+/// Times before the playlist start belong to the next day, like in the playlist.
+pub fn seek_position(
+    config: &PlayoutConfig,
+    nodes: &[Media],
+    target: &SeekTarget,
+) -> Result<(usize, f64), String> {
+    match target {
+        SeekTarget::Index { index } if *index < nodes.len() => Ok((*index, 0.0)),
+        SeekTarget::Index { index } => Err(format!(
+            "Index {index} is out of range, playlist has {} clips",
+            nodes.len()
+        )),
+        SeekTarget::Time { time } => {
+            let mut time_sec =
+                day_time(time).ok_or_else(|| format!("Time {time} is not like HH:MM:SS"))?;
+
+            if time_sec < config.playlist.start_sec.unwrap_or(0.0) {
+                time_sec += config.playlist.length_sec.unwrap_or(86400.0);
+            }
+
+            realtime_position(nodes, time_sec)
+                .ok_or_else(|| format!("Time {time} is not in the playlist"))
+        }
+    }
+}
+
+/// Jump to a clip of the playlist. In realtime mode a time seeks also into the clip.
+///
+/// The time shift gets updated, so the playout stays in sync with the new position.
+fn seek(
+    config: &PlayoutConfig,
+    play_control: &PlayerControl,
+    playout_stat: &PlayoutStatus,
+    proc_control: &ProcessControl,
+    body: &[u8],
+) -> Result<Value, String> {
+    if config.processing.mode != Playlist {
+        return Err("Seek works only in playlist mode".to_string());
+    }
+
+    let target: SeekTarget = serde_json::from_slice(body)
+        .map_err(|_| "Body needs a index, or a time like HH:MM:SS".to_string())?;
+    let current_list = play_control.current_list.lock().unwrap();
+    let (index, mut offset) = seek_position(config, &current_list, &target)?;
+    let media = current_list[index].clone();
+    drop(current_list);
+
+    if config.playlist.mode == PlaylistMode::Ordered {
+        offset = 0.0;
+    }
+
+    let (delta, _) = get_delta(config, &(media.begin.unwrap_or(0.0) + offset));
+    let current_date = playout_stat.current_date.lock().unwrap().clone();
+    *playout_stat.time_shift.lock().unwrap() = delta;
+    *playout_stat.date.lock().unwrap() = current_date.clone();
+    write_status(config, &current_date, delta);
+
+    // with a offset the playlist searches the position again, like on start
+    if offset > 0.0 {
+        playout_stat.list_init.store(true, Ordering::SeqCst);
+    } else {
+        play_control.index.store(index, Ordering::SeqCst);
+    }
+
+    proc_control.stop(Decoder)?;
+    info!(
+        "Seek to clip <yellow>{index}</> at <yellow>{offset:.3}</> seconds: <b><magenta>{}</></b>",
+        media.source
+    );
+
+    Ok(json!({
+        "operation": "seek",
+        "index": index,
+        "offset": offset,
+        "shifted_seconds": delta,
+        "source": media.source,
+    }))
+}
+
+/// Request a ad break, which starts at the next clip in the hls output.
+fn ad_break(
+    config: &PlayoutConfig,
+    playout_stat: &PlayoutStatus,
+    duration: Option<&str>,
+) -> Result<Value, String> {
+    if config.out.mode != HLS {
+        return Err("Ad break works only in HLS mode".to_string());
+    }
+
+    let duration = match duration.and_then(|d| d.parse::<f64>().ok()) {
+        Some(d) if d > 0.0 => d,
+        _ => return Err("Missing or invalid duration".to_string()),
+    };
+
+    info!("Ad break for <yellow>{duration}</> seconds on next clip");
+    *playout_stat.ad_break.lock().unwrap() = Some(duration);
+
+    Ok(json!({
+        "operation": "ad_break",
+        "duration": duration,
+    }))
+}
+
+/// Hold the playout on the current frame, the decoder waits until resume.
+fn pause(config: &PlayoutConfig, playout_stat: &PlayoutStatus) -> Result<Value, String> {
+    if config.out.mode == HLS {
+        return Err("Pause works not in HLS mode".to_string());
+    }
+
+    let mut pause = playout_stat.pause.lock().unwrap();
+
+    if pause.is_some() {
+        return Err("Playout is already paused".to_string());
+    }
+
+    info!("Pause playout");
+    *pause = Some(Instant::now());
+
+    Ok(json!({"operation": "pause"}))
+}
+
+/// Continue a paused playout, the paused time shifts the playlist.
+fn resume(config: &PlayoutConfig, playout_stat: &PlayoutStatus) -> Result<Value, String> {
+    let Some(start) = playout_stat.pause.lock().unwrap().take() else {
+        return Err("Playout is not paused".to_string());
+    };
+
+    let paused = start.elapsed().as_secs_f64();
+    let current_date = playout_stat.current_date.lock().unwrap().clone();
+    let mut time_shift = playout_stat.time_shift.lock().unwrap();
+
+    *time_shift -= paused;
+    write_status(config, &current_date, *time_shift);
+
+    info!("Resume playout after <yellow>{paused:.3}</> seconds");
+
+    Ok(json!({
+        "operation": "resume",
+        "paused_seconds": paused,
+    }))
+}
+
+/// Add a censor region to the running playout, it stays until it gets cleared.
+fn add_censor(
+    config: &PlayoutConfig,
+    playout_stat: &PlayoutStatus,
+    proc_control: &ProcessControl,
+    body: &[u8],
+) -> Result<Value, String> {
+    let region: CensorRegion =
+        serde_json::from_slice(body).map_err(|e| format!("Invalid censor region: {e}"))?;
+    region.check(config.processing.width, config.processing.height)?;
+
+    // one lock, so parallel requests can't overwrite each other's region
+    let mut live = config.live.write().unwrap();
+    let regions = live.censor.get_or_insert_with(Vec::new);
+    regions.push(region);
+
+    let count = regions.len()
+        + live
+            .config_censor
+            .as_ref()
+            .unwrap_or(&config.processing.censor)
+            .len();
+    drop(live);
+
+    info!("Add censor region, now <yellow>{count}</> active");
+    reload_filters(playout_stat, proc_control);
+
+    Ok(json!({
+        "operation": "add_censor",
+        "regions": count,
+    }))
+}
+
+/// Remove the regions from the control API, the ones from the config stay.
+fn clear_censor(
+    config: &PlayoutConfig,
+    playout_stat: &PlayoutStatus,
+    proc_control: &ProcessControl,
+) -> Result<Value, String> {
+    if config.live.write().unwrap().censor.take().is_none() {
+        return Err("No censor region was added".to_string());
+    }
+
+    info!("Clear censor regions");
+    reload_filters(playout_stat, proc_control);
+
+    Ok(json!({"operation": "clear_censor"}))
+}
+
+/// Start the ingest server again, after it was stopped by the idle timeout.
+fn start_ingest(config: &PlayoutConfig, proc_control: &ProcessControl) -> Result<Value, String> {
+    if !config.ingest.enable {
+        return Err("Ingest is disabled!".to_string());
+    }
+
+    if !proc_control.ingest_idle.swap(false, Ordering::SeqCst) {
+        return Err("Ingest server is already listening!".to_string());
+    }
+
+    info!("Start ingest server again");
+
+    Ok(json!({"operation": "start_ingest"}))
+}
+
+/// Replace the playlist with the one from the body, and reload it on the next clip.
+///
+/// Invalid playlists are rejected with their errors, nothing gets written then.
+fn put_playlist(
+    config: &PlayoutConfig,
+    playout_stat: &PlayoutStatus,
+    body: &[u8],
+) -> Result<Value, (&'static str, Value)> {
+    if config.processing.mode != Playlist {
+        return Err((
+            "400 Bad Request",
+            json!({"error": "Playlist upload works only in playlist mode!"}),
+        ));
+    }
+
+    let playlist = parse_playlist(&String::from_utf8_lossy(body), true)
+        .map_err(|errors| ("422 Unprocessable Entity", json!({ "errors": errors })))?;
+
+    let path = write_playlist(config, &playlist).map_err(|e| {
+        // a wrong date is an error of the playlist, not of the server
+        if e.kind() == ErrorKind::InvalidInput {
+            return ("422 Unprocessable Entity", json!({"error": e.to_string()}));
+        }
+
+        error!("Write playlist failed: {e}");
+
+        (
+            "500 Internal Server Error",
+            json!({"error": format!("Write playlist failed: {e}")}),
+        )
+    })?;
+
+    info!(
+        "Playlist from <yellow>{}</> replaced, reload on next clip: <b><magenta>{}</></b>",
+        playlist.date,
+        path.display()
+    );
+    playout_stat.reload.store(true, Ordering::SeqCst);
+
+    Ok(json!({"operation": "put_playlist", "path": path, "date": playlist.date}))
+}
+
+/// Current clip and ingest state.
+pub fn status(proc_control: &ProcessControl) -> Value {
+    let status = proc_control.channel_status();
+    let latency = proc_control.ingest_latency.lock().unwrap().clone();
+    let ms = |d: Option<Duration>| d.map(|d| d.as_secs_f64() * 1000.0);
+
+    json!({
+        "source": status.source,
+        "index": status.index,
+        "elapsed_sec": status.elapsed,
+        "remaining_sec": status.remaining,
+        "ingest_runs": status.ingest_active,
+        "last_error": status.last_error,
+        "clip_failures": status.clip_failures,
+        "uptime_sec": status.uptime,
+        "ingest_latency_p50_ms": ms(latency.p50()),
+        "ingest_latency_p95_ms": ms(latency.p95()),
+    })
+}
+
+fn handle_request(
+    stream: &TcpStream,
+    config: &PlayoutConfig,
+    play_control: &PlayerControl,
+    playout_stat: &PlayoutStatus,
+    proc_control: &ProcessControl,
+) -> std::io::Result<()> {
+    let mut request = read_request(stream)?;
+    let token = &config.control.token;
+    let bearer = format!("Bearer {token}");
+
+    if token.is_empty() || request.header("authorization") != Some(bearer.as_str()) {
+        return write_response(
+            stream,
+            "401 Unauthorized",
+            "application/json",
+            &json!({"error": "No authorization header or valid token found!"}).to_string(),
+        );
+    }
+
+    // the body is only read for clients with a valid token
+    request.read_body()?;
+
+    let result = match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/control/next") => next_clip(config, play_control, playout_stat, proc_control),
+        ("POST", "/control/reload") => {
+            info!("Reload playlist on next clip");
+            playout_stat.reload.store(true, Ordering::SeqCst);
+
+            Ok(json!({"operation": "reload_playlist"}))
+        }
+        ("POST", "/control/filters") => {
+            reload_filters(playout_stat, proc_control);
+
+            Ok(json!({"operation": "reload_filters"}))
+        }
+        ("POST", "/control/seek") => seek(
+            config,
+            play_control,
+            playout_stat,
+            proc_control,
+            &request.body,
+        ),
+        ("POST", "/control/pause") => pause(config, playout_stat),
+        ("POST", "/control/resume") => resume(config, playout_stat),
+        ("POST", "/control/ingest") => start_ingest(config, proc_control),
+        ("POST", "/control/censor") => {
+            add_censor(config, playout_stat, proc_control, &request.body)
+        }
+        ("DELETE", "/control/censor") => clear_censor(config, playout_stat, proc_control),
+        ("POST", "/control/adbreak") => ad_break(config, playout_stat, request.query("duration")),
+        ("GET", "/status") => Ok(status(proc_control)),
+        ("PUT", "/playlist") => {
+            let (code, value) = match put_playlist(config, playout_stat, &request.body) {
+                Ok(value) => ("200 OK", value),
+                Err(e) => e,
+            };
+
+            return write_response(stream, code, "application/json", &value.to_string());
+        }
+        _ => {
+            return write_response(
+                stream,
+                "404 Not Found",
+                "application/json",
+                &json!({"error": "Not found"}).to_string(),
+            )
+        }
+    };
+
+    match result {
+        Ok(value) => write_response(stream, "200 OK", "application/json", &value.to_string()),
+        Err(e) => write_response(
+            stream,
+            "400 Bad Request",
+            "application/json",
+            &json!({ "error": e }).to_string(),
+        ),
+    }
+}
+
+/// Control Server
+///
+/// Small REST server, for skipping the current clip, reloading or replacing the playlist,
+/// reloading filters, seeking to a clip or time, ad breaks, starting an idle ingest server
+/// and getting the status.
+pub fn control_server(
+    config: PlayoutConfig,
+    play_control: PlayerControl,
+    playout_stat: PlayoutStatus,
+    proc_control: ProcessControl,
+) {
+    let addr = config.control.address.clone();
+
+    let listener = match TcpListener::bind(&addr) {
+        Ok(l) => l,
+        Err(e) => {
+            error!("Unable to start control server: {e}");
+            return;
+        }
+    };
+
+    info!("Run control server, listening on: <b><magenta>http://{addr}</></b>");
+
+    serve(listener, "Control", move |stream| {
+        handle_request(stream, &config, &play_control, &playout_stat, &proc_control)
+    });
+}
@@ -0,0 +1,224 @@
+use std::{
+    io::{self, BufRead, BufReader, Error, ErrorKind, Read, Result, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use simplelog::*;
+
+use ffplayout_lib::utils::spawn_tagged;
+
+/// Time for reading the whole request and for writing the response.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Limit for the request line with all headers.
+pub const MAX_HEAD_LEN: u64 = 16 * 1024;
+
+/// Limit for the body, it is enough for a playlist of some thousand clips.
+pub const MAX_BODY_LEN: u64 = 16 * 1024 * 1024;
+
+/// Connections, which are handled at the same time.
+pub const MAX_CONNECTIONS: usize = 32;
+
+/// Minimal HTTP request, only what our small servers need.
+///
+/// The body stays in the stream, until [`Request::read_body`] reads it.
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    query: String,
+    headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    reader: BufReader<DeadlineStream>,
+}
+
+/// Stream for reading a request, it fails after the deadline, so a slow client can not hold the connection.
+struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+
+        if left.is_zero() {
+            return Err(Error::new(ErrorKind::TimedOut, "request timed out"));
+        }
+
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Request {
+    /// Get header value, the name is case insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Get value from query string, like `duration` in `/path?duration=30`.
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .split('&')
+            .filter_map(|q| q.split_once('='))
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v)
+    }
+
+    /// Read the body with the length from the header, a bigger one than [`MAX_BODY_LEN`] is an error.
+    pub fn read_body(&mut self) -> Result<()> {
+        let len = match self.header("content-length") {
+            Some(l) => l
+                .parse::<u64>()
+                .map_err(|_| Error::new(ErrorKind::InvalidInput, "invalid content length"))?,
+            None => return Ok(()),
+        };
+
+        if len > MAX_BODY_LEN {
+            return Err(too_large("body"));
+        }
+
+        (&mut self.reader).take(len).read_to_end(&mut self.body)?;
+
+        Ok(())
+    }
+}
+
+fn too_large(part: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("request {part} is too large"),
+    )
+}
+
+/// Read one line of the head, the remaining length gets reduced.
+fn read_head_line(reader: &mut BufReader<DeadlineStream>, remaining: &mut u64) -> Result<String> {
+    let mut line = vec![];
+    let len = reader.take(*remaining).read_until(b'\n', &mut line)? as u64;
+    *remaining -= len;
+
+    if !line.ends_with(b"\n") && *remaining == 0 {
+        return Err(too_large("head"));
+    }
+
+    Ok(String::from_utf8_lossy(&line).to_string())
+}
+
+/// Read request line and headers, with at most [`MAX_HEAD_LEN`] bytes.
+///
+/// The whole request, with the body, must arrive in [`REQUEST_TIMEOUT`].
+pub fn read_request(stream: &TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(DeadlineStream {
+        stream: stream.try_clone()?,
+        deadline: Instant::now() + REQUEST_TIMEOUT,
+    });
+    let mut remaining = MAX_HEAD_LEN;
+    let mut headers = vec![];
+    let request_line = read_head_line(&mut reader, &mut remaining)?;
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    loop {
+        let line = read_head_line(&mut reader, &mut remaining)?;
+
+        if line.trim().is_empty() {
+            break;
+        }
+
+        if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        headers,
+        body: vec![],
+        reader,
+    })
+}
+
+pub fn write_response(
+    mut stream: &TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
+
+/// Drain a bit of the unread request, closing with pending data would reset the connection,
+/// before the client got the response.
+fn linger(stream: &TcpStream) {
+    let _ = stream.shutdown(Shutdown::Write);
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+    let _ = io::copy(&mut stream.take(MAX_HEAD_LEN), &mut io::sink());
+}
+
+/// Accept connections and handle every one in its own thread, with a timeout for reading and writing.
+///
+/// A slow client blocks then only its own connection. Above [`MAX_CONNECTIONS`] new ones
+/// get a 503, a too large request gets a 413.
+pub fn serve<F>(listener: TcpListener, name: &'static str, handler: F)
+where
+    F: Fn(&TcpStream) -> Result<()> + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                debug!("{name} connection failed: {e}");
+                continue;
+            }
+        };
+
+        if let Err(e) = stream.set_write_timeout(Some(REQUEST_TIMEOUT)) {
+            debug!("{name} connection failed: {e}");
+            continue;
+        }
+
+        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            let _ = write_response(&stream, "503 Service Unavailable", "text/plain", "");
+            continue;
+        }
+
+        let handler = handler.clone();
+        let connections = connections.clone();
+
+        spawn_tagged(move || {
+            if let Err(e) = handler(&stream) {
+                if e.kind() == ErrorKind::InvalidData {
+                    let _ = write_response(&stream, "413 Payload Too Large", "text/plain", "");
+                    linger(&stream);
+                }
+
+                debug!("{name} request failed: {e}");
+            }
+
+            connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
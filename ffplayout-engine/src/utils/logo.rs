@@ -0,0 +1,86 @@
+use std::{
+    path::PathBuf,
+    sync::{atomic::Ordering, mpsc::channel},
+    time::Duration,
+};
+
+use notify::{
+    watcher,
+    DebouncedEvent::{Create, Rename, Write},
+    RecursiveMode, Watcher,
+};
+use simplelog::*;
+
+use ffplayout_lib::utils::{PlayoutConfig, PlayoutStatus, ProcessControl, ProcessUnit::*};
+
+/// Restart the filter stage of the decoder, so changed logos get loaded.
+///
+/// The current clip starts again on the current position. A running ingest
+/// server is not touched, it gets the new filters with the next connection.
+pub fn reload_filters(playout_stat: &PlayoutStatus, proc_control: &ProcessControl) {
+    info!("Restart current clip, for reloading filters");
+    playout_stat.list_init.store(true, Ordering::SeqCst);
+
+    if let Err(e) = proc_control.stop(Decoder) {
+        error!("{e}")
+    }
+}
+
+/// All logo files, which are in use.
+pub fn logo_paths(config: &PlayoutConfig) -> Vec<PathBuf> {
+    let mut paths = vec![];
+
+    if config.processing.add_logo {
+        paths.push(PathBuf::from(&config.processing.logo));
+    }
+
+    paths.extend(
+        config
+            .processing
+            .logos
+            .iter()
+            .map(|l| PathBuf::from(&l.path)),
+    );
+
+    paths
+        .into_iter()
+        .filter(|p| p.is_file())
+        .map(|p| p.canonicalize().unwrap_or(p))
+        .collect()
+}
+
+/// Watch the logo files and reload the filters, when one of them changes.
+pub fn logo_watchman(
+    config: PlayoutConfig,
+    playout_stat: PlayoutStatus,
+    proc_control: ProcessControl,
+) {
+    let paths = logo_paths(&config);
+    let (tx, rx) = channel();
+    let mut watcher = match watcher(tx, Duration::from_secs(1)) {
+        Ok(w) => w,
+        Err(e) => {
+            error!("Unable to watch logo files: {e}");
+            return;
+        }
+    };
+
+    // watch the folders, because image editors often replace the file
+    for folder in paths.iter().filter_map(|p| p.parent()) {
+        if let Err(e) = watcher.watch(folder, RecursiveMode::NonRecursive) {
+            error!("Unable to watch logo folder {folder:?}: {e}");
+        }
+    }
+
+    while !proc_control.is_terminated.load(Ordering::SeqCst) {
+        let changed = match rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(Create(path)) | Ok(Write(path)) | Ok(Rename(_, path)) => path,
+            _ => continue,
+        };
+
+        if paths.contains(&changed) {
+            info!("Logo changed: <b><magenta>{changed:?}</></b>");
+            reload_filters(&playout_stat, &proc_control);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    net::{TcpListener, TcpStream},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use simplelog::*;
+
+use crate::utils::http::{read_request, serve, write_response};
+use ffplayout_lib::utils::{PlayoutConfig, ProcessControl};
+
+/// Create metrics in Prometheus text exposition format.
+pub fn metrics_text(proc_control: &ProcessControl) -> String {
+    let status = proc_control.channel_status();
+    let stats = proc_control.encoder_stats.lock().unwrap().clone();
+    let latency = proc_control.ingest_latency.lock().unwrap().clone();
+    let seconds = |d: Option<Duration>| format!("{:.6}", d.unwrap_or_default().as_secs_f64());
+
+    let metrics = [
+        (
+            "ffplayout_ingest_running",
+            "gauge",
+            "Live ingest is running.",
+            (status.ingest_active as u8).to_string(),
+        ),
+        (
+            "ffplayout_current_clip_seconds_remaining",
+            "gauge",
+            "Remaining seconds of the current clip.",
+            format!("{:.3}", status.remaining),
+        ),
+        (
+            "ffplayout_decoder_restarts_total",
+            "counter",
+            "Decoder processes, which exits with an error.",
+            proc_control
+                .decoder_restarts
+                .load(Ordering::SeqCst)
+                .to_string(),
+        ),
+        (
+            "ffplayout_encoder_restarts_total",
+            "counter",
+            "Encoder processes, which exits with an error.",
+            proc_control
+                .encoder_restarts
+                .load(Ordering::SeqCst)
+                .to_string(),
+        ),
+        (
+            "ffplayout_dropped_frames_total",
+            "counter",
+            "Frames dropped by ffmpeg.",
+            proc_control
+                .dropped_frames
+                .load(Ordering::SeqCst)
+                .to_string(),
+        ),
+        (
+            "ffplayout_encoder_fps",
+            "gauge",
+            "Frames per second from the encoder.",
+            format!("{:.2}", stats.fps),
+        ),
+        (
+            "ffplayout_encoder_speed",
+            "gauge",
+            "Encoding speed, under 1.0 the encoder is too slow.",
+            format!("{:.2}", stats.speed),
+        ),
+        (
+            "ffplayout_encoder_dropped_frames",
+            "gauge",
+            "Frames dropped by the encoder.",
+            stats.drop.to_string(),
+        ),
+        (
+            "ffplayout_encoder_duplicated_frames",
+            "gauge",
+            "Frames duplicated by the encoder.",
+            stats.dup.to_string(),
+        ),
+        (
+            "ffplayout_live_children",
+            "gauge",
+            "Running ffmpeg/ffplay child processes.",
+            proc_control.live_children().to_string(),
+        ),
+        (
+            "ffplayout_ingest_latency_p50_seconds",
+            "gauge",
+            "Median time of ingest packages, from the queue to the encoder.",
+            seconds(latency.p50()),
+        ),
+        (
+            "ffplayout_ingest_latency_p95_seconds",
+            "gauge",
+            "95th percentile time of ingest packages, from the queue to the encoder.",
+            seconds(latency.p95()),
+        ),
+        (
+            "ffplayout_channel_uptime_seconds",
+            "gauge",
+            "Seconds since the channel is running.",
+            (status.uptime as u64).to_string(),
+        ),
+    ];
+
+    let mut text = String::new();
+
+    for (name, kind, help, value) in metrics {
+        text.push_str(&format!(
+            "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"
+        ));
+    }
+
+    text
+}
+
+/// Status line and body for the health checks.
+///
+/// `/healthz` is only the liveness of the encoder process, `/readyz` needs also
+/// encoder progress in the last `ready_seconds`.
+pub fn health_status(
+    path: &str,
+    config: &PlayoutConfig,
+    proc_control: &ProcessControl,
+) -> Option<(&'static str, &'static str)> {
+    let healthy = match path {
+        "/healthz" => proc_control.encoder_alive(),
+        "/readyz" => proc_control.encoder_ready(Duration::from_secs(config.metrics.ready_seconds)),
+        _ => return None,
+    };
+
+    match healthy {
+        true => Some(("200 OK", "ok")),
+        false => Some(("503 Service Unavailable", "unavailable")),
+    }
+}
+
+fn handle_request(
+    stream: &TcpStream,
+    config: &PlayoutConfig,
+    proc_control: &ProcessControl,
+) -> std::io::Result<()> {
+    let request = read_request(stream)?;
+
+    if request.method != "GET" {
+        return write_response(stream, "404 Not Found", "text/plain", "");
+    }
+
+    if request.path == "/metrics" {
+        let body = metrics_text(proc_control);
+
+        write_response(stream, "200 OK", "text/plain; version=0.0.4", &body)
+    } else if let Some((status, body)) = health_status(&request.path, config, proc_control) {
+        write_response(stream, status, "text/plain", body)
+    } else {
+        write_response(stream, "404 Not Found", "text/plain", "")
+    }
+}
+
+/// Metrics Server
+///
+/// Simple HTTP server, which serves the playout state under `/metrics`,
+/// and the health checks under `/healthz` and `/readyz`.
+/// It runs in its own thread and only reads from the shared states.
+pub fn metrics_server(config: PlayoutConfig, proc_control: ProcessControl) {
+    let addr = config.metrics.address.clone();
+
+    let listener = match TcpListener::bind(&addr) {
+        Ok(l) => l,
+        Err(e) => {
+            error!("Unable to start metrics server: {e}");
+            return;
+        }
+    };
+
+    info!("Run metrics server, listening on: <b><magenta>http://{addr}/metrics</></b>");
+
+    serve(listener, "Metrics", move |stream| {
+        handle_request(stream, &config, &proc_control)
+    });
+}
@@ -6,18 +6,28 @@ use std::{
 use regex::Regex;
 use simplelog::*;
 
+pub mod archive;
 pub mod arg_parse;
-
-pub use arg_parse::Args;
+pub mod channel;
+pub mod control;
+pub mod http;
+pub mod logo;
+pub mod metrics;
+#[cfg(unix)]
+pub mod reload;
+pub mod resume;
+pub mod webhook;
+
+pub use arg_parse::{Args, Command};
 use ffplayout_lib::{
     filter::Filters,
-    utils::{time_to_sec, OutputMode::*, PlayoutConfig, ProcessMode::*},
+    utils::{hide_secrets, time_to_sec, IngestMode, OutputMode::*, PlayoutConfig, ProcessMode::*},
     vec_strings,
 };
 
 /// Read command line arguments, and override the config with them.
 pub fn get_config(args: Args) -> PlayoutConfig {
-    let cfg_path = match args.channel {
+    let cfg_path = match &args.channel {
         Some(c) => {
             let path = PathBuf::from(format!("/etc/ffplayout/{c}.yml"));
 
@@ -33,7 +43,13 @@ pub fn get_config(args: Args) -> PlayoutConfig {
         None => args.config,
     };
 
-    let mut config = PlayoutConfig::new(cfg_path);
+    let mut config = PlayoutConfig::with_env(cfg_path);
+
+    if let Some(c) = args.channel {
+        if config.general.channel.is_empty() {
+            config.general.channel = c;
+        }
+    }
 
     if let Some(gen) = args.generate {
         config.general.generate = Some(gen);
@@ -60,7 +76,10 @@ pub fn get_config(args: Args) -> PlayoutConfig {
 
     if let Some(folder) = args.folder {
         config.storage.path = folder;
-        config.processing.mode = Folder;
+
+        if config.processing.mode != Loop {
+            config.processing.mode = Folder;
+        }
     }
 
     if let Some(start) = args.start {
@@ -82,25 +101,35 @@ pub fn get_config(args: Args) -> PlayoutConfig {
         config.playlist.infinit = args.infinit;
     }
 
-    if let Some(output) = args.output {
+    if args.dry_run {
+        config.out.mode = Null;
+    } else if let Some(output) = args.output {
         config.out.mode = output;
+    }
 
-        if config.out.mode == Null {
-            config.out.output_count = 1;
-            config.out.output_filter = None;
-            config.out.output_cmd = Some(vec_strings!["-f", "null", "-"]);
-        }
+    if config.out.mode == Null {
+        config.out.output_count = 1;
+        config.out.output_filter = None;
+        config.out.abr.enable = false;
+        config.out.output_cmd = Some(vec_strings!["-f", "null", "-"]);
     }
 
     if let Some(volume) = args.volume {
         config.processing.volume = volume;
     }
 
+    if args.command == Some(Command::Serve) {
+        config.control.enable = true;
+        config.metrics.enable = true;
+    }
+
     config
 }
 
 /// Format ingest and HLS logging output
 pub fn log_line(line: &str, level: &str) {
+    let line = &hide_secrets(line);
+
     if line.contains("[info]") && level.to_lowercase() == "info" {
         info!("<bright black>[Server]</> {}", line.replace("[info] ", ""))
     } else if line.contains("[warning]")
@@ -135,6 +164,168 @@ pub fn valid_stream(msg: &str) -> bool {
     false
 }
 
+/// App or stream name of a RTMP client, from a line of the ingest server log.
+///
+/// ffmpeg logs only names, which are different to the listen url.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RtmpConnection {
+    pub app: Option<String>,
+    pub stream: Option<String>,
+    pub expected: Option<String>,
+    pub address: Option<String>,
+}
+
+/// Parse the app or stream mismatch messages from the RTMP server.
+pub fn parse_rtmp_connection(line: &str) -> Option<RtmpConnection> {
+    let app_re = Regex::new(r"App field don't match up: (\S+) <-> (\S+)").unwrap();
+    let stream_re = Regex::new(r"Unexpected stream (\S+?)\??, expecting (\S+?)\??\s*$").unwrap();
+    let addr_re = Regex::new(r"\b(\d{1,3}(?:\.\d{1,3}){3}(?::\d+)?)\b").unwrap();
+    let address = addr_re.captures(line).map(|c| c[1].to_string());
+
+    if let Some(c) = app_re.captures(line) {
+        return Some(RtmpConnection {
+            app: Some(c[1].to_string()),
+            expected: Some(c[2].to_string()),
+            address,
+            ..Default::default()
+        });
+    }
+
+    stream_re.captures(line).map(|c| RtmpConnection {
+        stream: Some(c[1].to_string()),
+        expected: Some(c[2].to_string()),
+        address,
+        ..Default::default()
+    })
+}
+
+/// App and stream name from the listen url of the ingest server.
+///
+/// Like `live` and `stream` from `rtmp://0.0.0.0:1936/live/stream`.
+pub fn listen_connection(config: &PlayoutConfig) -> RtmpConnection {
+    let url = config
+        .ingest
+        .input_cmd
+        .iter()
+        .flatten()
+        .find(|s| s.contains("://"))
+        .cloned()
+        .unwrap_or_default();
+    let path = url
+        .split_once("://")
+        .and_then(|(_, rest)| rest.split_once('/'))
+        .map(|(_, path)| path.split('?').next().unwrap_or_default())
+        .unwrap_or_default();
+    let (app, stream) = path.rsplit_once('/').unwrap_or((path, ""));
+    let name = |n: &str| Some(n.to_string()).filter(|n| !n.is_empty());
+
+    RtmpConnection {
+        app: name(app),
+        stream: name(stream),
+        expected: name(stream),
+        address: None,
+    }
+}
+
+/// Check the client against the allowlist of the ingest config.
+///
+/// Without a list an app mismatch is allowed, like ffmpeg does it,
+/// and a stream name must match the listen url. With a list, also the names
+/// from the listen url must be in it.
+pub fn ingest_allowed(config: &PlayoutConfig, conn: &RtmpConnection) -> bool {
+    let ingest = &config.ingest;
+
+    if let Some(app) = &conn.app {
+        if !ingest.allowed_apps.is_empty() && !ingest.allowed_apps.contains(app) {
+            return false;
+        }
+    }
+
+    match &conn.stream {
+        Some(key) if ingest.allowed_keys.is_empty() => Some(key) == conn.expected.as_ref(),
+        Some(key) => ingest.allowed_keys.contains(key),
+        None => true,
+    }
+}
+
+/// Result of the client check, for one session of the ingest server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClientCheck {
+    #[default]
+    Pending,
+    Accepted,
+    Rejected,
+}
+
+/// Check of a RTMP client, on the log lines of the ingest server.
+///
+/// ffmpeg logs the app and stream name only, when they are different to the listen url,
+/// and that before it opens the input. So the client is checked, when the input is opened,
+/// and the stream data must wait until then. Other modes are accepted without a check.
+#[derive(Debug, Clone)]
+pub struct ClientGate {
+    conn: RtmpConnection,
+    pub state: ClientCheck,
+}
+
+impl ClientGate {
+    pub fn new(config: &PlayoutConfig) -> Self {
+        let state = match config.ingest.mode {
+            IngestMode::Rtmp => ClientCheck::Pending,
+            _ => ClientCheck::Accepted,
+        };
+
+        Self {
+            conn: listen_connection(config),
+            state,
+        }
+    }
+
+    /// Check a line of the ingest server log, a mismatch which is not allowed rejects
+    /// the client at once.
+    pub fn check_line(&mut self, config: &PlayoutConfig, line: &str) -> ClientCheck {
+        if self.state != ClientCheck::Pending {
+            return self.state;
+        }
+
+        if let Some(conn) = parse_rtmp_connection(line).filter(|_| line.contains("rtmp")) {
+            self.conn.app = conn.app.or(self.conn.app.take());
+            self.conn.stream = conn.stream.or(self.conn.stream.take());
+            self.conn.address = conn.address.or(self.conn.address.take());
+
+            if !ingest_allowed(config, &self.conn) {
+                self.reject();
+            }
+        } else if line.contains("Input #0") {
+            if ingest_allowed(config, &self.conn) {
+                self.state = ClientCheck::Accepted;
+            } else {
+                self.reject();
+            }
+        }
+
+        self.state
+    }
+
+    /// Reject the client, when the input was not opened in time.
+    pub fn timeout(&mut self) {
+        if self.state == ClientCheck::Pending {
+            self.reject();
+        }
+    }
+
+    fn reject(&mut self) {
+        self.state = ClientCheck::Rejected;
+
+        warn!(
+            "Reject ingest from <b><magenta>{}</></b>, app: <yellow>{}</>, stream: <yellow>{}</>",
+            self.conn.address.as_deref().unwrap_or("unknown address"),
+            self.conn.app.as_deref().unwrap_or("-"),
+            hide_secrets(self.conn.stream.as_deref().unwrap_or("-"))
+        );
+    }
+}
+
 /// Prepare output parameters
 ///
 /// Seek for multiple outputs and add mapping for it.
@@ -0,0 +1,79 @@
+use std::{path::Path, sync::atomic::Ordering, thread::sleep, time::Duration};
+
+use signal_hook::{consts::SIGHUP, iterator::Signals};
+use simplelog::*;
+
+use super::logo::reload_filters;
+use ffplayout_lib::utils::{spawn_tagged, PlayoutConfig, PlayoutStatus, ProcessControl};
+
+/// Read the config file again and take over the live settings.
+///
+/// Volume, logo, ticker text and censor regions get active with a filter reload. Changes,
+/// which need a restart, are only logged. On errors the running config stays untouched.
+pub fn reload_config(
+    config: &PlayoutConfig,
+    playout_stat: &PlayoutStatus,
+    proc_control: &ProcessControl,
+) -> Result<(), String> {
+    let new_config = PlayoutConfig::load_with_env(Path::new(&config.general.config_path))?;
+
+    if let Err(errors) = new_config.validate() {
+        let errors = errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        return Err(format!("Invalid config: {errors}"));
+    }
+
+    let (live, restart) = config.live_changes(&new_config);
+
+    for field in restart {
+        warn!("Config reload: <b><magenta>{field}</></b> changed, it needs a restart");
+    }
+
+    if let Some(mut live) = live {
+        info!("Config reload: apply new volume, logo, ticker text and censor regions");
+        let mut current = config.live.write().unwrap();
+        // the regions from the control api stay, they can change since the check above
+        live.censor = current.censor.take();
+        *current = live;
+        drop(current);
+        reload_filters(playout_stat, proc_control);
+    }
+
+    Ok(())
+}
+
+/// Reload the config on SIGHUP, see [`reload_config`].
+pub fn reload_handler(
+    config: PlayoutConfig,
+    playout_stat: PlayoutStatus,
+    proc_control: ProcessControl,
+) {
+    let mut signals = match Signals::new([SIGHUP]) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Unable to set SIGHUP handler: {e}");
+            return;
+        }
+    };
+
+    spawn_tagged(move || {
+        while !proc_control.is_terminated.load(Ordering::SeqCst) {
+            if signals.pending().next().is_some() {
+                info!(
+                    "Reload config: <b><magenta>{}</></b>",
+                    config.general.config_path
+                );
+
+                if let Err(e) = reload_config(&config, &playout_stat, &proc_control) {
+                    error!("Config reload failed, keep the running config: {e}");
+                }
+            }
+
+            sleep(Duration::from_millis(200));
+        }
+    });
+}
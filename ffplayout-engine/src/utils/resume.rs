@@ -0,0 +1,95 @@
+use std::{
+    fs,
+    sync::atomic::Ordering,
+    thread::sleep,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use simplelog::*;
+
+use ffplayout_lib::utils::{get_sec, PlayerControl, PlayoutConfig, PlayoutStatus, ProcessControl};
+
+const RESUME_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Playout position, which is saved for continuing after a restart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResumeState {
+    pub index: usize,
+    pub source: String,
+    pub elapsed: f64,
+    pub timestamp: u64,
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Get the current position, the elapsed time is corrected with the time shift.
+pub fn current_state(
+    config: &PlayoutConfig,
+    play_control: &PlayerControl,
+    playout_stat: &PlayoutStatus,
+) -> Option<ResumeState> {
+    let media = play_control.current_media.lock().unwrap().clone()?;
+    let mut time_sec = get_sec();
+    let mut shift = 0.0;
+
+    if time_sec < config.playlist.start_sec.unwrap() {
+        time_sec += config.playlist.length_sec.unwrap()
+    }
+
+    if *playout_stat.current_date.lock().unwrap() == *playout_stat.date.lock().unwrap() {
+        shift = *playout_stat.time_shift.lock().unwrap();
+    }
+
+    Some(ResumeState {
+        index: media.index?,
+        source: media.source,
+        elapsed: (time_sec - media.begin? + shift).max(0.0),
+        timestamp: unix_time(),
+    })
+}
+
+pub fn write_resume(config: &PlayoutConfig, state: &ResumeState) {
+    let data = serde_json::to_string(state).expect("Serialize resume data failed");
+
+    if let Err(e) = fs::write(&config.general.resume_file, data) {
+        error!(
+            "Unable to write resume file <b><magenta>{}</></b>: {e}",
+            config.general.resume_file
+        );
+    }
+}
+
+/// Read saved position, only when it is not older then the configured max age.
+pub fn read_resume(config: &PlayoutConfig) -> Option<ResumeState> {
+    let data = fs::read_to_string(&config.general.resume_file).ok()?;
+    let state: ResumeState = serde_json::from_str(&data).ok()?;
+
+    if unix_time().saturating_sub(state.timestamp) > config.general.resume_max_age {
+        debug!("Resume state is to old, ignore it");
+        return None;
+    }
+
+    Some(state)
+}
+
+/// Save the playout position periodically, until the playout is terminated.
+pub fn resume_writer(
+    config: PlayoutConfig,
+    play_control: PlayerControl,
+    playout_stat: PlayoutStatus,
+    proc_control: ProcessControl,
+) {
+    while !proc_control.is_terminated.load(Ordering::SeqCst) {
+        if let Some(state) = current_state(&config, &play_control, &playout_stat) {
+            write_resume(&config, &state);
+        }
+
+        sleep(RESUME_INTERVAL);
+    }
+}
@@ -0,0 +1,65 @@
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+use simplelog::*;
+
+use ffplayout_lib::utils::{hide_secrets, spawn_tagged, time_now, PlayoutConfig, REMOTE_TIMEOUT};
+
+/// Post the payload to the webhook, on failure it is tried one more time.
+pub fn send_hook(url: &str, payload: &Value) -> bool {
+    let client = match Client::builder().timeout(REMOTE_TIMEOUT).build() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Unable to create webhook client: {e}");
+            return false;
+        }
+    };
+
+    for attempt in 1..=2 {
+        match client
+            .post(url)
+            .json(payload)
+            .send()
+            .and_then(|r| r.error_for_status())
+        {
+            Ok(_) => {
+                debug!("Webhook <b><magenta>{}</></b> done", hide_secrets(url));
+                return true;
+            }
+            Err(e) => warn!(
+                "Webhook <b><magenta>{}</></b> failed (attempt {attempt}): {e}",
+                hide_secrets(url)
+            ),
+        }
+    }
+
+    error!(
+        "Webhook <b><magenta>{}</></b> not reachable!",
+        hide_secrets(url)
+    );
+
+    false
+}
+
+/// Inform automation about ingest connects and disconnects.
+///
+/// The hook runs in its own thread, so reading the ingest is not blocked.
+pub fn ingest_hook(config: &PlayoutConfig, connected: bool) {
+    let (url, event) = match connected {
+        true => (&config.ingest.on_connect_url, "ingest_connect"),
+        false => (&config.ingest.on_disconnect_url, "ingest_disconnect"),
+    };
+
+    if url.is_empty() {
+        return;
+    }
+
+    let url = url.clone();
+    let payload = json!({
+        "event": event,
+        "channel": config.general.channel,
+        "mode": config.ingest.mode,
+        "timestamp": time_now().to_rfc3339(),
+    });
+
+    spawn_tagged(move || send_hook(&url, &payload));
+}
@@ -0,0 +1,360 @@
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+    sync::{Mutex, OnceLock},
+    thread,
+};
+
+use chrono::{DateTime, Local};
+use crossbeam_channel::{bounded, Sender, TrySendError};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use simplelog::*;
+
+use crate::utils::{
+    controller::ProcessUnit::*, log_channel, set_log_channel, spawn_tagged, LoudnormMode, Media,
+    PlayoutConfig,
+};
+use crate::vec_strings;
+
+/// Measurements, which are cached, before the cache gets cleared.
+pub const MEASURE_CACHE_SIZE: usize = 1000;
+
+/// Aired clips, which wait for their loudness report. More clips are skipped.
+pub const REPORT_QUEUE_SIZE: usize = 100;
+
+/// Measurements by source and audio track.
+///
+/// A `None` is a running measurement, the clip plays then with single pass.
+/// Failed ones get removed, so the next airing tries it again.
+static MEASUREMENTS: OnceLock<Mutex<HashMap<String, Option<Measured>>>> = OnceLock::new();
+
+/// Queue to the worker, which measures the aired clips one after the other.
+static REPORT_QUEUE: OnceLock<Sender<ReportJob>> = OnceLock::new();
+
+/// A aired clip for the loudness report, with the channel for the log.
+struct ReportJob {
+    channel: Option<String>,
+    config: PlayoutConfig,
+    node: Media,
+    aired: String,
+}
+
+/// Measured values from the first loudnorm pass.
+#[derive(Debug, Clone, Deserialize)]
+struct Measured {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+}
+
+/// Extract the json block from ffmpeg output,
+/// the log output around it gets ignored.
+fn parse_measurement(output: &str) -> Option<Measured> {
+    let start = output.rfind('{')?;
+    let end = output[start..].find('}')? + start;
+
+    serde_json::from_str(&output[start..=end]).ok()
+}
+
+/// Loudness of a aired clip after EBU R128, one line in the loudness report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoudnessReport {
+    pub source: String,
+    pub aired: String,
+    /// Integrated loudness in LUFS.
+    pub integrated: f64,
+    /// Loudness range in LU.
+    pub lra: f64,
+    /// True peak in dBFS.
+    pub true_peak: f64,
+}
+
+/// Read the summary from ebur128, at the end of the ffmpeg output.
+pub fn parse_ebur128(source: &str, output: &str) -> Option<LoudnessReport> {
+    let summary = &output[output.rfind("Summary:")?..];
+    let value = |pattern: &str| {
+        Regex::new(pattern)
+            .unwrap()
+            .captures(summary)
+            .and_then(|c| c[1].parse::<f64>().ok())
+    };
+
+    Some(LoudnessReport {
+        source: source.to_string(),
+        aired: String::new(),
+        integrated: value(r"\bI:\s+(-?inf|-?[0-9.]+) LUFS")?,
+        lra: value(r"\bLRA:\s+([0-9.]+) LU\b")?,
+        true_peak: value(r"\bPeak:\s+(-?inf|-?[0-9.]+) dBFS")?,
+    })
+}
+
+/// Input and filter for a measurement of the aired part from the clip audio track.
+fn measure_cmd(node: &Media, filter: &str, nr: i32) -> Vec<String> {
+    let mut cmd = vec_strings!["-hide_banner", "-nostats", "-v", "info"];
+    let mut track = nr;
+
+    if node.seek > 0.0 {
+        cmd.append(&mut vec_strings!["-ss", node.seek]);
+    }
+
+    if Path::new(&node.audio).is_file() {
+        cmd.append(&mut vec_strings!["-i", node.audio]);
+        track = 0;
+    } else {
+        cmd.append(&mut vec_strings!["-i", node.source]);
+    }
+
+    cmd.append(&mut vec_strings![
+        "-t",
+        node.out - node.seek,
+        "-map",
+        format!("0:a:{track}"),
+        "-af",
+        filter,
+        "-f",
+        "null",
+        "-"
+    ]);
+
+    cmd
+}
+
+/// Run first pass to measure the loudness of the clip audio track.
+fn measure(node: &Media, config: &PlayoutConfig, nr: i32) -> Option<Measured> {
+    let loudnorm = &config.processing.loudnorm;
+    let filter = format!(
+        "loudnorm=I={}:TP={}:LRA={}:print_format=json",
+        loudnorm.integrated, loudnorm.true_peak, loudnorm.lra
+    );
+    let cmd = measure_cmd(node, &filter, nr);
+
+    let output = match Command::new(&config.processing.ffmpeg_path)
+        .args(cmd)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+    {
+        Ok(o) => o,
+        Err(e) => {
+            error!("couldn't spawn loudnorm measurement: {e}");
+            return None;
+        }
+    };
+
+    parse_measurement(&String::from_utf8_lossy(&output.stderr))
+}
+
+fn measurements() -> &'static Mutex<HashMap<String, Option<Measured>>> {
+    MEASUREMENTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The cut points are not part of the key, they change when the clip
+/// starts in the middle or is shortened at the end of the playlist.
+fn measure_key(node: &Media, nr: i32) -> String {
+    format!("{}|{}|{nr}", node.source, node.audio)
+}
+
+fn dual_pass(config: &PlayoutConfig, node: &Media) -> bool {
+    config.processing.loudnorm.enable
+        && config.processing.loudnorm_mode == LoudnormMode::Dual
+        && node.unit == Decoder
+        && Path::new(&node.source).is_file()
+}
+
+/// Measure the audio tracks of a clip in the background, before it gets aired.
+///
+/// The playlist calls it for the next clip, so the result is ready when the clip starts.
+/// Only local files get measured, so the source is the same as in the filter chain.
+pub fn measure_ahead(config: &PlayoutConfig, node: &Media) {
+    if !dual_pass(config, node) {
+        return;
+    }
+
+    for nr in 0..config.processing.audio_tracks {
+        let key = measure_key(node, nr);
+        let mut cache = measurements().lock().unwrap();
+
+        if cache.contains_key(&key) {
+            continue;
+        }
+
+        if cache.len() >= MEASURE_CACHE_SIZE {
+            cache.clear();
+        }
+
+        cache.insert(key.clone(), None);
+        drop(cache);
+
+        let config = config.clone();
+        let node = node.clone();
+
+        spawn_tagged(move || {
+            let measured = measure(&node, &config, nr);
+            let mut cache = measurements().lock().unwrap();
+
+            match measured {
+                Some(measured) => {
+                    cache.insert(key, Some(measured));
+                }
+                None => {
+                    warn!(
+                        "Loudnorm measurement failed for <b><magenta>{}</></b>",
+                        node.source
+                    );
+                    cache.remove(&key);
+                }
+            }
+        });
+    }
+}
+
+/// Create loudnorm filter, in dual mode with the measured values from the clip.
+///
+/// Without a finished measurement the clip plays with single pass, the filter chain
+/// must not wait for it.
+pub fn filter_node(config: &PlayoutConfig, node: &Media, nr: i32) -> String {
+    let loudnorm = &config.processing.loudnorm;
+    let mut filter = format!(
+        "loudnorm=I={}:TP={}:LRA={}",
+        loudnorm.integrated, loudnorm.true_peak, loudnorm.lra
+    );
+
+    if dual_pass(config, node) {
+        let measured = measurements()
+            .lock()
+            .unwrap()
+            .get(&measure_key(node, nr))
+            .cloned()
+            .flatten();
+
+        match measured {
+            Some(m) => filter.push_str(&format!(
+                ":measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+                m.input_i, m.input_tp, m.input_lra, m.input_thresh, m.target_offset
+            )),
+            None => {
+                info!(
+                    "No loudnorm measurement for <b><magenta>{}</></b>, use single pass",
+                    node.source
+                );
+
+                // ready for the next time, when the clip gets aired again
+                measure_ahead(config, node);
+            }
+        }
+    }
+
+    filter
+}
+
+/// Measure the first audio track of the clip with ebur128.
+///
+/// A clip without audio is skipped, it has nothing to report.
+pub fn loudness_report(node: &Media, config: &PlayoutConfig) -> Option<LoudnessReport> {
+    let no_audio = !Path::new(&node.audio).is_file()
+        && node
+            .probe
+            .as_ref()
+            .is_some_and(|p| p.audio_streams.is_empty());
+
+    if no_audio {
+        info!(
+            "Clip <b><magenta>{}</></b> has no audio, skip loudness report",
+            node.source
+        );
+        return None;
+    }
+
+    // the frame lines go to verbose, so only the summary is in the output
+    let cmd = measure_cmd(node, "ebur128=peak=true:framelog=verbose", 0);
+
+    let output = match Command::new(&config.processing.ffmpeg_path)
+        .args(cmd)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+    {
+        Ok(o) => o,
+        Err(e) => {
+            error!("couldn't spawn loudness measurement: {e}");
+            return None;
+        }
+    };
+
+    parse_ebur128(&node.source, &String::from_utf8_lossy(&output.stderr))
+}
+
+/// Append the report as json line to the report file.
+pub fn write_loudness_report(path: &Path, report: &LoudnessReport) -> Result<(), io::Error> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut line = serde_json::to_string(report)?;
+    line.push('\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())
+}
+
+fn report_worker(job: ReportJob) {
+    if let Some(channel) = &job.channel {
+        set_log_channel(channel);
+    }
+
+    let Some(mut report) = loudness_report(&job.node, &job.config) else {
+        return;
+    };
+
+    report.aired = job.aired;
+    let path = Path::new(&job.config.processing.loudness_report_path);
+
+    if let Err(e) = write_loudness_report(path, &report) {
+        error!(
+            "Unable to write loudness report <b><magenta>{}</></b>: {e}",
+            path.display()
+        );
+    }
+}
+
+/// Measure the clip in the background, when `loudness_report_path` is set.
+///
+/// It is called after the clip has aired, with the time when it started. One worker
+/// measures the clips in order, so the measurements don't take the cpu from the playout.
+pub fn report_loudness(config: &PlayoutConfig, node: &Media, aired: DateTime<Local>) {
+    if config.processing.loudness_report_path.is_empty() || !Path::new(&node.source).is_file() {
+        return;
+    }
+
+    let queue = REPORT_QUEUE.get_or_init(|| {
+        let (sender, receiver) = bounded(REPORT_QUEUE_SIZE);
+
+        // the worker serves all channels, every job sets its own log channel
+        thread::spawn(move || receiver.into_iter().for_each(report_worker));
+
+        sender
+    });
+
+    let job = ReportJob {
+        channel: log_channel(),
+        config: config.clone(),
+        node: node.clone(),
+        aired: aired.format("%Y-%m-%dT%H:%M:%S%:z").to_string(),
+    };
+
+    if let Err(TrySendError::Full(job)) = queue.try_send(job) {
+        warn!(
+            "Loudness report queue is full, skip <b><magenta>{}</></b>",
+            job.node.source
+        );
+    }
+}
@@ -37,3 +37,30 @@ pub fn filter_node(filter: &str) -> (String, String) {
 
     (video_filter, audio_filter)
 }
+
+/// Output links from the filter chain, which can not be used inside a custom filter.
+pub fn reserved_links(filter: &str) -> Vec<String> {
+    let re = Regex::new(r"\[[va]out\d+\]").unwrap();
+
+    re.find_iter(filter)
+        .map(|m| m.as_str().to_string())
+        .collect()
+}
+
+/// Apply custom filter from a playlist clip.
+///
+/// The clip filter gets appended after the global filters, before the output link.
+pub fn clip_filter_node(filter: &str, source: &str) -> (String, String) {
+    let links = reserved_links(filter);
+
+    if !links.is_empty() {
+        error!(
+            "Custom filter from <b><magenta>{source}</></b> uses reserved link names {}. Filter skipped!",
+            links.join(", ")
+        );
+
+        return (String::new(), String::new());
+    }
+
+    filter_node(filter)
+}
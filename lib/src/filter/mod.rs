@@ -7,12 +7,19 @@ use std::{
 use regex::Regex;
 use simplelog::*;
 
+mod a_loudnorm;
 mod custom;
 pub mod v_drawtext;
 
+pub use a_loudnorm::{
+    loudness_report, measure_ahead, parse_ebur128, report_loudness, write_loudness_report,
+    LoudnessReport,
+};
+
 use crate::utils::{
-    controller::ProcessUnit::*, fps_calc, get_delta, is_close, Media, MediaProbe, OutputMode::*,
-    PlayoutConfig,
+    controller::ProcessUnit::*, fps_calc, get_delta, is_close, CensorMode, CensorRegion,
+    Deinterlace, FitMode, FpsConvert, HwAccel, Logo, Media, MediaProbe, OutputMode::*,
+    PlayoutConfig, TimestampMode,
 };
 
 use super::vec_strings;
@@ -182,8 +189,40 @@ impl Default for Filters {
     }
 }
 
-fn deinterlace(field_order: &Option<String>, chain: &mut Filters) {
-    if let Some(order) = field_order {
+fn deinterlace_filter(config: &PlayoutConfig) -> Option<String> {
+    let filter = match config.processing.deinterlace {
+        Deinterlace::None => return None,
+        Deinterlace::Yadif => "yadif",
+        Deinterlace::Bwdif => "bwdif",
+    };
+
+    Some(match config.processing.hwaccel {
+        HwAccel::Nvenc => {
+            format!("format=yuv420p,hwupload_cuda,{filter}_cuda=0:-1:1,hwdownload,format=yuv420p")
+        }
+        HwAccel::Vaapi => {
+            let mode = match config.processing.deinterlace {
+                Deinterlace::Bwdif => "motion_adaptive",
+                _ => "default",
+            };
+
+            format!("format=nv12,hwupload,deinterlace_vaapi=mode={mode},hwdownload,format=nv12")
+        }
+        _ => format!("{filter}=0:-1:1"),
+    })
+}
+
+/// Deinterlace with the configured mode, or with yadif when the source is interlaced.
+/// Progressive clips pass untouched, for the others only frames marked as interlaced
+/// get deinterlaced.
+fn deinterlace(field_order: &Option<String>, chain: &mut Filters, config: &PlayoutConfig) {
+    if field_order.as_deref() == Some("progressive") {
+        return;
+    }
+
+    if let Some(filter) = deinterlace_filter(config) {
+        chain.add_filter(&filter, 0, Video)
+    } else if let Some(order) = field_order {
         if order != "progressive" {
             chain.add_filter("yadif=0:-1:0", 0, Video)
         }
@@ -191,7 +230,9 @@ fn deinterlace(field_order: &Option<String>, chain: &mut Filters) {
 }
 
 fn pad(aspect: f64, chain: &mut Filters, v_stream: &ffprobe::Stream, config: &PlayoutConfig) {
-    if !is_close(aspect, config.processing.aspect, 0.03) {
+    if config.processing.fit_mode == FitMode::Stretch
+        && !is_close(aspect, config.processing.aspect, 0.03)
+    {
         let mut scale = String::new();
 
         if let (Some(w), Some(h)) = (v_stream.width, v_stream.height) {
@@ -212,9 +253,54 @@ fn pad(aspect: f64, chain: &mut Filters, v_stream: &ffprobe::Stream, config: &Pl
     }
 }
 
+/// Convert to the target frame rate, with interpolation only for a known source rate.
 fn fps(fps: f64, chain: &mut Filters, config: &PlayoutConfig) {
-    if fps != config.processing.fps {
-        chain.add_filter(&format!("fps={}", config.processing.fps), 0, Video)
+    let target = config.processing.fps;
+
+    if fps == target {
+        return;
+    }
+
+    match config.processing.fps_convert {
+        FpsConvert::Interpolate if fps > 0.0 => chain.add_filter(
+            &format!("minterpolate=fps={target}:mi_mode=mci:mc_mode=aobmc:me_mode=bidir"),
+            0,
+            Video,
+        ),
+        _ => chain.add_filter(&format!("fps={target}"), 0, Video),
+    }
+}
+
+/// Scale to target size, with hardware acceleration the frames get scaled on the gpu.
+fn scale_filter(config: &PlayoutConfig) -> String {
+    let (w, h) = (config.processing.width, config.processing.height);
+
+    match config.processing.hwaccel {
+        HwAccel::None => format!("scale={w}:{h}"),
+        HwAccel::Nvenc => {
+            format!("format=yuv420p,hwupload_cuda,scale_npp={w}:{h},hwdownload,format=yuv420p")
+        }
+        HwAccel::Vaapi => {
+            format!("format=nv12,hwupload,scale_vaapi=w={w}:h={h},hwdownload,format=nv12")
+        }
+        HwAccel::Qsv => format!(
+            "format=nv12,hwupload=extra_hw_frames=64,scale_qsv=w={w}:h={h},hwdownload,format=nv12"
+        ),
+    }
+}
+
+/// Scale with keeping the source aspect, the rest gets padded or cropped.
+fn fit_filter(config: &PlayoutConfig) -> Option<String> {
+    let (w, h) = (config.processing.width, config.processing.height);
+
+    match config.processing.fit_mode {
+        FitMode::Stretch => None,
+        FitMode::Pad => Some(format!(
+            "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2"
+        )),
+        FitMode::Crop => Some(format!(
+            "scale={w}:{h}:force_original_aspect_ratio=increase,crop={w}:{h}"
+        )),
     }
 }
 
@@ -225,17 +311,19 @@ fn scale(
     chain: &mut Filters,
     config: &PlayoutConfig,
 ) {
-    // width: i64, height: i64
-    if let (Some(w), Some(h)) = (width, height) {
+    let fit = fit_filter(config)
+        .filter(|_| width.is_none() || !is_close(aspect, config.processing.aspect, 0.03));
+
+    if let Some(filter) = fit {
+        chain.add_filter(&filter, 0, Video);
+        chain.add_filter(
+            &format!("setdar=dar={}", config.processing.aspect),
+            0,
+            Video,
+        )
+    } else if let (Some(w), Some(h)) = (width, height) {
         if w != config.processing.width || h != config.processing.height {
-            chain.add_filter(
-                &format!(
-                    "scale={}:{}",
-                    config.processing.width, config.processing.height
-                ),
-                0,
-                Video,
-            );
+            chain.add_filter(&scale_filter(config), 0, Video);
         } else {
             chain.add_filter("null", 0, Video);
         }
@@ -248,70 +336,301 @@ fn scale(
             )
         }
     } else {
+        chain.add_filter(&scale_filter(config), 0, Video);
         chain.add_filter(
-            &format!(
-                "scale={}:{}",
-                config.processing.width, config.processing.height
-            ),
+            &format!("setdar=dar={}", config.processing.aspect),
             0,
             Video,
-        );
+        )
+    }
+}
+
+/// Convert all clips to the same color matrix and range, so switches between them look equal.
+fn color_normalize(chain: &mut Filters, config: &PlayoutConfig) {
+    if config.processing.color_normalize {
+        let matrix = &config.processing.color_matrix;
+        let range = config.processing.color_range;
+
         chain.add_filter(
-            &format!("setdar=dar={}", config.processing.aspect),
+            &format!(
+                "scale=in_color_matrix=auto:out_color_matrix={matrix}:out_range={range},setrange={range}"
+            ),
             0,
             Video,
         )
     }
 }
 
-fn fade(node: &mut Media, chain: &mut Filters, nr: i32, filter_type: FilterType) {
+fn fade(
+    node: &mut Media,
+    chain: &mut Filters,
+    config: &PlayoutConfig,
+    nr: i32,
+    filter_type: FilterType,
+) {
     let mut t = "";
+    let fade_in = config.processing.fade_in_duration;
+    let fade_out = config.processing.fade_out_duration;
 
     if filter_type == Audio {
         t = "a"
     }
 
-    if node.seek > 0.0 || node.unit == Ingest {
-        chain.add_filter(&format!("{t}fade=in:st=0:d=0.5"), nr, filter_type)
+    if fade_in > 0.0 && (node.seek > 0.0 || node.unit == Ingest) {
+        chain.add_filter(&format!("{t}fade=in:st=0:d={fade_in}"), nr, filter_type)
     }
 
-    if node.out != node.duration && node.out - node.seek > 1.0 {
+    // the fade-out starts from the known clip length, so it fits to cut and full clips
+    if fade_out > 0.0 && node.out - node.seek > fade_out {
         chain.add_filter(
-            &format!("{t}fade=out:st={}:d=1.0", (node.out - node.seek - 1.0)),
+            &format!(
+                "{t}fade=out:st={}:d={fade_out}",
+                (node.out - node.seek - fade_out)
+            ),
             nr,
             filter_type,
         )
     }
 }
 
+/// Logo input, a still image gets looped by the movie source.
+///
+/// Animations play through, and loop only with a frame count,
+/// because the loop filter needs to buffer all frames.
+fn logo_source(logo: &Logo) -> String {
+    let path = logo.path.replace('\\', "/").replace(':', "\\\\:");
+
+    match (logo.animated, logo.loop_size) {
+        (false, _) => format!("movie={path}:loop=0"),
+        (true, 0) => format!("movie={path}"),
+        (true, size) => format!("movie={path},loop=loop=-1:size={size}"),
+    }
+}
+
+/// A endless logo ends with the clip, a animation which plays only once keeps its last frame.
+fn logo_end(logo: &Logo) -> &'static str {
+    match logo.animated && logo.loop_size == 0 {
+        true => "",
+        false => ":shortest=1",
+    }
+}
+
 fn overlay(node: &mut Media, chain: &mut Filters, config: &PlayoutConfig) {
-    if config.processing.add_logo
-        && Path::new(&config.processing.logo).is_file()
-        && &node.category != "advertisement"
-    {
+    if &node.category == "advertisement" {
+        return;
+    }
+
+    let main_logo = config.processing.add_logo && Path::new(&config.processing.logo).is_file();
+    let mut logos = vec![];
+
+    if main_logo {
+        logos.push(Logo {
+            path: config.processing.logo.clone(),
+            scale: config.processing.logo_scale.clone(),
+            opacity: config.processing.logo_opacity,
+            x: config.processing.logo_x.clone(),
+            y: config.processing.logo_y.clone(),
+            enable: config.processing.logo_enable.expr(),
+            animated: config.processing.logo_animated,
+            loop_size: config.processing.logo_loop_size,
+        });
+    }
+
+    logos.extend(
+        config
+            .processing
+            .logos
+            .iter()
+            .filter(|l| Path::new(&l.path).is_file())
+            .cloned(),
+    );
+
+    if logos.is_empty() {
+        return;
+    }
+
+    let mut logo_chain = String::new();
+
+    for (i, logo) in logos.iter().enumerate() {
+        // every logo needs its own pad labels, the first one keeps the old names
+        let (v_pad, l_pad) = match i {
+            0 => ("v".to_string(), "l".to_string()),
+            _ => (format!("v{i}"), format!("l{i}")),
+        };
         let mut scale = String::new();
+        let mut enable = String::new();
 
-        if !config.processing.logo_scale.is_empty() {
-            scale = format!(",scale={}", config.processing.logo_scale);
+        if !logo.scale.is_empty() {
+            scale = format!(",scale={}", logo.scale);
         }
 
-        let mut logo_chain = format!(
-            "null[v];movie={}:loop=0,setpts=N/(FRAME_RATE*TB),format=rgba,colorchannelmixer=aa={}{scale}[l];[v][l]{}:shortest=1",
-            config.processing.logo.replace('\\', "/").replace(':', "\\\\:"), config.processing.logo_opacity, config.processing.logo_filter
-        );
+        if !logo.enable.is_empty() {
+            enable = format!(":enable='{}'", logo.enable);
+        }
+
+        // a raw logo_filter overrides the position values of the main logo
+        let position = if i == 0 && main_logo && !config.processing.logo_filter.is_empty() {
+            config.processing.logo_filter.clone()
+        } else {
+            format!("overlay={}:{}", logo.x, logo.y)
+        };
 
-        if node.last_ad.unwrap_or(false) {
-            logo_chain.push_str(",fade=in:st=0:d=1.0:alpha=1")
+        if i == 0 {
+            logo_chain.push_str("null");
         }
 
-        if node.next_ad.unwrap_or(false) {
-            logo_chain.push_str(&format!(
-                ",fade=out:st={}:d=1.0:alpha=1",
-                node.out - node.seek - 1.0
-            ))
+        logo_chain.push_str(&format!(
+            "[{v_pad}];{},setpts=N/(FRAME_RATE*TB),format=rgba,colorchannelmixer=aa={}{scale}[{l_pad}];[{v_pad}][{l_pad}]{position}{enable}{}",
+            logo_source(logo), logo.opacity, logo_end(logo)
+        ));
+    }
+
+    if node.last_ad.unwrap_or(false) {
+        logo_chain.push_str(",fade=in:st=0:d=1.0:alpha=1")
+    }
+
+    if node.next_ad.unwrap_or(false) {
+        logo_chain.push_str(&format!(
+            ",fade=out:st={}:d=1.0:alpha=1",
+            node.out - node.seek - 1.0
+        ))
+    }
+
+    chain.add_filter(&logo_chain, 0, Video);
+}
+
+/// Filter for one censored region, `nr` keeps the pad labels apart.
+pub fn censor_filter(region: &CensorRegion, nr: usize) -> String {
+    let CensorRegion {
+        x,
+        y,
+        width: w,
+        height: h,
+        ..
+    } = region;
+    let mut enable = String::new();
+
+    if region.end > region.start {
+        enable = format!(":enable='between(t,{},{})'", region.start, region.end);
+    }
+
+    match region.mode {
+        CensorMode::Blur => format!(
+            "split[cb{nr}][cr{nr}];[cr{nr}]crop={w}:{h}:{x}:{y},boxblur={}:1[cs{nr}];[cb{nr}][cs{nr}]overlay={x}:{y}{enable}",
+            region.strength
+        ),
+        CensorMode::Delogo => format!("delogo=x={x}:y={y}:w={w}:h={h}{enable}"),
+    }
+}
+
+/// Overlay of the live ingest in a small box, the ingest is the input with number `input`.
+///
+/// When the live stream ends, the playlist continues without the box.
+pub fn pip_filter(config: &PlayoutConfig, input: usize) -> String {
+    let pip = &config.ingest.pip;
+    let mut pip_box = format!(
+        "[{input}:v:0]scale={}:{},setpts=PTS-STARTPTS",
+        pip.width, pip.height
+    );
+
+    if pip.border > 0 {
+        pip_box.push_str(&format!(
+            ",pad={}:{}:{b}:{b}:color={}",
+            pip.width + pip.border * 2,
+            pip.height + pip.border * 2,
+            pip.border_color,
+            b = pip.border
+        ));
+    }
+
+    format!(
+        "null[vpip];{pip_box}[pip];[vpip][pip]overlay={}:{}:eof_action=pass",
+        pip.x, pip.y
+    )
+}
+
+/// Censor regions go before the logos, so they not get blurred.
+fn censor(chain: &mut Filters, config: &PlayoutConfig) {
+    for (i, region) in config.processing.censor.iter().enumerate() {
+        chain.add_filter(&censor_filter(region, i), 0, Video);
+    }
+}
+
+/// Burn-in subtitles, from external file or from an embedded subtitle stream (by index).
+fn subtitle(node: &Media, chain: &mut Filters, config: &PlayoutConfig) {
+    let sub = config.processing.subtitle.trim();
+
+    if sub.is_empty() {
+        return;
+    }
+
+    let filter = if let Ok(index) = sub.parse::<u32>() {
+        if node.unit == Ingest || !Path::new(&node.source).is_file() {
+            return;
         }
 
-        chain.add_filter(&logo_chain, 0, Video);
+        format!(
+            "subtitles='{}':si={index}",
+            node.source.replace('\\', "/").replace(':', "\\\\:")
+        )
+    } else if Path::new(sub).is_file() {
+        format!(
+            "subtitles='{}'",
+            sub.replace('\\', "/").replace(':', "\\\\:")
+        )
+    } else {
+        warn!("Subtitle file <b><magenta>{sub}</></b> not exists, skip subtitles!");
+
+        return;
+    };
+
+    chain.add_filter(&filter, 0, Video);
+}
+
+/// Convert to the configured pixel format, yuv420p is the default from the intermediate codec.
+fn pix_fmt(chain: &mut Filters, config: &PlayoutConfig) {
+    let pix_fmt = &config.processing.pix_fmt;
+
+    if pix_fmt != "yuv420p" {
+        chain.add_filter(&format!("format={pix_fmt}"), 0, Video);
+    }
+}
+
+/// Add a right to left scrolling text, on top of the logo.
+fn ticker(chain: &mut Filters, config: &PlayoutConfig) {
+    let ticker = &config.processing.ticker;
+
+    if ticker.enable {
+        if !Path::new(&ticker.textfile).is_file() {
+            warn!(
+                "Ticker text file <b><magenta>{}</></b> not exists, skip ticker!",
+                ticker.textfile
+            );
+
+            return;
+        }
+
+        let mut font = String::new();
+
+        if Path::new(&ticker.fontfile).is_file() {
+            font = format!(
+                ":fontfile='{}'",
+                ticker.fontfile.replace('\\', "/").replace(':', "\\\\:")
+            )
+        }
+
+        chain.add_filter(
+            &format!(
+                "drawtext=textfile='{}':reload=1{font}:fontsize={}:fontcolor={}:y={}:x=w-mod(t*{}\\,w+tw)",
+                ticker.textfile.replace('\\', "/").replace(':', "\\\\:"),
+                ticker.fontsize,
+                ticker.fontcolor,
+                ticker.y,
+                ticker.speed
+            ),
+            0,
+            Video,
+        );
     }
 }
 
@@ -352,14 +671,52 @@ fn add_text(
     }
 }
 
-fn add_audio(node: &Media, chain: &mut Filters, nr: i32) {
+/// Get ffmpeg channel layout name from channel count.
+fn channel_layout(channels: u8) -> String {
+    match channels {
+        1 => "mono".to_string(),
+        2 => "stereo".to_string(),
+        4 => "quad".to_string(),
+        6 => "5.1".to_string(),
+        8 => "7.1".to_string(),
+        c => format!("{c}c"),
+    }
+}
+
+fn add_audio(node: &Media, chain: &mut Filters, config: &PlayoutConfig, nr: i32) {
     let audio = format!(
-        "aevalsrc=0:channel_layout=stereo:duration={}:sample_rate=48000",
+        "aevalsrc=0:channel_layout={}:duration={}:sample_rate=48000",
+        channel_layout(config.processing.audio_channels),
         node.out - node.seek
     );
     chain.add_filter(&audio, nr, Audio);
 }
 
+/// Normalize the channel layout of the source to the configured one.
+///
+/// Ingest streams can not be probed before, so there the node is always added.
+fn audio_layout(node: &Media, chain: &mut Filters, config: &PlayoutConfig, nr: i32) {
+    let target = config.processing.audio_channels;
+    let probe = if Path::new(&node.audio).is_file() {
+        Some(MediaProbe::new(&node.audio))
+    } else {
+        node.probe.clone()
+    };
+
+    let channels = probe
+        .as_ref()
+        .and_then(|p| p.audio_streams.get(nr as usize))
+        .and_then(|a| a.channels);
+
+    if node.unit == Ingest || channels.is_some_and(|c| c != target as i64) {
+        chain.add_filter(
+            &format!("aformat=channel_layouts={}", channel_layout(target)),
+            nr,
+            Audio,
+        )
+    }
+}
+
 fn extend_audio(node: &mut Media, chain: &mut Filters, nr: i32) {
     let probe = if Path::new(&node.audio).is_file() {
         Some(MediaProbe::new(&node.audio))
@@ -383,12 +740,80 @@ fn extend_audio(node: &mut Media, chain: &mut Filters, nr: i32) {
     }
 }
 
+/// Start time delta in seconds between audio and video, over it a clip counts as out of sync.
+pub const AV_SYNC_THRESHOLD: f64 = 0.1;
+
+/// Check if the start time delta between audio and video is over the threshold.
+pub fn av_desync(delta: f64) -> bool {
+    delta.abs() > AV_SYNC_THRESHOLD
+}
+
+/// Start time delta between the first audio and video stream.
+fn av_start_delta(probe: &MediaProbe) -> Option<f64> {
+    let start = |stream: Option<&ffprobe::Stream>| {
+        stream
+            .and_then(|s| s.start_time.clone())
+            .and_then(|t| t.parse::<f64>().ok())
+    };
+
+    Some(start(probe.audio_streams.first())? - start(probe.video_streams.first())?)
+}
+
+/// Start the clip timestamps at zero, in the setpts timestamp mode.
+fn reset_pts(chain: &mut Filters, config: &PlayoutConfig, nr: i32, filter_type: FilterType) {
+    if config.processing.timestamp == TimestampMode::Setpts {
+        let prefix = match filter_type {
+            Audio => "a",
+            Video => "",
+        };
+
+        chain.add_filter(&format!("{prefix}setpts=PTS-STARTPTS"), nr, filter_type)
+    }
+}
+
+fn audio_sync(chain: &mut Filters, config: &PlayoutConfig, nr: i32) {
+    if config.processing.audio_sync {
+        chain.add_filter("aresample=async=1", nr, Audio)
+    }
+}
+
 fn audio_volume(chain: &mut Filters, config: &PlayoutConfig, nr: i32) {
     if config.processing.volume != 1.0 {
         chain.add_filter(&format!("volume={}", config.processing.volume), nr, Audio)
     }
 }
 
+/// Sane range for the clip gain from the playlist.
+pub const MAX_AUDIO_GAIN_DB: f64 = 30.0;
+
+/// Clip gain from the playlist, on top of the global volume.
+///
+/// It comes after loudnorm, which would level the gain out otherwise.
+fn audio_gain(node: &Media, chain: &mut Filters, nr: i32) {
+    let gain = node.audio_gain_db;
+
+    if gain == 0.0 {
+        return;
+    }
+
+    let clamped = gain.clamp(-MAX_AUDIO_GAIN_DB, MAX_AUDIO_GAIN_DB);
+
+    if clamped != gain && nr == 0 {
+        warn!(
+            "Audio gain <yellow>{gain}</> dB is out of range, use <yellow>{clamped}</> dB for: <b><magenta>{}</></b>",
+            node.source
+        );
+    }
+
+    chain.add_filter(&format!("volume={clamped}dB"), nr, Audio)
+}
+
+fn loudnorm(node: &Media, chain: &mut Filters, config: &PlayoutConfig, nr: i32) {
+    if config.processing.loudnorm.enable {
+        chain.add_filter(&a_loudnorm::filter_node(config, node, nr), nr, Audio)
+    }
+}
+
 fn aspect_calc(aspect_string: &Option<String>, config: &PlayoutConfig) -> f64 {
     let mut source_aspect = config.processing.aspect;
 
@@ -453,6 +878,40 @@ pub fn split_filter(chain: &mut Filters, count: usize, nr: i32, filter_type: Fil
     }
 }
 
+/// Split the video and the audio for every rendition of the ABR ladder.
+///
+/// Each rendition gets its own scale, the outputs are mapped in the order of the ladder.
+fn abr_filter(chain: &mut Filters, config: &PlayoutConfig) {
+    let renditions = &config.out.abr.renditions;
+    let count = renditions.len();
+    let mut video_filter = format!(
+        "split={count}{}",
+        (0..count)
+            .map(|i| format!("[vsplit_{i}]"))
+            .collect::<String>()
+    );
+
+    for (i, rendition) in renditions.iter().enumerate() {
+        video_filter.push_str(&format!(
+            ";[vsplit_{i}]scale={}:{}[vabr_{i}]",
+            rendition.width, rendition.height
+        ));
+    }
+
+    let audio_filter = format!(
+        "asplit={count}{}",
+        (0..count)
+            .map(|i| format!("[aabr_{i}]"))
+            .collect::<String>()
+    );
+
+    chain.add_filter(&video_filter, 0, Video);
+    chain.add_filter(&audio_filter, 0, Audio);
+    chain.output_map = (0..count)
+        .flat_map(|i| vec_strings!["-map", format!("[vabr_{i}]"), "-map", format!("[aabr_{i}]")])
+        .collect();
+}
+
 /// Process output filter chain and add new filters to existing ones.
 fn process_output_filters(config: &PlayoutConfig, chain: &mut Filters, custom_filter: &str) {
     let filter =
@@ -502,6 +961,7 @@ pub fn filter_chains(
     node: &mut Media,
     filter_chain: &Option<Arc<Mutex<Vec<String>>>>,
 ) -> Filters {
+    let config = &*config.live_config();
     let mut filters = Filters::new(config.clone(), 0);
 
     if node.unit == Encoder {
@@ -509,7 +969,9 @@ pub fn filter_chains(
             add_text(node, &mut filters, config, filter_chain);
         }
 
-        if let Some(f) = config.out.output_filter.clone() {
+        if config.out.abr.enable {
+            abr_filter(&mut filters, config);
+        } else if let Some(f) = config.out.output_filter.clone() {
             process_output_filters(config, &mut filters, &f)
         } else if config.out.output_count > 1 && !config.processing.audio_only {
             split_filter(&mut filters, config.out.output_count, 0, Video);
@@ -518,7 +980,28 @@ pub fn filter_chains(
         return filters;
     }
 
+    // a separate audio feed is the second input of the ingest server
+    let separate_audio = node.unit == Ingest && config.ingest.audio_input_cmd.is_some();
+
+    if separate_audio {
+        filters.audio_position = 1;
+    }
+
     if !config.processing.audio_only {
+        reset_pts(&mut filters, config, 0, Video);
+
+        // the last frame stays, when the video feed disconnects before the audio
+        if separate_audio {
+            filters.add_filter(
+                &format!(
+                    "tpad=stop_mode=clone:stop_duration={}",
+                    config.ingest.feed_hold_seconds
+                ),
+                0,
+                Video,
+            );
+        }
+
         if let Some(probe) = node.probe.as_ref() {
             if Path::new(&node.audio).is_file() {
                 filters.audio_position = 1;
@@ -528,7 +1011,7 @@ pub fn filter_chains(
                 let aspect = aspect_calc(&v_stream.display_aspect_ratio, config);
                 let frame_per_sec = fps_calc(&v_stream.r_frame_rate, 1.0);
 
-                deinterlace(&v_stream.field_order, &mut filters);
+                deinterlace(&v_stream.field_order, &mut filters, config);
                 pad(aspect, &mut filters, v_stream, config);
                 fps(frame_per_sec, &mut filters, config);
                 scale(
@@ -542,23 +1025,44 @@ pub fn filter_chains(
 
             extend_video(node, &mut filters);
         } else {
+            deinterlace(&None, &mut filters, config);
             fps(0.0, &mut filters, config);
             scale(None, None, 1.0, &mut filters, config);
         }
 
+        color_normalize(&mut filters, config);
+        subtitle(node, &mut filters, config);
         add_text(node, &mut filters, config, filter_chain);
-        fade(node, &mut filters, 0, Video);
+        fade(node, &mut filters, config, 0, Video);
+        censor(&mut filters, config);
         overlay(node, &mut filters, config);
+        ticker(&mut filters, config);
+        pix_fmt(&mut filters, config);
         realtime(node, &mut filters, config, Video);
     }
 
+    // the chain gets build again for the same clip, only the first build warns
+    if let Some(delta) = node
+        .probe
+        .as_ref()
+        .filter(|_| node.filter.is_none())
+        .and_then(av_start_delta)
+    {
+        if av_desync(delta) {
+            warn!(
+                "Audio starts <yellow>{delta:.3}</> seconds apart from video in: <b><magenta>{}</></b>",
+                node.source
+            );
+        }
+    }
+
     let (proc_vf, proc_af) = if node.unit == Ingest {
         custom::filter_node(&config.ingest.custom_filter)
     } else {
         custom::filter_node(&config.processing.custom_filter)
     };
 
-    let (list_vf, list_af) = custom::filter_node(&node.custom_filter);
+    let (list_vf, list_af) = custom::clip_filter_node(&node.custom_filter, &node.source);
 
     if config.processing.audio_only {
         realtime(node, &mut filters, config, Audio);
@@ -575,28 +1079,46 @@ pub fn filter_chains(
             .is_some()
             || Path::new(&node.audio).is_file()
         {
+            audio_sync(&mut filters, config, i);
             extend_audio(node, &mut filters, i);
         } else if node.unit == Decoder {
             warn!(
                 "Missing audio track (id {i}) from <b><magenta>{}</></b>",
                 node.source
             );
-            add_audio(node, &mut filters, i);
+            add_audio(node, &mut filters, config, i);
         }
 
+        audio_layout(node, &mut filters, config, i);
+
+        reset_pts(&mut filters, config, i, Audio);
+
         // add at least anull filter, for correct filter construction,
         // is important for split filter in HLS mode
         filters.add_filter("anull", i, Audio);
 
-        fade(node, &mut filters, i, Audio);
+        // silence follows, when the audio feed disconnects before the video
+        if separate_audio {
+            filters.add_filter(
+                &format!("apad=pad_dur={}", config.ingest.feed_hold_seconds),
+                i,
+                Audio,
+            );
+        }
+
+        fade(node, &mut filters, config, i, Audio);
         audio_volume(&mut filters, config, i);
+        loudnorm(node, &mut filters, config, i);
+        audio_gain(node, &mut filters, i);
 
         custom(&proc_af, &mut filters, i, Audio);
         custom(&list_af, &mut filters, i, Audio);
     }
 
     if config.out.mode == HLS {
-        if let Some(f) = config.out.output_filter.clone() {
+        if config.out.abr.enable {
+            abr_filter(&mut filters, config);
+        } else if let Some(f) = config.out.output_filter.clone() {
             process_output_filters(config, &mut filters, &f)
         }
     }
@@ -0,0 +1,265 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    process::Command,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use regex::Regex;
+use simplelog::*;
+
+use crate::utils::{CensorMode, FpsConvert, IngestMode, PlayoutConfig};
+
+/// Oldest ffmpeg version, which has all filters and options we use.
+pub const MIN_FFMPEG_VERSION: (u32, u32) = (4, 0);
+
+/// Build flags, which are no libraries.
+const IGNORE_FLAGS: [&str; 7] = [
+    "--enable-gpl",
+    "--enable-version3",
+    "--enable-runtime-cpudetect",
+    "--enable-avfilter",
+    "--enable-zlib",
+    "--enable-pic",
+    "--enable-nonfree",
+];
+
+/// Capabilities per ffmpeg binary, channels can use different ones.
+static CAPABILITIES: OnceLock<Mutex<HashMap<String, Arc<FfmpegCapabilities>>>> = OnceLock::new();
+
+thread_local! {
+    static FFPROBE_PATH: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Libraries, filters and protocols of the local ffmpeg.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FfmpegCapabilities {
+    pub libs: Vec<String>,
+    pub filters: Vec<String>,
+    pub input_protocols: Vec<String>,
+    pub output_protocols: Vec<String>,
+}
+
+impl FfmpegCapabilities {
+    /// Read the capabilities from the output of `-buildconf`, `-filters` and `-protocols`.
+    pub fn parse(buildconf: &str, filters: &str, protocols: &str) -> Self {
+        let (input_protocols, output_protocols) = parse_protocols(protocols);
+
+        Self {
+            libs: parse_libs(buildconf),
+            filters: parse_filters(filters),
+            input_protocols,
+            output_protocols,
+        }
+    }
+
+    pub fn has_lib(&self, lib: &str) -> bool {
+        self.libs.iter().any(|l| l == lib)
+    }
+
+    pub fn has_filter(&self, filter: &str) -> bool {
+        self.filters.iter().any(|f| f == filter)
+    }
+
+    pub fn has_input_protocol(&self, protocol: &str) -> bool {
+        self.input_protocols.iter().any(|p| p == protocol)
+    }
+}
+
+/// Libraries from the configure flags, like `libx264` from `--enable-libx264`.
+pub fn parse_libs(buildconf: &str) -> Vec<String> {
+    buildconf
+        .split_whitespace()
+        .filter(|f| f.starts_with("--enable-") && !IGNORE_FLAGS.contains(f))
+        .map(|f| f.replace("--enable-", ""))
+        .collect()
+}
+
+/// Filter names from the filter list, the lines look like: ` T.C overlay  VV->V  Overlay a video...`
+pub fn parse_filters(filters: &str) -> Vec<String> {
+    filters
+        .lines()
+        .filter(|l| l.contains("->"))
+        .filter_map(|l| {
+            let line = l.split_whitespace().collect::<Vec<_>>();
+
+            match line.len() > 2 {
+                true => Some(line[1].to_string()),
+                false => None,
+            }
+        })
+        .collect()
+}
+
+/// Input and output protocols, from the `Input:` and `Output:` blocks.
+pub fn parse_protocols(protocols: &str) -> (Vec<String>, Vec<String>) {
+    let mut input = vec![];
+    let mut output = vec![];
+    let mut block = None;
+
+    for line in protocols.lines().map(str::trim) {
+        match line {
+            "Input:" => block = Some(&mut input),
+            "Output:" => block = Some(&mut output),
+            "" => {}
+            name if !name.contains(' ') && !name.ends_with(':') => {
+                if let Some(list) = block.as_mut() {
+                    list.push(name.to_string())
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (input, output)
+}
+
+fn ffmpeg_output(ffmpeg: &str, args: &[&str]) -> Result<String, String> {
+    match Command::new(ffmpeg).args(args).output() {
+        Ok(out) => Ok(String::from_utf8_lossy(&out.stdout).to_string()),
+        Err(e) => Err(format!("couldn't spawn ffmpeg process {ffmpeg}: {e}")),
+    }
+}
+
+/// Major and minor version from `ffmpeg -version`, like `ffmpeg version 6.1.1-static`.
+///
+/// Git builds, like `ffmpeg version N-112345-gabcdef`, have no version number.
+pub fn parse_ffmpeg_version(output: &str) -> Option<(u32, u32)> {
+    let re = Regex::new(r"^ffmpeg version n?(\d+)\.(\d+)").unwrap();
+    let caps = re.captures(output.lines().next()?)?;
+
+    Some((caps[1].parse().ok()?, caps[2].parse().ok()?))
+}
+
+/// Check the version against [`MIN_FFMPEG_VERSION`], an unknown version is only a warning.
+pub fn check_ffmpeg_version(output: &str) -> Result<(), String> {
+    let (major, minor) = MIN_FFMPEG_VERSION;
+
+    match parse_ffmpeg_version(output) {
+        Some(version) if version < MIN_FFMPEG_VERSION => Err(format!(
+            "ffmpeg version {}.{} is to old, at least {major}.{minor} is needed!",
+            version.0, version.1
+        )),
+        Some(_) => Ok(()),
+        None => {
+            warn!(
+                "Unknown ffmpeg version, make sure it is at least <yellow>{major}.{minor}</>: {}",
+                output.lines().next().unwrap_or_default()
+            );
+
+            Ok(())
+        }
+    }
+}
+
+/// Run `-version` of the configured ffmpeg and check it.
+pub fn ffmpeg_version(config: &PlayoutConfig) -> Result<(), String> {
+    check_ffmpeg_version(&ffmpeg_output(
+        &config.processing.ffmpeg_path,
+        &["-version"],
+    )?)
+}
+
+/// Set the ffprobe binary for the probes of the current thread.
+///
+/// Every channel has its own, [`spawn_tagged`](crate::utils::spawn_tagged) hands it over
+/// to the threads of the channel.
+pub fn set_ffprobe_path(path: &str) {
+    FFPROBE_PATH.with(|p| *p.borrow_mut() = Some(path.to_string()));
+}
+
+/// Configured ffprobe binary of the current thread.
+pub fn ffprobe_path_set() -> Option<String> {
+    FFPROBE_PATH.with(|p| p.borrow().clone())
+}
+
+/// Configured ffprobe binary, without config it is `ffprobe` from PATH.
+pub fn ffprobe_path() -> String {
+    ffprobe_path_set().unwrap_or_else(|| "ffprobe".to_string())
+}
+
+/// Probe the ffmpeg binary, the result is cached for all channels with the same binary.
+pub fn ffmpeg_capabilities(ffmpeg: &str) -> Result<Arc<FfmpegCapabilities>, String> {
+    let cache = CAPABILITIES.get_or_init(Default::default);
+
+    if let Some(caps) = cache.lock().unwrap().get(ffmpeg) {
+        return Ok(caps.clone());
+    }
+
+    let caps = Arc::new(FfmpegCapabilities::parse(
+        &ffmpeg_output(ffmpeg, &["-hide_banner", "-buildconf"])?,
+        &ffmpeg_output(ffmpeg, &["-hide_banner", "-filters"])?,
+        &ffmpeg_output(ffmpeg, &["-hide_banner", "-protocols"])?,
+    ));
+
+    cache
+        .lock()
+        .unwrap()
+        .insert(ffmpeg.to_string(), caps.clone());
+
+    Ok(caps)
+}
+
+/// Capabilities, which the config needs, but ffmpeg not has.
+pub fn missing_capabilities(config: &PlayoutConfig, caps: &FfmpegCapabilities) -> Vec<String> {
+    let processing = &config.processing;
+    let mut missing = vec![];
+    let mut need_filter = |filter: &str, feature: &str| {
+        if !caps.has_filter(filter) {
+            missing.push(format!("filter '{filter}' for {feature}"));
+        }
+    };
+
+    if !processing.audio_only {
+        if processing.add_logo || !processing.logos.is_empty() {
+            need_filter("movie", "logo overlay");
+            need_filter("overlay", "logo overlay");
+        }
+
+        if !processing.subtitle.is_empty() {
+            need_filter("subtitles", "subtitles");
+        }
+
+        for region in &processing.censor {
+            match region.mode {
+                CensorMode::Blur => need_filter("boxblur", "censor region"),
+                CensorMode::Delogo => need_filter("delogo", "censor region"),
+            }
+        }
+
+        if processing.ticker.enable {
+            need_filter("drawtext", "ticker");
+        }
+
+        if config.text.add_text {
+            need_filter("drawtext", "text overlay");
+        }
+    }
+
+    if processing.loudnorm.enable {
+        need_filter("loudnorm", "loudnorm");
+    }
+
+    if processing.fps_convert == FpsConvert::Interpolate {
+        need_filter("minterpolate", "fps_convert");
+    }
+
+    if config.ingest.enable {
+        // rtsp is a format in ffmpeg, not a protocol
+        let protocol = match config.ingest.mode {
+            IngestMode::Rtmp => Some("rtmp"),
+            IngestMode::Rtsp => None,
+            IngestMode::Srt => Some("srt"),
+            IngestMode::Multicast => Some(config.ingest.multicast.protocol.as_str()),
+        };
+
+        if let Some(protocol) = protocol.filter(|p| !caps.has_input_protocol(p)) {
+            missing.push(format!(
+                "protocol '{protocol}' for {} ingest",
+                config.ingest.mode
+            ));
+        }
+    }
+
+    missing
+}
@@ -1,19 +1,30 @@
 use std::{
-    env, fmt,
-    fs::File,
+    borrow::Cow,
+    env, fmt, fs,
+    net::SocketAddr,
     path::{Path, PathBuf},
     process,
     str::FromStr,
+    sync::{Arc, RwLock},
+    time::Duration,
 };
 
 use log::LevelFilter;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use regex::Regex;
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
 use shlex::split;
 
 use super::vec_strings;
 use crate::utils::{free_tcp_socket, home_dir, time_to_sec, OutputMode::*};
 
 pub const DUMMY_LEN: f64 = 60.0;
+pub const REMOTE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Max frames, which the loop filter can hold.
+pub const MAX_LOOP_SIZE: u32 = 32767;
+/// Longest preroll, more only delays the clip switches.
+pub const MAX_PREROLL_MS: u64 = 5000;
 pub const IMAGE_FORMAT: [&str; 21] = [
     "bmp", "dds", "dpx", "exr", "gif", "hdr", "j2k", "jpg", "jpeg", "pcx", "pfm", "pgm", "phm",
     "png", "psd", "ppm", "sgi", "svg", "tga", "tif", "webp",
@@ -42,11 +53,60 @@ pub const FFMPEG_UNRECOVERABLE_ERRORS: [&str; 5] = [
     "Error while decoding stream #0:0: Invalid data found when processing input",
 ];
 
+/// Flags in the extra output arguments, which would break the pipeline.
+///
+/// Stream specifiers count too, like `-map:0` or `-filter:v`.
+pub const EXTRA_ARGS_DENYLIST: [&str; 7] = [
+    "-i",
+    "-map",
+    "-filter_complex",
+    "-lavfi",
+    "-filter",
+    "-vf",
+    "-af",
+];
+
+/// Pixel formats of mpeg2video, the default codec between decoder and encoder.
+const MPEG2_PIX_FMTS: [&str; 2] = ["yuv420p", "yuv422p"];
+
+/// Pixel formats of libx264, for the other formats between decoder and encoder.
+const X264_PIX_FMTS: [&str; 15] = [
+    "yuv420p",
+    "yuvj420p",
+    "yuv422p",
+    "yuvj422p",
+    "yuv444p",
+    "yuvj444p",
+    "nv12",
+    "nv16",
+    "nv21",
+    "yuv420p10le",
+    "yuv422p10le",
+    "yuv444p10le",
+    "nv20le",
+    "gray",
+    "gray10le",
+];
+
+/// Video encoders, which can't encode more then 8 bit per channel.
+pub const EIGHT_BIT_ENCODERS: [&str; 9] = [
+    "mpeg2video",
+    "mpeg4",
+    "libxvid",
+    "libvpx",
+    "flv",
+    "h264_nvenc",
+    "h264_qsv",
+    "h264_vaapi",
+    "h264_videotoolbox",
+];
+
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum OutputMode {
     Desktop,
     HLS,
+    Dash,
     Null,
     Stream,
 }
@@ -58,18 +118,134 @@ impl FromStr for OutputMode {
         match input {
             "desktop" => Ok(Self::Desktop),
             "hls" => Ok(Self::HLS),
+            "dash" => Ok(Self::Dash),
             "null" => Ok(Self::Null),
             "stream" => Ok(Self::Stream),
-            _ => Err("Use 'desktop', 'hls', 'null' or 'stream'".to_string()),
+            _ => Err("Use 'desktop', 'hls', 'dash', 'null' or 'stream'".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IngestMode {
+    #[default]
+    Rtmp,
+    Rtsp,
+    Srt,
+    Multicast,
+}
+
+impl fmt::Display for IngestMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IngestMode::Rtmp => write!(f, "rtmp"),
+            IngestMode::Rtsp => write!(f, "rtsp"),
+            IngestMode::Srt => write!(f, "srt"),
+            IngestMode::Multicast => write!(f, "multicast"),
+        }
+    }
+}
+
+/// How the ingest server handles a full channel, when the encoder reads too slow.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Backpressure {
+    #[default]
+    Block,
+    DropOldest,
+    Terminate,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MulticastProtocol {
+    #[default]
+    Udp,
+    Rtp,
+}
+
+impl MulticastProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            MulticastProtocol::Udp => "udp",
+            MulticastProtocol::Rtp => "rtp",
+        }
+    }
+}
+
+impl fmt::Display for MulticastProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Output container, it sets the `-f` of the output.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Container {
+    Flv,
+    Mpegts,
+    Hls,
+    Mkv,
+    Mp4,
+    Rtsp,
+}
+
+impl Container {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Container::Flv => "flv",
+            Container::Mpegts => "mpegts",
+            Container::Hls => "hls",
+            Container::Mkv => "mkv",
+            Container::Mp4 => "mp4",
+            Container::Rtsp => "rtsp",
+        }
+    }
+
+    /// Name of the ffmpeg muxer.
+    pub fn muxer(&self) -> &'static str {
+        match *self {
+            Container::Mkv => "matroska",
+            _ => self.as_str(),
+        }
+    }
+
+    /// Containers, which work with the output target, `None` when all of them can work.
+    ///
+    /// Streaming protocols are checked by the scheme, files by the extension.
+    pub fn for_target(target: &str) -> Option<&'static [Container]> {
+        let target = target.to_lowercase();
+
+        match target.split_once("://").map(|(scheme, _)| scheme) {
+            Some("rtmp" | "rtmps") => Some(&[Container::Flv]),
+            Some("srt" | "udp" | "rtp") => Some(&[Container::Mpegts]),
+            Some("rtsp" | "rtsps") => Some(&[Container::Rtsp]),
+            _ => match Path::new(&target).extension().and_then(|e| e.to_str()) {
+                Some("m3u8") => Some(&[Container::Hls]),
+                Some("ts") => Some(&[Container::Mpegts]),
+                Some("flv") => Some(&[Container::Flv]),
+                Some("mkv") => Some(&[Container::Mkv]),
+                Some("mp4" | "mov") => Some(&[Container::Mp4]),
+                _ => None,
+            },
         }
     }
 }
 
+impl fmt::Display for Container {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum ProcessMode {
     Folder,
     Playlist,
+    Loop,
 }
 
 impl fmt::Display for ProcessMode {
@@ -77,6 +253,7 @@ impl fmt::Display for ProcessMode {
         match *self {
             ProcessMode::Folder => write!(f, "folder"),
             ProcessMode::Playlist => write!(f, "playlist"),
+            ProcessMode::Loop => write!(f, "loop"),
         }
     }
 }
@@ -88,7 +265,8 @@ impl FromStr for ProcessMode {
         match input {
             "folder" => Ok(Self::Folder),
             "playlist" => Ok(Self::Playlist),
-            _ => Err("Use 'folder' or 'playlist'".to_string()),
+            "loop" => Ok(Self::Loop),
+            _ => Err("Use 'folder', 'playlist' or 'loop'".to_string()),
         }
     }
 }
@@ -131,6 +309,10 @@ where
 pub struct PlayoutConfig {
     pub general: General,
     pub rpc_server: RpcServer,
+    #[serde(default)]
+    pub metrics: Metrics,
+    #[serde(default)]
+    pub control: Control,
     pub mail: Mail,
     pub logging: Logging,
     pub processing: Processing,
@@ -139,6 +321,10 @@ pub struct PlayoutConfig {
     pub storage: Storage,
     pub text: Text,
     pub out: Out,
+
+    /// Settings from a config reload, all clones share them.
+    #[serde(skip)]
+    pub live: Arc<RwLock<LiveSettings>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -146,12 +332,27 @@ pub struct General {
     pub help_text: String,
     pub stop_threshold: f64,
 
+    #[serde(default)]
+    pub channel: String,
+
     #[serde(default)]
     pub stat_file: String,
 
+    #[serde(default)]
+    pub resume_on_restart: bool,
+
+    #[serde(default)]
+    pub resume_file: String,
+
+    #[serde(default = "default_resume_max_age")]
+    pub resume_max_age: u64,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub generate: Option<Vec<String>>,
 
+    #[serde(skip_serializing, skip_deserializing)]
+    pub config_path: String,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub ffmpeg_filters: Vec<String>,
 
@@ -167,6 +368,48 @@ pub struct RpcServer {
     pub authorization: String,
 }
 
+/// Prometheus metrics endpoint.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Metrics {
+    pub help_text: String,
+    pub enable: bool,
+    pub address: String,
+    pub ready_seconds: u64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            help_text: String::new(),
+            enable: false,
+            address: "127.0.0.1:9598".to_string(),
+            ready_seconds: 10,
+        }
+    }
+}
+
+/// REST control server, requests need the token as bearer authorization.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Control {
+    pub help_text: String,
+    pub enable: bool,
+    pub address: String,
+    pub token: String,
+}
+
+impl Default for Control {
+    fn default() -> Self {
+        Self {
+            help_text: String::new(),
+            enable: false,
+            address: "127.0.0.1:9600".to_string(),
+            token: String::new(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Mail {
     pub help_text: String,
@@ -178,6 +421,14 @@ pub struct Mail {
     pub recipient: String,
     pub mail_level: String,
     pub interval: u64,
+    #[serde(default)]
+    pub smtp_port: Option<u16>,
+    #[serde(default = "default_alert_failures")]
+    pub alert_failures: usize,
+    #[serde(default = "default_alert_window")]
+    pub alert_window: u64,
+    #[serde(default = "default_alert_cooldown")]
+    pub alert_cooldown: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -197,6 +448,30 @@ pub struct Logging {
     pub level: LevelFilter,
     pub ffmpeg_level: String,
     pub ingest_level: Option<String>,
+    #[serde(default)]
+    pub format: LogFormat,
+    #[serde(default)]
+    pub rotate: LogRotate,
+    #[serde(default)]
+    pub per_channel_files: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct LogRotate {
+    #[serde(default)]
+    pub max_size_mb: u64,
+    #[serde(default)]
+    pub max_files: usize,
+    #[serde(default)]
+    pub max_age_days: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -209,153 +484,1681 @@ pub struct Processing {
     pub height: i64,
     pub aspect: f64,
     pub fps: f64,
+    #[serde(default)]
+    pub fps_convert: FpsConvert,
     pub add_logo: bool,
     pub logo: String,
     pub logo_scale: String,
     pub logo_opacity: f32,
+    #[serde(default = "default_logo_x")]
+    pub logo_x: String,
+    #[serde(default = "default_logo_y")]
+    pub logo_y: String,
+    #[serde(default)]
     pub logo_filter: String,
+    #[serde(default)]
+    pub logo_animated: bool,
+    #[serde(default)]
+    pub logo_loop_size: u32,
+    #[serde(default)]
+    pub logo_enable: LogoEnable,
+    #[serde(default)]
+    pub logos: Vec<Logo>,
+    #[serde(default)]
+    pub censor: Vec<CensorRegion>,
     #[serde(default = "default_tracks")]
     pub audio_tracks: i32,
     #[serde(default = "default_channels")]
     pub audio_channels: u8,
     pub volume: f64,
+    #[serde(default = "default_fade_in")]
+    pub fade_in_duration: f64,
+    #[serde(default)]
+    pub fade_out_duration: f64,
     #[serde(default)]
     pub custom_filter: String,
+    #[serde(default)]
+    pub ticker: Ticker,
+    #[serde(default)]
+    pub subtitle: String,
+    #[serde(default)]
+    pub loudnorm: Loudnorm,
+    #[serde(default)]
+    pub loudnorm_mode: LoudnormMode,
+    #[serde(default)]
+    pub loudness_report_path: String,
+    #[serde(default)]
+    pub hwaccel: HwAccel,
+    #[serde(default)]
+    pub deinterlace: Deinterlace,
+    #[serde(default)]
+    pub fit_mode: FitMode,
+    #[serde(default)]
+    pub color_normalize: bool,
+    #[serde(default)]
+    pub audio_sync: bool,
+    #[serde(default = "default_color_matrix")]
+    pub color_matrix: String,
+    #[serde(default)]
+    pub color_range: ColorRange,
+    #[serde(default)]
+    pub seek_mode: SeekMode,
+    #[serde(default)]
+    pub snap_to_keyframe: bool,
+    #[serde(default = "default_pix_fmt")]
+    pub pix_fmt: String,
+    #[serde(default)]
+    pub decoder_mode: DecoderMode,
+    #[serde(default)]
+    pub gop_size: u32,
+    #[serde(default = "default_input_reconnect")]
+    pub input_reconnect: u32,
+    #[serde(default)]
+    pub stall_timeout_seconds: u64,
+    #[serde(default)]
+    pub preroll_ms: u64,
+    #[serde(default)]
+    pub realtime_read: bool,
+    #[serde(default)]
+    pub timestamp: TimestampMode,
+    #[serde(default = "default_ffmpeg_path")]
+    pub ffmpeg_path: String,
+    #[serde(default = "default_ffprobe_path")]
+    pub ffprobe_path: String,
+    #[serde(default)]
+    pub ffmpeg_threads: i32,
+    #[serde(default)]
+    pub filter_threads: i32,
+    #[serde(default)]
+    pub nice: i32,
+    #[serde(default)]
+    pub cgroup: String,
+    #[serde(default)]
+    pub video_bitrate: String,
+    #[serde(default)]
+    pub audio_bitrate: String,
+    #[serde(default)]
+    pub maxrate: String,
+    #[serde(default)]
+    pub bufsize: String,
 
     #[serde(skip_serializing, skip_deserializing)]
     pub cmd: Option<Vec<String>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Ingest {
-    pub help_text: String,
-    pub enable: bool,
-    input_param: String,
-    #[serde(default)]
-    pub custom_filter: String,
-
-    #[serde(skip_serializing, skip_deserializing)]
-    pub input_cmd: Option<Vec<String>>,
+/// Ordered starts always with the first clip, realtime with the clip for the current time.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistMode {
+    Ordered,
+    #[default]
+    Realtime,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Playlist {
-    pub help_text: String,
-    pub path: String,
-    pub day_start: String,
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SeekMode {
+    #[default]
+    Fast,
+    Accurate,
+}
 
-    #[serde(skip_serializing, skip_deserializing)]
-    pub start_sec: Option<f64>,
+/// Timestamps of the decoder output.
+///
+/// Auto leaves them to ffmpeg, genpts regenerates missing timestamps, setpts starts every
+/// clip at zero, copyts keeps the timestamps from the source.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampMode {
+    #[default]
+    Auto,
+    Genpts,
+    Copyts,
+    Setpts,
+}
 
-    pub length: String,
+/// Per clip starts a new decoder for every clip, continuous starts it before the clip before ends.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DecoderMode {
+    #[default]
+    PerClip,
+    Continuous,
+}
 
-    #[serde(skip_serializing, skip_deserializing)]
-    pub length_sec: Option<f64>,
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Deinterlace {
+    #[default]
+    None,
+    Yadif,
+    Bwdif,
+}
 
-    pub infinit: bool,
+/// Target color range, for normalizing the colors.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorRange {
+    #[default]
+    Limited,
+    Full,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Storage {
-    pub help_text: String,
-    pub path: String,
-    #[serde(skip_serializing, skip_deserializing)]
-    pub paths: Vec<String>,
-    pub filler_clip: String,
-    pub extensions: Vec<String>,
-    pub shuffle: bool,
+impl fmt::Display for ColorRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ColorRange::Limited => write!(f, "limited"),
+            ColorRange::Full => write!(f, "full"),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Text {
-    pub help_text: String,
-    pub add_text: bool,
+/// How sources with a different aspect ratio fit into the output size.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FitMode {
+    #[default]
+    Stretch,
+    Pad,
+    Crop,
+}
 
-    #[serde(skip_serializing, skip_deserializing)]
-    pub node_pos: Option<usize>,
+/// Frame rate conversion, interpolate creates the new frames with motion compensation.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FpsConvert {
+    #[default]
+    Simple,
+    Interpolate,
+}
 
-    #[serde(skip_serializing, skip_deserializing)]
-    pub zmq_stream_socket: Option<String>,
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HwAccel {
+    #[default]
+    None,
+    Nvenc,
+    Vaapi,
+    Qsv,
+}
 
-    #[serde(skip_serializing, skip_deserializing)]
-    pub zmq_server_socket: Option<String>,
+impl HwAccel {
+    /// Name of the accelerator, like `ffmpeg -hwaccels` list it.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::None => None,
+            Self::Nvenc => Some("cuda"),
+            Self::Vaapi => Some("vaapi"),
+            Self::Qsv => Some("qsv"),
+        }
+    }
+}
 
-    pub fontfile: String,
-    pub text_from_filename: bool,
-    pub style: String,
-    pub regex: String,
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LoudnormMode {
+    #[default]
+    Single,
+    Dual,
 }
 
+/// Loudness normalization targets, the mode is `processing.loudnorm_mode`.
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Out {
-    pub help_text: String,
-    pub mode: OutputMode,
-    pub output_param: String,
-
-    #[serde(skip_serializing, skip_deserializing)]
-    pub output_count: usize,
-    #[serde(skip_serializing, skip_deserializing)]
-    pub output_filter: Option<String>,
-    #[serde(skip_serializing, skip_deserializing)]
-    pub output_cmd: Option<Vec<String>>,
+#[serde(default)]
+pub struct Loudnorm {
+    pub enable: bool,
+    pub integrated: f64,
+    pub true_peak: f64,
+    pub lra: f64,
 }
 
-fn default_tracks() -> i32 {
-    1
+impl Default for Loudnorm {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            integrated: -18.0,
+            true_peak: -1.5,
+            lra: 11.0,
+        }
+    }
 }
 
-fn default_channels() -> u8 {
-    2
+/// Filter for a censored region.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CensorMode {
+    #[default]
+    Blur,
+    Delogo,
 }
 
-impl PlayoutConfig {
-    /// Read config from YAML file, and set some extra config values.
-    pub fn new(cfg_path: Option<String>) -> Self {
-        let mut config_path = PathBuf::from("/etc/ffplayout/ffplayout.yml");
+/// Region of the frame, which gets blurred or removed with delogo.
+///
+/// With a `end` after `start` it is active only in this time of the clip, in seconds.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct CensorRegion {
+    pub mode: CensorMode,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub strength: u32,
+    pub start: f64,
+    pub end: f64,
+}
 
-        if let Some(cfg) = cfg_path {
-            config_path = PathBuf::from(cfg);
+impl Default for CensorRegion {
+    fn default() -> Self {
+        Self {
+            mode: CensorMode::Blur,
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+            strength: 10,
+            start: 0.0,
+            end: 0.0,
         }
+    }
+}
 
-        if !config_path.is_file() {
-            if Path::new("./assets/ffplayout.yml").is_file() {
-                config_path = PathBuf::from("./assets/ffplayout.yml")
-            } else if let Some(p) = env::current_exe().ok().as_ref().and_then(|op| op.parent()) {
-                config_path = p.join("ffplayout.yml")
-            };
+impl CensorRegion {
+    /// The region must fit in the frame, boxblur needs a radius from the half chroma plane.
+    pub fn check(&self, frame_width: i64, frame_height: i64) -> Result<(), String> {
+        if self.width == 0 || self.height == 0 {
+            return Err("width and height must be positive".to_string());
         }
 
-        let f = match File::open(&config_path) {
-            Ok(file) => file,
-            Err(_) => {
-                println!(
-                    "{config_path:?} doesn't exists!\nPut \"ffplayout.yml\" in \"/etc/playout/\" or beside the executable!"
-                );
-                process::exit(1);
-            }
-        };
-
-        let mut config: PlayoutConfig =
-            serde_yaml::from_reader(f).expect("Could not read config file.");
-        config.general.generate = None;
+        if self.x as i64 + self.width as i64 > frame_width
+            || self.y as i64 + self.height as i64 > frame_height
+        {
+            return Err(format!(
+                "region {}x{} at {}:{} is outside of the {frame_width}x{frame_height} frame",
+                self.width, self.height, self.x, self.y
+            ));
+        }
 
-        config.general.stat_file = home_dir()
-            .unwrap_or_else(env::temp_dir)
-            .join(if config.general.stat_file.is_empty() {
-                ".ffp_status"
-            } else {
-                &config.general.stat_file
-            })
-            .display()
-            .to_string();
+        if self.mode == CensorMode::Blur
+            && (self.strength == 0 || self.strength > self.width.min(self.height) / 4)
+        {
+            return Err(format!(
+                "strength must be between 1 and {}, a quarter of the region size",
+                self.width.min(self.height) / 4
+            ));
+        }
 
-        if config.logging.ingest_level.is_none() {
-            config.logging.ingest_level = Some(config.logging.ffmpeg_level.clone())
+        if self.end < 0.0 || self.start < 0.0 {
+            return Err("start and end must not be negative".to_string());
         }
 
-        config.playlist.start_sec = Some(time_to_sec(&config.playlist.day_start));
+        Ok(())
+    }
+}
 
-        if config.playlist.length.contains(':') {
+/// Additional logo, which get overlaid after the main logo.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Logo {
+    pub path: String,
+    pub scale: String,
+    pub opacity: f32,
+    pub x: String,
+    pub y: String,
+    pub enable: String,
+    pub animated: bool,
+    pub loop_size: u32,
+}
+
+impl Default for Logo {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            scale: String::new(),
+            opacity: 1.0,
+            x: default_logo_x(),
+            y: default_logo_y(),
+            enable: String::new(),
+            animated: false,
+            loop_size: 0,
+        }
+    }
+}
+
+/// Time window of the main logo, in seconds of the clip.
+///
+/// With a `period` the window repeats, `start` and `end` are then inside of every period.
+/// Without a `end` after `start` the logo is always on.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct LogoEnable {
+    pub start: f64,
+    pub end: f64,
+    pub period: f64,
+}
+
+impl LogoEnable {
+    /// Expression for the enable option of the overlay, empty when the logo is always on.
+    pub fn expr(&self) -> String {
+        if self.end <= self.start {
+            return String::new();
+        }
+
+        match self.period > 0.0 {
+            true => format!(
+                "between(mod(t,{}),{},{})",
+                self.period, self.start, self.end
+            ),
+            false => format!("between(t,{},{})", self.start, self.end),
+        }
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        if self.start < 0.0 || self.end < 0.0 || self.period < 0.0 {
+            return Err("start, end and period must not be negative".to_string());
+        }
+
+        if self.period > 0.0 && self.end > self.period {
+            return Err(format!(
+                "end {} is after the period of {} seconds",
+                self.end, self.period
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Output target for the tee muxer, all targets share the same encoding.
+///
+/// The encoded streams are copied to every target, without encoding them again,
+/// `options` can have a `select`, for sending only some of the streams.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TeeOutput {
+    pub format: String,
+    pub options: String,
+    pub ignore_failure: bool,
+    pub target: String,
+}
+
+/// Multicast group for ingest and output, the stream is always mpegts.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Multicast {
+    pub protocol: MulticastProtocol,
+    pub address: String,
+    pub ttl: u8,
+    pub pkt_size: u32,
+    pub fifo_size: u32,
+}
+
+impl Default for Multicast {
+    fn default() -> Self {
+        Self {
+            protocol: MulticastProtocol::Udp,
+            address: String::new(),
+            ttl: 16,
+            pkt_size: 1316,
+            fifo_size: 0,
+        }
+    }
+}
+
+/// Picture-in-picture box, which shows the live ingest over the playlist.
+///
+/// `x` and `y` are expressions of the overlay filter, the border is around the box.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Pip {
+    pub enable: bool,
+    pub width: u32,
+    pub height: u32,
+    pub x: String,
+    pub y: String,
+    pub border: u32,
+    pub border_color: String,
+}
+
+impl Default for Pip {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            width: 480,
+            height: 270,
+            x: "W-w-20".to_string(),
+            y: "20".to_string(),
+            border: 0,
+            border_color: "white".to_string(),
+        }
+    }
+}
+
+impl Multicast {
+    /// Url with the socket options, like `udp://239.0.0.1:1234?ttl=16&pkt_size=1316`.
+    ///
+    /// The ttl is only used for sending, the fifo only for receiving over udp.
+    pub fn url(&self, output: bool) -> String {
+        let mut options = vec![];
+
+        if output {
+            options.push(format!("ttl={}", self.ttl));
+        }
+
+        if self.pkt_size > 0 {
+            options.push(format!("pkt_size={}", self.pkt_size));
+        }
+
+        if !output && self.fifo_size > 0 && self.protocol == MulticastProtocol::Udp {
+            options.push(format!("fifo_size={}", self.fifo_size));
+            options.push("overrun_nonfatal=1".to_string());
+        }
+
+        let mut url = format!("{}://{}", self.protocol, self.address);
+
+        if !options.is_empty() {
+            url.push_str(&format!("?{}", options.join("&")));
+        }
+
+        url
+    }
+
+    /// Ingest input, which joins the group.
+    pub fn input_cmd(&self) -> Vec<String> {
+        match self.protocol {
+            MulticastProtocol::Udp => vec_strings!["-f", "mpegts", "-i", self.url(false)],
+            MulticastProtocol::Rtp => vec_strings!["-i", self.url(false)],
+        }
+    }
+
+    /// Output to the group, as target for the tee muxer or as single output.
+    pub fn tee_output(&self) -> TeeOutput {
+        let format = match self.protocol {
+            MulticastProtocol::Udp => "mpegts",
+            MulticastProtocol::Rtp => "rtp_mpegts",
+        };
+
+        TeeOutput {
+            format: format.to_string(),
+            target: self.url(true),
+            ..Default::default()
+        }
+    }
+
+    /// Check the group address, it needs a port.
+    ///
+    /// Addresses outside of 224.0.0.0/4 and ff00::/8 still work as unicast, they give `Ok(false)`.
+    pub fn check_address(&self) -> Result<bool, String> {
+        self.address
+            .parse::<SocketAddr>()
+            .map(|addr| addr.ip().is_multicast())
+            .map_err(|_| {
+                format!(
+                    "'{}' is no address with port, like 239.0.0.1:1234",
+                    self.address
+                )
+            })
+    }
+}
+
+/// Recording of the program to dated files, which rolls over after every segment.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Archive {
+    pub dir: String,
+    pub segment_seconds: u64,
+    pub max_age_days: u64,
+}
+
+impl Default for Archive {
+    fn default() -> Self {
+        Self {
+            dir: String::new(),
+            segment_seconds: 3600,
+            max_age_days: 0,
+        }
+    }
+}
+
+/// Step of the encoder fallback ladder, empty values keep the settings from `output_param`.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct FallbackStep {
+    pub size: String,
+    pub preset: String,
+}
+
+impl FallbackStep {
+    /// Output command with the step settings, the size gets scaled before the output target.
+    pub fn output_cmd(&self, mut cmd: Vec<String>) -> Vec<String> {
+        if !self.preset.is_empty() {
+            for i in 1..cmd.len() {
+                if cmd[i - 1] == "-preset" || cmd[i - 1].starts_with("-preset:") {
+                    cmd[i] = self.preset.clone();
+                }
+            }
+        }
+
+        if !self.size.is_empty() {
+            let mut tail = cmd.split_off(cmd.len().saturating_sub(1));
+            cmd.append(&mut vec_strings!["-s", self.size]);
+            cmd.append(&mut tail);
+        }
+
+        cmd
+    }
+}
+
+/// Restart the encoder with the next ladder step, when it is too slow for `slow_seconds`.
+/// After `cooldown_seconds` in realtime it goes one step back.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct EncoderFallback {
+    pub enable: bool,
+    pub slow_seconds: u64,
+    pub cooldown_seconds: u64,
+    pub ladder: Vec<FallbackStep>,
+}
+
+impl Default for EncoderFallback {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            slow_seconds: 30,
+            cooldown_seconds: 300,
+            ladder: vec![],
+        }
+    }
+}
+
+/// Program title and next clip as output metadata.
+///
+/// The `title` template can have `{title}`, `{next}` and `{channel}`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct OutputMetadata {
+    pub enable: bool,
+    pub title: String,
+    pub program_date_time: bool,
+}
+
+impl Default for OutputMetadata {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            title: "{title} - {channel}".to_string(),
+            program_date_time: false,
+        }
+    }
+}
+
+/// Tee output for the archive, with the segment muxer and a timestamp in the file name.
+pub fn archive_output(archive: &Archive) -> TeeOutput {
+    TeeOutput {
+        format: "segment".to_string(),
+        options: format!(
+            "segment_time={}:segment_format=matroska:strftime=1:reset_timestamps=1",
+            archive.segment_seconds
+        ),
+        ignore_failure: true,
+        target: Path::new(&archive.dir)
+            .join("%Y-%m-%d_%H-%M-%S.mkv")
+            .display()
+            .to_string(),
+    }
+}
+
+/// Move the single output target with its format from the command to a tee output.
+///
+/// The last argument is only the target, when it is no option and no value of a option.
+fn main_tee_output(cmd: &mut Vec<String>) -> Option<TeeOutput> {
+    let i = cmd.iter().rposition(|c| c == "-f")?;
+    let last = cmd.len().checked_sub(1).filter(|l| *l > i + 1)?;
+    let is_option = |arg: &str| arg.starts_with('-') && arg != "-";
+
+    if is_option(&cmd[last]) || (last - 1 > i + 1 && is_option(&cmd[last - 1])) {
+        return None;
+    }
+
+    let target = cmd.remove(last);
+    let format = cmd.drain(i..i + 2).nth(1)?;
+
+    Some(TeeOutput {
+        format,
+        target,
+        ..Default::default()
+    })
+}
+
+/// Formats, which need the codec headers global and not in the stream.
+const GLOBAL_HEADER_FORMATS: [&str; 5] = ["flv", "mp4", "mov", "matroska", "segment"];
+
+/// Escape the characters, which the tee muxer uses for separating targets and options.
+fn tee_escape(target: &str) -> String {
+    let mut escaped = String::new();
+
+    for c in target.chars() {
+        if matches!(c, '\\' | '|' | ':' | '[' | ']') {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Encode with global headers, when one of the tee outputs needs them.
+pub fn global_header_cmd(mut cmd: Vec<String>, outputs: &[TeeOutput]) -> Vec<String> {
+    if !outputs
+        .iter()
+        .any(|o| GLOBAL_HEADER_FORMATS.contains(&o.format.as_str()))
+    {
+        return cmd;
+    }
+
+    match cmd.iter().position(|c| c == "-flags") {
+        Some(i) if i + 1 < cmd.len() => {
+            if !cmd[i + 1].contains("global_header") {
+                cmd[i + 1].push_str("+global_header");
+            }
+        }
+        _ => cmd.append(&mut vec_strings!["-flags", "+global_header"]),
+    }
+
+    cmd
+}
+
+/// Build the tee muxer target, like `[f=flv:onfail=ignore]rtmp\://...|[f=mp4]/record.mp4`.
+pub fn tee_target(outputs: &[TeeOutput]) -> String {
+    outputs
+        .iter()
+        .map(|o| {
+            let mut options = vec![format!("f={}", o.format)];
+
+            if !o.options.is_empty() {
+                options.push(o.options.clone());
+            }
+
+            if o.ignore_failure {
+                options.push("onfail=ignore".to_string());
+            }
+
+            format!("[{}]{}", options.join(":"), tee_escape(&o.target))
+        })
+        .collect::<Vec<String>>()
+        .join("|")
+}
+
+/// HLS muxer arguments from the output settings, only the configured ones are set.
+pub fn hls_args(out: &Out) -> Vec<String> {
+    let mut args = vec![];
+
+    if let Some(time) = out.hls_segment_seconds {
+        args.append(&mut vec_strings!["-hls_time", time]);
+    }
+
+    if let Some(size) = out.hls_list_size {
+        args.append(&mut vec_strings!["-hls_list_size", size]);
+    }
+
+    if let Some(flags) = out.hls_flags.as_ref().filter(|f| !f.is_empty()) {
+        args.append(&mut vec_strings!["-hls_flags", flags]);
+    }
+
+    args
+}
+
+/// Set the muxer of the last output, it replaces the `-f` from `output_param`.
+pub fn container_output_cmd(mut cmd: Vec<String>, container: Container) -> Vec<String> {
+    if let Some(i) = cmd
+        .iter()
+        .rposition(|c| c == "-f")
+        .filter(|i| i + 2 < cmd.len())
+    {
+        cmd[i + 1] = container.muxer().to_string();
+    } else {
+        let mut tail = cmd.split_off(cmd.len().saturating_sub(1));
+        cmd.append(&mut vec_strings!["-f", container.muxer()]);
+        cmd.append(&mut tail);
+    }
+
+    cmd
+}
+
+fn is_rtmp_target(target: &str) -> bool {
+    target.starts_with("rtmp://") || target.starts_with("rtmps://")
+}
+
+/// Output is mpegts, from the muxer or the target.
+fn is_ts_output(cmd: &[String]) -> bool {
+    let muxer = cmd
+        .iter()
+        .rposition(|c| c == "-f")
+        .and_then(|i| cmd.get(i + 1));
+
+    match muxer {
+        Some(muxer) => muxer == "mpegts",
+        None => cmd
+            .last()
+            .and_then(|t| Container::for_target(t))
+            .is_some_and(|c| c == [Container::Mpegts]),
+    }
+}
+
+/// Connection and stream names for the output target.
+///
+/// RTMP targets get the flash version and tcUrl, mpegts outputs the service names.
+pub fn target_args(out: &Out, cmd: &[String]) -> Vec<String> {
+    let mut args = vec![];
+
+    if cmd.last().is_some_and(|t| is_rtmp_target(t)) {
+        if !out.rtmp_flashver.is_empty() {
+            args.append(&mut vec_strings!["-rtmp_flashver", out.rtmp_flashver]);
+        }
+
+        if !out.tcurl.is_empty() {
+            args.append(&mut vec_strings!["-rtmp_tcurl", out.tcurl]);
+        }
+    } else if is_ts_output(cmd) {
+        if !out.service_name.is_empty() {
+            args.append(&mut vec_strings![
+                "-metadata",
+                format!("service_name={}", out.service_name)
+            ]);
+        }
+
+        if !out.service_provider.is_empty() {
+            args.append(&mut vec_strings![
+                "-metadata",
+                format!("service_provider={}", out.service_provider)
+            ]);
+        }
+    }
+
+    args
+}
+
+/// Add the [`target_args`] before the output target.
+pub fn target_output_cmd(mut cmd: Vec<String>, out: &Out) -> Vec<String> {
+    let mut args = target_args(out, &cmd);
+    let mut target = cmd.split_off(cmd.len().saturating_sub(1));
+    cmd.append(&mut args);
+    cmd.append(&mut target);
+
+    cmd
+}
+
+/// Replace the HLS arguments in the output command with the configured ones.
+///
+/// They are placed before the segment filename, or before the playlist path.
+pub fn hls_output_cmd(mut cmd: Vec<String>, out: &Out) -> Vec<String> {
+    let mut args = hls_args(out);
+
+    for arg in args.iter().step_by(2) {
+        if let Some(i) = cmd.iter().position(|c| c == arg) {
+            cmd.drain(i..(i + 2).min(cmd.len()));
+        }
+    }
+
+    let pos = cmd
+        .iter()
+        .position(|c| c == "-hls_segment_filename")
+        .unwrap_or(cmd.len().saturating_sub(1));
+
+    let mut tail = cmd.split_off(pos);
+    cmd.append(&mut args);
+    cmd.append(&mut tail);
+
+    cmd
+}
+
+/// Bitrate arguments from the typed processing fields, empty fields are left out.
+pub fn bitrate_args(processing: &Processing) -> Vec<String> {
+    let mut args = vec![];
+
+    for (arg, value) in [
+        ("-b:v", &processing.video_bitrate),
+        ("-b:a", &processing.audio_bitrate),
+        ("-maxrate", &processing.maxrate),
+        ("-bufsize", &processing.bufsize),
+    ] {
+        if !value.is_empty() {
+            args.append(&mut vec_strings![arg, value]);
+        }
+    }
+
+    args
+}
+
+/// Replace the bitrate arguments in the output command with the typed fields.
+///
+/// The other arguments from the output parameters are kept.
+pub fn bitrate_output_cmd(mut cmd: Vec<String>, processing: &Processing) -> Vec<String> {
+    let mut args = bitrate_args(processing);
+
+    for arg in args.iter().step_by(2) {
+        if let Some(i) = cmd.iter().position(|c| c == arg) {
+            cmd.drain(i..(i + 2).min(cmd.len()));
+        }
+    }
+
+    args.append(&mut cmd);
+
+    args
+}
+
+/// Bits per second from a ffmpeg bitrate, like `1300k` or `2.5M`.
+pub fn bitrate_value(rate: &str) -> Option<f64> {
+    let rate = rate.trim();
+    let (number, factor) = match rate.chars().last()? {
+        'k' | 'K' => (&rate[..rate.len() - 1], 1_000.0),
+        'M' => (&rate[..rate.len() - 1], 1_000_000.0),
+        'G' => (&rate[..rate.len() - 1], 1_000_000_000.0),
+        _ => (rate, 1.0),
+    };
+
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite() && *n >= 0.0)
+        .map(|n| n * factor)
+}
+
+/// Extra arguments, which are not allowed. The format is set by the output mode,
+/// only the desktop mode has none.
+pub fn denied_extra_args(out: &Out) -> Vec<String> {
+    out.extra_args
+        .iter()
+        .filter(|a| {
+            let flag = a.split(':').next().unwrap_or_default();
+
+            EXTRA_ARGS_DENYLIST.contains(&flag) || (*a == "-f" && out.mode != Desktop)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Insert the extra arguments in the output command, in the given order.
+///
+/// They stand before the output target, which is the last argument.
+pub fn extra_args_cmd(mut cmd: Vec<String>, out: &Out) -> Vec<String> {
+    let mut tail = cmd.split_off(cmd.len().saturating_sub(1));
+    cmd.extend(out.extra_args.iter().cloned());
+    cmd.append(&mut tail);
+
+    cmd
+}
+
+/// Dash muxer arguments, the output target is the manifest file.
+pub fn dash_args(out: &Out) -> Vec<String> {
+    vec_strings![
+        "-f",
+        "dash",
+        "-seg_duration",
+        out.dash.segment_seconds,
+        "-use_template",
+        out.dash.use_template as u8,
+        "-adaptation_sets",
+        out.dash.adaptation_sets
+    ]
+}
+
+/// Set the dash muxer in the output command, before the manifest path.
+pub fn dash_output_cmd(mut cmd: Vec<String>, out: &Out) -> Vec<String> {
+    if let Some(i) = cmd.iter().position(|c| c == "-f") {
+        cmd.drain(i..(i + 2).min(cmd.len()));
+    }
+
+    let mut tail = cmd.split_off(cmd.len().saturating_sub(1));
+    cmd.append(&mut dash_args(out));
+    cmd.append(&mut tail);
+
+    cmd
+}
+
+/// Encoder and muxer arguments for the ABR ladder.
+///
+/// Every rendition gets its own bitrates, they stand before the muxer, so they win over
+/// the general ones from 'output_param'. In hls mode the variants go into the master playlist.
+pub fn abr_output_cmd(mut cmd: Vec<String>, out: &Out) -> Vec<String> {
+    let renditions = &out.abr.renditions;
+    let mut rates = vec![];
+
+    for (i, rendition) in renditions.iter().enumerate() {
+        rates.append(&mut vec_strings![
+            format!("-b:v:{i}"),
+            format!("{}k", rendition.video_bitrate),
+            format!("-b:a:{i}"),
+            format!("{}k", rendition.audio_bitrate)
+        ]);
+    }
+
+    let pos = cmd
+        .iter()
+        .position(|c| c == "-f")
+        .unwrap_or(cmd.len().saturating_sub(1));
+    let mut tail = cmd.split_off(pos);
+    cmd.append(&mut rates);
+    cmd.append(&mut tail);
+
+    if out.mode == HLS {
+        for arg in ["-master_pl_name", "-var_stream_map"] {
+            if let Some(i) = cmd.iter().position(|c| c == arg) {
+                cmd.drain(i..(i + 2).min(cmd.len()));
+            }
+        }
+
+        let stream_map = renditions
+            .iter()
+            .enumerate()
+            .map(|(i, r)| format!("v:{i},a:{i},name:{}p", r.height))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let mut tail = cmd.split_off(cmd.len().saturating_sub(1));
+        cmd.append(&mut vec_strings![
+            "-master_pl_name",
+            out.abr.master_playlist,
+            "-var_stream_map",
+            stream_map
+        ]);
+        cmd.append(&mut tail);
+    }
+
+    cmd
+}
+
+/// Scrolling text ticker, the text is read from file and reloaded on every frame.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Ticker {
+    pub enable: bool,
+    pub textfile: String,
+    pub fontfile: String,
+    pub fontsize: u32,
+    pub fontcolor: String,
+    pub y: String,
+    pub speed: f64,
+}
+
+impl Default for Ticker {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            textfile: String::new(),
+            fontfile: String::new(),
+            fontsize: 24,
+            fontcolor: "#ffffff".to_string(),
+            y: "h-line_h-10".to_string(),
+            speed: 100.0,
+        }
+    }
+}
+
+/// Values, which a config reload can change while the playout runs.
+///
+/// They take effect with the next filter build, `None` keeps the value from the start.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LiveSettings {
+    pub volume: Option<f64>,
+    pub logo: Option<String>,
+    pub ticker_textfile: Option<String>,
+    /// Censor regions from the reloaded config file.
+    pub config_censor: Option<Vec<CensorRegion>>,
+    /// Censor regions from the control API, they come on top of the ones from the config.
+    pub censor: Option<Vec<CensorRegion>>,
+}
+
+impl LiveSettings {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Settings for the dash muxer.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DashOutput {
+    pub segment_seconds: f64,
+    pub use_template: bool,
+    pub adaptation_sets: String,
+}
+
+impl Default for DashOutput {
+    fn default() -> Self {
+        Self {
+            segment_seconds: 4.0,
+            use_template: true,
+            adaptation_sets: "id=0,streams=v id=1,streams=a".to_string(),
+        }
+    }
+}
+
+/// One rendition of the ABR ladder, the bitrates are in kbit/s.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct Rendition {
+    pub width: i64,
+    pub height: i64,
+    pub video_bitrate: u32,
+    pub audio_bitrate: u32,
+}
+
+/// Adaptive bitrate ladder, for hls and dash output.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AbrLadder {
+    pub enable: bool,
+    pub master_playlist: String,
+    pub renditions: Vec<Rendition>,
+}
+
+impl Default for AbrLadder {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            master_playlist: "master.m3u8".to_string(),
+            renditions: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Ingest {
+    pub help_text: String,
+    pub enable: bool,
+    #[serde(default)]
+    pub mode: IngestMode,
+    input_param: String,
+    #[serde(default = "default_srt_address")]
+    pub srt_address: String,
+    #[serde(default = "default_srt_latency")]
+    pub srt_latency: u64,
+    #[serde(default)]
+    pub srt_passphrase: String,
+    #[serde(default)]
+    pub probesize: Option<u64>,
+    #[serde(default)]
+    pub analyzeduration: Option<u64>,
+    #[serde(default)]
+    pub multicast: Multicast,
+    #[serde(default)]
+    pub custom_filter: String,
+    #[serde(default = "default_buffer_size")]
+    pub buffer_size: usize,
+    #[serde(default = "default_reconnect_base")]
+    pub reconnect_base_ms: u64,
+    #[serde(default = "default_reconnect_max")]
+    pub reconnect_max_ms: u64,
+    #[serde(default = "default_takeover")]
+    pub takeover: bool,
+    #[serde(default)]
+    pub on_connect_url: String,
+    #[serde(default)]
+    pub on_disconnect_url: String,
+    #[serde(default)]
+    pub idle_timeout_seconds: u64,
+    #[serde(default)]
+    pub backpressure: Backpressure,
+    #[serde(default = "default_backpressure_timeout")]
+    pub backpressure_timeout_ms: u64,
+    #[serde(default)]
+    pub allowed_keys: Vec<String>,
+    #[serde(default)]
+    pub allowed_apps: Vec<String>,
+    #[serde(default)]
+    pub pip: Pip,
+    #[serde(default)]
+    pub audio_input_param: String,
+    #[serde(default = "default_feed_hold")]
+    pub feed_hold_seconds: f64,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub input_cmd: Option<Vec<String>>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub audio_input_cmd: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Playlist {
+    pub help_text: String,
+    #[serde(default)]
+    pub mode: PlaylistMode,
+    pub path: String,
+    pub day_start: String,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub start_sec: Option<f64>,
+
+    pub length: String,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub length_sec: Option<f64>,
+
+    pub infinit: bool,
+    #[serde(default)]
+    pub cache_dir: String,
+    #[serde(default)]
+    pub intro: String,
+    #[serde(default)]
+    pub outro: String,
+    #[serde(default = "default_bumper_interval")]
+    pub bumper_interval: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Storage {
+    pub help_text: String,
+    pub path: String,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub paths: Vec<String>,
+    pub filler_clip: String,
+    pub extensions: Vec<String>,
+    pub shuffle: bool,
+    #[serde(default)]
+    pub weights: Vec<FolderWeight>,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub resolvers: Vec<SourceResolver>,
+}
+
+/// Weight of a file or subfolder in shuffle mode, the path is relative to the storage path.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FolderWeight {
+    pub path: String,
+    pub weight: usize,
+}
+
+/// Command, which rewrites sources with a custom scheme, before they get played.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SourceResolver {
+    pub scheme: String,
+    pub command: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Text {
+    pub help_text: String,
+    pub add_text: bool,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub node_pos: Option<usize>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub zmq_stream_socket: Option<String>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub zmq_server_socket: Option<String>,
+
+    pub fontfile: String,
+    pub text_from_filename: bool,
+    pub style: String,
+    pub regex: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Out {
+    pub help_text: String,
+    pub mode: OutputMode,
+    pub output_param: String,
+    #[serde(default)]
+    pub container: Option<Container>,
+    #[serde(default)]
+    pub tee: Vec<TeeOutput>,
+    #[serde(default)]
+    pub preview: bool,
+    #[serde(default)]
+    pub hls_segment_seconds: Option<f64>,
+    #[serde(default)]
+    pub hls_list_size: Option<u32>,
+    #[serde(default)]
+    pub hls_flags: Option<String>,
+    #[serde(default)]
+    pub dash: DashOutput,
+    #[serde(default)]
+    pub abr: AbrLadder,
+    #[serde(default)]
+    pub archive: Archive,
+    #[serde(default)]
+    pub multicast: Multicast,
+    #[serde(default)]
+    pub fallback: EncoderFallback,
+    #[serde(default)]
+    pub metadata: OutputMetadata,
+    #[serde(default)]
+    pub rtmp_flashver: String,
+    #[serde(default)]
+    pub tcurl: String,
+    #[serde(default)]
+    pub service_name: String,
+    #[serde(default)]
+    pub service_provider: String,
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub output_count: usize,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub output_filter: Option<String>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub output_cmd: Option<Vec<String>>,
+}
+
+fn default_ffmpeg_path() -> String {
+    "ffmpeg".to_string()
+}
+
+fn default_ffprobe_path() -> String {
+    "ffprobe".to_string()
+}
+
+fn default_alert_failures() -> usize {
+    3
+}
+
+fn default_alert_window() -> u64 {
+    300
+}
+
+fn default_alert_cooldown() -> u64 {
+    3600
+}
+
+fn default_backpressure_timeout() -> u64 {
+    5000
+}
+
+fn default_feed_hold() -> f64 {
+    2.0
+}
+
+fn default_takeover() -> bool {
+    true
+}
+
+fn default_bumper_interval() -> String {
+    "01:00:00".to_string()
+}
+
+fn default_pix_fmt() -> String {
+    "yuv420p".to_string()
+}
+
+fn default_color_matrix() -> String {
+    "bt709".to_string()
+}
+
+fn default_tracks() -> i32 {
+    1
+}
+
+fn default_channels() -> u8 {
+    2
+}
+
+fn default_input_reconnect() -> u32 {
+    5
+}
+
+fn default_resume_max_age() -> u64 {
+    120
+}
+
+fn default_fade_in() -> f64 {
+    0.5
+}
+
+fn default_logo_x() -> String {
+    "W-w-12".to_string()
+}
+
+fn default_logo_y() -> String {
+    "12".to_string()
+}
+
+fn default_buffer_size() -> usize {
+    65088
+}
+
+fn default_srt_address() -> String {
+    "0.0.0.0:40077".to_string()
+}
+
+fn default_srt_latency() -> u64 {
+    120000
+}
+
+fn default_reconnect_base() -> u64 {
+    1000
+}
+
+fn default_reconnect_max() -> u64 {
+    30000
+}
+
+impl Ingest {
+    /// Build the listen input parameters for SRT ingest.
+    ///
+    /// Latency is given in microseconds, like ffmpeg expect it. The passphrase gets percent
+    /// encoded, so characters like `&` or `=` don't break the url options.
+    pub fn srt_input_cmd(&self) -> Vec<String> {
+        let mut url = format!(
+            "srt://{}?mode=listener&latency={}",
+            self.srt_address, self.srt_latency
+        );
+
+        if !self.srt_passphrase.is_empty() {
+            url.push_str(&format!(
+                "&passphrase={}",
+                utf8_percent_encode(&self.srt_passphrase, NON_ALPHANUMERIC)
+            ));
+        }
+
+        vec_strings!["-f", "mpegts", "-i", url]
+    }
+
+    /// Probe size in bytes and analyze duration in microseconds for the ingest input.
+    ///
+    /// Without config values, srt and multicast get larger ones, because mpegts streams can start
+    /// with only some of their streams. The other modes use the ffmpeg defaults.
+    pub fn probe_cmd(&self) -> Vec<String> {
+        let (probesize, analyzeduration) = match self.mode {
+            IngestMode::Srt | IngestMode::Multicast => (Some(10_000_000), Some(10_000_000)),
+            _ => (None, None),
+        };
+        let mut cmd = vec![];
+
+        if let Some(size) = self.probesize.or(probesize) {
+            cmd.append(&mut vec_strings!["-probesize", size]);
+        }
+
+        if let Some(duration) = self.analyzeduration.or(analyzeduration) {
+            cmd.append(&mut vec_strings!["-analyzeduration", duration]);
+        }
+
+        cmd
+    }
+
+    /// Second server input for a separate audio feed, the video comes then only from the first.
+    pub fn audio_listen_cmd(&self) -> Option<Vec<String>> {
+        let input = split(self.audio_input_param.as_str()).filter(|i| !i.is_empty())?;
+        let mut cmd = self.probe_cmd();
+        cmd.extend(input);
+
+        Some(cmd)
+    }
+
+    /// Server input from the ingest mode, the probe parameters come first.
+    pub fn listen_cmd(&self) -> Option<Vec<String>> {
+        let input = match self.mode {
+            IngestMode::Srt => Some(self.srt_input_cmd()),
+            IngestMode::Multicast => Some(self.multicast.input_cmd()),
+            _ => split(self.input_param.as_str()),
+        }?;
+        let mut cmd = self.probe_cmd();
+        cmd.extend(input);
+
+        Some(cmd)
+    }
+}
+
+impl PlayoutConfig {
+    /// Config with the settings from the last reload, for building the filters.
+    pub fn live_config(&self) -> Cow<'_, Self> {
+        let live = self.live.read().unwrap().clone();
+
+        if live.is_empty() {
+            return Cow::Borrowed(self);
+        }
+
+        let mut config = self.clone();
+
+        if let Some(volume) = live.volume {
+            config.processing.volume = volume;
+        }
+
+        if let Some(logo) = live.logo {
+            config.processing.logo = logo;
+        }
+
+        if let Some(textfile) = live.ticker_textfile {
+            config.processing.ticker.textfile = textfile;
+        }
+
+        if let Some(censor) = live.config_censor {
+            config.processing.censor = censor;
+        }
+
+        if let Some(censor) = live.censor {
+            config.processing.censor.extend(censor);
+        }
+
+        Cow::Owned(config)
+    }
+
+    /// Compare a reloaded config with the running one.
+    ///
+    /// Returns the new live settings, when one of them changed, and the changed fields, which need a restart.
+    pub fn live_changes(&self, new: &Self) -> (Option<LiveSettings>, Vec<&'static str>) {
+        let (op, np) = (&self.processing, &new.processing);
+
+        let checks = [
+            ("processing.mode", op.mode != np.mode),
+            ("processing.width", op.width != np.width),
+            ("processing.height", op.height != np.height),
+            ("processing.fps", op.fps != np.fps),
+            ("processing.pix_fmt", op.pix_fmt != np.pix_fmt),
+            (
+                "processing.audio_tracks",
+                op.audio_tracks != np.audio_tracks,
+            ),
+            (
+                "processing.audio_channels",
+                op.audio_channels != np.audio_channels,
+            ),
+            (
+                "ingest.input_param",
+                self.ingest.input_cmd != new.ingest.input_cmd,
+            ),
+            (
+                "ingest.audio_input_param",
+                self.ingest.audio_input_cmd != new.ingest.audio_input_cmd,
+            ),
+            (
+                "ingest.feed_hold_seconds",
+                self.ingest.feed_hold_seconds != new.ingest.feed_hold_seconds,
+            ),
+            (
+                "ingest.allowed_keys",
+                self.ingest.allowed_keys != new.ingest.allowed_keys,
+            ),
+            (
+                "ingest.allowed_apps",
+                self.ingest.allowed_apps != new.ingest.allowed_apps,
+            ),
+            ("out.mode", self.out.mode != new.out.mode),
+            (
+                "out.output_param",
+                self.out.output_param != new.out.output_param,
+            ),
+        ];
+
+        let restart = checks
+            .into_iter()
+            .filter_map(|(field, changed)| changed.then_some(field))
+            .collect();
+
+        let current = self.live_config();
+        let cp = &current.processing;
+        let live_state = self.live.read().unwrap().clone();
+        let config_censor = live_state
+            .config_censor
+            .as_ref()
+            .unwrap_or(&self.processing.censor);
+        let live = (cp.volume != np.volume
+            || cp.logo != np.logo
+            || cp.ticker.textfile != np.ticker.textfile
+            || *config_censor != np.censor)
+            .then(|| LiveSettings {
+                volume: Some(np.volume),
+                logo: Some(np.logo.clone()),
+                ticker_textfile: Some(np.ticker.textfile.clone()),
+                config_censor: Some(np.censor.clone()),
+                censor: live_state.censor,
+            });
+
+        (live, restart)
+    }
+
+    /// Read the config file as it is, the format is detected by the file extension.
+    /// Supported are `.toml`, `.json` and `.yml`/`.yaml`, which is also the fallback.
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+
+        match ext.as_deref() {
+            Some("toml") => toml::from_str(&data).map_err(|e| e.to_string()),
+            Some("json") => serde_json::from_str(&data).map_err(|e| e.to_string()),
+            _ => serde_yaml::from_str(&data).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Override config values with environment variables, like `FFPLAYOUT_PROCESSING_WIDTH=1280`.
+    /// The name is the config path in uppercase, joined with underscores.
+    ///
+    /// Precedence is: environment > config file > default value.
+    pub fn apply_env<I>(self, vars: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut config =
+            serde_json::to_value(&self).map_err(|e| ConfigError::new("env", &e.to_string()))?;
+
+        for (key, val) in vars {
+            let Some(field) = key
+                .strip_prefix(ENV_PREFIX)
+                .and_then(|path| env_field(&mut config, path))
+            else {
+                continue;
+            };
+
+            *field = env_value(field, &val).ok_or_else(|| {
+                ConfigError::new(&key, &format!("value \"{val}\" has the wrong type"))
+            })?;
+        }
+
+        serde_json::from_value(config).map_err(|e| ConfigError::new("env", &e.to_string()))
+    }
+
+    /// Read config from YAML file, and set some extra config values.
+    pub fn new(cfg_path: Option<String>) -> Self {
+        Self::open(cfg_path, false)
+    }
+
+    /// Like [`PlayoutConfig::new`], but environment variables override the file values.
+    ///
+    /// Only the playout engine uses it, configs which get saved again should not contain them.
+    pub fn with_env(cfg_path: Option<String>) -> Self {
+        Self::open(cfg_path, true)
+    }
+
+    fn open(cfg_path: Option<String>, with_env: bool) -> Self {
+        let mut config_path = PathBuf::from("/etc/ffplayout/ffplayout.yml");
+
+        if let Some(cfg) = cfg_path {
+            config_path = PathBuf::from(cfg);
+        }
+
+        if !config_path.is_file() {
+            if Path::new("./assets/ffplayout.yml").is_file() {
+                config_path = PathBuf::from("./assets/ffplayout.yml")
+            } else if let Some(p) = env::current_exe().ok().as_ref().and_then(|op| op.parent()) {
+                config_path = p.join("ffplayout.yml")
+            };
+        }
+
+        if !config_path.is_file() {
+            println!(
+                "{config_path:?} doesn't exists!\nPut \"ffplayout.yml\" in \"/etc/playout/\" or beside the executable!"
+            );
+            process::exit(1);
+        }
+
+        match Self::read(&config_path, with_env) {
+            Ok(config) => config,
+            Err(e) => {
+                println!("{e}");
+                process::exit(1);
+            }
+        }
+    }
+
+    /// Read the config file and build the ffmpeg parameters from it.
+    ///
+    /// Unlike [`PlayoutConfig::new`] it not exits on errors, so a running playout can reload it.
+    pub fn load(config_path: &Path) -> Result<Self, String> {
+        Self::read(config_path, false)
+    }
+
+    /// Like [`PlayoutConfig::load`], with the environment overrides from [`PlayoutConfig::apply_env`].
+    pub fn load_with_env(config_path: &Path) -> Result<Self, String> {
+        Self::read(config_path, true)
+    }
+
+    fn read(config_path: &Path, with_env: bool) -> Result<Self, String> {
+        let mut config = Self::from_path(config_path)
+            .map_err(|e| format!("Could not read config file {config_path:?}: {e}"))?;
+
+        if with_env {
+            config = config
+                .apply_env(env::vars())
+                .map_err(|e| format!("Config error from environment {e}"))?;
+        }
+
+        config.general.generate = None;
+        config.general.config_path = config_path.display().to_string();
+
+        config.general.stat_file = home_dir()
+            .unwrap_or_else(env::temp_dir)
+            .join(if config.general.stat_file.is_empty() {
+                ".ffp_status"
+            } else {
+                &config.general.stat_file
+            })
+            .display()
+            .to_string();
+
+        config.general.resume_file = home_dir()
+            .unwrap_or_else(env::temp_dir)
+            .join(if config.general.resume_file.is_empty() {
+                ".ffp_resume"
+            } else {
+                &config.general.resume_file
+            })
+            .display()
+            .to_string();
+
+        if config.logging.ingest_level.is_none() {
+            config.logging.ingest_level = Some(config.logging.ffmpeg_level.clone())
+        }
+
+        if config.playlist.cache_dir.is_empty() {
+            config.playlist.cache_dir = env::temp_dir()
+                .join("ffplayout_playlists")
+                .display()
+                .to_string();
+        }
+
+        config.playlist.start_sec = Some(match config.playlist.mode {
+            PlaylistMode::Ordered => time_to_sec("now"),
+            PlaylistMode::Realtime => time_to_sec(&config.playlist.day_start),
+        });
+
+        if config.playlist.length.contains(':') {
             config.playlist.length_sec = Some(time_to_sec(&config.playlist.length));
         } else {
             config.playlist.length_sec = Some(86400.0);
@@ -392,27 +2195,46 @@ impl PlayoutConfig {
         } else {
             process_cmd.append(&mut vec_strings![
                 "-pix_fmt",
-                "yuv420p",
+                &config.processing.pix_fmt,
                 "-r",
-                &config.processing.fps,
-                "-c:v",
-                "mpeg2video",
-                "-g",
-                "1",
-                "-b:v",
-                &bitrate,
-                "-minrate",
-                &bitrate,
-                "-maxrate",
-                &bitrate,
-                "-bufsize",
-                &buff_size
+                &config.processing.fps
             ]);
+
+            // the pipe to the encoder is mpegts, so codecs like ffv1 or prores can't be used,
+            // formats which mpeg2video doesn't support go lossless over x264
+            if intermediate_codec(&config.processing.pix_fmt) == Some("libx264") {
+                process_cmd.append(&mut vec_strings![
+                    "-c:v",
+                    "libx264",
+                    "-preset",
+                    "ultrafast",
+                    "-qp",
+                    "0",
+                    "-g",
+                    "1"
+                ]);
+            } else {
+                process_cmd.append(&mut vec_strings![
+                    "-c:v",
+                    "mpeg2video",
+                    "-g",
+                    "1",
+                    "-b:v",
+                    &bitrate,
+                    "-minrate",
+                    &bitrate,
+                    "-maxrate",
+                    &bitrate,
+                    "-bufsize",
+                    &buff_size
+                ]);
+            }
         }
 
         process_cmd.append(&mut pre_audio_codec(
             &config.processing.custom_filter,
             &config.ingest.custom_filter,
+            config.processing.loudnorm.enable,
         ));
         process_cmd.append(&mut vec_strings![
             "-ar",
@@ -426,7 +2248,8 @@ impl PlayoutConfig {
 
         config.processing.cmd = Some(process_cmd);
 
-        config.ingest.input_cmd = split(config.ingest.input_param.as_str());
+        config.ingest.input_cmd = config.ingest.listen_cmd();
+        config.ingest.audio_input_cmd = config.ingest.audio_listen_cmd();
 
         config.out.output_count = 1;
         config.out.output_filter = None;
@@ -434,6 +2257,50 @@ impl PlayoutConfig {
         if config.out.mode == Null {
             config.out.output_cmd = Some(vec_strings!["-f", "null", "-"]);
         } else if let Some(mut cmd) = split(config.out.output_param.as_str()) {
+            if let Some(container) = config.out.container.filter(|_| config.out.tee.is_empty()) {
+                cmd = container_output_cmd(cmd, container);
+            }
+
+            if config.out.mode == Stream && config.out.tee.is_empty() {
+                cmd = target_output_cmd(cmd, &config.out);
+            }
+
+            if config.out.mode == Stream && !config.out.multicast.address.is_empty() {
+                let multicast = config.out.multicast.tee_output();
+
+                if config.out.tee.is_empty() {
+                    // the group address replaces the output target
+                    main_tee_output(&mut cmd);
+                    cmd.append(&mut vec_strings!["-f", multicast.format, multicast.target]);
+                } else {
+                    config.out.tee.push(multicast);
+                }
+            }
+
+            if config.out.mode == Stream && !config.out.archive.dir.is_empty() {
+                // archive needs the tee muxer, a single output gets moved to it
+                if config.out.tee.is_empty() {
+                    config.out.tee.extend(main_tee_output(&mut cmd));
+                }
+
+                config.out.tee.push(archive_output(&config.out.archive));
+            }
+
+            if config.out.mode == Stream && !config.out.tee.is_empty() {
+                cmd = global_header_cmd(cmd, &config.out.tee);
+                cmd.append(&mut vec_strings!["-f", "tee", tee_target(&config.out.tee)]);
+            }
+
+            if config.out.mode == HLS {
+                cmd = hls_output_cmd(cmd, &config.out);
+            } else if config.out.mode == Dash {
+                cmd = dash_output_cmd(cmd, &config.out);
+            }
+
+            if config.out.abr.enable {
+                cmd = abr_output_cmd(cmd, &config.out);
+            }
+
             // get output count according to the var_stream_map value, or by counting output parameters
             if let Some(i) = cmd.clone().iter().position(|m| m == "-var_stream_map") {
                 config.out.output_count = cmd[i + 1].split_whitespace().count();
@@ -451,9 +2318,41 @@ impl PlayoutConfig {
                 cmd.remove(i);
             }
 
+            let gop = match config.processing.gop_size {
+                0 if config.out.mode == HLS => {
+                    hls_time(&cmd).map(|t| gop_size(config.processing.fps, t))
+                }
+                0 if config.out.mode == Dash => Some(gop_size(
+                    config.processing.fps,
+                    config.out.dash.segment_seconds,
+                )),
+                0 => None,
+                g => Some(g),
+            };
+
+            // set keyframe interval, so that every hls segment starts with a keyframe,
+            // a interval from the output parameters stays
+            if let Some(g) = gop.filter(|_| keyframe_interval(&cmd).is_none()) {
+                let mut gop_cmd = vec_strings!["-g", g, "-keyint_min", g, "-sc_threshold", "0"];
+                gop_cmd.append(&mut cmd);
+                cmd = gop_cmd;
+            }
+
+            if [Stream, HLS, Dash].contains(&config.out.mode) {
+                cmd = bitrate_output_cmd(cmd, &config.processing);
+            }
+
             config.out.output_cmd = Some(cmd);
         }
 
+        if denied_extra_args(&config.out).is_empty() {
+            config.out.output_cmd = config
+                .out
+                .output_cmd
+                .take()
+                .map(|cmd| extra_args_cmd(cmd, &config.out));
+        }
+
         // when text overlay without text_from_filename is on, turn also the RPC server on,
         // to get text messages from it
         if config.text.add_text && !config.text.text_from_filename {
@@ -468,8 +2367,706 @@ impl PlayoutConfig {
             config.text.node_pos = None;
         }
 
-        config
+        Ok(config)
+    }
+
+    /// Check config values, before any ffmpeg process get started.
+    ///
+    /// All problems are collected, so they can be fixed at once.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = vec![];
+        let processing = &self.processing;
+        let loudnorm = &processing.loudnorm;
+
+        if self.ingest.enable
+            && self
+                .ingest
+                .input_cmd
+                .as_ref()
+                .is_none_or(|cmd| cmd.is_empty())
+        {
+            errors.push(ConfigError::new(
+                "ingest.input_param",
+                "ingest is enabled, but no input command is set",
+            ));
+        }
+
+        for (field, multicast, used) in [
+            (
+                "ingest.multicast.address",
+                &self.ingest.multicast,
+                self.ingest.enable && self.ingest.mode == IngestMode::Multicast,
+            ),
+            (
+                "out.multicast.address",
+                &self.out.multicast,
+                self.out.mode == Stream && !self.out.multicast.address.is_empty(),
+            ),
+        ] {
+            if !used {
+                continue;
+            }
+
+            match multicast.check_address() {
+                Ok(true) => {}
+                Ok(false) => log::warn!(
+                    "{field}: {} is no multicast group, it gets used as unicast",
+                    multicast.address
+                ),
+                Err(e) => errors.push(ConfigError::new(field, &e)),
+            }
+        }
+
+        if processing.cmd.as_ref().is_none_or(|cmd| cmd.is_empty()) {
+            errors.push(ConfigError::new(
+                "processing",
+                "processing settings are empty",
+            ));
+        }
+
+        if processing.width <= 0 || processing.height <= 0 {
+            errors.push(ConfigError::new(
+                "processing.width/height",
+                "width and height must be positive",
+            ));
+        }
+
+        for (field, bumper) in [
+            ("playlist.intro", &self.playlist.intro),
+            ("playlist.outro", &self.playlist.outro),
+        ] {
+            if !bumper.is_empty() && !Path::new(bumper).is_file() {
+                errors.push(ConfigError::new(
+                    field,
+                    &format!("bumper file {bumper} not exists"),
+                ));
+            }
+        }
+
+        if !(self.playlist.intro.is_empty() && self.playlist.outro.is_empty())
+            && self.playlist.bumper_interval.split(':').count() != 3
+        {
+            errors.push(ConfigError::new(
+                "playlist.bumper_interval",
+                "interval needs the format hh:mm:ss",
+            ));
+        }
+
+        if self.logging.rotate.max_size_mb > 0 && self.logging.rotate.max_files == 0 {
+            errors.push(ConfigError::new(
+                "logging.rotate.max_files",
+                "size rotation needs at least one backup file",
+            ));
+        }
+
+        if self.out.hls_segment_seconds.is_some_and(|t| t <= 0.0) {
+            errors.push(ConfigError::new(
+                "out.hls_segment_seconds",
+                "segment length must be positive",
+            ));
+        }
+
+        if self.out.abr.enable {
+            let abr = &self.out.abr;
+
+            if ![HLS, Dash].contains(&self.out.mode) {
+                errors.push(ConfigError::new(
+                    "out.abr",
+                    "ABR ladder works only in hls and dash mode",
+                ));
+            }
+
+            if processing.audio_only || processing.audio_tracks != 1 {
+                errors.push(ConfigError::new(
+                    "out.abr",
+                    "ABR ladder needs video and exactly one audio track",
+                ));
+            }
+
+            if abr.renditions.is_empty() {
+                errors.push(ConfigError::new(
+                    "out.abr.renditions",
+                    "at least one rendition is needed",
+                ));
+            }
+
+            for (i, rendition) in abr.renditions.iter().enumerate() {
+                if rendition.width <= 0 || rendition.height <= 0 {
+                    errors.push(ConfigError::new(
+                        &format!("out.abr.renditions[{i}]"),
+                        "width and height must be positive",
+                    ));
+                }
+
+                if rendition.video_bitrate == 0 || rendition.audio_bitrate == 0 {
+                    errors.push(ConfigError::new(
+                        &format!("out.abr.renditions[{i}]"),
+                        "video and audio bitrate must be positive",
+                    ));
+                }
+            }
+
+            if self.out.mode == HLS
+                && !self
+                    .out
+                    .output_cmd
+                    .as_ref()
+                    .and_then(|cmd| cmd.last())
+                    .is_some_and(|path| path.contains("%v"))
+            {
+                errors.push(ConfigError::new(
+                    "out.output_param",
+                    "hls playlist path needs a '%v' for the variant streams",
+                ));
+            }
+        }
+
+        if self.out.mode == Dash {
+            if self.out.dash.segment_seconds <= 0.0 {
+                errors.push(ConfigError::new(
+                    "out.dash.segment_seconds",
+                    "segment length must be positive",
+                ));
+            }
+
+            let folder = self
+                .out
+                .output_cmd
+                .as_ref()
+                .and_then(|cmd| cmd.last())
+                .and_then(|target| Path::new(target).parent())
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+
+            if !writable(folder) {
+                errors.push(ConfigError::new(
+                    "out.output_param",
+                    &format!("dash output folder {folder:?} is not writable"),
+                ));
+            }
+        }
+
+        if !self.out.archive.dir.is_empty() && self.out.archive.segment_seconds == 0 {
+            errors.push(ConfigError::new(
+                "out.archive.segment_seconds",
+                "segment length must be positive",
+            ));
+        }
+
+        if self.out.hls_list_size == Some(0) {
+            errors.push(ConfigError::new(
+                "out.hls_list_size",
+                "list size must be at least 1",
+            ));
+        }
+
+        if let Some(container) = self.out.container {
+            let target = self
+                .out
+                .output_cmd
+                .as_ref()
+                .and_then(|cmd| cmd.last())
+                .filter(|_| self.out.tee.is_empty());
+
+            if ![Stream, HLS].contains(&self.out.mode) {
+                errors.push(ConfigError::new(
+                    "out.container",
+                    "container works only in stream and hls mode",
+                ));
+            } else if self.out.mode == HLS && container != Container::Hls {
+                errors.push(ConfigError::new(
+                    "out.container",
+                    "hls mode needs the hls container",
+                ));
+            } else if let Some(allowed) = target.and_then(|t| Container::for_target(t)) {
+                if !allowed.contains(&container) {
+                    let allowed = allowed.iter().map(|c| c.as_str()).collect::<Vec<_>>();
+
+                    errors.push(ConfigError::new(
+                        "out.container",
+                        &format!(
+                            "container {container} doesn't work with the output target, use: {}",
+                            allowed.join(", ")
+                        ),
+                    ));
+                }
+            }
+        }
+
+        let out = &self.out;
+        let single_output = out.mode == Stream
+            && out.tee.is_empty()
+            && out.multicast.address.is_empty()
+            && out.archive.dir.is_empty();
+        let output_cmd = out.output_cmd.clone().unwrap_or_default();
+
+        for (field, value, fits, target) in [
+            (
+                "out.rtmp_flashver",
+                &out.rtmp_flashver,
+                output_cmd.last().is_some_and(|t| is_rtmp_target(t)),
+                "a rtmp target",
+            ),
+            (
+                "out.tcurl",
+                &out.tcurl,
+                output_cmd.last().is_some_and(|t| is_rtmp_target(t)),
+                "a rtmp target",
+            ),
+            (
+                "out.service_name",
+                &out.service_name,
+                is_ts_output(&output_cmd),
+                "a mpegts output",
+            ),
+            (
+                "out.service_provider",
+                &out.service_provider,
+                is_ts_output(&output_cmd),
+                "a mpegts output",
+            ),
+        ] {
+            if value.is_empty() {
+                continue;
+            }
+
+            if !single_output {
+                errors.push(ConfigError::new(
+                    field,
+                    "works only in stream mode with a single output",
+                ));
+            } else if !fits {
+                errors.push(ConfigError::new(
+                    field,
+                    &format!("works only with {target}"),
+                ));
+            }
+        }
+
+        if self.out.fallback.enable {
+            let fallback = &self.out.fallback;
+
+            if ![Dash, Null, Stream].contains(&self.out.mode) {
+                errors.push(ConfigError::new(
+                    "out.fallback.enable",
+                    "fallback works only in stream, dash and null mode",
+                ));
+            }
+
+            if fallback.ladder.is_empty() {
+                errors.push(ConfigError::new(
+                    "out.fallback.ladder",
+                    "ladder needs at least one step",
+                ));
+            }
+
+            let size = Regex::new(r"^\d+x\d+$").unwrap();
+
+            for step in fallback.ladder.iter().filter(|s| !s.size.is_empty()) {
+                if !size.is_match(&step.size) {
+                    errors.push(ConfigError::new(
+                        "out.fallback.ladder.size",
+                        &format!("'{}' is no size like 854x480", step.size),
+                    ));
+                }
+            }
+
+            if fallback.slow_seconds == 0 || fallback.cooldown_seconds == 0 {
+                errors.push(ConfigError::new(
+                    "out.fallback",
+                    "slow_seconds and cooldown_seconds must be positive",
+                ));
+            }
+        }
+
+        for arg in denied_extra_args(&self.out) {
+            errors.push(ConfigError::new(
+                "out.extra_args",
+                &format!("'{arg}' is not allowed, it breaks the pipeline"),
+            ));
+        }
+
+        if let Some(audio_cmd) = &self.ingest.audio_input_cmd {
+            if !audio_cmd.iter().any(|a| a == "-i") {
+                errors.push(ConfigError::new(
+                    "ingest.audio_input_param",
+                    "audio input needs a '-i' with the stream address",
+                ));
+            }
+
+            if processing.audio_only {
+                errors.push(ConfigError::new(
+                    "ingest.audio_input_param",
+                    "separate audio input needs video from the main input",
+                ));
+            }
+
+            if self.ingest.feed_hold_seconds <= 0.0 {
+                errors.push(ConfigError::new(
+                    "ingest.feed_hold_seconds",
+                    "hold time must be positive",
+                ));
+            }
+        }
+
+        let pip = &self.ingest.pip;
+
+        if pip.enable {
+            if self.ingest.takeover {
+                errors.push(ConfigError::new(
+                    "ingest.pip.enable",
+                    "picture-in-picture works only without takeover",
+                ));
+            }
+
+            if self.out.mode == HLS || processing.audio_only {
+                errors.push(ConfigError::new(
+                    "ingest.pip.enable",
+                    "picture-in-picture needs video and doesn't work in hls mode",
+                ));
+            }
+
+            // the output filter replaces the whole chain, also the overlay
+            if self.out.output_filter.is_some() {
+                errors.push(ConfigError::new(
+                    "ingest.pip.enable",
+                    "picture-in-picture doesn't work with a -filter_complex in the output_param",
+                ));
+            }
+
+            if pip.width == 0 || pip.height == 0 {
+                errors.push(ConfigError::new(
+                    "ingest.pip.width",
+                    "width and height must be positive",
+                ));
+            }
+        }
+
+        for (field, list) in [
+            ("ingest.allowed_keys", &self.ingest.allowed_keys),
+            ("ingest.allowed_apps", &self.ingest.allowed_apps),
+        ] {
+            if list.is_empty() {
+                continue;
+            }
+
+            if self.ingest.mode != IngestMode::Rtmp {
+                errors.push(ConfigError::new(field, "allowlist works only in rtmp mode"));
+            } else if list.iter().any(|v| v.trim().is_empty()) {
+                errors.push(ConfigError::new(field, "allowlist contains a empty name"));
+            }
+        }
+
+        if self.ingest.backpressure == Backpressure::Terminate
+            && self.ingest.backpressure_timeout_ms == 0
+        {
+            errors.push(ConfigError::new(
+                "ingest.backpressure_timeout_ms",
+                "timeout must be positive",
+            ));
+        }
+
+        if processing.preroll_ms > MAX_PREROLL_MS {
+            errors.push(ConfigError::new(
+                "processing.preroll_ms",
+                &format!("preroll can be at most {MAX_PREROLL_MS} ms"),
+            ));
+        }
+
+        if processing.decoder_mode == DecoderMode::Continuous && self.out.mode == HLS {
+            errors.push(ConfigError::new(
+                "processing.decoder_mode",
+                "continuous decoder works not in hls mode",
+            ));
+        }
+
+        if !processing.audio_only && processing.pix_fmt.is_empty() {
+            errors.push(ConfigError::new(
+                "processing.pix_fmt",
+                "pixel format is not set",
+            ));
+        } else if !processing.audio_only && intermediate_codec(&processing.pix_fmt).is_none() {
+            errors.push(ConfigError::new(
+                "processing.pix_fmt",
+                &format!(
+                    "pixel format {} is not supported between decoder and encoder",
+                    processing.pix_fmt
+                ),
+            ));
+        }
+
+        if processing.ffmpeg_threads < 0 {
+            errors.push(ConfigError::new(
+                "processing.ffmpeg_threads",
+                "thread count can not be negative",
+            ));
+        }
+
+        if processing.filter_threads < 0 {
+            errors.push(ConfigError::new(
+                "processing.filter_threads",
+                "thread count can not be negative",
+            ));
+        }
+
+        if !(-20..=19).contains(&processing.nice) {
+            errors.push(ConfigError::new(
+                "processing.nice",
+                "nice level must be between -20 and 19",
+            ));
+        }
+
+        if processing.fps <= 0.0 {
+            errors.push(ConfigError::new("processing.fps", "fps must be positive"));
+        }
+
+        for weight in &self.storage.weights {
+            if weight.weight == 0 || weight.path.is_empty() {
+                errors.push(ConfigError::new(
+                    "storage.weights",
+                    &format!(
+                        "weight for '{}' needs a path and must be at least 1",
+                        weight.path
+                    ),
+                ));
+            }
+        }
+
+        if processing.mode == ProcessMode::Loop && !Path::new(&self.storage.path).exists() {
+            errors.push(ConfigError::new(
+                "storage.path",
+                &format!("loop source {} not exists", self.storage.path),
+            ));
+        }
+
+        if processing.add_logo && !Path::new(&processing.logo).is_file() {
+            errors.push(ConfigError::new(
+                "processing.logo",
+                &format!("logo file {} not exists", processing.logo),
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&processing.logo_opacity) {
+            errors.push(ConfigError::new(
+                "processing.logo_opacity",
+                "opacity must be between 0.0 and 1.0",
+            ));
+        }
+
+        if processing.logo_loop_size > MAX_LOOP_SIZE
+            || processing.logos.iter().any(|l| l.loop_size > MAX_LOOP_SIZE)
+        {
+            errors.push(ConfigError::new(
+                "processing.logo_loop_size",
+                &format!("loop size can be at most {MAX_LOOP_SIZE} frames"),
+            ));
+        }
+
+        if let Err(e) = check_logo_filter(&processing.logo_filter) {
+            errors.push(ConfigError::new("processing.logo_filter", &e));
+        }
+
+        if let Err(e) = processing.logo_enable.check() {
+            errors.push(ConfigError::new("processing.logo_enable", &e));
+        }
+
+        for region in &processing.censor {
+            if let Err(e) = region.check(processing.width, processing.height) {
+                errors.push(ConfigError::new("processing.censor", &e));
+            }
+        }
+
+        if processing
+            .logos
+            .iter()
+            .any(|l| !(0.0..=1.0).contains(&l.opacity))
+        {
+            errors.push(ConfigError::new(
+                "processing.logos.opacity",
+                "opacity must be between 0.0 and 1.0",
+            ));
+        }
+
+        for (field, rate) in [
+            ("processing.video_bitrate", &processing.video_bitrate),
+            ("processing.audio_bitrate", &processing.audio_bitrate),
+            ("processing.maxrate", &processing.maxrate),
+            ("processing.bufsize", &processing.bufsize),
+        ] {
+            if !rate.is_empty() && bitrate_value(rate).is_none_or(|r| r <= 0.0) {
+                errors.push(ConfigError::new(
+                    field,
+                    &format!("'{rate}' is no positive bitrate, like 1300k"),
+                ));
+            }
+        }
+
+        if let (Some(bitrate), Some(maxrate)) = (
+            bitrate_value(&processing.video_bitrate),
+            bitrate_value(&processing.maxrate),
+        ) {
+            if maxrate < bitrate {
+                errors.push(ConfigError::new(
+                    "processing.maxrate",
+                    "maxrate must be at least the video bitrate",
+                ));
+            }
+        }
+
+        if loudnorm.enable {
+            if !(-70.0..=-5.0).contains(&loudnorm.integrated) {
+                errors.push(ConfigError::new(
+                    "processing.loudnorm.integrated",
+                    "integrated loudness must be between -70.0 and -5.0",
+                ));
+            }
+
+            if !(-9.0..=0.0).contains(&loudnorm.true_peak) {
+                errors.push(ConfigError::new(
+                    "processing.loudnorm.true_peak",
+                    "true peak must be between -9.0 and 0.0",
+                ));
+            }
+
+            if !(1.0..=50.0).contains(&loudnorm.lra) {
+                errors.push(ConfigError::new(
+                    "processing.loudnorm.lra",
+                    "loudness range must be between 1.0 and 50.0",
+                ));
+            }
+        }
+
+        for output in &self.out.tee {
+            if output.format.is_empty() || output.target.is_empty() {
+                errors.push(ConfigError::new(
+                    "out.tee",
+                    "every output needs a format and a target",
+                ));
+            }
+        }
+
+        if !self.out.tee.is_empty() && self.out.tee.iter().all(|o| o.ignore_failure) {
+            errors.push(ConfigError::new(
+                "out.tee.ignore_failure",
+                "at least one output must not ignore failures",
+            ));
+        }
+
+        if self.out.preview && self.out.mode != Stream {
+            errors.push(ConfigError::new(
+                "out.preview",
+                "preview works only in stream mode",
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Get segment length from hls output parameters.
+pub fn hls_time(cmd: &[String]) -> Option<f64> {
+    cmd.iter()
+        .position(|c| c == "-hls_time")
+        .and_then(|i| cmd.get(i + 1))
+        .and_then(|t| t.parse().ok())
+}
+
+/// Keyframe interval from the output parameters, by `-g` or the keyint of `-x264-params`.
+pub fn keyframe_interval(cmd: &[String]) -> Option<u32> {
+    let value = |key: &str| {
+        cmd.iter()
+            .position(|c| c == key)
+            .and_then(|i| cmd.get(i + 1))
+    };
+
+    if let Some(g) = value("-g") {
+        return g.parse().ok();
+    }
+
+    value("-x264-params")?
+        .split(':')
+        .find_map(|p| p.strip_prefix("keyint="))
+        .and_then(|k| k.parse().ok())
+}
+
+/// The raw logo filter must be a single overlay, because it gets inserted into the filter chain.
+///
+/// Characters, which can close the filter or start a new one, are not allowed.
+pub fn check_logo_filter(filter: &str) -> Result<(), String> {
+    if filter.is_empty() {
+        return Ok(());
+    }
+
+    let Some(expression) = filter.strip_prefix("overlay=") else {
+        return Err(format!(
+            "'{filter}' is no overlay expression, like 'overlay=W-w-12:12'"
+        ));
+    };
+
+    let mut invalid = vec![];
+
+    for c in expression.chars() {
+        if !c.is_ascii_alphanumeric() && !"_.:=+-*/() ".contains(c) && !invalid.contains(&c) {
+            invalid.push(c);
+        }
+    }
+
+    match invalid.is_empty() {
+        true => Ok(()),
+        false => Err(format!(
+            "invalid characters in overlay expression: {}",
+            invalid
+                .iter()
+                .map(|c| format!("'{c}'"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        )),
+    }
+}
+
+/// Bits per channel from the pixel format, like 10 for `yuv420p10le` or `p010le`.
+pub fn bit_depth(pix_fmt: &str) -> u8 {
+    let depth = Regex::new(r"p0?(\d{2})(le|be)?$").unwrap();
+
+    depth
+        .captures(pix_fmt)
+        .and_then(|c| c[1].parse().ok())
+        .unwrap_or(8)
+}
+
+/// Codec between decoder and encoder, which supports the pixel format.
+pub fn intermediate_codec(pix_fmt: &str) -> Option<&'static str> {
+    if MPEG2_PIX_FMTS.contains(&pix_fmt) {
+        Some("mpeg2video")
+    } else if X264_PIX_FMTS.contains(&pix_fmt) {
+        Some("libx264")
+    } else {
+        None
+    }
+}
+
+/// The video encoder of the output, when it can't encode the bit depth of the pixel format.
+pub fn eight_bit_encoder(config: &PlayoutConfig) -> Option<String> {
+    if bit_depth(&config.processing.pix_fmt) <= 8 {
+        return None;
     }
+
+    let cmd = config.out.output_cmd.as_ref()?;
+
+    cmd.iter()
+        .position(|c| c == "-c:v" || c == "-vcodec")
+        .and_then(|i| cmd.get(i + 1))
+        .filter(|e| EIGHT_BIT_ENCODERS.contains(&e.as_str()))
+        .cloned()
+}
+
+/// GOP size in frames, for segments of the given length.
+pub fn gop_size(fps: f64, segment: f64) -> u32 {
+    (fps * segment).round() as u32
 }
 
 impl Default for PlayoutConfig {
@@ -478,13 +3075,99 @@ impl Default for PlayoutConfig {
     }
 }
 
+/// Test if files can be created in the folder, without creating one.
+#[cfg(unix)]
+fn writable(folder: &Path) -> bool {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let Ok(path) = CString::new(folder.as_os_str().as_bytes()) else {
+        return false;
+    };
+
+    folder.is_dir() && unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) } == 0
+}
+
+/// Test if files can be created in the folder, without creating one.
+#[cfg(not(unix))]
+fn writable(folder: &Path) -> bool {
+    fs::metadata(folder).is_ok_and(|m| m.is_dir() && !m.permissions().readonly())
+}
+
+/// Prefix for environment variables, which override config values.
+const ENV_PREFIX: &str = "FFPLAYOUT_";
+
+/// Invalid config value, with the field path from the yaml file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub field: String,
+    pub message: String,
+}
+
+impl ConfigError {
+    fn new(field: &str, message: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Find the config value for the env path, like `PROCESSING_LOUDNORM_INTEGRATED`.
+/// Keys can contain underscores too, so the whole path must end on an existing field.
+fn env_field<'a>(value: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    let key = env_key(value, path)?;
+    let (key, rest) = key.split_first()?;
+    let mut field = value.get_mut(key)?;
+
+    for key in rest {
+        field = field.get_mut(key)?;
+    }
+
+    Some(field)
+}
+
+/// Resolve the env path to the exact chain of config keys, trying every section which matches.
+fn env_key(value: &Value, path: &str) -> Option<Vec<String>> {
+    value.as_object()?.iter().find_map(|(k, v)| {
+        let name = k.to_uppercase();
+
+        if path == name {
+            return Some(vec![k.clone()]);
+        }
+
+        let rest = path.strip_prefix(&format!("{name}_"))?;
+        let mut keys = env_key(v, rest)?;
+        keys.insert(0, k.clone());
+
+        Some(keys)
+    })
+}
+
+/// Parse the env value into the type of the current config value.
+fn env_value(current: &Value, val: &str) -> Option<Value> {
+    match current {
+        Value::Bool(_) => val.parse().ok().map(Value::Bool),
+        Value::Number(_) => serde_json::from_str(val).ok().filter(Value::is_number),
+        Value::String(_) => Some(Value::String(val.to_string())),
+        Value::Null => Some(serde_json::from_str(val).unwrap_or(Value::String(val.to_string()))),
+        Value::Array(_) => serde_json::from_str(val).ok().filter(Value::is_array),
+        Value::Object(_) => serde_json::from_str(val).ok().filter(Value::is_object),
+    }
+}
+
 /// When custom_filter contains loudnorm filter use a different audio encoder,
 /// s302m has higher quality, but is experimental
 /// and works not well together with the loudnorm filter.
-fn pre_audio_codec(proc_filter: &str, ingest_filter: &str) -> Vec<String> {
+fn pre_audio_codec(proc_filter: &str, ingest_filter: &str, loudnorm: bool) -> Vec<String> {
     let mut codec = vec_strings!["-c:a", "s302m", "-strict", "-2", "-sample_fmt", "s16"];
 
-    if proc_filter.contains("loudnorm") || ingest_filter.contains("loudnorm") {
+    if loudnorm || proc_filter.contains("loudnorm") || ingest_filter.contains("loudnorm") {
         codec = vec_strings!["-c:a", "mp2", "-b:a", "384k"];
     }
 
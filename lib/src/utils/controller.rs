@@ -1,17 +1,22 @@
 use std::{
-    fmt,
+    collections::{HashMap, VecDeque},
+    fmt, io,
     process::Child,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
-        Arc, Mutex,
+        Arc, Mutex, RwLock,
     },
+    thread::sleep,
+    time::{Duration, Instant},
 };
 
+use crossbeam_channel::Sender;
 use jsonrpc_http_server::CloseHandle;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use simplelog::*;
 
-use crate::utils::Media;
+use crate::utils::{get_sec, spawn_error, Media};
 
 /// Defined process units.
 #[derive(Clone, Debug, Default, Copy, Eq, Serialize, Deserialize, PartialEq)]
@@ -34,6 +39,177 @@ impl fmt::Display for ProcessUnit {
 
 use ProcessUnit::*;
 
+/// Max count of stderr lines, which are saved per process.
+const STDERR_LINES: usize = 50;
+
+/// Failure history of the processes, to detect when ffmpeg keeps crashing.
+#[derive(Debug, Default)]
+pub struct ProcessFailures {
+    times: [VecDeque<Instant>; 3],
+    pub last_alert: Option<Instant>,
+}
+
+impl ProcessFailures {
+    /// Add a failure and return how many failures happen inside the time window.
+    pub fn push(&mut self, unit: ProcessUnit, window: Duration) -> usize {
+        let index = match unit {
+            Decoder => 0,
+            Encoder => 1,
+            Ingest => 2,
+        };
+        let times = &mut self.times[index];
+        times.push_back(Instant::now());
+
+        while times.front().is_some_and(|t| t.elapsed() > window) {
+            times.pop_front();
+        }
+
+        times.len()
+    }
+}
+
+/// Statistics from the ffmpeg progress line, like:
+/// `frame=  250 fps= 25 q=28.0 size=    1024kB time=00:00:10.00 bitrate= 838.9kbits/s dup=0 drop=3 speed=1.00x`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EncoderStats {
+    pub frame: u64,
+    pub fps: f64,
+    pub drop: u64,
+    pub dup: u64,
+    pub speed: f64,
+}
+
+impl EncoderStats {
+    /// Parse a stats line, values which are missing or not available stays at zero.
+    pub fn parse(line: &str) -> Option<Self> {
+        // ffmpeg ends stats lines with a carriage return, so take only the last one
+        let line = line.trim_end().rsplit('\r').next()?;
+
+        if !line.contains("frame=") || !line.contains("speed=") {
+            return None;
+        }
+
+        let re = Regex::new(r"(\w+)=\s*([^\s]+)").unwrap();
+        let mut stats = Self::default();
+
+        for cap in re.captures_iter(line) {
+            let value = cap[2].trim_end_matches('x');
+
+            match &cap[1] {
+                "frame" => stats.frame = value.parse().unwrap_or_default(),
+                "fps" => stats.fps = value.parse().unwrap_or_default(),
+                "drop" => stats.drop = value.parse().unwrap_or_default(),
+                "dup" => stats.dup = value.parse().unwrap_or_default(),
+                "speed" => stats.speed = value.parse().unwrap_or_default(),
+                _ => (),
+            }
+        }
+
+        Some(stats)
+    }
+
+    /// Frames dropped since the last stats, a smaller value comes from a new process.
+    pub fn new_drops(&self, last: &Self) -> u64 {
+        if self.drop >= last.drop {
+            self.drop - last.drop
+        } else {
+            self.drop
+        }
+    }
+}
+
+/// Ingest package with its length and the time, when it was queued.
+pub type IngestPackage = (usize, Vec<u8>, Instant);
+
+/// Samples, which the latency window holds.
+pub const LATENCY_SAMPLES: usize = 500;
+
+/// Rolling window of the time, which ingest packages need from the queue to the encoder.
+#[derive(Clone, Debug, Default)]
+pub struct LatencyWindow {
+    samples: VecDeque<Duration>,
+}
+
+impl LatencyWindow {
+    pub fn push(&mut self, latency: Duration) {
+        if self.samples.len() == LATENCY_SAMPLES {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(latency);
+    }
+
+    /// Nearest rank percentile, `None` without samples.
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = self.samples.iter().copied().collect::<Vec<_>>();
+        sorted.sort();
+
+        let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+
+        Some(sorted[rank.clamp(1, sorted.len()) - 1])
+    }
+
+    pub fn p50(&self) -> Option<Duration> {
+        self.percentile(50.0)
+    }
+
+    pub fn p95(&self) -> Option<Duration> {
+        self.percentile(95.0)
+    }
+}
+
+/// Snapshot of the channel state, for the status endpoint and metrics.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ChannelStatus {
+    pub source: Option<String>,
+    pub index: Option<usize>,
+    pub elapsed: f64,
+    pub remaining: f64,
+    pub ingest_active: bool,
+    pub last_error: Option<String>,
+    pub clip_failures: HashMap<String, usize>,
+    pub uptime: f64,
+}
+
+/// Channel state, which the decoder, ingest and encoder update.
+///
+/// All values are behind one lock, so a reader gets them always together.
+#[derive(Clone, Debug, Default)]
+pub struct StatusState {
+    source: Option<String>,
+    index: Option<usize>,
+    begin: f64,
+    out: f64,
+    ingest_active: bool,
+    last_error: Option<String>,
+    clip_failures: HashMap<String, usize>,
+}
+
+impl StatusState {
+    /// Status at the given time of day in seconds, elapsed and remaining time come from the clip begin.
+    pub fn snapshot(&self, time_sec: f64, uptime: f64) -> ChannelStatus {
+        let elapsed = match self.source {
+            Some(_) => (time_sec - self.begin).max(0.0),
+            None => 0.0,
+        };
+
+        ChannelStatus {
+            source: self.source.clone(),
+            index: self.index,
+            elapsed,
+            remaining: (self.out - elapsed).max(0.0),
+            ingest_active: self.ingest_active,
+            last_error: self.last_error.clone(),
+            clip_failures: self.clip_failures.clone(),
+            uptime,
+        }
+    }
+}
+
 /// Process Controller
 ///
 /// We save here some global states, about what is running and which processes are alive.
@@ -43,10 +219,27 @@ pub struct ProcessControl {
     pub decoder_term: Arc<Mutex<Option<Child>>>,
     pub encoder_term: Arc<Mutex<Option<Child>>>,
     pub server_term: Arc<Mutex<Option<Child>>>,
+    pub preview_term: Arc<Mutex<Option<Child>>>,
     pub server_is_running: Arc<AtomicBool>,
+    pub ingest_idle: Arc<AtomicBool>,
     pub rpc_handle: Arc<Mutex<Option<CloseHandle>>>,
     pub is_terminated: Arc<AtomicBool>,
     pub is_alive: Arc<AtomicBool>,
+    pub decoder_restarts: Arc<AtomicUsize>,
+    pub encoder_restarts: Arc<AtomicUsize>,
+    pub dropped_frames: Arc<AtomicUsize>,
+    pub encoder_stats: Arc<Mutex<EncoderStats>>,
+    pub start_time: Instant,
+    pub last_progress: Arc<Mutex<Instant>>,
+    pub watchdog_pause: Arc<AtomicBool>,
+    pub encoder_stalled: Arc<AtomicBool>,
+    pub encoder_level: Arc<AtomicUsize>,
+    pub failures: Arc<Mutex<ProcessFailures>>,
+    pub decoder_lines: Arc<Mutex<VecDeque<String>>>,
+    pub encoder_lines: Arc<Mutex<VecDeque<String>>>,
+    pub server_lines: Arc<Mutex<VecDeque<String>>>,
+    pub status: Arc<RwLock<StatusState>>,
+    pub ingest_latency: Arc<Mutex<LatencyWindow>>,
 }
 
 impl ProcessControl {
@@ -55,10 +248,27 @@ impl ProcessControl {
             decoder_term: Arc::new(Mutex::new(None)),
             encoder_term: Arc::new(Mutex::new(None)),
             server_term: Arc::new(Mutex::new(None)),
+            preview_term: Arc::new(Mutex::new(None)),
             server_is_running: Arc::new(AtomicBool::new(false)),
+            ingest_idle: Arc::new(AtomicBool::new(false)),
             rpc_handle: Arc::new(Mutex::new(None)),
             is_terminated: Arc::new(AtomicBool::new(false)),
             is_alive: Arc::new(AtomicBool::new(true)),
+            decoder_restarts: Arc::new(AtomicUsize::new(0)),
+            encoder_restarts: Arc::new(AtomicUsize::new(0)),
+            dropped_frames: Arc::new(AtomicUsize::new(0)),
+            encoder_stats: Arc::new(Mutex::new(EncoderStats::default())),
+            start_time: Instant::now(),
+            last_progress: Arc::new(Mutex::new(Instant::now())),
+            watchdog_pause: Arc::new(AtomicBool::new(false)),
+            encoder_stalled: Arc::new(AtomicBool::new(false)),
+            encoder_level: Arc::new(AtomicUsize::new(0)),
+            failures: Arc::new(Mutex::new(ProcessFailures::default())),
+            decoder_lines: Arc::new(Mutex::new(VecDeque::with_capacity(STDERR_LINES))),
+            encoder_lines: Arc::new(Mutex::new(VecDeque::with_capacity(STDERR_LINES))),
+            server_lines: Arc::new(Mutex::new(VecDeque::with_capacity(STDERR_LINES))),
+            status: Arc::new(RwLock::new(StatusState::default())),
+            ingest_latency: Arc::new(Mutex::new(LatencyWindow::default())),
         }
     }
 }
@@ -70,6 +280,111 @@ impl Default for ProcessControl {
 }
 
 impl ProcessControl {
+    fn term(&self, unit: ProcessUnit) -> &Arc<Mutex<Option<Child>>> {
+        match unit {
+            Decoder => &self.decoder_term,
+            Encoder => &self.encoder_term,
+            Ingest => &self.server_term,
+        }
+    }
+
+    /// Store the new process of the unit. The old one gets waited for,
+    /// when it still runs it gets killed, so it can't stay behind as zombie.
+    pub fn set_child(&self, unit: ProcessUnit, child: Child) {
+        let old = self.term(unit).lock().unwrap().replace(child);
+
+        if let Some(mut proc) = old {
+            reap_child(&mut proc, unit);
+        }
+    }
+
+    /// Register the clip, which starts now.
+    pub fn set_clip(&self, node: &Media) {
+        let mut status = self.status.write().unwrap();
+        status.source = Some(node.source.clone());
+        status.index = node.index;
+        status.begin = node.begin.unwrap_or_else(get_sec);
+        status.out = node.out;
+    }
+
+    /// Set the ingest state, the atomic stays as fast path for the playout loops.
+    ///
+    /// A new ingest connection starts with an empty latency window,
+    /// so the percentiles show only the current session.
+    pub fn set_ingest_active(&self, active: bool) {
+        let mut status = self.status.write().unwrap();
+
+        if !self.server_is_running.swap(active, Ordering::SeqCst) && active {
+            *self.ingest_latency.lock().unwrap() = LatencyWindow::default();
+        }
+
+        status.ingest_active = active;
+    }
+
+    pub fn set_last_error(&self, unit: ProcessUnit, message: &str) {
+        self.status.write().unwrap().last_error = Some(format!("[{unit}] {message}"));
+    }
+
+    /// A process couldn't start, log the reason and stop the playout.
+    ///
+    /// Without the process there is nothing to restart, so the threads can finish in order.
+    pub fn spawn_failed(&self, unit: ProcessUnit, bin: &str, e: &io::Error) {
+        let msg = spawn_error(unit, bin, e);
+
+        error!("{msg}");
+        self.set_last_error(unit, &msg);
+        self.stop_all();
+    }
+
+    /// Count a failed decoder run of the clip, it returns the failures of the clip so far.
+    pub fn count_clip_failure(&self, source: &str) -> usize {
+        let mut status = self.status.write().unwrap();
+        let count = status.clip_failures.entry(source.to_string()).or_insert(0);
+        *count += 1;
+
+        *count
+    }
+
+    /// Current state of the channel.
+    pub fn channel_status(&self) -> ChannelStatus {
+        self.status
+            .read()
+            .unwrap()
+            .snapshot(get_sec(), self.start_time.elapsed().as_secs_f64())
+    }
+
+    /// Encoder process is running.
+    pub fn encoder_alive(&self) -> bool {
+        self.encoder_term
+            .lock()
+            .unwrap()
+            .as_mut()
+            .is_some_and(|proc| matches!(proc.try_wait(), Ok(None)))
+    }
+
+    /// Encoder is running and made progress in the last `max_age`.
+    pub fn encoder_ready(&self, max_age: Duration) -> bool {
+        self.encoder_alive() && self.last_progress.lock().unwrap().elapsed() <= max_age
+    }
+
+    /// Reap exited processes and count the still running ones.
+    pub fn live_children(&self) -> usize {
+        [
+            &self.decoder_term,
+            &self.encoder_term,
+            &self.server_term,
+            &self.preview_term,
+        ]
+        .iter()
+        .filter(|term| {
+            term.lock()
+                .unwrap()
+                .as_mut()
+                .is_some_and(|proc| matches!(proc.try_wait(), Ok(None)))
+        })
+        .count()
+    }
+
     pub fn stop(&self, unit: ProcessUnit) -> Result<(), String> {
         match unit {
             Decoder => {
@@ -130,11 +445,87 @@ impl ProcessControl {
         Ok(())
     }
 
+    /// Count a clip process which exits with an error, killed processes have no exit code.
+    /// Returns true, when the process failed.
+    pub fn count_restart(&self, unit: ProcessUnit) -> bool {
+        let mut term = match unit {
+            Decoder => self.decoder_term.lock().unwrap(),
+            Encoder => self.encoder_term.lock().unwrap(),
+            Ingest => self.server_term.lock().unwrap(),
+        };
+
+        if let Some(proc) = term.as_mut() {
+            if let Ok(Some(status)) = proc.try_wait() {
+                if matches!(status.code(), Some(c) if c != 0) {
+                    match unit {
+                        Decoder => self.decoder_restarts.fetch_add(1, Ordering::SeqCst),
+                        Encoder => self.encoder_restarts.fetch_add(1, Ordering::SeqCst),
+                        Ingest => 0,
+                    };
+
+                    return true;
+                }
+
+                self.clear_lines(unit);
+            }
+        }
+
+        false
+    }
+
+    fn lines(&self, unit: ProcessUnit) -> &Arc<Mutex<VecDeque<String>>> {
+        match unit {
+            Decoder => &self.decoder_lines,
+            Encoder => &self.encoder_lines,
+            Ingest => &self.server_lines,
+        }
+    }
+
+    /// Save stderr line from process, only the last lines are kept.
+    pub fn push_line(&self, unit: ProcessUnit, line: &str) {
+        let mut lines = self.lines(unit).lock().unwrap();
+
+        if lines.len() >= STDERR_LINES {
+            lines.pop_front();
+        }
+
+        lines.push_back(line.to_string());
+    }
+
+    /// Snapshot of the last stderr lines from process.
+    pub fn last_errors(&self, unit: ProcessUnit) -> Vec<String> {
+        self.lines(unit).lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear_lines(&self, unit: ProcessUnit) {
+        self.lines(unit).lock().unwrap().clear();
+    }
+
+    /// Stop reading from ingest, send a close message (zero length package) over the channel
+    /// and wait until the consumer has written all queued packages, before the server get killed.
+    pub fn drain_and_terminate(&self, sender: &Sender<IngestPackage>, timeout: Duration) {
+        debug!("Drain ingest channel and terminate");
+        self.is_terminated.store(true, Ordering::SeqCst);
+        let start = Instant::now();
+
+        if let Err(e) = sender.send_timeout((0, vec![], Instant::now()), timeout) {
+            error!("Ingest server could not send close message: {e}");
+        }
+
+        drain_channel(sender, timeout.saturating_sub(start.elapsed()));
+
+        if let Err(e) = self.stop(Ingest) {
+            if !e.contains("exited process") {
+                error!("{e}")
+            }
+        }
+    }
+
     /// No matter what is running, terminate them all.
     pub fn stop_all(&self) {
         debug!("Stop all child processes");
         self.is_terminated.store(true, Ordering::SeqCst);
-        self.server_is_running.store(false, Ordering::SeqCst);
+        self.set_ingest_active(false);
 
         if self.is_alive.load(Ordering::SeqCst) {
             self.is_alive.store(false, Ordering::SeqCst);
@@ -155,10 +546,62 @@ impl ProcessControl {
                     }
                 }
             }
+
+            // close the preview window
+            if let Some(proc) = self.preview_term.lock().unwrap().as_mut() {
+                if let Err(e) = proc.kill() {
+                    debug!("Preview {e:?}")
+                }
+
+                if let Err(e) = proc.wait() {
+                    error!("Preview {e:?}")
+                }
+            }
         }
     }
 }
 
+/// Wait for a replaced process, std caches the exit status, so a second wait is harmless.
+fn reap_child(proc: &mut Child, unit: ProcessUnit) {
+    if let Ok(None) = proc.try_wait() {
+        warn!("{unit} process is replaced, but still runs, kill it");
+
+        if let Err(e) = proc.kill() {
+            debug!("{unit} {e:?}");
+        }
+    }
+
+    if let Err(e) = proc.wait() {
+        error!("{unit} {e:?}");
+    }
+}
+
+/// Reap stragglers periodically. A process, which is killed externally,
+/// stays otherwise as zombie until it gets replaced.
+pub fn child_reaper(proc_control: ProcessControl) {
+    while !proc_control.is_terminated.load(Ordering::SeqCst) {
+        proc_control.live_children();
+
+        sleep(Duration::from_secs(5));
+    }
+}
+
+/// Wait until the consumer has read all queued ingest packages.
+fn drain_channel(sender: &Sender<IngestPackage>, timeout: Duration) {
+    let start = Instant::now();
+
+    while !sender.is_empty() && start.elapsed() < timeout {
+        sleep(Duration::from_millis(10));
+    }
+
+    if !sender.is_empty() {
+        warn!(
+            "Ingest channel not drained, drop <yellow>{}</> package(s)",
+            sender.len()
+        );
+    }
+}
+
 // impl Drop for ProcessControl {
 //     fn drop(&mut self) {
 //         self.stop_all()
@@ -192,20 +635,26 @@ impl Default for PlayerControl {
 /// Global playout control, for move forward/backward clip, or resetting playlist/state.
 #[derive(Clone, Debug)]
 pub struct PlayoutStatus {
+    pub ad_break: Arc<Mutex<Option<f64>>>,
     pub chain: Option<Arc<Mutex<Vec<String>>>>,
     pub current_date: Arc<Mutex<String>>,
     pub date: Arc<Mutex<String>>,
     pub list_init: Arc<AtomicBool>,
+    pub pause: Arc<Mutex<Option<Instant>>>,
+    pub reload: Arc<AtomicBool>,
     pub time_shift: Arc<Mutex<f64>>,
 }
 
 impl PlayoutStatus {
     pub fn new() -> Self {
         Self {
+            ad_break: Arc::new(Mutex::new(None)),
             chain: None,
             current_date: Arc::new(Mutex::new(String::new())),
             date: Arc::new(Mutex::new(String::new())),
             list_init: Arc::new(AtomicBool::new(true)),
+            pause: Arc::new(Mutex::new(None)),
+            reload: Arc::new(AtomicBool::new(false)),
             time_shift: Arc::new(Mutex::new(0.0)),
         }
     }
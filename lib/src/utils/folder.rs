@@ -1,4 +1,6 @@
 use std::{
+    collections::{HashMap, VecDeque},
+    mem,
     path::Path,
     sync::{
         atomic::{AtomicUsize, Ordering},
@@ -6,11 +8,11 @@ use std::{
     },
 };
 
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 use simplelog::*;
 use walkdir::WalkDir;
 
-use crate::utils::{get_sec, include_file, Media, PlayoutConfig};
+use crate::utils::{get_sec, include_file, Media, PlayoutConfig, ProcessMode::Loop};
 
 /// Folder Sources
 ///
@@ -22,6 +24,7 @@ pub struct FolderSource {
     pub nodes: Arc<Mutex<Vec<Media>>>,
     current_node: Media,
     index: Arc<AtomicUsize>,
+    rng: StdRng,
 }
 
 impl FolderSource {
@@ -44,6 +47,12 @@ impl FolderSource {
         }
 
         for path in &path_list {
+            // loop mode can play a single file
+            if config.processing.mode == Loop && Path::new(path).is_file() {
+                media_list.push(Media::new(0, path, false));
+                continue;
+            }
+
             if !Path::new(path).is_dir() {
                 error!("Path not exists: <b><magenta>{path}</></b>");
             }
@@ -67,10 +76,14 @@ impl FolderSource {
             );
         }
 
+        let mut rng = match config.storage.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
         if config.storage.shuffle {
             info!("Shuffle files");
-            let mut rng = thread_rng();
-            media_list.shuffle(&mut rng);
+            shuffle_nodes(config, &mut media_list, &mut rng, None);
         } else {
             media_list.sort_by(|d1, d2| d1.source.cmp(&d2.source));
         }
@@ -89,18 +102,19 @@ impl FolderSource {
             nodes: current_list,
             current_node: Media::new(0, "", false),
             index: global_index,
+            rng,
         }
     }
 
     fn shuffle(&mut self) {
-        let mut rng = thread_rng();
         let mut nodes = self.nodes.lock().unwrap();
 
-        nodes.shuffle(&mut rng);
-
-        for (index, item) in nodes.iter_mut().enumerate() {
-            item.index = Some(index);
-        }
+        shuffle_nodes(
+            &self.config,
+            &mut nodes,
+            &mut self.rng,
+            Some(self.current_node.source.as_str()),
+        );
     }
 
     fn sort(&mut self) {
@@ -114,6 +128,91 @@ impl FolderSource {
     }
 }
 
+/// Weight of a source, the most specific path from `storage.weights` wins.
+pub fn file_weight(config: &PlayoutConfig, source: &str) -> usize {
+    let path = Path::new(source);
+    let relative = path.strip_prefix(&config.storage.path).unwrap_or(path);
+
+    config
+        .storage
+        .weights
+        .iter()
+        .filter(|w| relative.starts_with(&w.path) || path.starts_with(&w.path))
+        .max_by_key(|w| Path::new(&w.path).components().count())
+        .map_or(1, |w| w.weight)
+}
+
+/// After placing `source`, the `left` nodes can still be ordered without a repeat.
+///
+/// Only sources with more then one node can break it.
+fn fits(counts: &HashMap<String, usize>, source: &str, left: usize) -> bool {
+    counts.iter().all(|(s, c)| match s == source {
+        true => c - 1 <= left / 2,
+        false => *c <= left.div_ceil(2),
+    })
+}
+
+/// Shuffle the nodes, weighted files get added multiple times.
+///
+/// The same file is never played twice in a row, as long as there are enough other files,
+/// `last` is the file played before the list starts.
+pub fn shuffle_nodes<R: Rng>(
+    config: &PlayoutConfig,
+    nodes: &mut Vec<Media>,
+    rng: &mut R,
+    last: Option<&str>,
+) {
+    // sorted first, so a seed gives always the same order
+    nodes.sort_by(|d1, d2| d1.source.cmp(&d2.source));
+    nodes.dedup_by(|d1, d2| d1.source == d2.source);
+
+    let mut counts = HashMap::new();
+    let mut copies = vec![];
+
+    for node in nodes.iter() {
+        let weight = file_weight(config, &node.source);
+
+        if weight > 1 {
+            counts.insert(node.source.clone(), weight);
+        }
+
+        for _ in 1..weight {
+            copies.push(node.clone());
+        }
+    }
+
+    nodes.append(&mut copies);
+    nodes.shuffle(rng);
+
+    // take the next shuffled node, which is not a repeat and leaves the rest placeable
+    let mut rest = VecDeque::from(mem::take(nodes));
+    let mut prev = last.map(str::to_string);
+
+    while !rest.is_empty() {
+        let left = rest.len() - 1;
+        let pos = rest
+            .iter()
+            .position(|n| prev.as_ref() != Some(&n.source) && fits(&counts, &n.source, left))
+            .unwrap_or(0);
+        let node = rest.remove(pos).unwrap();
+
+        if let Some(count) = counts.get_mut(&node.source) {
+            *count -= 1;
+
+            if *count < 2 {
+                counts.remove(&node.source);
+            }
+        }
+
+        prev = Some(node.source.clone());
+        nodes.push(node);
+    }
+
+    for (index, item) in nodes.iter_mut().enumerate() {
+        item.index = Some(index);
+    }
+}
+
 /// Create iterator for folder source
 impl Iterator for FolderSource {
     type Item = Media;
@@ -1,18 +1,33 @@
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::File,
-    path::Path,
-    sync::{atomic::AtomicBool, Arc},
-    thread,
+    fs::{self, File},
+    io::{self, Error, ErrorKind, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
 };
 
+use chrono::NaiveDate;
+use reqwest::{blocking::Client, header, StatusCode};
 use simplelog::*;
 
 use crate::utils::{
-    controller::ProcessUnit::*, get_date, is_remote, modified_time, time_from_header,
-    validate_playlist, Media, PlayoutConfig, DUMMY_LEN,
+    controller::ProcessUnit::*, get_date, is_remote, keyframes, modified_time, playlist_errors,
+    remote_validator, snap_to_keyframe, spawn_tagged, time_to_sec, validate_playlist, Media,
+    PlayoutConfig, DUMMY_LEN, REMOTE_TIMEOUT,
 };
 
+/// Playlist path, which reads the playlist from stdin.
+pub const STDIN_PLAYLIST: &str = "-";
+
+/// Stdin can be read only once, so the playlist from there is kept for later reads.
+static STDIN_LIST: OnceLock<JsonPlaylist> = OnceLock::new();
+
+/// Count for the temp files of [`write_playlist`], so parallel writes don't share one.
+static TMP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
 /// This is our main playlist object, it holds all necessary information for the current day.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct JsonPlaylist {
@@ -107,6 +122,7 @@ fn loop_playlist(
                 out: item.out,
                 duration: item.duration,
                 category: item.category.clone(),
+                title: item.title.clone(),
                 source: item.source.clone(),
                 audio: item.audio.clone(),
                 cmd: item.cmd.clone(),
@@ -117,6 +133,8 @@ fn loop_playlist(
                 next_ad: Some(false),
                 filter: None,
                 custom_filter: String::new(),
+                source_headers: item.source_headers.clone(),
+                audio_gain_db: item.audio_gain_db,
             };
 
             if begin < start_sec + length {
@@ -135,29 +153,401 @@ fn loop_playlist(
     playlist
 }
 
-/// Read json playlist file, fills JsonPlaylist struct and set some extra values,
-/// which we need to process.
-pub fn read_json(
+/// Probe bumper clip, it is only used when the file has a valid duration.
+fn bumper(path: &str, category: &str) -> Option<Media> {
+    if path.is_empty() {
+        return None;
+    }
+
+    let mut media = Media::new(0, path, true);
+
+    if media.duration <= 0.0 {
+        error!("Bumper <b><magenta>{path}</></b> has no valid duration, skip it!");
+        return None;
+    }
+
+    media.category = category.to_string();
+    media.process = Some(true);
+    media.last_ad = Some(false);
+    media.next_ad = Some(false);
+
+    Some(media)
+}
+
+/// Insert outro and intro around every boundary from `interval`, counted from midnight.
+///
+/// The clip which would overflow the boundary gets trimmed, so that the outro ends on
+/// the boundary and the intro starts with it. When there is not enough time left for the
+/// clip, it moves behind the intro. All begin times and indexes are recalculated.
+pub fn splice_bumpers(
+    program: Vec<Media>,
+    start_sec: f64,
+    interval: f64,
+    intro: Option<&Media>,
+    outro: Option<&Media>,
+) -> Vec<Media> {
+    let intro_len = intro.map_or(0.0, |m| m.out - m.seek);
+    let outro_len = outro.map_or(0.0, |m| m.out - m.seek);
+
+    if (intro.is_none() && outro.is_none()) || interval <= intro_len + outro_len + 1.0 {
+        return program;
+    }
+
+    let mut program_list = vec![];
+    let mut begin = start_sec;
+    let mut boundary = ((start_sec / interval).floor() + 1.0) * interval;
+
+    for item in program {
+        loop {
+            let length = item.out - item.seek;
+
+            if begin + length + outro_len <= boundary {
+                let mut media = item;
+                media.begin = Some(begin);
+                begin += length;
+                program_list.push(media);
+
+                break;
+            }
+
+            let available = boundary - outro_len - begin;
+            let trimmed = available >= 1.0;
+
+            if trimmed {
+                let mut media = item.clone();
+                media.out = media.seek + available;
+                media.begin = Some(begin);
+                begin += available;
+                program_list.push(media);
+            }
+
+            for bumper in [outro, intro].into_iter().flatten() {
+                let mut media = bumper.clone();
+                media.begin = Some(begin);
+                begin += media.out - media.seek;
+                program_list.push(media);
+            }
+
+            boundary += interval;
+
+            if trimmed {
+                break;
+            }
+        }
+    }
+
+    for (i, item) in program_list.iter_mut().enumerate() {
+        item.index = Some(i);
+    }
+
+    program_list
+}
+
+/// Add the configured intro and outro bumpers to the playlist.
+fn add_bumpers(config: &PlayoutConfig, mut playlist: JsonPlaylist, start_sec: f64) -> JsonPlaylist {
+    let intro = bumper(&config.playlist.intro, "intro");
+    let outro = bumper(&config.playlist.outro, "outro");
+
+    if intro.is_some() || outro.is_some() {
+        playlist.program = splice_bumpers(
+            playlist.program,
+            start_sec,
+            time_to_sec(&config.playlist.bumper_interval),
+            intro.as_ref(),
+            outro.as_ref(),
+        );
+    }
+
+    playlist
+}
+
+/// Snap the cut points of the clips to keyframes, before the begin times are set.
+///
+/// Only local clips with a cut get scanned.
+fn snap_cut_points(config: &PlayoutConfig, mut playlist: JsonPlaylist) -> JsonPlaylist {
+    if !config.processing.snap_to_keyframe {
+        return playlist;
+    }
+
+    for item in playlist.program.iter_mut() {
+        if (item.seek <= 0.0 && item.out >= item.duration)
+            || is_remote(&item.source)
+            || !Path::new(&item.source).is_file()
+        {
+            continue;
+        }
+
+        let (seek, out) =
+            snap_to_keyframe(&keyframes(&item.source), item.seek, item.out, item.duration);
+
+        if seek != item.seek || out != item.out {
+            debug!(
+                "Snap cut to keyframe, in: <yellow>{:.3}</> -> <yellow>{seek:.3}</>, out: <yellow>{:.3}</> -> <yellow>{out:.3}</> of <b><magenta>{}</></b>",
+                item.seek, item.out, item.source
+            );
+
+            item.seek = seek;
+            item.out = out;
+        }
+    }
+
+    playlist
+}
+
+/// Loop or prepare the playlist, and add the bumpers to it.
+fn prepare_playlist(
     config: &PlayoutConfig,
-    path: Option<String>,
+    current_file: String,
+    playlist: JsonPlaylist,
+    start_sec: f64,
+) -> JsonPlaylist {
+    let playlist = snap_cut_points(config, playlist);
+
+    match config.playlist.infinit {
+        true => {
+            let playlist = loop_playlist(config, current_file, playlist);
+            add_bumpers(config, playlist, start_sec)
+        }
+        false => {
+            let playlist = add_bumpers(config, playlist, start_sec);
+            set_defaults(playlist, current_file, start_sec)
+        }
+    }
+}
+
+/// Validators from the last successful download of a remote playlist.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheHeader {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    modified: Option<String>,
+}
+
+/// Path of the local copy from a remote playlist.
+pub fn remote_cache(config: &PlayoutConfig, url: &str) -> PathBuf {
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|u| u.rsplit('/').next())
+        .filter(|n| !n.is_empty())
+        .unwrap_or("playlist.json");
+
+    Path::new(&config.playlist.cache_dir).join(name)
+}
+
+fn write_cache(cache: &Path, body: &str, header: &CacheHeader) -> Result<(), Error> {
+    if let Some(parent) = cache.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(cache, body)?;
+    fs::write(
+        cache.with_extension("header"),
+        serde_json::to_string(header).unwrap_or_default(),
+    )
+}
+
+/// Download remote playlist, with the cached validators the server can answer with
+/// "304 Not Modified". On errors the last cached playlist is used.
+///
+/// Returns the playlist body and its modification value.
+fn fetch_remote(config: &PlayoutConfig, url: &str) -> Option<(String, Option<String>)> {
+    let cache = remote_cache(config, url);
+    let cached_header: CacheHeader = fs::read_to_string(cache.with_extension("header"))
+        .ok()
+        .filter(|_| cache.is_file())
+        .and_then(|h| serde_json::from_str(&h).ok())
+        .unwrap_or_default();
+
+    let response = Client::builder()
+        .timeout(REMOTE_TIMEOUT)
+        .build()
+        .and_then(|client| {
+            let mut request = client.get(url);
+
+            if let Some(etag) = &cached_header.etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+
+            if let Some(last_modified) = &cached_header.last_modified {
+                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+
+            request.send()
+        });
+
+    match response {
+        Ok(resp) if resp.status() == StatusCode::NOT_MODIFIED => {
+            debug!("Remote playlist <b><magenta>{url}</></b> not modified, use cache");
+
+            if let Ok(body) = fs::read_to_string(&cache) {
+                return Some((body, cached_header.modified));
+            }
+        }
+        Ok(resp) if resp.status().is_success() => {
+            let headers = resp.headers().clone();
+
+            match resp.text() {
+                Ok(body) => {
+                    let header = CacheHeader {
+                        etag: headers
+                            .get(header::ETAG)
+                            .and_then(|e| e.to_str().ok())
+                            .map(|e| e.to_string()),
+                        last_modified: headers
+                            .get(header::LAST_MODIFIED)
+                            .and_then(|l| l.to_str().ok())
+                            .map(|l| l.to_string()),
+                        modified: remote_validator(&headers),
+                    };
+
+                    if let Err(e) = write_cache(&cache, &body, &header) {
+                        warn!("Unable to cache playlist <b><magenta>{cache:?}</></b>: {e}");
+                    }
+
+                    return Some((body, header.modified));
+                }
+                Err(e) => warn!("Reading remote playlist <b><magenta>{url}</></b> failed: {e}"),
+            }
+        }
+        Ok(resp) => warn!(
+            "Remote playlist <b><magenta>{url}</></b> not available: {}",
+            resp.status()
+        ),
+        Err(e) => warn!("Remote playlist <b><magenta>{url}</></b> not reachable: {e}"),
+    }
+
+    let body = fs::read_to_string(&cache).ok()?;
+    warn!("Use last cached playlist: <b><magenta>{cache:?}</></b>");
+
+    Some((body, cached_header.modified))
+}
+
+/// Log broken items, but keep the playlist, so the valid clips still play.
+fn log_playlist_errors(playlist: &JsonPlaylist) {
+    for e in playlist_errors(playlist, false) {
+        error!("Playlist {}, {e}", playlist.date);
+    }
+}
+
+pub fn is_stdin_playlist(path: &str) -> bool {
+    path == STDIN_PLAYLIST
+}
+
+/// Read and validate a playlist from a reader, for the playlist from stdin.
+///
+/// A broken json gives the dummy playlist, like a broken playlist file.
+pub fn playlist_from_reader<R: Read>(
+    config: &PlayoutConfig,
+    reader: R,
     is_terminated: Arc<AtomicBool>,
-    seek: bool,
-    next_start: f64,
+    date: String,
 ) -> JsonPlaylist {
     let config_clone = config.clone();
-    let mut playlist_path = Path::new(&config.playlist.path).to_owned();
     let start_sec = config.playlist.start_sec.unwrap();
-    let date = get_date(seek, start_sec, next_start);
+    let mut playlist: JsonPlaylist = match serde_json::from_reader(reader) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Playlist from stdin not readable! {e}");
+            JsonPlaylist::new(date.clone(), start_sec)
+        }
+    };
+
+    // catch empty program list
+    if playlist.program.is_empty() {
+        playlist = JsonPlaylist::new(date, start_sec)
+    }
+
+    log_playlist_errors(&playlist);
+
+    let list_clone = playlist.clone();
+
+    spawn_tagged(move || validate_playlist(list_clone, is_terminated, config_clone));
+
+    playlist
+}
+
+/// Path of the playlist for the date, in a playlist folder it is `YYYY/MM/YYYY-MM-DD.json`.
+///
+/// When the config points to a single file, this file is used for every date.
+pub fn playlist_file(config: &PlayoutConfig, date: &str) -> PathBuf {
+    let playlist_path = Path::new(&config.playlist.path);
 
     if playlist_path.is_dir() || is_remote(&config.playlist.path) {
         let d: Vec<&str> = date.split('-').collect();
-        playlist_path = playlist_path
+
+        return playlist_path
             .join(d[0])
             .join(d[1])
-            .join(date.clone())
+            .join(date)
             .with_extension("json");
     }
 
+    playlist_path.to_owned()
+}
+
+/// Write the playlist to its file, see [`playlist_file`].
+///
+/// The json goes first to a temp file in the same folder, the rename replaces
+/// the playlist at once. So a reader gets the old or the new playlist, never a part of it.
+pub fn write_playlist(config: &PlayoutConfig, playlist: &JsonPlaylist) -> Result<PathBuf, Error> {
+    if is_remote(&config.playlist.path) {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "remote playlist can't be written",
+        ));
+    }
+
+    if is_stdin_playlist(&config.playlist.path) {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "playlist from stdin can't be written",
+        ));
+    }
+
+    if NaiveDate::parse_from_str(&playlist.date, "%Y-%m-%d").is_err() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("date {} is not in format YYYY-MM-DD", playlist.date),
+        ));
+    }
+
+    let path = playlist_file(config, &playlist.date);
+    let tmp_path = path.with_extension(format!(
+        "json.{}.{}.tmp",
+        std::process::id(),
+        TMP_COUNT.fetch_add(1, Ordering::SeqCst)
+    ));
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(&tmp_path, serde_json::to_string_pretty(playlist)?)?;
+
+    if let Err(e) = fs::rename(&tmp_path, &path) {
+        let _ = fs::remove_file(&tmp_path);
+
+        return Err(e);
+    }
+
+    Ok(path)
+}
+
+/// Read json playlist file, fills JsonPlaylist struct and set some extra values,
+/// which we need to process.
+pub fn read_json(
+    config: &PlayoutConfig,
+    path: Option<String>,
+    is_terminated: Arc<AtomicBool>,
+    seek: bool,
+    next_start: f64,
+) -> JsonPlaylist {
+    let config_clone = config.clone();
+    let start_sec = config.playlist.start_sec.unwrap();
+    let date = get_date(seek, start_sec, next_start);
+    let mut playlist_path = playlist_file(config, &date);
+
     let mut current_file = playlist_path.as_path().display().to_string();
 
     if let Some(p) = path {
@@ -165,33 +555,40 @@ pub fn read_json(
         current_file = p
     }
 
-    if is_remote(&current_file) {
-        let response = reqwest::blocking::Client::new().get(&current_file).send();
+    if is_stdin_playlist(&current_file) {
+        let playlist = STDIN_LIST
+            .get_or_init(|| {
+                info!("Read playlist from stdin");
+                playlist_from_reader(config, io::stdin().lock(), is_terminated, date)
+            })
+            .clone();
 
-        if let Ok(resp) = response {
-            if resp.status().is_success() {
-                let headers = resp.headers().clone();
+        return prepare_playlist(config, current_file, playlist, start_sec);
+    }
 
-                if let Ok(body) = resp.text() {
-                    let mut playlist: JsonPlaylist =
-                        serde_json::from_str(&body).expect("Could't read remote json playlist.");
+    if is_remote(&current_file) {
+        if let Some((body, modified)) = fetch_remote(config, &current_file) {
+            let mut playlist: JsonPlaylist = match serde_json::from_str(&body) {
+                Ok(p) => p,
+                Err(e) => {
+                    error!("Remote playlist not readable! {e}");
+                    JsonPlaylist::new(date.clone(), start_sec)
+                }
+            };
 
-                    if let Some(time) = time_from_header(&headers) {
-                        playlist.modified = Some(time.to_string());
-                    }
+            // catch empty program list
+            if playlist.program.is_empty() {
+                playlist = JsonPlaylist::new(date, start_sec)
+            }
 
-                    let list_clone = playlist.clone();
+            log_playlist_errors(&playlist);
+            playlist.modified = modified;
 
-                    thread::spawn(move || {
-                        validate_playlist(list_clone, is_terminated, config_clone)
-                    });
+            let list_clone = playlist.clone();
 
-                    match config.playlist.infinit {
-                        true => return loop_playlist(config, current_file, playlist),
-                        false => return set_defaults(playlist, current_file, start_sec),
-                    }
-                }
-            }
+            spawn_tagged(move || validate_playlist(list_clone, is_terminated, config_clone));
+
+            return prepare_playlist(config, current_file, playlist, start_sec);
         }
     } else if playlist_path.is_file() {
         let f = File::options()
@@ -212,16 +609,14 @@ pub fn read_json(
             playlist = JsonPlaylist::new(date, start_sec)
         }
 
+        log_playlist_errors(&playlist);
         playlist.modified = modified_time(&current_file);
 
         let list_clone = playlist.clone();
 
-        thread::spawn(move || validate_playlist(list_clone, is_terminated, config_clone));
+        spawn_tagged(move || validate_playlist(list_clone, is_terminated, config_clone));
 
-        match config.playlist.infinit {
-            true => return loop_playlist(config, current_file, playlist),
-            false => return set_defaults(playlist, current_file, start_sec),
-        }
+        return prepare_playlist(config, current_file, playlist, start_sec);
     }
 
     error!("Playlist <b><magenta>{current_file}</></b> not exist!");
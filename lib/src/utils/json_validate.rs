@@ -1,5 +1,7 @@
 use std::{
+    fmt,
     io::{BufRead, BufReader, Error, ErrorKind},
+    path::Path,
     process::{Command, Stdio},
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -7,19 +9,119 @@ use std::{
     },
 };
 
+use serde::Serialize;
 use simplelog::*;
 
 use crate::utils::{
-    loop_image, sec_to_time, seek_and_length, valid_source, vec_strings, JsonPlaylist, Media,
-    OutputMode::Null, PlayoutConfig, FFMPEG_IGNORE_ERRORS, IMAGE_FORMAT,
+    is_remote, loop_image, sec_to_time, seek_and_length, valid_source, vec_strings, JsonPlaylist,
+    LoudnormMode, Media, OutputMode::Null, PlayoutConfig, FFMPEG_IGNORE_ERRORS, IMAGE_FORMAT,
 };
 
+/// Invalid playlist, with the program index and the field of the item.
+///
+/// Errors from reading the json have no index, their message contains the line and column.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlaylistError {
+    pub index: Option<usize>,
+    pub field: String,
+    pub message: String,
+}
+
+impl PlaylistError {
+    fn new(index: Option<usize>, field: &str, message: &str) -> Self {
+        Self {
+            index,
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for PlaylistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.index {
+            Some(i) => write!(f, "program[{i}].{}: {}", self.field, self.message),
+            None => write!(f, "{}: {}", self.field, self.message),
+        }
+    }
+}
+
+/// Problems of all playlist items, not only the first one.
+///
+/// With `check_files` local sources must exist, remote sources are not requested.
+pub fn playlist_errors(playlist: &JsonPlaylist, check_files: bool) -> Vec<PlaylistError> {
+    let mut errors = vec![];
+
+    for (i, item) in playlist.program.iter().enumerate() {
+        if item.source.is_empty() {
+            errors.push(PlaylistError::new(Some(i), "source", "source is missing"));
+        } else if check_files && !is_remote(&item.source) && !Path::new(&item.source).is_file() {
+            errors.push(PlaylistError::new(
+                Some(i),
+                "source",
+                &format!("file {} not exists", item.source),
+            ));
+        }
+
+        if item.duration <= 0.0 {
+            errors.push(PlaylistError::new(
+                Some(i),
+                "duration",
+                "duration must be positive",
+            ));
+        }
+
+        if item.seek < 0.0 {
+            errors.push(PlaylistError::new(
+                Some(i),
+                "in",
+                "in-point must not be negative",
+            ));
+        }
+
+        if item.seek > item.out {
+            errors.push(PlaylistError::new(
+                Some(i),
+                "out",
+                &format!("out-point {} is before in-point {}", item.out, item.seek),
+            ));
+        }
+    }
+
+    errors
+}
+
+/// Check the items of the playlist, see [`playlist_errors`].
+pub fn check_playlist(
+    playlist: JsonPlaylist,
+    check_files: bool,
+) -> Result<JsonPlaylist, Vec<PlaylistError>> {
+    let errors = playlist_errors(&playlist, check_files);
+
+    match errors.is_empty() {
+        true => Ok(playlist),
+        false => Err(errors),
+    }
+}
+
+/// Read the playlist from json and check its items, see [`check_playlist`].
+pub fn parse_playlist(json: &str, check_files: bool) -> Result<JsonPlaylist, Vec<PlaylistError>> {
+    // the serde message ends with the line and column
+    let playlist: JsonPlaylist = serde_json::from_str(json)
+        .map_err(|e| vec![PlaylistError::new(None, "json", &e.to_string())])?;
+
+    check_playlist(playlist, check_files)
+}
+
 /// check if ffmpeg can read the file and apply filter to it.
+///
+/// With `sample` only the first frames are decoded, otherwise the whole clip runs through the filters.
 fn check_media(
     mut node: Media,
     pos: usize,
     begin: f64,
     config: &PlayoutConfig,
+    sample: bool,
 ) -> Result<(), Error> {
     let mut enc_cmd = vec_strings!["-hide_banner", "-nostats", "-v", "level+error"];
     let mut error_list = vec![];
@@ -39,9 +141,11 @@ fn check_media(
         ));
     }
 
-    // take care, that no seek and length command is added.
-    node.seek = 0.0;
-    node.out = node.duration;
+    if sample {
+        // take care, that no seek and length command is added.
+        node.seek = 0.0;
+        node.out = node.duration;
+    }
 
     if node
         .source
@@ -52,7 +156,7 @@ fn check_media(
     {
         node.cmd = Some(loop_image(&node));
     } else {
-        node.cmd = Some(seek_and_length(&node));
+        node.cmd = Some(seek_and_length(&config, &node));
     }
 
     node.add_filter(&config, &None);
@@ -66,9 +170,14 @@ fn check_media(
     enc_cmd.append(&mut node.cmd.unwrap_or_default());
     enc_cmd.append(&mut filter.cmd());
     enc_cmd.append(&mut filter.map());
-    enc_cmd.append(&mut vec_strings!["-t", "0.1", "-f", "null", "-"]);
 
-    let mut enc_proc = match Command::new("ffmpeg")
+    if sample {
+        enc_cmd.append(&mut vec_strings!["-t", "0.1"]);
+    }
+
+    enc_cmd.append(&mut vec_strings!["-f", "null", "-"]);
+
+    let mut enc_proc = match Command::new(&config.processing.ffmpeg_path)
         .args(enc_cmd.clone())
         .stderr(Stdio::piped())
         .spawn()
@@ -93,19 +202,18 @@ fn check_media(
         }
     }
 
-    if !error_list.is_empty() {
-        error!(
-            "<bright black>[Validator]</> ffmpeg error on position <yellow>{pos}</> - {}: <b><magenta>{}</></b>:\n{}",
-            sec_to_time(begin),
-            node.source,
-            error_list.join("\n")
-        )
-    }
-
-    error_list.clear();
+    let status = enc_proc.wait()?;
 
-    if let Err(e) = enc_proc.wait() {
-        error!("Validation process: {e:?}");
+    if !error_list.is_empty() || !status.success() {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!(
+                "<bright black>[Validator]</> ffmpeg error on position <yellow>{pos}</> - {}: <b><magenta>{}</></b>:\n{}",
+                sec_to_time(begin),
+                node.source,
+                error_list.join("\n")
+            ),
+        ));
     }
 
     Ok(())
@@ -130,6 +238,9 @@ pub fn validate_playlist(
         config.text.add_text = false;
     }
 
+    // Measuring loudness is not needed for validation.
+    config.processing.loudnorm_mode = LoudnormMode::Single;
+
     let mut length = config.playlist.length_sec.unwrap();
     let mut begin = config.playlist.start_sec.unwrap();
 
@@ -145,7 +256,7 @@ pub fn validate_playlist(
         let pos = index + 1;
 
         if valid_source(&item.source) {
-            if let Err(e) = check_media(item.clone(), pos, begin, &config) {
+            if let Err(e) = check_media(item.clone(), pos, begin, &config, true) {
                 error!("{e}");
             };
         } else {
@@ -168,3 +279,53 @@ pub fn validate_playlist(
 
     debug!("Validation done...");
 }
+
+/// Run every clip of the playlist through decoder and filters, without encoding.
+///
+/// Unlike [`validate_playlist`] the complete clips are processed, so also logo, subtitles
+/// and loudnorm get checked. Returns the number of failed clips.
+pub fn analyze_playlist(playlist: &JsonPlaylist, mut config: PlayoutConfig) -> usize {
+    let mut begin = config.playlist.start_sec.unwrap();
+    let mut failed = 0;
+
+    if config.text.add_text && !config.text.text_from_filename {
+        config.text.add_text = false;
+    }
+
+    info!(
+        "Analyze playlist from: <yellow>{}</>, with <yellow>{}</> clips",
+        playlist.date,
+        playlist.program.len()
+    );
+
+    for (index, item) in playlist.program.iter().enumerate() {
+        let pos = index + 1;
+
+        if !valid_source(&item.source) {
+            error!(
+                "Source on position <yellow>{pos}</> {} not exists: <b><magenta>\"{}\"</></b>",
+                sec_to_time(begin),
+                item.source
+            );
+            failed += 1;
+        } else if let Err(e) = check_media(item.clone(), pos, begin, &config, false) {
+            error!("{e}");
+            failed += 1;
+        } else {
+            info!(
+                "Position <yellow>{pos}</> {}: <b><magenta>{}</></b> ok",
+                sec_to_time(begin),
+                item.source
+            );
+        }
+
+        begin += item.out - item.seek;
+    }
+
+    info!(
+        "Analyze done, <yellow>{failed}</> of <yellow>{}</> clips failed",
+        playlist.program.len()
+    );
+
+    failed
+}
@@ -0,0 +1,115 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, ErrorKind},
+    path::PathBuf,
+    process::Command,
+};
+
+use simplelog::*;
+
+use crate::utils::{PlayoutConfig, ProcessUnit};
+
+/// Process file of the configured cgroup, only on Linux.
+pub fn cgroup_procs(config: &PlayoutConfig) -> Option<PathBuf> {
+    let cgroup = config.processing.cgroup.trim();
+
+    if !cfg!(target_os = "linux") || cgroup.is_empty() {
+        return None;
+    }
+
+    Some(PathBuf::from(cgroup).join("cgroup.procs"))
+}
+
+/// ffmpeg command of the playout, with the process limits from config.
+pub fn ffmpeg_command(config: &PlayoutConfig) -> Command {
+    let mut cmd = Command::new(&config.processing.ffmpeg_path);
+    apply_limits(&mut cmd, config);
+
+    cmd
+}
+
+/// Error message for a process, which couldn't start.
+///
+/// A missing binary is the common case on a new setup, so it gets a hint to the config.
+pub fn spawn_error(unit: ProcessUnit, bin: &str, e: &io::Error) -> String {
+    match e.kind() {
+        ErrorKind::NotFound if bin == "ffplay" => {
+            format!("ffplay not found on PATH, the {unit} of the desktop output needs it")
+        }
+        ErrorKind::NotFound => format!(
+            "ffmpeg not found on PATH; set processing.ffmpeg_path, couldn't start {unit} with '{bin}'"
+        ),
+        _ => format!("couldn't spawn {unit} process: {e}"),
+    }
+}
+
+/// Set nice level and cgroup in the child, before ffmpeg gets executed.
+///
+/// After the fork only async signal safe calls are allowed, so there is no logging.
+/// A failed call leaves the process without the limit, see [`check_limits`].
+#[cfg(unix)]
+pub fn apply_limits(cmd: &mut Command, config: &PlayoutConfig) {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt, os::unix::process::CommandExt};
+
+    let nice = config.processing.nice;
+    let cgroup = cgroup_procs(config).and_then(|p| CString::new(p.as_os_str().as_bytes()).ok());
+
+    if nice == 0 && cgroup.is_none() {
+        return;
+    }
+
+    unsafe {
+        cmd.pre_exec(move || {
+            if let Some(path) = &cgroup {
+                // pid 0 moves the writing process
+                let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+
+                if fd >= 0 {
+                    libc::write(fd, b"0".as_ptr().cast(), 1);
+                    libc::close(fd);
+                }
+            }
+
+            if nice != 0 {
+                libc::setpriority(libc::PRIO_PROCESS as _, 0, nice);
+            }
+
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub fn apply_limits(_cmd: &mut Command, _config: &PlayoutConfig) {}
+
+/// Warn about process limits, which can't be applied. The playout runs then without them.
+pub fn check_limits(config: &PlayoutConfig) {
+    let nice = config.processing.nice;
+    let cgroup = config.processing.cgroup.trim();
+
+    if !cfg!(unix) && (nice != 0 || !cgroup.is_empty()) {
+        warn!("Process limits 'nice' and 'cgroup' are not supported on this platform, ignore them");
+        return;
+    }
+
+    #[cfg(unix)]
+    if nice < 0 && unsafe { libc::geteuid() } != 0 {
+        warn!("Negative nice level <yellow>{nice}</> needs root, ffmpeg runs maybe with the default priority");
+    }
+
+    if cgroup.is_empty() {
+        return;
+    }
+
+    match cgroup_procs(config) {
+        Some(procs) => {
+            if let Err(e) = OpenOptions::new().write(true).open(&procs) {
+                warn!(
+                    "Cgroup <b><magenta>{}</></b> is not writable, ffmpeg runs outside of it: {e}",
+                    procs.display()
+                );
+            }
+        }
+        None => warn!("Cgroups are only supported on Linux, ignore <b><magenta>{cgroup}</></b>"),
+    }
+}
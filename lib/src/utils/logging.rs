@@ -2,16 +2,19 @@ extern crate log;
 extern crate simplelog;
 
 use std::{
-    path::Path,
+    cell::RefCell,
+    collections::HashMap,
+    io::{self, Write},
+    path::{Path, PathBuf},
     sync::{atomic::Ordering, Arc, Mutex},
-    thread::{self, sleep},
-    time::Duration,
+    thread::{self, sleep, JoinHandle},
+    time::{Duration, Instant},
 };
 
 use chrono::prelude::*;
 use file_rotate::{
     compression::Compression,
-    suffix::{AppendTimestamp, DateFrom, FileLimit},
+    suffix::{AppendTimestamp, DateFrom, FileLimit, SuffixScheme},
     ContentLimit, FileRotate, TimeFrequency,
 };
 use lettre::{
@@ -20,9 +23,52 @@ use lettre::{
 };
 use log::{Level, LevelFilter, Log, Metadata, Record};
 use regex::Regex;
+use serde_json::json;
 use simplelog::*;
 
-use crate::utils::{PlayoutConfig, ProcessControl};
+use crate::utils::{
+    capabilities::{ffprobe_path_set, set_ffprobe_path},
+    config::Logging,
+    LogFormat, PlayoutConfig, ProcessControl, ProcessUnit,
+};
+
+thread_local! {
+    static LOG_CHANNEL: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Tag all log messages from the current thread with the channel name.
+pub fn set_log_channel(channel: &str) {
+    LOG_CHANNEL.with(|c| *c.borrow_mut() = Some(channel.to_string()));
+}
+
+/// Channel name from the current thread.
+pub fn log_channel() -> Option<String> {
+    LOG_CHANNEL.with(|c| c.borrow().clone())
+}
+
+/// Spawn a thread, which logs with the same channel tag like the current one.
+///
+/// It probes also with the same ffprobe binary.
+pub fn spawn_tagged<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let channel = log_channel();
+    let ffprobe = ffprobe_path_set();
+
+    thread::spawn(move || {
+        if let Some(channel) = channel {
+            set_log_channel(&channel);
+        }
+
+        if let Some(ffprobe) = ffprobe {
+            set_ffprobe_path(&ffprobe);
+        }
+
+        f()
+    })
+}
 
 /// send log messages to mail recipient
 pub fn send_mail(cfg: &PlayoutConfig, msg: String) {
@@ -53,7 +99,13 @@ pub fn send_mail(cfg: &PlayoutConfig, msg: String) {
             transporter = SmtpTransport::starttls_relay(cfg.mail.smtp_server.clone().as_str());
         }
 
-        let mailer = transporter.unwrap().credentials(credentials).build();
+        let mut builder = transporter.unwrap().credentials(credentials);
+
+        if let Some(port) = cfg.mail.smtp_port {
+            builder = builder.port(port);
+        }
+
+        let mailer = builder.build();
 
         // Send the mail
         if let Err(e) = mailer.send(&mail) {
@@ -64,6 +116,42 @@ pub fn send_mail(cfg: &PlayoutConfig, msg: String) {
     }
 }
 
+/// Send one alert mail, when a process fails too often in the configured time window.
+///
+/// After sending, no other alert goes out until the cooldown is over.
+pub fn failure_alert(cfg: &PlayoutConfig, proc_ctl: &ProcessControl, unit: ProcessUnit) {
+    if !(cfg.mail.recipient.contains('@') && cfg.mail.recipient.contains('.')) {
+        return;
+    }
+
+    let mut failures = proc_ctl.failures.lock().unwrap();
+    let count = failures.push(unit, Duration::from_secs(cfg.mail.alert_window));
+
+    if count <= cfg.mail.alert_failures
+        || failures
+            .last_alert
+            .is_some_and(|t| t.elapsed() < Duration::from_secs(cfg.mail.alert_cooldown))
+    {
+        return;
+    }
+
+    failures.last_alert = Some(Instant::now());
+
+    let msg = format!(
+        "{unit} failed {count} times in the last {} seconds.\n\nLast errors:\n{}",
+        cfg.mail.alert_window,
+        proc_ctl.last_errors(unit).join("\n")
+    );
+
+    drop(failures);
+
+    let mut alert_cfg = cfg.clone();
+    alert_cfg.mail.subject = format!("{} - {unit} keeps failing", cfg.mail.subject);
+
+    // don't block the calling process loop
+    thread::spawn(move || send_mail(&alert_cfg, msg));
+}
+
 /// Basic Mail Queue
 ///
 /// Check every give seconds for messages and send them.
@@ -154,6 +242,237 @@ impl SharedLogger for LogMailer {
     }
 }
 
+/// Logger wrapper, which prefix every message with the channel tag from the thread.
+pub struct ChannelTag {
+    inner: Box<dyn SharedLogger>,
+}
+
+impl ChannelTag {
+    pub fn new(inner: Box<dyn SharedLogger>) -> Box<ChannelTag> {
+        Box::new(ChannelTag { inner })
+    }
+}
+
+impl Log for ChannelTag {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        match log_channel() {
+            Some(channel) => self.inner.log(
+                &Record::builder()
+                    .args(format_args!(
+                        "<bright black>[{channel}]</> {}",
+                        record.args()
+                    ))
+                    .metadata(record.metadata().clone())
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .build(),
+            ),
+            None => self.inner.log(record),
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+impl SharedLogger for ChannelTag {
+    fn level(&self) -> LevelFilter {
+        self.inner.level()
+    }
+
+    fn config(&self) -> Option<&Config> {
+        self.inner.config()
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+type LoggerFactory = Box<dyn Fn(&str) -> Box<dyn SharedLogger> + Send + Sync>;
+
+/// Route the records to one logger per channel, the loggers are created on the first record.
+///
+/// Records without channel tag go to the logger with the empty name.
+pub struct ChannelFiles {
+    level: LevelFilter,
+    config: Config,
+    factory: LoggerFactory,
+    loggers: Mutex<HashMap<String, Box<dyn SharedLogger>>>,
+}
+
+impl ChannelFiles {
+    pub fn new(
+        log_level: LevelFilter,
+        config: Config,
+        factory: LoggerFactory,
+    ) -> Box<ChannelFiles> {
+        Box::new(ChannelFiles {
+            level: log_level,
+            config,
+            factory,
+            loggers: Mutex::new(HashMap::new()),
+        })
+    }
+}
+
+impl Log for ChannelFiles {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let channel = log_channel().unwrap_or_default();
+        let mut loggers = self.loggers.lock().unwrap();
+
+        loggers
+            .entry(channel)
+            .or_insert_with_key(|c| (self.factory)(c))
+            .log(record);
+    }
+
+    fn flush(&self) {
+        for logger in self.loggers.lock().unwrap().values() {
+            logger.flush();
+        }
+    }
+}
+
+impl SharedLogger for ChannelFiles {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&Config> {
+        Some(&self.config)
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+/// Log file for the channel, it lives next to the main log file.
+pub fn channel_log_path(log_path: &str, channel: &str) -> String {
+    if channel.is_empty() {
+        return log_path.to_string();
+    }
+
+    let name: String = channel
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+
+    Path::new(log_path)
+        .with_file_name(format!("{name}.log"))
+        .display()
+        .to_string()
+}
+
+/// Json logger, writes every record as one line with: ts, level, target, msg and channel.
+///
+/// The `ts` is in local time only with `local_time`, like the time in the text logger.
+pub struct JsonLogger<W: Write + Send + 'static> {
+    level: LevelFilter,
+    config: Config,
+    channel: String,
+    local_time: bool,
+    writable: Mutex<W>,
+}
+
+impl<W: Write + Send + 'static> JsonLogger<W> {
+    pub fn new(
+        log_level: LevelFilter,
+        config: Config,
+        channel: String,
+        local_time: bool,
+        writable: W,
+    ) -> Box<JsonLogger<W>> {
+        Box::new(JsonLogger {
+            level: log_level,
+            config,
+            channel,
+            local_time,
+            writable: Mutex::new(writable),
+        })
+    }
+}
+
+impl<W: Write + Send + 'static> Log for JsonLogger<W> {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        // same ignore list like in the text logger config
+        metadata.level() <= self.level
+            && !["hyper", "sqlx", "reqwest", "rpc"]
+                .iter()
+                .any(|t| metadata.target().contains(t))
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            let mut writer = self.writable.lock().unwrap();
+
+            let channel = log_channel().unwrap_or_else(|| self.channel.clone());
+
+            if let Err(e) = writeln!(writer, "{}", json_line(record, &channel, self.local_time)) {
+                eprintln!("Write json log failed: {e}");
+            }
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self.writable.lock().unwrap().flush();
+    }
+}
+
+impl<W: Write + Send + 'static> SharedLogger for JsonLogger<W> {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&Config> {
+        Some(&self.config)
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+/// Serialize log record to a json string, without color markup.
+pub fn json_line(record: &Record<'_>, channel: &str, local_time: bool) -> String {
+    let ts = if local_time {
+        Local::now().to_rfc3339_opts(SecondsFormat::Millis, false)
+    } else {
+        Utc::now().to_rfc3339_opts(SecondsFormat::Millis, false)
+    };
+
+    json!({
+        "ts": ts,
+        "level": record.level().to_string(),
+        "target": record.target(),
+        "msg": strip_markup(&record.args().to_string()),
+        "channel": channel,
+    })
+    .to_string()
+}
+
+/// Remove paris color tags, like `<b><magenta>text</></b>`, and terminal colors.
+pub fn strip_markup(text: &str) -> String {
+    let regex = Regex::new(
+        r"</?(b|i|u|dimmed|((bright|on)[ -])?(black|red|green|yellow|blue|magenta|cyan|white))?>",
+    )
+    .unwrap();
+
+    clean_string(&regex.replace_all(text, ""))
+}
+
 /// Workaround to remove color information from log
 ///
 /// ToDo: maybe in next version from simplelog this is not necessary anymore.
@@ -163,6 +482,86 @@ fn clean_string(text: &str) -> String {
     regex.replace_all(text, "").to_string()
 }
 
+/// Numbered backups, like `ffplayout.log.1`, which get removed by count or by age.
+struct AppendCountAge {
+    path: PathBuf,
+    max_files: usize,
+    max_age: Option<Duration>,
+}
+
+impl SuffixScheme for AppendCountAge {
+    type Repr = usize;
+
+    fn rotate_file(
+        &mut self,
+        _basepath: &Path,
+        _: Option<&usize>,
+        suffix: &Option<usize>,
+    ) -> io::Result<usize> {
+        Ok(suffix.map_or(1, |s| s + 1))
+    }
+
+    fn parse(&self, suffix: &str) -> Option<usize> {
+        suffix.parse().ok()
+    }
+
+    fn too_old(&self, suffix: &usize, file_number: usize) -> bool {
+        if file_number >= self.max_files {
+            return true;
+        }
+
+        let Some(max_age) = self.max_age else {
+            return false;
+        };
+
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{suffix}"));
+
+        std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age > max_age)
+    }
+}
+
+/// Log file, which rotates by size when `rotate.max_size_mb` is set, otherwise it rotates daily.
+///
+/// On size rotation backups are also removed, when they are older then `rotate.max_age_days`.
+/// The loggers lock the writer, so it is safe to log from all threads.
+pub fn log_file(path: &str, config: &Logging) -> Box<dyn Write + Send> {
+    if config.rotate.max_size_mb > 0 {
+        let scheme = AppendCountAge {
+            path: PathBuf::from(path),
+            max_files: config.rotate.max_files,
+            max_age: (config.rotate.max_age_days > 0)
+                .then(|| Duration::from_secs(config.rotate.max_age_days * 86400)),
+        };
+
+        return Box::new(FileRotate::new(
+            path,
+            scheme,
+            ContentLimit::BytesSurpassed(config.rotate.max_size_mb as usize * 1024 * 1024),
+            Compression::None,
+            #[cfg(unix)]
+            None,
+        ));
+    }
+
+    Box::new(FileRotate::new(
+        path,
+        AppendTimestamp::with_format(
+            "%Y-%m-%d",
+            FileLimit::MaxFiles(config.backup_count),
+            DateFrom::DateYesterday,
+        ),
+        ContentLimit::Time(TimeFrequency::Daily),
+        Compression::None,
+        #[cfg(unix)]
+        None,
+    ))
+}
+
 /// Initialize our logging, to have:
 ///
 /// - console logger
@@ -193,9 +592,15 @@ pub fn init_logging(
         .set_time_level(time_level)
         .clone();
 
+    // without a local offset the text logger stays on utc, the json logger follows it
+    let mut local_time = false;
+
     if app_config.local_time {
         log_config = match log_config.set_time_offset_to_local() {
-            Ok(local) => local.clone(),
+            Ok(local) => {
+                local_time = true;
+                local.clone()
+            }
             Err(_) => log_config,
         };
     };
@@ -215,25 +620,49 @@ pub fn init_logging(
                 .display()
                 .to_string();
         } else if Path::new(&app_config.path).is_file() {
-            log_path = app_config.path
+            log_path = app_config.path.clone()
         } else {
             println!("Logging path not exists!")
         }
 
-        let log_file = FileRotate::new(
-            log_path,
-            AppendTimestamp::with_format(
-                "%Y-%m-%d",
-                FileLimit::MaxFiles(app_config.backup_count),
-                DateFrom::DateYesterday,
-            ),
-            ContentLimit::Time(TimeFrequency::Daily),
-            Compression::None,
-            #[cfg(unix)]
-            None,
-        );
+        let level = app_config.level;
+        let main_channel = config.general.channel.clone();
+        let logging = app_config.clone();
+        let logger_config = file_config.clone();
+
+        let factory = move |channel: &str| -> Box<dyn SharedLogger> {
+            let log_file = log_file(&channel_log_path(&log_path, channel), &logging);
+
+            if logging.format == LogFormat::Json {
+                let name = Some(channel)
+                    .filter(|c| !c.is_empty())
+                    .unwrap_or(&main_channel);
+
+                JsonLogger::new(
+                    level,
+                    logger_config.clone(),
+                    name.to_string(),
+                    local_time,
+                    log_file,
+                )
+            } else {
+                ChannelTag::new(WriteLogger::new(level, logger_config.clone(), log_file))
+            }
+        };
 
-        app_logger.push(WriteLogger::new(app_config.level, file_config, log_file));
+        if app_config.per_channel_files {
+            app_logger.push(ChannelFiles::new(level, file_config, Box::new(factory)));
+        } else {
+            app_logger.push(factory(""));
+        }
+    } else if app_config.format == LogFormat::Json {
+        app_logger.push(JsonLogger::new(
+            app_config.level,
+            log_config.build(),
+            config.general.channel.clone(),
+            local_time,
+            io::stdout(),
+        ));
     } else {
         let term_config = log_config
             .clone()
@@ -246,12 +675,12 @@ pub fn init_logging(
             ))
             .build();
 
-        app_logger.push(TermLogger::new(
+        app_logger.push(ChannelTag::new(TermLogger::new(
             app_config.level,
             term_config,
             TerminalMode::Mixed,
             ColorChoice::Auto,
-        ));
+        )));
     }
 
     // set mail logger only the recipient is set in config
@@ -271,7 +700,11 @@ pub fn init_logging(
             _ => LevelFilter::Error,
         };
 
-        app_logger.push(LogMailer::new(filter, mail_config, messages.unwrap()));
+        app_logger.push(ChannelTag::new(LogMailer::new(
+            filter,
+            mail_config,
+            messages.unwrap(),
+        )));
     }
 
     app_logger
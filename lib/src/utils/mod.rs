@@ -1,19 +1,21 @@
 use std::{
+    collections::HashMap,
     ffi::OsStr,
     fs::{self, metadata, File},
-    io::{BufRead, BufReader, Error},
+    io::{self, BufRead, BufReader, Error, ErrorKind},
     net::TcpListener,
     path::{Path, PathBuf},
     process::{exit, ChildStderr, Command, Stdio},
-    sync::{Arc, Mutex},
-    time::{self, UNIX_EPOCH},
+    sync::{atomic::Ordering, Arc, Mutex, OnceLock},
+    thread,
+    time::{self, SystemTime, UNIX_EPOCH},
 };
 
 #[cfg(not(windows))]
 use std::env;
 
 use chrono::{prelude::*, Duration};
-use ffprobe::{ffprobe, Format, Stream};
+use ffprobe::{ffprobe_config, Config, Format, Stream};
 use jsonrpc_http_server::hyper::HeaderMap;
 use rand::prelude::*;
 use regex::Regex;
@@ -22,6 +24,7 @@ use serde::{de::Deserializer, Deserialize, Serialize};
 use serde_json::json;
 use simplelog::*;
 
+mod capabilities;
 pub mod config;
 pub mod controller;
 pub mod folder;
@@ -29,32 +32,62 @@ mod generator;
 pub mod import;
 pub mod json_serializer;
 mod json_validate;
+mod limits;
 mod logging;
 
 #[cfg(windows)]
 mod windows;
 
+pub use capabilities::{
+    check_ffmpeg_version, ffmpeg_capabilities, ffmpeg_version, ffprobe_path, missing_capabilities,
+    parse_ffmpeg_version, parse_filters, parse_libs, parse_protocols, set_ffprobe_path,
+    FfmpegCapabilities, MIN_FFMPEG_VERSION,
+};
 pub use config::{
-    self as playout_config,
+    self as playout_config, abr_output_cmd, archive_output, bit_depth, bitrate_args,
+    bitrate_output_cmd, bitrate_value, check_logo_filter, container_output_cmd, dash_args,
+    dash_output_cmd, denied_extra_args, eight_bit_encoder, extra_args_cmd, global_header_cmd,
+    gop_size, hls_args, hls_output_cmd, hls_time, intermediate_codec, keyframe_interval,
+    target_args, target_output_cmd, tee_target, Backpressure, CensorMode, CensorRegion, ColorRange,
+    ConfigError, Container, DecoderMode, Deinterlace, EncoderFallback, FallbackStep, FitMode,
+    FolderWeight, FpsConvert, HwAccel, IngestMode, LiveSettings, LogFormat, Logo, LogoEnable,
+    LoudnormMode, Multicast, MulticastProtocol, OutputMetadata,
     OutputMode::{self, *},
-    PlayoutConfig,
+    Pip, PlaylistMode, PlayoutConfig,
     ProcessMode::{self, *},
-    DUMMY_LEN, FFMPEG_IGNORE_ERRORS, FFMPEG_UNRECOVERABLE_ERRORS, IMAGE_FORMAT,
+    Rendition, SeekMode, SourceResolver, TeeOutput, TimestampMode, DUMMY_LEN, EIGHT_BIT_ENCODERS,
+    EXTRA_ARGS_DENYLIST, FFMPEG_IGNORE_ERRORS, FFMPEG_UNRECOVERABLE_ERRORS, IMAGE_FORMAT,
+    REMOTE_TIMEOUT,
 };
 pub use controller::{
-    PlayerControl, PlayoutStatus, ProcessControl,
+    child_reaper, ChannelStatus, EncoderStats, IngestPackage, LatencyWindow, PlayerControl,
+    PlayoutStatus, ProcessControl, ProcessFailures,
     ProcessUnit::{self, *},
+    StatusState,
 };
 pub use generator::generate_playlist;
-pub use json_serializer::{read_json, JsonPlaylist};
-pub use json_validate::validate_playlist;
-pub use logging::{init_logging, send_mail};
+pub use json_serializer::{
+    is_stdin_playlist, playlist_file, playlist_from_reader, read_json, remote_cache,
+    splice_bumpers, write_playlist, JsonPlaylist, STDIN_PLAYLIST,
+};
+pub use json_validate::{
+    analyze_playlist, check_playlist, parse_playlist, playlist_errors, validate_playlist,
+    PlaylistError,
+};
+pub use limits::{apply_limits, cgroup_procs, check_limits, ffmpeg_command, spawn_error};
+pub use logging::{
+    channel_log_path, failure_alert, init_logging, json_line, log_channel, log_file, send_mail,
+    set_log_channel, spawn_tagged, strip_markup, ChannelTag,
+};
 
 use crate::{
     filter::{filter_chains, Filters},
     vec_strings,
 };
 
+/// Seconds, which accurate seek mode decodes before the in-point.
+const ACCURATE_SEEK_OFFSET: f64 = 3.0;
+
 /// Video clip struct to hold some important states and comments for current media.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Media {
@@ -74,6 +107,14 @@ pub struct Media {
         skip_serializing_if = "is_empty_string"
     )]
     pub category: String,
+
+    #[serde(
+        default,
+        deserialize_with = "null_string",
+        skip_serializing_if = "is_empty_string"
+    )]
+    pub title: String,
+
     #[serde(deserialize_with = "null_string")]
     pub source: String,
 
@@ -93,6 +134,12 @@ pub struct Media {
     #[serde(default, skip_serializing_if = "is_empty_string")]
     pub custom_filter: String,
 
+    #[serde(default, skip_serializing_if = "is_empty_string")]
+    pub source_headers: String,
+
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub audio_gain_db: f64,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub probe: Option<MediaProbe>,
 
@@ -133,11 +180,14 @@ impl Media {
             out: duration,
             duration,
             category: String::new(),
+            title: String::new(),
             source: src.to_string(),
             audio: String::new(),
             cmd: Some(vec_strings!["-i", src]),
             filter: None,
             custom_filter: String::new(),
+            source_headers: String::new(),
+            audio_gain_db: 0.0,
             probe,
             last_ad: Some(false),
             next_ad: Some(false),
@@ -146,6 +196,18 @@ impl Media {
         }
     }
 
+    /// Title from the playlist, or the file name without extension.
+    pub fn display_title(&self) -> String {
+        if !self.title.is_empty() {
+            return self.title.clone();
+        }
+
+        Path::new(&self.source)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
     pub fn add_probe(&mut self) {
         if self.probe.is_none() {
             let probe = MediaProbe::new(&self.source);
@@ -185,6 +247,9 @@ impl PartialEq for Media {
             && self.category == other.category
             && self.audio == other.audio
             && self.custom_filter == other.custom_filter
+            && self.source_headers == other.source_headers
+            && self.audio_gain_db == other.audio_gain_db
+            && self.title == other.title
     }
 }
 
@@ -202,6 +267,10 @@ fn is_empty_string(st: &String) -> bool {
     *st == String::new()
 }
 
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
 /// We use the ffprobe crate, but we map the metadata to our needs.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct MediaProbe {
@@ -212,7 +281,7 @@ pub struct MediaProbe {
 
 impl MediaProbe {
     pub fn new(input: &str) -> Self {
-        let probe = ffprobe(input);
+        let probe = ffprobe_config(Config::builder().ffprobe_bin(ffprobe_path()).build(), input);
         let mut a_stream = vec![];
         let mut v_stream = vec![];
 
@@ -344,16 +413,26 @@ pub fn time_from_header(headers: &HeaderMap) -> Option<DateTime<Local>> {
     None
 }
 
-/// Get file modification time.
+/// Get ETag from header, or the last modification time when no ETag exists.
+pub fn remote_validator(headers: &HeaderMap) -> Option<String> {
+    if let Some(etag) = headers.get(header::ETAG).and_then(|e| e.to_str().ok()) {
+        return Some(etag.to_string());
+    }
+
+    time_from_header(headers).map(|t| t.to_string())
+}
+
+/// Get file modification time, for remote files the ETag is preferred.
 pub fn modified_time(path: &str) -> Option<String> {
     if is_remote(path) {
-        let response = reqwest::blocking::Client::new().head(path).send();
+        let response = reqwest::blocking::Client::builder()
+            .timeout(REMOTE_TIMEOUT)
+            .build()
+            .and_then(|client| client.head(path).send());
 
         if let Ok(resp) = response {
             if resp.status().is_success() {
-                if let Some(time) = time_from_header(resp.headers()) {
-                    return Some(time.to_string());
-                }
+                return remote_validator(resp.headers());
             }
         }
 
@@ -450,6 +529,60 @@ pub fn check_sync(config: &PlayoutConfig, delta: f64) -> bool {
     true
 }
 
+/// Thread limits for the ffmpeg instances, 0 let ffmpeg decide.
+///
+/// Before the input they are for decoding, after the input for encoding.
+pub fn threads_cmd(config: &PlayoutConfig) -> Vec<String> {
+    let mut cmd = vec![];
+
+    if config.processing.ffmpeg_threads > 0 {
+        cmd.append(&mut vec_strings![
+            "-threads",
+            config.processing.ffmpeg_threads
+        ]);
+    }
+
+    if config.processing.filter_threads > 0 {
+        cmd.append(&mut vec_strings![
+            "-filter_threads",
+            config.processing.filter_threads
+        ]);
+    }
+
+    cmd
+}
+
+/// Input parameters for hardware acceleration, they need to be placed before the input.
+pub fn hw_input_cmd(config: &PlayoutConfig) -> Vec<String> {
+    match config.processing.hwaccel {
+        HwAccel::None => vec![],
+        HwAccel::Nvenc => vec_strings![
+            "-init_hw_device",
+            "cuda=hw",
+            "-filter_hw_device",
+            "hw",
+            "-hwaccel",
+            "cuda"
+        ],
+        HwAccel::Vaapi => vec_strings![
+            "-init_hw_device",
+            "vaapi=hw:/dev/dri/renderD128",
+            "-filter_hw_device",
+            "hw",
+            "-hwaccel",
+            "vaapi"
+        ],
+        HwAccel::Qsv => vec_strings![
+            "-init_hw_device",
+            "qsv=hw",
+            "-filter_hw_device",
+            "hw",
+            "-hwaccel",
+            "qsv"
+        ],
+    }
+}
+
 /// Loop image until target duration is reached.
 pub fn loop_image(node: &Media) -> Vec<String> {
     let duration = node.out - node.seek;
@@ -490,21 +623,24 @@ pub fn loop_filler(node: &Media) -> Vec<String> {
 }
 
 /// Set clip seek in and length value.
-pub fn seek_and_length(node: &Media) -> Vec<String> {
+pub fn seek_and_length(config: &PlayoutConfig, node: &Media) -> Vec<String> {
     let mut source_cmd = vec![];
     let mut cut_audio = false;
+    let (input_seek, output_seek) = seek_points(config, node.seek);
 
-    if node.seek > 0.5 {
-        source_cmd.append(&mut vec_strings!["-ss", node.seek])
+    if input_seek > 0.0 {
+        source_cmd.append(&mut vec_strings!["-ss", input_seek])
     }
 
+    source_cmd.append(&mut reconnect_cmd(config, &node.source));
+    source_cmd.append(&mut headers_cmd(node));
     source_cmd.append(&mut vec_strings!["-i", node.source.clone()]);
 
     if Path::new(&node.audio).is_file() {
         let audio_probe = MediaProbe::new(&node.audio);
 
-        if node.seek > 0.5 {
-            source_cmd.append(&mut vec_strings!["-ss", node.seek])
+        if input_seek > 0.0 {
+            source_cmd.append(&mut vec_strings!["-ss", input_seek])
         }
 
         source_cmd.append(&mut vec_strings!["-i", node.audio.clone()]);
@@ -520,6 +656,10 @@ pub fn seek_and_length(node: &Media) -> Vec<String> {
         }
     }
 
+    if output_seek > 0.0 {
+        source_cmd.append(&mut vec_strings!["-ss", output_seek])
+    }
+
     if node.duration > node.out || cut_audio {
         source_cmd.append(&mut vec_strings!["-t", node.out - node.seek]);
     }
@@ -527,6 +667,123 @@ pub fn seek_and_length(node: &Media) -> Vec<String> {
     source_cmd
 }
 
+/// Keyframes of the scanned clips, with the modification time of the file at the scan.
+type KeyframeCache = HashMap<String, (SystemTime, Arc<Vec<f64>>)>;
+
+static KEYFRAMES: OnceLock<Mutex<KeyframeCache>> = OnceLock::new();
+
+/// Timestamps of the video keyframes, from the packet flags of ffprobe.
+///
+/// Only the packets are read, so it runs fast also for long clips. The result is kept
+/// until the file changes, so a playlist reload doesn't scan every clip again.
+pub fn keyframes(source: &str) -> Arc<Vec<f64>> {
+    let cache = KEYFRAMES.get_or_init(|| Mutex::new(HashMap::new()));
+    let modified = metadata(source).and_then(|m| m.modified()).ok();
+
+    if let Some((time, frames)) = cache.lock().unwrap().get(source) {
+        if Some(*time) == modified {
+            return frames.clone();
+        }
+    }
+
+    let Some(frames) = scan_keyframes(source).map(Arc::new) else {
+        return Arc::new(vec![]);
+    };
+
+    if let Some(time) = modified {
+        cache
+            .lock()
+            .unwrap()
+            .insert(source.to_string(), (time, frames.clone()));
+    }
+
+    frames
+}
+
+fn scan_keyframes(source: &str) -> Option<Vec<f64>> {
+    let output = match Command::new(ffprobe_path())
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "packet=pts_time,flags",
+            "-of",
+            "csv=p=0",
+            source,
+        ])
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(o) => o,
+        Err(e) => {
+            error!("Unable to read keyframes from <b><magenta>{source}</></b>: {e}");
+            return None;
+        }
+    };
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let mut frames: Vec<f64> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| l.split_once(','))
+        .filter(|(_, flags)| flags.starts_with('K'))
+        .filter_map(|(pts, _)| pts.parse::<f64>().ok())
+        .collect();
+
+    // packets are in decoding order
+    frames.sort_by(|a, b| a.total_cmp(b));
+    Some(frames)
+}
+
+/// Move the in- and out-point of a clip to the nearest keyframe.
+///
+/// Clip begin and end are already clean cuts, they stay. A out-point, which would
+/// not be after the in-point anymore, stays too.
+pub fn snap_to_keyframe(keyframes: &[f64], seek: f64, out: f64, duration: f64) -> (f64, f64) {
+    let nearest = |time: f64, after: f64| {
+        keyframes
+            .iter()
+            .copied()
+            .filter(|k| *k > after)
+            .min_by(|a, b| (a - time).abs().total_cmp(&(b - time).abs()))
+    };
+
+    let mut new_seek = seek;
+    let mut new_out = out;
+
+    if seek > 0.0 {
+        new_seek = nearest(seek, -1.0).unwrap_or(seek);
+    }
+
+    if out < duration {
+        new_out = nearest(out, new_seek).unwrap_or(out);
+    }
+
+    (new_seek, new_out)
+}
+
+/// Split the in-point in a input and output seek.
+///
+/// Fast mode seeks only on the input, accurate mode seeks on the input to a point some
+/// seconds before and decodes the rest, so the clip starts on the exact frame.
+fn seek_points(config: &PlayoutConfig, seek: f64) -> (f64, f64) {
+    if seek <= 0.5 {
+        return (0.0, 0.0);
+    }
+
+    match config.processing.seek_mode {
+        SeekMode::Fast => (seek, 0.0),
+        SeekMode::Accurate if seek > ACCURATE_SEEK_OFFSET => {
+            (seek - ACCURATE_SEEK_OFFSET, ACCURATE_SEEK_OFFSET)
+        }
+        SeekMode::Accurate => (0.0, seek),
+    }
+}
+
 /// Create a dummy clip as a placeholder for missing video files.
 pub fn gen_dummy(config: &PlayoutConfig, duration: f64) -> (String, Vec<String>) {
     let color = "#121212";
@@ -573,6 +830,199 @@ pub fn is_remote(path: &str) -> bool {
     Regex::new(r"^https?://.*").unwrap().is_match(path)
 }
 
+/// Keep the source timestamps in the copyts mode.
+pub fn timestamp_cmd(config: &PlayoutConfig) -> Vec<String> {
+    match config.processing.timestamp {
+        TimestampMode::Copyts => vec_strings!["-copyts"],
+        _ => vec![],
+    }
+}
+
+/// Reconnect options for remote sources.
+///
+/// This options are from the http protocol, ffmpeg rejects them for local files and other protocols.
+pub fn reconnect_cmd(config: &PlayoutConfig, source: &str) -> Vec<String> {
+    let delay = config.processing.input_reconnect;
+
+    if delay == 0 || !is_remote(source) {
+        return vec![];
+    }
+
+    vec_strings![
+        "-reconnect",
+        "1",
+        "-reconnect_streamed",
+        "1",
+        "-reconnect_delay_max",
+        delay
+    ]
+}
+
+/// Live sources, which ffmpeg can't read faster than realtime anyway.
+pub fn is_live(source: &str) -> bool {
+    Regex::new(r"^(rtmps?|rtsp|srt|udp|rtp)://.*")
+        .unwrap()
+        .is_match(source)
+}
+
+/// Add the input flags, `-re` for file inputs, when `processing.realtime_read` is on,
+/// and `-fflags +genpts` for the genpts timestamp mode.
+///
+/// The flags belong to the input, so they go in front of the options of every `-i`.
+pub fn input_flags_cmd(config: &PlayoutConfig, input: Vec<String>) -> Vec<String> {
+    let genpts = config.processing.timestamp == TimestampMode::Genpts;
+
+    if !config.processing.realtime_read && !genpts {
+        return input;
+    }
+
+    let mut cmd = vec![];
+    let mut group = vec![];
+    let mut args = input.into_iter();
+
+    while let Some(arg) = args.next() {
+        let is_input = arg == "-i";
+        group.push(arg);
+
+        if is_input {
+            if let Some(source) = args.next() {
+                if config.processing.realtime_read && !is_live(&source) {
+                    cmd.push("-re".to_string());
+                }
+
+                if genpts {
+                    cmd.append(&mut vec_strings!["-fflags", "+genpts"]);
+                }
+
+                group.push(source);
+            }
+
+            cmd.append(&mut group);
+        }
+    }
+
+    cmd.append(&mut group);
+
+    cmd
+}
+
+/// Quote the command for a shell, so it can be copied and run.
+pub fn quote_cmd(program: &str, args: &[String]) -> String {
+    let mut line = program.to_string();
+
+    for arg in args {
+        line.push(' ');
+        line.push_str(&shlex::quote(arg));
+    }
+
+    line
+}
+
+/// Hide secrets in urls and commands, before they get logged.
+pub fn hide_secrets(text: &str) -> String {
+    // request headers from the playlist can contain credentials, their values are hidden together
+    let headers = Regex::new(r"(-headers )(?s:.+?)\r\n( -|$)").unwrap();
+    let auth = Regex::new(r"(?i)(authorization:\s*)[^\r\n']*").unwrap();
+    // like the srt passphrase, or the signature and token of presigned urls
+    let query =
+        Regex::new(r"(?i)((?:passphrase|token|sig|signature|credential)=)[^&\s']*").unwrap();
+
+    let text = headers.replace_all(text, "${1}*****${2}");
+    let text = auth.replace_all(&text, "${1}*****");
+
+    query.replace_all(&text, "${1}*****").to_string()
+}
+
+/// Request headers for authenticated remote sources, they need to stand before the input.
+///
+/// Multiple headers are separated by new lines in the playlist, ffmpeg wants them CRLF terminated.
+pub fn headers_cmd(node: &Media) -> Vec<String> {
+    if node.source_headers.trim().is_empty() || !is_remote(&node.source) {
+        return vec![];
+    }
+
+    let headers: String = node
+        .source_headers
+        .lines()
+        .map(str::trim)
+        .filter(|h| !h.is_empty())
+        .map(|h| format!("{h}\r\n"))
+        .collect();
+
+    vec_strings!["-headers", headers]
+}
+
+/// Time a resolver command has, to print the source.
+pub const RESOLVER_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+/// Rewrite the source with the resolver for its scheme, like a `s3://` path into a presigned URL.
+///
+/// The resolver command gets the source as `{source}` placeholder and has to print the new source to stdout.
+/// Sources without matching resolver stay untouched.
+pub fn resolve_source(config: &PlayoutConfig, source: &str) -> Result<String, String> {
+    let resolver = match config
+        .storage
+        .resolvers
+        .iter()
+        .find(|r| source.starts_with(&format!("{}://", r.scheme)))
+    {
+        Some(r) => r,
+        None => return Ok(source.to_string()),
+    };
+
+    let args: Vec<String> = shlex::split(&resolver.command)
+        .unwrap_or_default()
+        .iter()
+        .map(|a| a.replace("{source}", source))
+        .collect();
+
+    if args.is_empty() {
+        return Err(format!(
+            "Empty resolver command for scheme <b><magenta>{}</></b>",
+            resolver.scheme
+        ));
+    }
+
+    let mut child = Command::new(&args[0])
+        .args(&args[1..])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Resolver <b><magenta>{}</></b> failed: {e}", args[0]))?;
+    let start = time::Instant::now();
+
+    // a hanging resolver would block the playout, so it gets killed after the timeout
+    while child.try_wait().map_err(|e| e.to_string())?.is_none() {
+        if start.elapsed() > RESOLVER_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+
+            return Err(format!(
+                "Resolver for <b><magenta>{source}</></b> got no result in {} seconds",
+                RESOLVER_TIMEOUT.as_secs()
+            ));
+        }
+
+        thread::sleep(time::Duration::from_millis(20));
+    }
+
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Resolver for <b><magenta>{source}</></b> failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "" => Err(format!(
+            "Resolver for <b><magenta>{source}</></b> returned no source"
+        )),
+        resolved => Ok(resolved.to_string()),
+    }
+}
+
 /// Validate input
 ///
 /// Check if input is a remote source, or from storage and see if it exists.
@@ -630,6 +1080,184 @@ pub fn include_file(config: PlayoutConfig, file_path: &Path) -> bool {
     include
 }
 
+/// Time with a encoding speed under 1.0, before a warning is logged.
+const SLOW_SPEED_WINDOW: time::Duration = time::Duration::from_secs(30);
+
+/// Map the level tag from a ffmpeg line to the log level, the tag is removed from the message.
+///
+/// Needs the `+level` flag in the ffmpeg loglevel, lines without known tag are debug messages.
+pub fn ffmpeg_level(line: &str) -> (Level, String) {
+    let levels = [
+        ("[fatal] ", Level::Error),
+        ("[error] ", Level::Error),
+        ("[warning] ", Level::Warn),
+        ("[info] ", Level::Info),
+    ];
+
+    for (tag, level) in levels {
+        if line.contains(tag) {
+            return (level, line.replacen(tag, "", 1));
+        }
+    }
+
+    let msg = Regex::new(r"\[(verbose|debug|trace)\] ")
+        .unwrap()
+        .replace(line, "");
+
+    (Level::Debug, msg.to_string())
+}
+
+/// Longest kept stderr line, the rest of a longer line is dropped.
+pub const MAX_LINE_LEN: usize = 16 * 1024;
+
+/// Lines from ffmpeg stderr.
+///
+/// They end with a new line, or with a carriage return like the stats lines,
+/// empty lines are skipped.
+pub struct FfmpegLines<R> {
+    reader: R,
+}
+
+impl<R: BufRead> FfmpegLines<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+}
+
+impl<R: BufRead> Iterator for FfmpegLines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = vec![];
+
+        loop {
+            let buf = match self.reader.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if buf.is_empty() {
+                return (!line.is_empty()).then(|| Ok(String::from_utf8_lossy(&line).into()));
+            }
+
+            let end = buf.iter().position(|b| *b == b'\n' || *b == b'\r');
+            let len = end.unwrap_or(buf.len());
+            let room = MAX_LINE_LEN.saturating_sub(line.len());
+
+            line.extend_from_slice(&buf[..len.min(room)]);
+            self.reader.consume(end.map_or(len, |e| e + 1));
+
+            if end.is_some() && !line.is_empty() {
+                return Some(Ok(String::from_utf8_lossy(&line).into()));
+            }
+        }
+    }
+}
+
+/// Size of a mpegts packet, the decoder and the ingest server write mpegts.
+pub const TS_PACKET_SIZE: usize = 188;
+
+/// Whole mpegts packets from the reads of a pipe.
+///
+/// A read can end in the middle of a packet, the rest waits for the next read.
+/// So a dropped chunk never cuts a packet and the stream stays in sync.
+#[derive(Debug, Default)]
+pub struct TsPackets {
+    rest: Vec<u8>,
+}
+
+impl TsPackets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The whole packets of the rest and the new data, can be empty.
+    pub fn push(&mut self, data: &[u8]) -> Vec<u8> {
+        self.rest.extend_from_slice(data);
+        let whole = self.rest.len() - self.rest.len() % TS_PACKET_SIZE;
+        let rest = self.rest.split_off(whole);
+
+        std::mem::replace(&mut self.rest, rest)
+    }
+
+    /// Forget the rest, the next read comes from a new source.
+    pub fn reset(&mut self) {
+        self.rest.clear();
+    }
+}
+
+/// Clock rate of the mpegts timestamps.
+const TS_CLOCK: f64 = 90000.0;
+
+/// Presentation timestamp of a mpegts packet, when it starts a audio or video frame.
+pub fn ts_pts(packet: &[u8]) -> Option<u64> {
+    if packet.len() < TS_PACKET_SIZE || packet[0] != 0x47 || packet[1] & 0x40 == 0 {
+        return None;
+    }
+
+    let start = match (packet[3] >> 4) & 0x03 {
+        1 => 4,
+        3 => 5 + packet[4] as usize,
+        _ => return None,
+    };
+
+    let pes = packet.get(start..start + 14)?;
+
+    if pes[..3] != [0, 0, 1] || !matches!(pes[3], 0xBD | 0xC0..=0xEF) || pes[7] & 0x80 == 0 {
+        return None;
+    }
+
+    let pts = |i: usize| pes[9 + i] as u64;
+
+    Some(
+        ((pts(0) >> 1) & 0x07) << 30
+            | pts(1) << 22
+            | (pts(2) >> 1) << 15
+            | pts(3) << 7
+            | pts(4) >> 1,
+    )
+}
+
+/// Media time of a mpegts stream, from the timestamps of its packets.
+///
+/// Other then the wall clock it says how much of the source is read, also when the pipe is faster then realtime.
+#[derive(Debug, Default)]
+pub struct MediaClock {
+    packets: TsPackets,
+    first: Option<u64>,
+    elapsed: u64,
+}
+
+impl MediaClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the data of a read, it can end in the middle of a packet.
+    pub fn push(&mut self, data: &[u8]) {
+        for pts in self
+            .packets
+            .push(data)
+            .chunks(TS_PACKET_SIZE)
+            .filter_map(ts_pts)
+        {
+            let first = *self.first.get_or_insert(pts);
+            self.elapsed = self.elapsed.max(pts.saturating_sub(first));
+        }
+    }
+
+    /// Seconds between the first and the latest timestamp.
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed as f64 / TS_CLOCK
+    }
+
+    /// Start again, for a new source.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
 /// Read ffmpeg stderr decoder and encoder instance
 /// and log the output.
 pub fn stderr_reader(
@@ -637,38 +1265,66 @@ pub fn stderr_reader(
     suffix: ProcessUnit,
     proc_control: ProcessControl,
 ) -> Result<(), Error> {
-    for line in buffer.lines() {
+    let mut slow_since: Option<time::Instant> = None;
+
+    for line in FfmpegLines::new(buffer) {
         let line = line?;
 
         if FFMPEG_IGNORE_ERRORS.iter().any(|i| line.contains(*i)) {
             continue;
         }
 
-        if line.contains("[info]") {
-            info!(
-                "<bright black>[{suffix}]</> {}",
-                line.replace("[info] ", "")
-            )
-        } else if line.contains("[warning]") {
-            warn!(
-                "<bright black>[{suffix}]</> {}",
-                line.replace("[warning] ", "")
-            )
-        } else if line.contains("[error]") || line.contains("[fatal]") {
-            error!(
-                "<bright black>[{suffix}]</> {}",
-                line.replace("[error] ", "").replace("[fatal] ", "")
-            );
+        // progress lines are only for the stats, not for logging
+        if let Some(stats) = EncoderStats::parse(&line) {
+            if stats.speed > 0.0 && stats.speed < 1.0 {
+                let since = slow_since.get_or_insert_with(time::Instant::now);
+
+                if since.elapsed() >= SLOW_SPEED_WINDOW {
+                    warn!(
+                        "<bright black>[{suffix}]</> Encoding speed is <yellow>{}x</> since {} seconds, the machine can't keep up!",
+                        stats.speed,
+                        since.elapsed().as_secs()
+                    );
+                    slow_since = Some(time::Instant::now());
+                }
+            } else {
+                slow_since = None;
+            }
+
+            let mut last_stats = proc_control.encoder_stats.lock().unwrap();
+
+            // new frames are progress, also when the watchdog doesn't read it
+            if suffix == Encoder && stats.frame != last_stats.frame {
+                *proc_control.last_progress.lock().unwrap() = time::Instant::now();
+            }
+
+            proc_control
+                .dropped_frames
+                .fetch_add(stats.new_drops(&last_stats) as usize, Ordering::SeqCst);
+
+            *last_stats = stats;
+            continue;
+        }
+
+        proc_control.push_line(suffix, &line);
 
-            if FFMPEG_UNRECOVERABLE_ERRORS
+        let (level, msg) = ffmpeg_level(&line);
+
+        log::log!(level, "<bright black>[{suffix}]</> {msg}");
+
+        if level == Level::Error {
+            proc_control.set_last_error(suffix, &msg);
+        }
+
+        if level == Level::Error
+            && (FFMPEG_UNRECOVERABLE_ERRORS
                 .iter()
                 .any(|i| line.contains(*i))
                 || (line.contains("No such file or directory")
-                    && !line.contains("failed to delete old segment"))
-            {
-                proc_control.stop_all();
-                exit(1);
-            }
+                    && !line.contains("failed to delete old segment")))
+        {
+            proc_control.stop_all();
+            exit(1);
         }
     }
 
@@ -694,83 +1350,60 @@ fn is_in_system(name: &str) -> Result<(), String> {
 }
 
 fn ffmpeg_filter_and_libs(config: &mut PlayoutConfig) -> Result<(), String> {
-    let ignore_flags = [
-        "--enable-gpl",
-        "--enable-version3",
-        "--enable-runtime-cpudetect",
-        "--enable-avfilter",
-        "--enable-zlib",
-        "--enable-pic",
-        "--enable-nonfree",
-    ];
+    let caps = ffmpeg_capabilities(&config.processing.ffmpeg_path)?;
 
-    let mut ff_proc = match Command::new("ffmpeg")
-        .args(["-filters"])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-    {
-        Err(e) => {
-            return Err(format!("couldn't spawn ffmpeg process: {e}"));
-        }
-        Ok(proc) => proc,
-    };
+    config.general.ffmpeg_libs = caps.libs.clone();
+    config.general.ffmpeg_filters = caps.filters.clone();
 
-    let out_buffer = BufReader::new(ff_proc.stdout.take().unwrap());
-    let err_buffer = BufReader::new(ff_proc.stderr.take().unwrap());
-
-    // stderr shows only the ffmpeg configuration
-    // get codec library's
-    for line in err_buffer.lines().flatten() {
-        if line.contains("configuration:") {
-            let configs = line.split_whitespace();
-
-            for flag in configs {
-                if flag.contains("--enable") && !ignore_flags.contains(&flag) {
-                    config
-                        .general
-                        .ffmpeg_libs
-                        .push(flag.replace("--enable-", ""));
-                }
-            }
-            break;
-        }
+    let missing = missing_capabilities(config, &caps);
+
+    if !missing.is_empty() {
+        return Err(format!("ffmpeg has no {}!", missing.join(", ")));
     }
 
-    // stdout shows filter from ffmpeg
-    // get filters
-    for line in out_buffer.lines().flatten() {
-        if line.contains('>') {
-            let filter_line = line.split_whitespace().collect::<Vec<_>>();
+    Ok(())
+}
 
-            if filter_line.len() > 2 {
-                config
-                    .general
-                    .ffmpeg_filters
-                    .push(filter_line[1].to_string())
-            }
+/// Check if the configured hardware accelerator is supported by ffmpeg,
+/// when not fallback to software processing.
+fn ffmpeg_hwaccel(config: &mut PlayoutConfig) {
+    if let Some(name) = config.processing.hwaccel.name() {
+        let available = match Command::new(&config.processing.ffmpeg_path)
+            .args(["-hide_banner", "-hwaccels"])
+            .stderr(Stdio::null())
+            .output()
+        {
+            Ok(out) => String::from_utf8_lossy(&out.stdout)
+                .lines()
+                .any(|l| l.trim() == name),
+            Err(_) => false,
+        };
+
+        if !available {
+            warn!(
+                "Hardware acceleration <yellow>{name}</> is not available, fallback to software!"
+            );
+            config.processing.hwaccel = HwAccel::None;
         }
     }
-
-    if let Err(e) = ff_proc.wait() {
-        error!("{:?}", e)
-    };
-
-    Ok(())
 }
 
 /// Validate ffmpeg/ffprobe/ffplay.
 ///
 /// Check if they are in system and has all libs and codecs we need.
 pub fn validate_ffmpeg(config: &mut PlayoutConfig) -> Result<(), String> {
-    is_in_system("ffmpeg")?;
-    is_in_system("ffprobe")?;
+    is_in_system(&config.processing.ffmpeg_path)?;
+    is_in_system(&config.processing.ffprobe_path)?;
+    ffmpeg_version(config)?;
+    set_ffprobe_path(&config.processing.ffprobe_path);
 
     if config.out.mode == Desktop {
         is_in_system("ffplay")?;
     }
 
     ffmpeg_filter_and_libs(config)?;
+    ffmpeg_hwaccel(config);
+    check_limits(config);
 
     if config
         .out
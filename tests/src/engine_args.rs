@@ -0,0 +1,62 @@
+use clap::Parser;
+
+use ffplayout::utils::{get_config, Args, Command};
+
+#[test]
+fn no_command() {
+    let args = Args::try_parse_from(["ffplayout", "-c", "../assets/ffplayout.yml"]).unwrap();
+
+    assert_eq!(args.command, None);
+    assert_eq!(args.config, Some("../assets/ffplayout.yml".to_string()));
+}
+
+#[test]
+fn validate_command() {
+    let args = Args::try_parse_from([
+        "ffplayout",
+        "validate",
+        "--config",
+        "../assets/ffplayout.yml",
+        "-d",
+        "2023-02-08",
+    ])
+    .unwrap();
+
+    assert_eq!(args.command, Some(Command::Validate));
+    assert_eq!(args.config, Some("../assets/ffplayout.yml".to_string()));
+    assert_eq!(args.date, Some("2023-02-08".to_string()));
+    assert_eq!(args.channel, None);
+}
+
+#[test]
+fn play_command_with_channel() {
+    let args = Args::try_parse_from(["ffplayout", "-i", "-o", "null", "play"]).unwrap();
+
+    assert_eq!(args.command, Some(Command::Play));
+    assert!(args.infinit);
+    assert_eq!(args.channel, None);
+
+    // without a command the first value is the channel
+    let args = Args::try_parse_from(["ffplayout", "news"]).unwrap();
+
+    assert_eq!(args.command, None);
+    assert_eq!(args.channel, Some("news".to_string()));
+}
+
+#[test]
+fn serve_command_enables_servers() {
+    let args =
+        Args::try_parse_from(["ffplayout", "serve", "-c", "../assets/ffplayout.yml"]).unwrap();
+
+    assert_eq!(args.command, Some(Command::Serve));
+
+    let config = get_config(args);
+
+    assert!(config.control.enable);
+    assert!(config.metrics.enable);
+}
+
+#[test]
+fn unknown_command() {
+    assert!(Args::try_parse_from(["ffplayout", "validate", "--no-such-flag"]).is_err());
+}
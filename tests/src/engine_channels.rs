@@ -0,0 +1,41 @@
+use std::{sync::atomic::Ordering, thread::sleep, time::Duration};
+
+use ffplayout::utils::channel::spawn_channel;
+use ffplayout_lib::utils::{PlayerControl, PlayoutConfig, PlayoutStatus, ProcessControl};
+
+fn mock_playout(
+    _config: PlayoutConfig,
+    _play_control: PlayerControl,
+    _playout_stat: PlayoutStatus,
+    proc_control: ProcessControl,
+) {
+    while !proc_control.is_terminated.load(Ordering::SeqCst) {
+        sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn isolated_channels() {
+    let mut config1 = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config1.general.channel = "channel1".into();
+    let mut config2 = config1.clone();
+    config2.general.channel = "channel2".into();
+
+    let channel1 = spawn_channel(config1, mock_playout);
+    let channel2 = spawn_channel(config2, mock_playout);
+
+    assert_eq!(channel1.name, "channel1");
+    assert_eq!(channel2.name, "channel2");
+
+    channel1.proc_control.stop_all();
+    channel1.join();
+
+    sleep(Duration::from_millis(100));
+
+    assert!(!channel2.proc_control.is_terminated.load(Ordering::SeqCst));
+    assert!(channel2.proc_control.is_alive.load(Ordering::SeqCst));
+    assert!(!channel2.is_finished());
+
+    channel2.proc_control.stop_all();
+    channel2.join();
+}
@@ -1,8 +1,31 @@
-use std::fs;
+use std::{
+    env, fs, process,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
 
-use ffplayout::{input::playlist::gen_source, utils::prepare_output_cmd};
+use ffplayout::{
+    input::playlist::{fill_gap, gen_source},
+    output::{
+        ad_break_length, check_stall, cue_playlist, decoder_cmd, discontinuity_cmd, encoder_cmd,
+        fallback_config, hls_writer_cmd, ingest_cmd, log_format, metadata_cmd, next_due, out_time,
+        pip_active, pip_cmd, print_commands, recovery_input_cmd, recovery_node, CueMark,
+        FallbackAction, FallbackLadder, Preroll, CONCAT_LEAD, RECOVERY_FLAGS,
+    },
+    utils::prepare_output_cmd,
+};
 use ffplayout_lib::{
-    utils::{Media, OutputMode::*, PlayoutConfig, ProcessUnit::*},
+    filter::{
+        av_desync, censor_filter, filter_chains, loudness_report, parse_ebur128, pip_filter,
+        report_loudness, write_loudness_report, LoudnessReport,
+    },
+    utils::{
+        abr_output_cmd, bit_depth, eight_bit_encoder, intermediate_codec, is_remote,
+        resolve_source, seek_and_length, ts_pts, CensorMode, CensorRegion, ColorRange, Deinterlace,
+        FitMode, FpsConvert, HwAccel, JsonPlaylist, Logo, LogoEnable, LoudnormMode, Media,
+        MediaClock, OutputMode::*, PlayoutConfig, ProcessControl, ProcessMode, ProcessUnit::*,
+        Rendition, SeekMode, SourceResolver, TimestampMode, RESOLVER_TIMEOUT,
+    },
     vec_strings,
 };
 
@@ -34,89 +57,164 @@ fn video_audio_input() {
 }
 
 #[test]
-fn video_audio_custom_filter1_input() {
+fn video_audio_logo_position_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
-    config.processing.add_logo = false;
-    config.processing.custom_filter = "[0:v]gblur=2[c_v_out];[0:a]volume=0.2[c_a_out]".to_string();
+    config.processing.add_logo = true;
+    config.processing.logo_x = "12".into();
+    config.processing.logo_y = "H-h-12".into();
+    config.processing.logo_scale = "100:-1".into();
+    config.processing.logo_filter = String::new();
+    let logo_path = fs::canonicalize("./assets/logo.png").unwrap();
+    config.processing.logo = logo_path.to_string_lossy().to_string();
 
     let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
     let media = gen_source(&config, media_obj, &None);
 
-    let test_filter_cmd = vec_strings![
-        "-filter_complex",
-        "[0:v:0]scale=1024:576,gblur=2[vout0];[0:a:0]anull,volume=0.2[aout0]"
-    ];
-
-    let test_filter_map = vec_strings!["-map", "[vout0]", "-map", "[aout0]"];
+    let test_filter_cmd =
+        vec_strings![
+            "-filter_complex",
+            format!("[0:v:0]scale=1024:576,null[v];movie={}:loop=0,setpts=N/(FRAME_RATE*TB),format=rgba,colorchannelmixer=aa=0.7,scale=100:-1[l];[v][l]overlay=12:H-h-12:shortest=1[vout0];[0:a:0]anull[aout0]", config.processing.logo)
+        ];
 
-    assert_eq!(
-        media.cmd,
-        Some(vec_strings!["-i", "./assets/with_audio.mp4"])
-    );
-    assert_eq!(media.filter.clone().unwrap().cmd(), test_filter_cmd);
-    assert_eq!(media.filter.unwrap().map(), test_filter_map);
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
 }
 
 #[test]
-fn video_audio_custom_filter2_input() {
+fn video_audio_multiple_logos_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
-    config.processing.add_logo = false;
-    config.processing.custom_filter =
-        "[0:v]null[v];movie=logo.png[l];[v][l]overlay[c_v_out];[0:a]volume=0.2[c_a_out]"
-            .to_string();
+    config.processing.add_logo = true;
+    config.processing.logo_filter = String::new();
+    let logo_path = fs::canonicalize("./assets/logo.png").unwrap();
+    config.processing.logo = logo_path.to_string_lossy().to_string();
+    config.processing.logos = vec![
+        Logo {
+            path: config.processing.logo.clone(),
+            x: "12".into(),
+            y: "H-h-12".into(),
+            enable: "between(t,0,10)".into(),
+            ..Default::default()
+        },
+        Logo {
+            path: "./assets/not_exists.png".into(),
+            ..Default::default()
+        },
+    ];
 
     let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
     let media = gen_source(&config, media_obj, &None);
 
-    let test_filter_cmd = vec_strings![
-        "-filter_complex",
-        "[0:v:0]scale=1024:576,null[v];movie=logo.png[l];[v][l]overlay[vout0];[0:a:0]anull,volume=0.2[aout0]"
-    ];
-
-    let test_filter_map = vec_strings!["-map", "[vout0]", "-map", "[aout0]"];
+    let test_filter_cmd =
+        vec_strings![
+            "-filter_complex",
+            format!("[0:v:0]scale=1024:576,null[v];movie={0}:loop=0,setpts=N/(FRAME_RATE*TB),format=rgba,colorchannelmixer=aa=0.7[l];[v][l]overlay=W-w-12:12:shortest=1[v1];movie={0}:loop=0,setpts=N/(FRAME_RATE*TB),format=rgba,colorchannelmixer=aa=1[l1];[v1][l1]overlay=12:H-h-12:enable='between(t,0,10)':shortest=1[vout0];[0:a:0]anull[aout0]", config.processing.logo)
+        ];
 
-    assert_eq!(
-        media.cmd,
-        Some(vec_strings!["-i", "./assets/with_audio.mp4"])
-    );
-    assert_eq!(media.filter.clone().unwrap().cmd(), test_filter_cmd);
-    assert_eq!(media.filter.unwrap().map(), test_filter_map);
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
 }
 
 #[test]
-fn video_audio_custom_filter3_input() {
+fn video_audio_logo_enable_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
-    config.processing.add_logo = false;
-    config.processing.custom_filter =
-        "[v_in];movie=logo.png[l];[v_in][l]overlay[c_v_out];[0:a]volume=0.2[c_a_out]".to_string();
+    config.processing.add_logo = true;
+    config.processing.logo_filter = String::new();
+    let logo_path = fs::canonicalize("./assets/logo.png").unwrap();
+    config.processing.logo = logo_path.to_string_lossy().to_string();
+    config.processing.logo_enable = LogoEnable {
+        start: 0.0,
+        end: 600.0,
+        period: 1800.0,
+    };
 
     let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
     let media = gen_source(&config, media_obj, &None);
 
-    let test_filter_cmd = vec_strings![
-        "-filter_complex",
-        "[0:v:0]scale=1024:576[v_in];movie=logo.png[l];[v_in][l]overlay[vout0];[0:a:0]anull,volume=0.2[aout0]"
-    ];
+    let test_filter_cmd =
+        vec_strings![
+            "-filter_complex",
+            format!("[0:v:0]scale=1024:576,null[v];movie={}:loop=0,setpts=N/(FRAME_RATE*TB),format=rgba,colorchannelmixer=aa=0.7[l];[v][l]overlay=W-w-12:12:enable='between(mod(t,1800),0,600)':shortest=1[vout0];[0:a:0]anull[aout0]", config.processing.logo)
+        ];
 
-    let test_filter_map = vec_strings!["-map", "[vout0]", "-map", "[aout0]"];
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+}
+
+#[test]
+fn video_audio_animated_logo_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = true;
+    config.processing.logo_filter = String::new();
+    let logo_path = fs::canonicalize("./assets/logo.png").unwrap();
+    config.processing.logo = logo_path.to_string_lossy().to_string();
+
+    let static_filter = gen_source(
+        &config,
+        Media::new(0, "./assets/with_audio.mp4", true),
+        &None,
+    )
+    .filter
+    .unwrap()
+    .cmd();
 
     assert_eq!(
-        media.cmd,
-        Some(vec_strings!["-i", "./assets/with_audio.mp4"])
+        static_filter,
+        vec_strings![
+            "-filter_complex",
+            format!("[0:v:0]scale=1024:576,null[v];movie={}:loop=0,setpts=N/(FRAME_RATE*TB),format=rgba,colorchannelmixer=aa=0.7[l];[v][l]overlay=W-w-12:12:shortest=1[vout0];[0:a:0]anull[aout0]", config.processing.logo)
+        ]
+    );
+
+    config.processing.logo_animated = true;
+
+    let once_filter = gen_source(
+        &config,
+        Media::new(0, "./assets/with_audio.mp4", true),
+        &None,
+    )
+    .filter
+    .unwrap()
+    .cmd();
+
+    assert_eq!(
+        once_filter,
+        vec_strings![
+            "-filter_complex",
+            format!("[0:v:0]scale=1024:576,null[v];movie={},setpts=N/(FRAME_RATE*TB),format=rgba,colorchannelmixer=aa=0.7[l];[v][l]overlay=W-w-12:12[vout0];[0:a:0]anull[aout0]", config.processing.logo)
+        ]
+    );
+
+    config.processing.logo_loop_size = 50;
+
+    let loop_filter = gen_source(
+        &config,
+        Media::new(0, "./assets/with_audio.mp4", true),
+        &None,
+    )
+    .filter
+    .unwrap()
+    .cmd();
+
+    assert_eq!(
+        loop_filter,
+        vec_strings![
+            "-filter_complex",
+            format!("[0:v:0]scale=1024:576,null[v];movie={},loop=loop=-1:size=50,setpts=N/(FRAME_RATE*TB),format=rgba,colorchannelmixer=aa=0.7[l];[v][l]overlay=W-w-12:12:shortest=1[vout0];[0:a:0]anull[aout0]", config.processing.logo)
+        ]
     );
-    assert_eq!(media.filter.clone().unwrap().cmd(), test_filter_cmd);
-    assert_eq!(media.filter.unwrap().map(), test_filter_map);
 }
 
 #[test]
-fn dual_audio_aevalsrc_input() {
+fn video_audio_logo_filter_override_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
-    config.processing.audio_tracks = 2;
-    config.processing.add_logo = false;
+    config.processing.add_logo = true;
+    config.processing.logo_x = "12".into();
+    config.processing.logo_y = "12".into();
+    config.processing.logo_filter = "overlay=W-w-24:24".into();
+    let logo_path = fs::canonicalize("./assets/logo.png").unwrap();
+    config.processing.logo = logo_path.to_string_lossy().to_string();
 
     let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
     let media = gen_source(&config, media_obj, &None);
@@ -124,969 +222,759 @@ fn dual_audio_aevalsrc_input() {
     let test_filter_cmd =
         vec_strings![
             "-filter_complex",
-            "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0];aevalsrc=0:channel_layout=stereo:duration=30:sample_rate=48000,anull[aout1]"
+            format!("[0:v:0]scale=1024:576,null[v];movie={}:loop=0,setpts=N/(FRAME_RATE*TB),format=rgba,colorchannelmixer=aa=0.7[l];[v][l]overlay=W-w-24:24:shortest=1[vout0];[0:a:0]anull[aout0]", config.processing.logo)
         ];
 
-    let test_filter_map = vec_strings!["-map", "[vout0]", "-map", "[aout0]", "-map", "[aout1]"];
-
-    assert_eq!(
-        media.cmd,
-        Some(vec_strings!["-i", "./assets/with_audio.mp4"])
-    );
-    assert_eq!(media.filter.clone().unwrap().cmd(), test_filter_cmd);
-    assert_eq!(media.filter.unwrap().map(), test_filter_map);
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
 }
 
 #[test]
-fn dual_audio_input() {
+fn video_audio_missing_logo_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
-    config.processing.audio_tracks = 2;
-    config.processing.add_logo = false;
+    config.processing.add_logo = true;
+    config.processing.logo = "./assets/missing_logo.png".into();
 
-    let media_obj = Media::new(0, "./assets/dual_audio.mp4", true);
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
     let media = gen_source(&config, media_obj, &None);
 
     let test_filter_cmd = vec_strings![
         "-filter_complex",
-        "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0];[0:a:1]anull[aout1]"
+        "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0]"
     ];
 
-    let test_filter_map = vec_strings!["-map", "[vout0]", "-map", "[aout0]", "-map", "[aout1]"];
-
-    assert_eq!(
-        media.cmd,
-        Some(vec_strings!["-i", "./assets/dual_audio.mp4"])
-    );
-    assert_eq!(media.filter.clone().unwrap().cmd(), test_filter_cmd);
-    assert_eq!(media.filter.unwrap().map(), test_filter_map);
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
 }
 
 #[test]
-fn video_separate_audio_input() {
+fn video_audio_ticker_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
-    config.processing.audio_tracks = 1;
     config.processing.add_logo = false;
+    config.processing.ticker.enable = true;
+    config.processing.ticker.textfile = "./assets/ticker.txt".into();
+    config.processing.ticker.fontfile = String::new();
 
-    let mut media_obj = Media::new(0, "./assets/no_audio.mp4", true);
-    media_obj.audio = "./assets/audio.mp3".to_string();
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
     let media = gen_source(&config, media_obj, &None);
 
     let test_filter_cmd = vec_strings![
         "-filter_complex",
-        "[0:v:0]scale=1024:576[vout0];[1:a:0]anull[aout0]"
+        "[0:v:0]scale=1024:576,drawtext=textfile='./assets/ticker.txt':reload=1:fontsize=24:fontcolor=#ffffff:y=h-line_h-10:x=w-mod(t*100\\,w+tw)[vout0];[0:a:0]anull[aout0]"
     ];
 
-    let test_filter_map = vec_strings!["-map", "[vout0]", "-map", "[aout0]"];
-
-    assert_eq!(
-        media.cmd,
-        Some(vec_strings![
-            "-i",
-            "./assets/no_audio.mp4",
-            "-i",
-            "./assets/audio.mp3",
-            "-t",
-            "30"
-        ])
-    );
-    assert_eq!(media.filter.clone().unwrap().cmd(), test_filter_cmd);
-    assert_eq!(media.filter.unwrap().map(), test_filter_map);
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
 }
 
 #[test]
-fn video_audio_stream() {
+fn video_audio_subtitle_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.out.output_cmd = Some(vec_strings![
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost/live/stream"
-    ]);
+    config.processing.subtitle = "./assets/subtitle.vtt".into();
 
-    let enc_prefix = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0"
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576,subtitles='./assets/subtitle.vtt'[vout0];[0:a:0]anull[aout0]"
     ];
 
-    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &None);
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
 
-    let test_cmd = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost/live/stream"
+    config.processing.subtitle = "./assets/missing.srt".into();
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0]"
     ];
 
-    assert_eq!(enc_cmd, test_cmd);
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
 }
 
 #[test]
-fn video_audio_filter1_stream() {
+fn video_audio_loudnorm_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.text.add_text = false;
-    config.out.output_filter = Some("[0:v]gblur=2[vout0];[0:a]volume=0.2[aout0]".to_string());
-    config.out.output_cmd = Some(vec_strings![
-        "-map",
-        "[vout0]",
-        "-map",
-        "[aout0]",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost/live/stream"
-    ]);
+    config.processing.loudnorm.enable = true;
+    config.processing.loudnorm_mode = LoudnormMode::Single;
 
-    let mut media = Media::new(0, "", false);
-    media.unit = Encoder;
-    media.add_filter(&config, &None);
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
 
-    let enc_prefix = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0"
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576[vout0];[0:a:0]anull,loudnorm=I=-18:TP=-1.5:LRA=11[aout0]"
     ];
 
-    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+}
 
-    let test_cmd = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0",
-        "-filter_complex",
-        "[0:v]gblur=2[vout0];[0:a]volume=0.2[aout0]",
-        "-map",
-        "[vout0]",
-        "-map",
-        "[aout0]",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost/live/stream"
-    ];
+#[test]
+fn loudnorm_dual_single_pass_fallback() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.processing.loudnorm.enable = true;
+    config.processing.loudnorm_mode = LoudnormMode::Dual;
+    // the measurement fails, so there are never measured values
+    config.processing.ffmpeg_path = "./not_existing_ffmpeg".to_string();
 
-    assert_eq!(enc_cmd, test_cmd);
+    let start = Instant::now();
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(media.filter.unwrap().cmd()[1].ends_with("loudnorm=I=-18:TP=-1.5:LRA=11[aout0]"));
 }
 
 #[test]
-fn video_audio_filter2_stream() {
+fn video_audio_fps_convert_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.text.add_text = true;
-    config.text.fontfile = String::new();
-    config.out.output_filter = Some("[0:v]gblur=2[vout0];[0:a]volume=0.2[aout0]".to_string());
-    config.out.output_cmd = Some(vec_strings![
-        "-map",
-        "[vout0]",
-        "-map",
-        "[aout0]",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost/live/stream"
-    ]);
+    // the source has 25 fps
+    config.processing.fps = 30.0;
 
-    let mut media = Media::new(0, "", false);
-    media.unit = Encoder;
-    media.add_filter(&config, &None);
+    let simple = gen_source(
+        &config,
+        Media::new(0, "./assets/with_audio.mp4", true),
+        &None,
+    );
 
-    let socket = config
-        .text
-        .zmq_stream_socket
-        .clone()
-        .unwrap()
-        .replace(':', "\\:");
+    assert_eq!(
+        simple.filter.unwrap().cmd(),
+        vec_strings![
+            "-filter_complex",
+            "[0:v:0]fps=30,scale=1024:576[vout0];[0:a:0]anull[aout0]"
+        ]
+    );
 
-    let enc_prefix = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0"
-    ];
+    config.processing.fps_convert = FpsConvert::Interpolate;
 
-    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+    let interpolate = gen_source(
+        &config,
+        Media::new(0, "./assets/with_audio.mp4", true),
+        &None,
+    );
 
-    let test_cmd = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0",
+    assert_eq!(
+        interpolate.filter.unwrap().cmd(),
+        vec_strings![
+            "-filter_complex",
+            "[0:v:0]minterpolate=fps=30:mi_mode=mci:mc_mode=aobmc:me_mode=bidir,scale=1024:576[vout0];[0:a:0]anull[aout0]"
+        ]
+    );
+
+    // same frame rate needs no conversion
+    config.processing.fps = 25.0;
+
+    let same = gen_source(
+        &config,
+        Media::new(0, "./assets/with_audio.mp4", true),
+        &None,
+    );
+
+    assert_eq!(
+        same.filter.unwrap().cmd(),
+        vec_strings![
+            "-filter_complex",
+            "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0]"
+        ]
+    );
+}
+
+#[test]
+fn ebur128_loudness_report() {
+    let output = "[Parsed_ebur128_0 @ 0x55d1] t: 9.9 TARGET:-23 LUFS M: -21.4 S: -22.0 I: -22.8 LUFS LRA: 4.1 LU
+[Parsed_ebur128_0 @ 0x55d1] Summary:
+
+  Integrated loudness:
+    I:         -23.4 LUFS
+    Threshold: -33.9 LUFS
+
+  Loudness range:
+    LRA:         6.2 LU
+    Threshold: -44.1 LUFS
+    LRA low:   -27.5 LUFS
+    LRA high:  -21.3 LUFS
+
+  True peak:
+    Peak:       -1.8 dBFS
+";
+
+    let report = parse_ebur128("./assets/with_audio.mp4", output).unwrap();
+
+    assert_eq!(report.source, "./assets/with_audio.mp4");
+    assert_eq!(report.integrated, -23.4);
+    assert_eq!(report.lra, 6.2);
+    assert_eq!(report.true_peak, -1.8);
+
+    // the frame lines alone have no summary
+    assert_eq!(parse_ebur128("", output.lines().next().unwrap()), None);
+
+    let path = std::env::temp_dir()
+        .join(format!("ffplayout_loudness_{}", std::process::id()))
+        .join("report.jsonl");
+    let _ = fs::remove_file(&path);
+
+    write_loudness_report(&path, &report).unwrap();
+    write_loudness_report(&path, &report).unwrap();
+
+    let lines = fs::read_to_string(&path).unwrap();
+
+    assert_eq!(lines.lines().count(), 2);
+    assert_eq!(
+        serde_json::from_str::<LoudnessReport>(lines.lines().next().unwrap()).unwrap(),
+        report
+    );
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    // nothing to measure without audio
+    let config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    let silent = Media::new(0, "./assets/no_audio.mp4", true);
+
+    assert_eq!(loudness_report(&silent, &config), None);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn loudness_report_after_airing() {
+    use chrono::{Local, TimeZone};
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("ffplayout_report_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let ffmpeg = dir.join("ffmpeg");
+    let path = dir.join("report.jsonl");
+    // fake ffmpeg, which prints the ebur128 summary
+    fs::write(
+        &ffmpeg,
+        "#!/bin/sh\nprintf 'Summary:\\n  I: -23.4 LUFS\\n  LRA: 6.2 LU\\n  Peak: -1.8 dBFS\\n' >&2\n",
+    )
+    .unwrap();
+    fs::set_permissions(&ffmpeg, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.processing.ffmpeg_path = ffmpeg.display().to_string();
+    config.processing.loudness_report_path = path.display().to_string();
+    let node = Media::new(0, "./assets/with_audio.mp4", false);
+    let aired = Local.with_ymd_and_hms(2024, 2, 1, 10, 0, 0).unwrap();
+
+    report_loudness(&config, &node, aired);
+
+    let start = Instant::now();
+
+    while !path.is_file() && start.elapsed() < Duration::from_secs(10) {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    let lines = fs::read_to_string(&path).unwrap();
+    let report: LoudnessReport = serde_json::from_str(lines.lines().next().unwrap()).unwrap();
+
+    assert_eq!(report.source, "./assets/with_audio.mp4");
+    assert_eq!(
+        report.aired,
+        aired.format("%Y-%m-%dT%H:%M:%S%:z").to_string()
+    );
+    assert_eq!(report.integrated, -23.4);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn video_audio_hwaccel_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.processing.hwaccel = HwAccel::Vaapi;
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd = vec_strings![
         "-filter_complex",
-        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='',gblur=2[vout0];[0:a]volume=0.2[aout0]"),
-        "-map",
-        "[vout0]",
-        "-map",
-        "[aout0]",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost/live/stream"
+        "[0:v:0]format=nv12,hwupload,scale_vaapi=w=1024:h=576,hwdownload,format=nv12[vout0];[0:a:0]anull[aout0]"
     ];
 
-    assert_eq!(enc_cmd, test_cmd);
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
 }
 
 #[test]
-fn video_audio_filter3_stream() {
+fn video_audio_fade_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.text.add_text = true;
-    config.text.fontfile = String::new();
-    config.out.output_filter = Some(
-        "[0:v]null[o];movie=/path/to/lower_third.png[l];[o][l]overlay=shortest=1[vout0]"
-            .to_string(),
-    );
-    config.out.output_cmd = Some(vec_strings![
-        "-map",
-        "[vout0]",
-        "-map",
-        "0:a",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost/live/stream"
-    ]);
 
-    let mut media = Media::new(0, "", false);
-    media.unit = Encoder;
-    media.add_filter(&config, &None);
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    media_obj.seek = 5.0;
+    media_obj.out = 20.0;
+    let media = gen_source(&config, media_obj.clone(), &None);
 
-    let socket = config
-        .text
-        .zmq_stream_socket
-        .clone()
-        .unwrap()
-        .replace(':', "\\:");
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576,fade=in:st=0:d=0.5[vout0];[0:a:0]anull,afade=in:st=0:d=0.5[aout0]"
+    ];
 
-    let enc_prefix = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0"
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+
+    config.processing.fade_in_duration = 1.0;
+    config.processing.fade_out_duration = 2.0;
+    let media = gen_source(&config, media_obj.clone(), &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576,fade=in:st=0:d=1,fade=out:st=13:d=2[vout0];[0:a:0]anull,afade=in:st=0:d=1,afade=out:st=13:d=2[aout0]"
     ];
 
-    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
 
-    let test_cmd = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0",
+    config.processing.fade_in_duration = 0.0;
+    config.processing.fade_out_duration = 0.0;
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd = vec_strings![
         "-filter_complex",
-        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='',null[o];movie=/path/to/lower_third.png[l];[o][l]overlay=shortest=1[vout0]"),
-        "-map",
-        "[vout0]",
-        "-map",
-        "0:a",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost/live/stream"
+        "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0]"
     ];
 
-    assert_eq!(enc_cmd, test_cmd);
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
 }
 
 #[test]
-fn video_audio_filter4_stream() {
+fn video_audio_fade_out_full_clip() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.text.add_text = true;
-    config.text.fontfile = String::new();
-    config.out.output_filter = Some(
-        "[0:v]null[o];movie=/path/to/lower_third.png[l];[o][l]overlay=shortest=1[vout0];[0:a:0]volume=0.2[aout0]"
-            .to_string(),
-    );
-    config.out.output_cmd = Some(vec_strings![
-        "-map",
-        "[vout0]",
-        "-map",
-        "[aout0]",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost/live/stream"
-    ]);
-
-    let mut media = Media::new(0, "", false);
-    media.unit = Encoder;
-    media.add_filter(&config, &None);
-
-    let socket = config
-        .text
-        .zmq_stream_socket
-        .clone()
-        .unwrap()
-        .replace(':', "\\:");
-
-    let enc_prefix = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0"
-    ];
+    config.processing.fade_out_duration = 1.5;
 
-    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    media_obj.out = 20.0;
+    let media = gen_source(&config, media_obj, &None);
 
-    let test_cmd = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0",
+    let test_filter_cmd = vec_strings![
         "-filter_complex",
-        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='',null[o];movie=/path/to/lower_third.png[l];[o][l]overlay=shortest=1[vout0];[0:a:0]volume=0.2[aout0]"),
-        "-map",
-        "[vout0]",
-        "-map",
-        "[aout0]",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost/live/stream"
+        "[0:v:0]scale=1024:576,fade=out:st=18.5:d=1.5[vout0];[0:a:0]anull,afade=out:st=18.5:d=1.5[aout0]"
     ];
 
-    assert_eq!(enc_cmd, test_cmd);
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
 }
 
 #[test]
-fn video_dual_audio_stream() {
+fn video_audio_deinterlace_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.processing.audio_tracks = 2;
-    config.text.add_text = false;
-    config.out.output_cmd = Some(vec_strings![
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40051"
-    ]);
 
-    let mut media = Media::new(0, "", false);
-    media.unit = Encoder;
-    media.add_filter(&config, &None);
-
-    let enc_prefix = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0"
+    let modes = [
+        (Deinterlace::None, "yadif=0:-1:0,"),
+        (Deinterlace::Yadif, "yadif=0:-1:1,"),
+        (Deinterlace::Bwdif, "bwdif=0:-1:1,"),
     ];
 
-    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+    for (mode, filter) in modes {
+        config.processing.deinterlace = mode;
 
-    let test_cmd = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0",
-        "-map",
-        "0:v",
-        "-map",
-        "0:a:0",
-        "-map",
-        "0:a:1",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40051"
-    ];
+        // the test clip is progressive, it gets never deinterlaced
+        let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+        let media = gen_source(&config, media_obj, &None);
 
-    assert_eq!(enc_cmd, test_cmd);
+        let test_filter_cmd = vec_strings![
+            "-filter_complex",
+            "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0]"
+        ];
+
+        assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+
+        let mut media = Media::new(0, "./assets/with_audio.mp4", true);
+        media.probe.as_mut().unwrap().video_streams[0].field_order = Some("tt".into());
+
+        let test_filter_cmd = vec_strings![
+            "-filter_complex",
+            format!("[0:v:0]{filter}scale=1024:576[vout0];[0:a:0]anull[aout0]")
+        ];
+
+        assert_eq!(
+            filter_chains(&config, &mut media, &None).cmd(),
+            test_filter_cmd
+        );
+    }
 }
 
 #[test]
-fn video_dual_audio_filter_stream() {
+fn video_audio_hwaccel_deinterlace_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.processing.audio_tracks = 2;
-    config.text.fontfile = String::new();
-    config.out.output_cmd = Some(vec_strings![
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40051"
-    ]);
+    config.processing.deinterlace = Deinterlace::Yadif;
+    config.processing.hwaccel = HwAccel::Nvenc;
 
-    let enc_prefix = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0"
+    let mut media = Media::new(0, "./assets/with_audio.mp4", true);
+    media.probe.as_mut().unwrap().video_streams[0].field_order = Some("tt".into());
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]format=yuv420p,hwupload_cuda,yadif_cuda=0:-1:1,hwdownload,format=yuv420p,format=yuv420p,hwupload_cuda,scale_npp=1024:576,hwdownload,format=yuv420p[vout0];[0:a:0]anull[aout0]"
     ];
 
-    let socket = config
-        .text
-        .zmq_stream_socket
-        .clone()
-        .unwrap()
-        .replace(':', "\\:");
+    assert_eq!(
+        filter_chains(&config, &mut media, &None).cmd(),
+        test_filter_cmd
+    );
 
-    let mut media = Media::new(0, "", false);
-    media.unit = Encoder;
-    media.add_filter(&config, &None);
+    config.processing.deinterlace = Deinterlace::Bwdif;
+    config.processing.hwaccel = HwAccel::Vaapi;
 
-    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+    let mut media = Media::new(0, "./assets/with_audio.mp4", true);
+    media.probe.as_mut().unwrap().video_streams[0].field_order = Some("tt".into());
 
-    let test_cmd = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0",
+    let test_filter_cmd = vec_strings![
         "-filter_complex",
-        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text=''[vout0]"),
-        "-map",
-        "[vout0]",
-        "-map",
-        "0:a:0",
-        "-map",
-        "0:a:1",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40051"
+        "[0:v:0]format=nv12,hwupload,deinterlace_vaapi=mode=motion_adaptive,hwdownload,format=nv12,format=nv12,hwupload,scale_vaapi=w=1024:h=576,hwdownload,format=nv12[vout0];[0:a:0]anull[aout0]"
     ];
 
-    assert_eq!(enc_cmd, test_cmd);
+    assert_eq!(
+        filter_chains(&config, &mut media, &None).cmd(),
+        test_filter_cmd
+    );
 }
 
 #[test]
-fn video_audio_multi_stream() {
+fn video_audio_seek_mode_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.out.output_cmd = Some(vec_strings![
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost/live/stream",
-        "-s",
-        "512x288",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost:1936/live/stream"
-    ]);
 
-    let enc_prefix = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0"
-    ];
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    media_obj.seek = 20.0;
+    let media = gen_source(&config, media_obj.clone(), &None);
 
-    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &None);
+    assert_eq!(
+        media.cmd,
+        Some(vec_strings!["-ss", "20", "-i", "./assets/with_audio.mp4"])
+    );
 
-    let test_cmd = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost/live/stream",
-        "-s",
-        "512x288",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "flv",
-        "rtmp://localhost:1936/live/stream"
-    ];
+    config.processing.seek_mode = SeekMode::Accurate;
+    let media = gen_source(&config, media_obj.clone(), &None);
 
-    assert_eq!(enc_cmd, test_cmd);
+    assert_eq!(
+        media.cmd,
+        Some(vec_strings![
+            "-ss",
+            "17",
+            "-i",
+            "./assets/with_audio.mp4",
+            "-ss",
+            "3"
+        ])
+    );
+
+    media_obj.seek = 2.0;
+    let media = gen_source(&config, media_obj, &None);
+
+    assert_eq!(
+        media.cmd,
+        Some(vec_strings!["-i", "./assets/with_audio.mp4", "-ss", "2"])
+    );
 }
 
 #[test]
-fn video_dual_audio_multi_stream() {
+fn missing_source_filler() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.processing.audio_tracks = 2;
-    config.out.output_cmd = Some(vec_strings![
-        "-map",
-        "0:v",
-        "-map",
-        "0:a:0",
-        "-map",
-        "0:a:1",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40051",
-        "-map",
-        "0:v",
-        "-map",
-        "0:a:0",
-        "-map",
-        "0:a:1",
-        "-s",
-        "512x288",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40052"
-    ]);
-
-    let enc_prefix = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0"
-    ];
-
-    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &None);
+    config.storage.filler_clip = "./assets/with_audio.mp4".to_string();
 
-    let test_cmd = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0",
-        "-map",
-        "0:v",
-        "-map",
-        "0:a:0",
-        "-map",
-        "0:a:1",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40051",
-        "-map",
-        "0:v",
-        "-map",
-        "0:a:0",
-        "-map",
-        "0:a:1",
-        "-s",
-        "512x288",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40052"
-    ];
+    let mut media_obj = Media::new(0, "./assets/missing.mp4", false);
+    media_obj.duration = 20.0;
+    media_obj.out = 20.0;
+    let media = gen_source(&config, media_obj, &None);
 
-    assert_eq!(enc_cmd, test_cmd);
+    assert_eq!(media.source, "./assets/with_audio.mp4");
+    assert_eq!(
+        media.cmd,
+        Some(vec_strings!["-i", "./assets/with_audio.mp4", "-t", "20"])
+    );
 }
 
 #[test]
-fn video_audio_text_multi_stream() {
+fn short_source_filler() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.text.add_text = true;
-    config.text.fontfile = String::new();
-    config.out.output_count = 2;
-    config.out.output_cmd = Some(vec_strings![
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40051",
-        "-s",
-        "512x288",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40052"
-    ]);
+    config.storage.filler_clip = "./assets/av_sync.mp4".to_string();
 
-    let enc_prefix = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0"
-    ];
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    media_obj.begin = Some(100.0);
+    media_obj.out = 45.0;
 
-    let socket = config
-        .text
-        .zmq_stream_socket
-        .clone()
-        .unwrap()
-        .replace(':', "\\:");
+    let filler = fill_gap(&config, &mut media_obj).unwrap();
 
-    let mut media = Media::new(0, "", false);
-    media.unit = Encoder;
-    media.add_filter(&config, &None);
+    assert_eq!(media_obj.out, 30.0);
+    assert_eq!(filler.source, "./assets/av_sync.mp4");
+    assert_eq!(filler.begin, Some(130.0));
+    assert_eq!(filler.out, 15.0);
 
-    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+    let media = gen_source(&config, filler, &None);
 
-    let test_cmd = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        "level+error",
-        "-re",
-        "-i",
-        "pipe:0",
-        "-filter_complex",
-        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='',split=2[vout_0_0][vout_0_1]"),
-        "-map",
-        "[vout_0_0]",
-        "-map",
-        "0:a:0",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40051",
-        "-map",
-        "[vout_0_1]",
-        "-map",
-        "0:a:0",
-        "-s",
-        "512x288",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40052"
-    ];
+    assert_eq!(media.cmd.unwrap().last().unwrap(), "15");
 
-    assert_eq!(enc_cmd, test_cmd);
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+
+    assert!(fill_gap(&config, &mut media_obj).is_none());
 }
 
 #[test]
-fn video_dual_audio_multi_filter_stream() {
+fn video_audio_custom_filter1_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.processing.audio_tracks = 2;
-    config.out.output_count = 2;
-    config.text.fontfile = String::new();
-    config.out.output_cmd = Some(vec_strings![
-        "-map",
-        "0:v",
-        "-map",
-        "0:a:0",
-        "-map",
-        "0:a:1",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40051",
-        "-map",
-        "0:v",
-        "-map",
-        "0:a:0",
-        "-map",
-        "0:a:1",
-        "-s",
-        "512x288",
+    config.processing.custom_filter = "[0:v]gblur=2[c_v_out];[0:a]volume=0.2[c_a_out]".to_string();
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576,gblur=2[vout0];[0:a:0]anull,volume=0.2[aout0]"
+    ];
+
+    let test_filter_map = vec_strings!["-map", "[vout0]", "-map", "[aout0]"];
+
+    assert_eq!(
+        media.cmd,
+        Some(vec_strings!["-i", "./assets/with_audio.mp4"])
+    );
+    assert_eq!(media.filter.clone().unwrap().cmd(), test_filter_cmd);
+    assert_eq!(media.filter.unwrap().map(), test_filter_map);
+}
+
+#[test]
+fn video_audio_custom_filter2_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.processing.custom_filter =
+        "[0:v]null[v];movie=logo.png[l];[v][l]overlay[c_v_out];[0:a]volume=0.2[c_a_out]"
+            .to_string();
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576,null[v];movie=logo.png[l];[v][l]overlay[vout0];[0:a:0]anull,volume=0.2[aout0]"
+    ];
+
+    let test_filter_map = vec_strings!["-map", "[vout0]", "-map", "[aout0]"];
+
+    assert_eq!(
+        media.cmd,
+        Some(vec_strings!["-i", "./assets/with_audio.mp4"])
+    );
+    assert_eq!(media.filter.clone().unwrap().cmd(), test_filter_cmd);
+    assert_eq!(media.filter.unwrap().map(), test_filter_map);
+}
+
+#[test]
+fn video_audio_custom_filter3_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.processing.custom_filter =
+        "[v_in];movie=logo.png[l];[v_in][l]overlay[c_v_out];[0:a]volume=0.2[c_a_out]".to_string();
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576[v_in];movie=logo.png[l];[v_in][l]overlay[vout0];[0:a:0]anull,volume=0.2[aout0]"
+    ];
+
+    let test_filter_map = vec_strings!["-map", "[vout0]", "-map", "[aout0]"];
+
+    assert_eq!(
+        media.cmd,
+        Some(vec_strings!["-i", "./assets/with_audio.mp4"])
+    );
+    assert_eq!(media.filter.clone().unwrap().cmd(), test_filter_cmd);
+    assert_eq!(media.filter.unwrap().map(), test_filter_map);
+}
+
+#[test]
+fn video_audio_clip_custom_filter_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.processing.custom_filter = "[0:v]gblur=2[c_v_out]".to_string();
+
+    let file = fs::read_to_string("./assets/playlists/custom_filter.json").unwrap();
+    let playlist: JsonPlaylist = serde_json::from_str(&file).unwrap();
+
+    let media = gen_source(&config, playlist.program[0].clone(), &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576,gblur=2,boxblur=10[vout0];[0:a:0]anull,volume=0.5[aout0]"
+    ];
+
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+
+    // clip filter with reserved output link gets skipped
+    let media = gen_source(&config, playlist.program[1].clone(), &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576,gblur=2[vout0];[0:a:0]anull[aout0]"
+    ];
+
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+}
+
+#[test]
+fn mono_to_stereo_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    media_obj.probe.as_mut().unwrap().audio_streams[0].channels = Some(1);
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576[vout0];[0:a:0]aformat=channel_layouts=stereo,anull[aout0]"
+    ];
+
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+}
+
+#[test]
+fn surround_to_stereo_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    media_obj.probe.as_mut().unwrap().audio_streams[0].channels = Some(6);
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576[vout0];[0:a:0]aformat=channel_layouts=stereo,anull[aout0]"
+    ];
+
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+
+    config.processing.audio_channels = 6;
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576[vout0];[0:a:0]aformat=channel_layouts=5.1,anull[aout0]"
+    ];
+
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+}
+
+#[test]
+fn dual_audio_aevalsrc_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.audio_tracks = 2;
+    config.processing.add_logo = false;
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd =
+        vec_strings![
+            "-filter_complex",
+            "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0];aevalsrc=0:channel_layout=stereo:duration=30:sample_rate=48000,anull[aout1]"
+        ];
+
+    let test_filter_map = vec_strings!["-map", "[vout0]", "-map", "[aout0]", "-map", "[aout1]"];
+
+    assert_eq!(
+        media.cmd,
+        Some(vec_strings!["-i", "./assets/with_audio.mp4"])
+    );
+    assert_eq!(media.filter.clone().unwrap().cmd(), test_filter_cmd);
+    assert_eq!(media.filter.unwrap().map(), test_filter_map);
+}
+
+#[test]
+fn dual_audio_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.audio_tracks = 2;
+    config.processing.add_logo = false;
+
+    let media_obj = Media::new(0, "./assets/dual_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0];[0:a:1]anull[aout1]"
+    ];
+
+    let test_filter_map = vec_strings!["-map", "[vout0]", "-map", "[aout0]", "-map", "[aout1]"];
+
+    assert_eq!(
+        media.cmd,
+        Some(vec_strings!["-i", "./assets/dual_audio.mp4"])
+    );
+    assert_eq!(media.filter.clone().unwrap().cmd(), test_filter_cmd);
+    assert_eq!(media.filter.unwrap().map(), test_filter_map);
+}
+
+#[test]
+fn video_separate_audio_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.audio_tracks = 1;
+    config.processing.add_logo = false;
+
+    let mut media_obj = Media::new(0, "./assets/no_audio.mp4", true);
+    media_obj.audio = "./assets/audio.mp3".to_string();
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale=1024:576[vout0];[1:a:0]anull[aout0]"
+    ];
+
+    let test_filter_map = vec_strings!["-map", "[vout0]", "-map", "[aout0]"];
+
+    assert_eq!(
+        media.cmd,
+        Some(vec_strings![
+            "-i",
+            "./assets/no_audio.mp4",
+            "-i",
+            "./assets/audio.mp3",
+            "-t",
+            "30"
+        ])
+    );
+    assert_eq!(media.filter.clone().unwrap().cmd(), test_filter_cmd);
+    assert_eq!(media.filter.unwrap().map(), test_filter_map);
+}
+
+#[test]
+fn video_audio_stream() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.out.output_cmd = Some(vec_strings![
         "-c:v",
         "libx264",
         "-c:a",
@@ -1098,8 +986,8 @@ fn video_dual_audio_multi_filter_stream() {
         "-flags",
         "+global_header",
         "-f",
-        "mpegts",
-        "srt://127.0.0.1:40052"
+        "flv",
+        "rtmp://localhost/live/stream"
     ]);
 
     let enc_prefix = vec_strings![
@@ -1112,18 +1000,7 @@ fn video_dual_audio_multi_filter_stream() {
         "pipe:0"
     ];
 
-    let socket = config
-        .text
-        .zmq_stream_socket
-        .clone()
-        .unwrap()
-        .replace(':', "\\:");
-
-    let mut media = Media::new(0, "", false);
-    media.unit = Encoder;
-    media.add_filter(&config, &None);
-
-    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &None);
 
     let test_cmd = vec_strings![
         "-hide_banner",
@@ -1133,35 +1010,6 @@ fn video_dual_audio_multi_filter_stream() {
         "-re",
         "-i",
         "pipe:0",
-        "-filter_complex",
-        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='',split=2[vout_0_0][vout_0_1]"),
-        "-map",
-        "[vout_0_0]",
-        "-map",
-        "0:a:0",
-        "-map",
-        "0:a:1",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40051",
-        "-map",
-        "[vout_0_1]",
-        "-map",
-        "0:a:0",
-        "-map",
-        "0:a:1",
-        "-s",
-        "512x288",
         "-c:v",
         "libx264",
         "-c:a",
@@ -1173,46 +1021,25 @@ fn video_dual_audio_multi_filter_stream() {
         "-flags",
         "+global_header",
         "-f",
-        "mpegts",
-        "srt://127.0.0.1:40052"
+        "flv",
+        "rtmp://localhost/live/stream"
     ];
 
     assert_eq!(enc_cmd, test_cmd);
 }
 
 #[test]
-fn video_audio_text_filter_stream() {
+fn video_audio_filter1_stream() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
     config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.processing.audio_tracks = 1;
-    config.text.add_text = true;
-    config.text.fontfile = String::new();
-    config.out.output_count = 2;
+    config.text.add_text = false;
+    config.out.output_filter = Some("[0:v]gblur=2[vout0];[0:a]volume=0.2[aout0]".to_string());
     config.out.output_cmd = Some(vec_strings![
         "-map",
-        "0:v",
-        "-map",
-        "0:a:0",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40051",
-        "-map",
-        "0:v",
+        "[vout0]",
         "-map",
-        "0:a:0",
-        "-s",
-        "512x288",
+        "[aout0]",
         "-c:v",
         "libx264",
         "-c:a",
@@ -1224,10 +1051,14 @@ fn video_audio_text_filter_stream() {
         "-flags",
         "+global_header",
         "-f",
-        "mpegts",
-        "srt://127.0.0.1:40052"
+        "flv",
+        "rtmp://localhost/live/stream"
     ]);
 
+    let mut media = Media::new(0, "", false);
+    media.unit = Encoder;
+    media.add_filter(&config, &None);
+
     let enc_prefix = vec_strings![
         "-hide_banner",
         "-nostats",
@@ -1238,17 +1069,6 @@ fn video_audio_text_filter_stream() {
         "pipe:0"
     ];
 
-    let socket = config
-        .text
-        .zmq_stream_socket
-        .clone()
-        .unwrap()
-        .replace(':', "\\:");
-
-    let mut media = Media::new(0, "", false);
-    media.unit = Encoder;
-    media.add_filter(&config, &None);
-
     let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
 
     let test_cmd = vec_strings![
@@ -1260,30 +1080,11 @@ fn video_audio_text_filter_stream() {
         "-i",
         "pipe:0",
         "-filter_complex",
-        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='',split=2[vout_0_0][vout_0_1]"),
-        "-map",
-        "[vout_0_0]",
-        "-map",
-        "0:a:0",
-        "-c:v",
-        "libx264",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-b:a",
-        "128k",
-        "-flags",
-        "+global_header",
-        "-f",
-        "mpegts",
-        "srt://127.0.0.1:40051",
+        "[0:v]gblur=2[vout0];[0:a]volume=0.2[aout0]",
         "-map",
-        "[vout_0_1]",
+        "[vout0]",
         "-map",
-        "0:a:0",
-        "-s",
-        "512x288",
+        "[aout0]",
         "-c:v",
         "libx264",
         "-c:a",
@@ -1295,20 +1096,26 @@ fn video_audio_text_filter_stream() {
         "-flags",
         "+global_header",
         "-f",
-        "mpegts",
-        "srt://127.0.0.1:40052"
+        "flv",
+        "rtmp://localhost/live/stream"
     ];
 
     assert_eq!(enc_cmd, test_cmd);
 }
 
 #[test]
-fn video_audio_hls() {
+fn video_audio_filter2_stream() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
-    config.out.mode = HLS;
+    config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.text.add_text = false;
+    config.text.add_text = true;
+    config.text.fontfile = String::new();
+    config.out.output_filter = Some("[0:v]gblur=2[vout0];[0:a]volume=0.2[aout0]".to_string());
     config.out.output_cmd = Some(vec_strings![
+        "-map",
+        "[vout0]",
+        "-map",
+        "[aout0]",
         "-c:v",
         "libx264",
         "-c:a",
@@ -1318,22 +1125,22 @@ fn video_audio_hls() {
         "-b:a",
         "128k",
         "-flags",
-        "+cgop",
+        "+global_header",
         "-f",
-        "hls",
-        "-hls_time",
-        "6",
-        "-hls_list_size",
-        "600",
-        "-hls_flags",
-        "append_list+delete_segments+omit_endlist",
-        "-hls_segment_filename",
-        "/usr/share/ffplayout/public/live/stream-%d.ts",
-        "/usr/share/ffplayout/public/live/stream.m3u8"
+        "flv",
+        "rtmp://localhost/live/stream"
     ]);
 
-    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
-    let media = gen_source(&config, media_obj, &None);
+    let mut media = Media::new(0, "", false);
+    media.unit = Encoder;
+    media.add_filter(&config, &None);
+
+    let socket = config
+        .text
+        .zmq_stream_socket
+        .clone()
+        .unwrap()
+        .replace(':', "\\:");
 
     let enc_prefix = vec_strings![
         "-hide_banner",
@@ -1342,7 +1149,7 @@ fn video_audio_hls() {
         "level+error",
         "-re",
         "-i",
-        "./assets/with_audio.mp4"
+        "pipe:0"
     ];
 
     let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
@@ -1354,9 +1161,9 @@ fn video_audio_hls() {
         "level+error",
         "-re",
         "-i",
-        "./assets/with_audio.mp4",
+        "pipe:0",
         "-filter_complex",
-        "[0:v:0]scale=1024:576,realtime=speed=1[vout0];[0:a:0]anull[aout0]",
+        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='',gblur=2[vout0];[0:a]volume=0.2[aout0]"),
         "-map",
         "[vout0]",
         "-map",
@@ -1370,34 +1177,31 @@ fn video_audio_hls() {
         "-b:a",
         "128k",
         "-flags",
-        "+cgop",
+        "+global_header",
         "-f",
-        "hls",
-        "-hls_time",
-        "6",
-        "-hls_list_size",
-        "600",
-        "-hls_flags",
-        "append_list+delete_segments+omit_endlist",
-        "-hls_segment_filename",
-        "/usr/share/ffplayout/public/live/stream-%d.ts",
-        "/usr/share/ffplayout/public/live/stream.m3u8"
+        "flv",
+        "rtmp://localhost/live/stream"
     ];
 
     assert_eq!(enc_cmd, test_cmd);
 }
 
 #[test]
-fn video_audio_sub_meta_hls() {
+fn video_audio_filter3_stream() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
-    config.out.mode = HLS;
+    config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.text.add_text = false;
+    config.text.add_text = true;
+    config.text.fontfile = String::new();
+    config.out.output_filter = Some(
+        "[0:v]null[o];movie=/path/to/lower_third.png[l];[o][l]overlay=shortest=1[vout0]"
+            .to_string(),
+    );
     config.out.output_cmd = Some(vec_strings![
         "-map",
-        "0:s:0",
-        "-map_metadata",
-        "0",
+        "[vout0]",
+        "-map",
+        "0:a",
         "-c:v",
         "libx264",
         "-c:a",
@@ -1407,22 +1211,22 @@ fn video_audio_sub_meta_hls() {
         "-b:a",
         "128k",
         "-flags",
-        "+cgop",
+        "+global_header",
         "-f",
-        "hls",
-        "-hls_time",
-        "6",
-        "-hls_list_size",
-        "600",
-        "-hls_flags",
-        "append_list+delete_segments+omit_endlist",
-        "-hls_segment_filename",
-        "/usr/share/ffplayout/public/live/stream-%d.ts",
-        "/usr/share/ffplayout/public/live/stream.m3u8"
+        "flv",
+        "rtmp://localhost/live/stream"
     ]);
 
-    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
-    let media = gen_source(&config, media_obj, &None);
+    let mut media = Media::new(0, "", false);
+    media.unit = Encoder;
+    media.add_filter(&config, &None);
+
+    let socket = config
+        .text
+        .zmq_stream_socket
+        .clone()
+        .unwrap()
+        .replace(':', "\\:");
 
     let enc_prefix = vec_strings![
         "-hide_banner",
@@ -1431,7 +1235,7 @@ fn video_audio_sub_meta_hls() {
         "level+error",
         "-re",
         "-i",
-        "./assets/with_audio.mp4"
+        "pipe:0"
     ];
 
     let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
@@ -1443,17 +1247,13 @@ fn video_audio_sub_meta_hls() {
         "level+error",
         "-re",
         "-i",
-        "./assets/with_audio.mp4",
+        "pipe:0",
         "-filter_complex",
-        "[0:v:0]scale=1024:576,realtime=speed=1[vout0];[0:a:0]anull[aout0]",
+        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='',null[o];movie=/path/to/lower_third.png[l];[o][l]overlay=shortest=1[vout0]"),
         "-map",
         "[vout0]",
         "-map",
-        "[aout0]",
-        "-map",
-        "0:s:0",
-        "-map_metadata",
-        "0",
+        "0:a",
         "-c:v",
         "libx264",
         "-c:a",
@@ -1463,31 +1263,31 @@ fn video_audio_sub_meta_hls() {
         "-b:a",
         "128k",
         "-flags",
-        "+cgop",
+        "+global_header",
         "-f",
-        "hls",
-        "-hls_time",
-        "6",
-        "-hls_list_size",
-        "600",
-        "-hls_flags",
-        "append_list+delete_segments+omit_endlist",
-        "-hls_segment_filename",
-        "/usr/share/ffplayout/public/live/stream-%d.ts",
-        "/usr/share/ffplayout/public/live/stream.m3u8"
+        "flv",
+        "rtmp://localhost/live/stream"
     ];
 
     assert_eq!(enc_cmd, test_cmd);
 }
 
 #[test]
-fn video_multi_audio_hls() {
+fn video_audio_filter4_stream() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
-    config.out.mode = HLS;
+    config.out.mode = Stream;
     config.processing.add_logo = false;
-    config.processing.audio_tracks = 2;
-    config.text.add_text = false;
+    config.text.add_text = true;
+    config.text.fontfile = String::new();
+    config.out.output_filter = Some(
+        "[0:v]null[o];movie=/path/to/lower_third.png[l];[o][l]overlay=shortest=1[vout0];[0:a:0]volume=0.2[aout0]"
+            .to_string(),
+    );
     config.out.output_cmd = Some(vec_strings![
+        "-map",
+        "[vout0]",
+        "-map",
+        "[aout0]",
         "-c:v",
         "libx264",
         "-c:a",
@@ -1497,22 +1297,22 @@ fn video_multi_audio_hls() {
         "-b:a",
         "128k",
         "-flags",
-        "+cgop",
+        "+global_header",
         "-f",
-        "hls",
-        "-hls_time",
-        "6",
-        "-hls_list_size",
-        "600",
-        "-hls_flags",
-        "append_list+delete_segments+omit_endlist",
-        "-hls_segment_filename",
-        "/usr/share/ffplayout/public/live/stream-%d.ts",
-        "/usr/share/ffplayout/public/live/stream.m3u8"
+        "flv",
+        "rtmp://localhost/live/stream"
     ]);
 
-    let media_obj = Media::new(0, "./assets/dual_audio.mp4", true);
-    let media = gen_source(&config, media_obj, &None);
+    let mut media = Media::new(0, "", false);
+    media.unit = Encoder;
+    media.add_filter(&config, &None);
+
+    let socket = config
+        .text
+        .zmq_stream_socket
+        .clone()
+        .unwrap()
+        .replace(':', "\\:");
 
     let enc_prefix = vec_strings![
         "-hide_banner",
@@ -1521,7 +1321,7 @@ fn video_multi_audio_hls() {
         "level+error",
         "-re",
         "-i",
-        "./assets/dual_audio.mp4"
+        "pipe:0"
     ];
 
     let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
@@ -1533,15 +1333,13 @@ fn video_multi_audio_hls() {
         "level+error",
         "-re",
         "-i",
-        "./assets/dual_audio.mp4",
+        "pipe:0",
         "-filter_complex",
-        "[0:v:0]scale=1024:576,realtime=speed=1[vout0];[0:a:0]anull[aout0];[0:a:1]anull[aout1]",
+        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='',null[o];movie=/path/to/lower_third.png[l];[o][l]overlay=shortest=1[vout0];[0:a:0]volume=0.2[aout0]"),
         "-map",
         "[vout0]",
         "-map",
         "[aout0]",
-        "-map",
-        "[aout1]",
         "-c:v",
         "libx264",
         "-c:a",
@@ -1551,73 +1349,41 @@ fn video_multi_audio_hls() {
         "-b:a",
         "128k",
         "-flags",
-        "+cgop",
+        "+global_header",
         "-f",
-        "hls",
-        "-hls_time",
-        "6",
-        "-hls_list_size",
-        "600",
-        "-hls_flags",
-        "append_list+delete_segments+omit_endlist",
-        "-hls_segment_filename",
-        "/usr/share/ffplayout/public/live/stream-%d.ts",
-        "/usr/share/ffplayout/public/live/stream.m3u8"
+        "flv",
+        "rtmp://localhost/live/stream"
     ];
 
     assert_eq!(enc_cmd, test_cmd);
 }
 
 #[test]
-fn multi_video_audio_hls() {
+fn video_dual_audio_stream() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
-    config.out.mode = HLS;
+    config.out.mode = Stream;
     config.processing.add_logo = false;
+    config.processing.audio_tracks = 2;
     config.text.add_text = false;
-    config.out.output_count = 2;
-    config.out.output_filter = Some(
-        "[0:v]split=2[v1_out][v2];[v2]scale=w=512:h=288[v2_out];[0:a]asplit=2[a1][a2]".to_string(),
-    );
     config.out.output_cmd = Some(vec_strings![
-        "-map",
-        "[v1_out]",
-        "-map",
-        "[a1]",
         "-c:v",
         "libx264",
-        "-flags",
-        "+cgop",
         "-c:a",
         "aac",
-        "-map",
-        "[v2_out]",
-        "-map",
-        "[a2]",
-        "-c:v:1",
-        "libx264",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
         "-flags",
-        "+cgop",
-        "-c:a:1",
-        "aac",
+        "+global_header",
         "-f",
-        "hls",
-        "-hls_time",
-        "6",
-        "-hls_list_size",
-        "600",
-        "-hls_flags",
-        "append_list+delete_segments+omit_endlist",
-        "-hls_segment_filename",
-        "/usr/share/ffplayout/public/live/stream_%v-%d.ts",
-        "-master_pl_name",
-        "master.m3u8",
-        "-var_stream_map",
-        "v:0,a:0,name:720p v:1,a:1,name:288p",
-        "/usr/share/ffplayout/public/live/stream_%v.m3u8"
+        "mpegts",
+        "srt://127.0.0.1:40051"
     ]);
 
-    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
-    let media = gen_source(&config, media_obj, &None);
+    let mut media = Media::new(0, "", false);
+    media.unit = Encoder;
+    media.add_filter(&config, &None);
 
     let enc_prefix = vec_strings![
         "-hide_banner",
@@ -1626,7 +1392,7 @@ fn multi_video_audio_hls() {
         "level+error",
         "-re",
         "-i",
-        "./assets/with_audio.mp4"
+        "pipe:0"
     ];
 
     let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
@@ -1638,103 +1404,54 @@ fn multi_video_audio_hls() {
         "level+error",
         "-re",
         "-i",
-        "./assets/with_audio.mp4",
-        "-filter_complex",
-        "[0:v:0]scale=1024:576,realtime=speed=1,split=2[v1_out][v2];[v2]scale=w=512:h=288[v2_out];[0:a]asplit=2[a1][a2]",
+        "pipe:0",
         "-map",
-        "[v1_out]",
+        "0:v",
         "-map",
-        "[a1]",
+        "0:a:0",
+        "-map",
+        "0:a:1",
         "-c:v",
         "libx264",
-        "-flags",
-        "+cgop",
         "-c:a",
         "aac",
-        "-map",
-        "[v2_out]",
-        "-map",
-        "[a2]",
-        "-c:v:1",
-        "libx264",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
         "-flags",
-        "+cgop",
-        "-c:a:1",
-        "aac",
+        "+global_header",
         "-f",
-        "hls",
-        "-hls_time",
-        "6",
-        "-hls_list_size",
-        "600",
-        "-hls_flags",
-        "append_list+delete_segments+omit_endlist",
-        "-hls_segment_filename",
-        "/usr/share/ffplayout/public/live/stream_%v-%d.ts",
-        "-master_pl_name",
-        "master.m3u8",
-        "-var_stream_map",
-        "v:0,a:0,name:720p v:1,a:1,name:288p",
-        "/usr/share/ffplayout/public/live/stream_%v.m3u8"
+        "mpegts",
+        "srt://127.0.0.1:40051"
     ];
 
     assert_eq!(enc_cmd, test_cmd);
 }
 
 #[test]
-fn multi_video_multi_audio_hls() {
+fn video_dual_audio_filter_stream() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
-    config.out.mode = HLS;
+    config.out.mode = Stream;
     config.processing.add_logo = false;
     config.processing.audio_tracks = 2;
-    config.text.add_text = false;
-    config.out.output_count = 2;
-    config.out.output_filter = Some("[0:v]split=2[v1_out][v2];[v2]scale=w=512:h=288[v2_out];[0:a:0]asplit=2[a_0_1][a_0_2];[0:a:1]asplit=2[a_1_1][a_1_2]".to_string());
+    config.text.fontfile = String::new();
     config.out.output_cmd = Some(vec_strings![
-        "-map",
-        "[v1_out]",
-        "-map",
-        "[a_0_1]",
-        "-map",
-        "[a_1_1]",
         "-c:v",
         "libx264",
-        "-flags",
-        "+cgop",
         "-c:a",
         "aac",
-        "-map",
-        "[v2_out]",
-        "-map",
-        "[a_0_2]",
-        "-map",
-        "[a_1_2]",
-        "-c:v:1",
-        "libx264",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
         "-flags",
-        "+cgop",
-        "-c:a:1",
-        "aac",
+        "+global_header",
         "-f",
-        "hls",
-        "-hls_time",
-        "6",
-        "-hls_list_size",
-        "600",
-        "-hls_flags",
-        "append_list+delete_segments+omit_endlist",
-        "-hls_segment_filename",
-        "/usr/share/ffplayout/public/live/stream_%v-%d.ts",
-        "-master_pl_name",
-        "master.m3u8",
-        "-var_stream_map",
-        "v:0,a:0,a:1,name:720p v:1,a:2,a:3,name:288p",
-        "/usr/share/ffplayout/public/live/stream_%v.m3u8"
+        "mpegts",
+        "srt://127.0.0.1:40051"
     ]);
 
-    let media_obj = Media::new(0, "./assets/dual_audio.mp4", true);
-    let media = gen_source(&config, media_obj, &None);
-
     let enc_prefix = vec_strings![
         "-hide_banner",
         "-nostats",
@@ -1742,9 +1459,20 @@ fn multi_video_multi_audio_hls() {
         "level+error",
         "-re",
         "-i",
-        "./assets/dual_audio.mp4"
+        "pipe:0"
     ];
 
+    let socket = config
+        .text
+        .zmq_stream_socket
+        .clone()
+        .unwrap()
+        .replace(':', "\\:");
+
+    let mut media = Media::new(0, "", false);
+    media.unit = Encoder;
+    media.add_filter(&config, &None);
+
     let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
 
     let test_cmd = vec_strings![
@@ -1754,49 +1482,2505 @@ fn multi_video_multi_audio_hls() {
         "level+error",
         "-re",
         "-i",
-        "./assets/dual_audio.mp4",
+        "pipe:0",
         "-filter_complex",
-        "[0:v:0]scale=1024:576,realtime=speed=1,split=2[v1_out][v2];[v2]scale=w=512:h=288[v2_out];[0:a:0]anull,asplit=2[a_0_1][a_0_2];[0:a:1]anull,asplit=2[a_1_1][a_1_2]",
+        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text=''[vout0]"),
         "-map",
-        "[v1_out]",
+        "[vout0]",
         "-map",
-        "[a_0_1]",
+        "0:a:0",
         "-map",
-        "[a_1_1]",
+        "0:a:1",
         "-c:v",
         "libx264",
-        "-flags",
-        "+cgop",
         "-c:a",
         "aac",
-        "-map",
-        "[v2_out]",
-        "-map",
-        "[a_0_2]",
-        "-map",
-        "[a_1_2]",
-        "-c:v:1",
-        "libx264",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
         "-flags",
-        "+cgop",
-        "-c:a:1",
-        "aac",
+        "+global_header",
         "-f",
-        "hls",
-        "-hls_time",
-        "6",
-        "-hls_list_size",
-        "600",
-        "-hls_flags",
-        "append_list+delete_segments+omit_endlist",
-        "-hls_segment_filename",
-        "/usr/share/ffplayout/public/live/stream_%v-%d.ts",
-        "-master_pl_name",
-        "master.m3u8",
-        "-var_stream_map",
-        "v:0,a:0,a:1,name:720p v:1,a:2,a:3,name:288p",
-        "/usr/share/ffplayout/public/live/stream_%v.m3u8"
+        "mpegts",
+        "srt://127.0.0.1:40051"
     ];
 
     assert_eq!(enc_cmd, test_cmd);
 }
+
+#[test]
+fn video_audio_multi_stream() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.out.output_cmd = Some(vec_strings![
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "flv",
+        "rtmp://localhost/live/stream",
+        "-s",
+        "512x288",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "flv",
+        "rtmp://localhost:1936/live/stream"
+    ]);
+
+    let enc_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "pipe:0"
+    ];
+
+    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &None);
+
+    let test_cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "pipe:0",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "flv",
+        "rtmp://localhost/live/stream",
+        "-s",
+        "512x288",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "flv",
+        "rtmp://localhost:1936/live/stream"
+    ];
+
+    assert_eq!(enc_cmd, test_cmd);
+}
+
+#[test]
+fn video_dual_audio_multi_stream() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.processing.audio_tracks = 2;
+    config.out.output_cmd = Some(vec_strings![
+        "-map",
+        "0:v",
+        "-map",
+        "0:a:0",
+        "-map",
+        "0:a:1",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40051",
+        "-map",
+        "0:v",
+        "-map",
+        "0:a:0",
+        "-map",
+        "0:a:1",
+        "-s",
+        "512x288",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40052"
+    ]);
+
+    let enc_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "pipe:0"
+    ];
+
+    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &None);
+
+    let test_cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "pipe:0",
+        "-map",
+        "0:v",
+        "-map",
+        "0:a:0",
+        "-map",
+        "0:a:1",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40051",
+        "-map",
+        "0:v",
+        "-map",
+        "0:a:0",
+        "-map",
+        "0:a:1",
+        "-s",
+        "512x288",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40052"
+    ];
+
+    assert_eq!(enc_cmd, test_cmd);
+}
+
+#[test]
+fn video_audio_text_multi_stream() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.text.add_text = true;
+    config.text.fontfile = String::new();
+    config.out.output_count = 2;
+    config.out.output_cmd = Some(vec_strings![
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40051",
+        "-s",
+        "512x288",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40052"
+    ]);
+
+    let enc_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "pipe:0"
+    ];
+
+    let socket = config
+        .text
+        .zmq_stream_socket
+        .clone()
+        .unwrap()
+        .replace(':', "\\:");
+
+    let mut media = Media::new(0, "", false);
+    media.unit = Encoder;
+    media.add_filter(&config, &None);
+
+    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+
+    let test_cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "pipe:0",
+        "-filter_complex",
+        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='',split=2[vout_0_0][vout_0_1]"),
+        "-map",
+        "[vout_0_0]",
+        "-map",
+        "0:a:0",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40051",
+        "-map",
+        "[vout_0_1]",
+        "-map",
+        "0:a:0",
+        "-s",
+        "512x288",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40052"
+    ];
+
+    assert_eq!(enc_cmd, test_cmd);
+}
+
+#[test]
+fn video_dual_audio_multi_filter_stream() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.processing.audio_tracks = 2;
+    config.out.output_count = 2;
+    config.text.fontfile = String::new();
+    config.out.output_cmd = Some(vec_strings![
+        "-map",
+        "0:v",
+        "-map",
+        "0:a:0",
+        "-map",
+        "0:a:1",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40051",
+        "-map",
+        "0:v",
+        "-map",
+        "0:a:0",
+        "-map",
+        "0:a:1",
+        "-s",
+        "512x288",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40052"
+    ]);
+
+    let enc_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "pipe:0"
+    ];
+
+    let socket = config
+        .text
+        .zmq_stream_socket
+        .clone()
+        .unwrap()
+        .replace(':', "\\:");
+
+    let mut media = Media::new(0, "", false);
+    media.unit = Encoder;
+    media.add_filter(&config, &None);
+
+    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+
+    let test_cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "pipe:0",
+        "-filter_complex",
+        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='',split=2[vout_0_0][vout_0_1]"),
+        "-map",
+        "[vout_0_0]",
+        "-map",
+        "0:a:0",
+        "-map",
+        "0:a:1",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40051",
+        "-map",
+        "[vout_0_1]",
+        "-map",
+        "0:a:0",
+        "-map",
+        "0:a:1",
+        "-s",
+        "512x288",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40052"
+    ];
+
+    assert_eq!(enc_cmd, test_cmd);
+}
+
+#[test]
+fn video_audio_text_filter_stream() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.processing.audio_tracks = 1;
+    config.text.add_text = true;
+    config.text.fontfile = String::new();
+    config.out.output_count = 2;
+    config.out.output_cmd = Some(vec_strings![
+        "-map",
+        "0:v",
+        "-map",
+        "0:a:0",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40051",
+        "-map",
+        "0:v",
+        "-map",
+        "0:a:0",
+        "-s",
+        "512x288",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40052"
+    ]);
+
+    let enc_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "pipe:0"
+    ];
+
+    let socket = config
+        .text
+        .zmq_stream_socket
+        .clone()
+        .unwrap()
+        .replace(':', "\\:");
+
+    let mut media = Media::new(0, "", false);
+    media.unit = Encoder;
+    media.add_filter(&config, &None);
+
+    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+
+    let test_cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "pipe:0",
+        "-filter_complex",
+        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='',split=2[vout_0_0][vout_0_1]"),
+        "-map",
+        "[vout_0_0]",
+        "-map",
+        "0:a:0",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40051",
+        "-map",
+        "[vout_0_1]",
+        "-map",
+        "0:a:0",
+        "-s",
+        "512x288",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+global_header",
+        "-f",
+        "mpegts",
+        "srt://127.0.0.1:40052"
+    ];
+
+    assert_eq!(enc_cmd, test_cmd);
+}
+
+#[test]
+fn video_audio_hls() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = HLS;
+    config.processing.add_logo = false;
+    config.text.add_text = false;
+    config.out.output_cmd = Some(vec_strings![
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+cgop",
+        "-f",
+        "hls",
+        "-hls_time",
+        "6",
+        "-hls_list_size",
+        "600",
+        "-hls_flags",
+        "append_list+delete_segments+omit_endlist",
+        "-hls_segment_filename",
+        "/usr/share/ffplayout/public/live/stream-%d.ts",
+        "/usr/share/ffplayout/public/live/stream.m3u8"
+    ]);
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let enc_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "./assets/with_audio.mp4"
+    ];
+
+    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+
+    let test_cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "./assets/with_audio.mp4",
+        "-filter_complex",
+        "[0:v:0]scale=1024:576,realtime=speed=1[vout0];[0:a:0]anull[aout0]",
+        "-map",
+        "[vout0]",
+        "-map",
+        "[aout0]",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+cgop",
+        "-f",
+        "hls",
+        "-hls_time",
+        "6",
+        "-hls_list_size",
+        "600",
+        "-hls_flags",
+        "append_list+delete_segments+omit_endlist",
+        "-hls_segment_filename",
+        "/usr/share/ffplayout/public/live/stream-%d.ts",
+        "/usr/share/ffplayout/public/live/stream.m3u8"
+    ];
+
+    assert_eq!(enc_cmd, test_cmd);
+}
+
+#[test]
+fn video_audio_sub_meta_hls() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = HLS;
+    config.processing.add_logo = false;
+    config.text.add_text = false;
+    config.out.output_cmd = Some(vec_strings![
+        "-map",
+        "0:s:0",
+        "-map_metadata",
+        "0",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+cgop",
+        "-f",
+        "hls",
+        "-hls_time",
+        "6",
+        "-hls_list_size",
+        "600",
+        "-hls_flags",
+        "append_list+delete_segments+omit_endlist",
+        "-hls_segment_filename",
+        "/usr/share/ffplayout/public/live/stream-%d.ts",
+        "/usr/share/ffplayout/public/live/stream.m3u8"
+    ]);
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let enc_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "./assets/with_audio.mp4"
+    ];
+
+    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+
+    let test_cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "./assets/with_audio.mp4",
+        "-filter_complex",
+        "[0:v:0]scale=1024:576,realtime=speed=1[vout0];[0:a:0]anull[aout0]",
+        "-map",
+        "[vout0]",
+        "-map",
+        "[aout0]",
+        "-map",
+        "0:s:0",
+        "-map_metadata",
+        "0",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+cgop",
+        "-f",
+        "hls",
+        "-hls_time",
+        "6",
+        "-hls_list_size",
+        "600",
+        "-hls_flags",
+        "append_list+delete_segments+omit_endlist",
+        "-hls_segment_filename",
+        "/usr/share/ffplayout/public/live/stream-%d.ts",
+        "/usr/share/ffplayout/public/live/stream.m3u8"
+    ];
+
+    assert_eq!(enc_cmd, test_cmd);
+}
+
+#[test]
+fn video_multi_audio_hls() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = HLS;
+    config.processing.add_logo = false;
+    config.processing.audio_tracks = 2;
+    config.text.add_text = false;
+    config.out.output_cmd = Some(vec_strings![
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+cgop",
+        "-f",
+        "hls",
+        "-hls_time",
+        "6",
+        "-hls_list_size",
+        "600",
+        "-hls_flags",
+        "append_list+delete_segments+omit_endlist",
+        "-hls_segment_filename",
+        "/usr/share/ffplayout/public/live/stream-%d.ts",
+        "/usr/share/ffplayout/public/live/stream.m3u8"
+    ]);
+
+    let media_obj = Media::new(0, "./assets/dual_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let enc_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "./assets/dual_audio.mp4"
+    ];
+
+    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+
+    let test_cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "./assets/dual_audio.mp4",
+        "-filter_complex",
+        "[0:v:0]scale=1024:576,realtime=speed=1[vout0];[0:a:0]anull[aout0];[0:a:1]anull[aout1]",
+        "-map",
+        "[vout0]",
+        "-map",
+        "[aout0]",
+        "-map",
+        "[aout1]",
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-ar",
+        "44100",
+        "-b:a",
+        "128k",
+        "-flags",
+        "+cgop",
+        "-f",
+        "hls",
+        "-hls_time",
+        "6",
+        "-hls_list_size",
+        "600",
+        "-hls_flags",
+        "append_list+delete_segments+omit_endlist",
+        "-hls_segment_filename",
+        "/usr/share/ffplayout/public/live/stream-%d.ts",
+        "/usr/share/ffplayout/public/live/stream.m3u8"
+    ];
+
+    assert_eq!(enc_cmd, test_cmd);
+}
+
+#[test]
+fn abr_ladder_hls() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = HLS;
+    config.processing.add_logo = false;
+    config.text.add_text = false;
+    config.out.abr.enable = true;
+    config.out.abr.renditions = vec![
+        Rendition {
+            width: 1024,
+            height: 576,
+            video_bitrate: 2000,
+            audio_bitrate: 128,
+        },
+        Rendition {
+            width: 640,
+            height: 360,
+            video_bitrate: 800,
+            audio_bitrate: 96,
+        },
+        Rendition {
+            width: 384,
+            height: 216,
+            video_bitrate: 400,
+            audio_bitrate: 64,
+        },
+    ];
+    config.out.output_cmd = Some(abr_output_cmd(
+        vec_strings![
+            "-c:v",
+            "libx264",
+            "-flags",
+            "+cgop",
+            "-c:a",
+            "aac",
+            "-f",
+            "hls",
+            "-hls_segment_filename",
+            "/usr/share/ffplayout/public/live/stream_%v-%d.ts",
+            "/usr/share/ffplayout/public/live/stream_%v.m3u8"
+        ],
+        &config.out,
+    ));
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let enc_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "./assets/with_audio.mp4"
+    ];
+
+    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+
+    let test_cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "./assets/with_audio.mp4",
+        "-filter_complex",
+        "[0:v:0]scale=1024:576,realtime=speed=1,split=3[vsplit_0][vsplit_1][vsplit_2];[vsplit_0]scale=1024:576[vabr_0];[vsplit_1]scale=640:360[vabr_1];[vsplit_2]scale=384:216[vabr_2];[0:a:0]anull,asplit=3[aabr_0][aabr_1][aabr_2]",
+        "-map",
+        "[vabr_0]",
+        "-map",
+        "[aabr_0]",
+        "-map",
+        "[vabr_1]",
+        "-map",
+        "[aabr_1]",
+        "-map",
+        "[vabr_2]",
+        "-map",
+        "[aabr_2]",
+        "-c:v",
+        "libx264",
+        "-flags",
+        "+cgop",
+        "-c:a",
+        "aac",
+        "-b:v:0",
+        "2000k",
+        "-b:a:0",
+        "128k",
+        "-b:v:1",
+        "800k",
+        "-b:a:1",
+        "96k",
+        "-b:v:2",
+        "400k",
+        "-b:a:2",
+        "64k",
+        "-f",
+        "hls",
+        "-hls_segment_filename",
+        "/usr/share/ffplayout/public/live/stream_%v-%d.ts",
+        "-master_pl_name",
+        "master.m3u8",
+        "-var_stream_map",
+        "v:0,a:0,name:576p v:1,a:1,name:360p v:2,a:2,name:216p",
+        "/usr/share/ffplayout/public/live/stream_%v.m3u8"
+    ];
+
+    assert_eq!(enc_cmd, test_cmd);
+
+    // every rung needs a positive bitrate
+    config.out.abr.renditions[1].video_bitrate = 0;
+
+    assert_eq!(
+        config.validate().unwrap_err()[0].field,
+        "out.abr.renditions[1]".to_string()
+    );
+
+    config.out.abr.renditions.clear();
+
+    assert_eq!(
+        config.validate().unwrap_err()[0].field,
+        "out.abr.renditions".to_string()
+    );
+}
+
+#[test]
+fn multi_video_audio_hls() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = HLS;
+    config.processing.add_logo = false;
+    config.text.add_text = false;
+    config.out.output_count = 2;
+    config.out.output_filter = Some(
+        "[0:v]split=2[v1_out][v2];[v2]scale=w=512:h=288[v2_out];[0:a]asplit=2[a1][a2]".to_string(),
+    );
+    config.out.output_cmd = Some(vec_strings![
+        "-map",
+        "[v1_out]",
+        "-map",
+        "[a1]",
+        "-c:v",
+        "libx264",
+        "-flags",
+        "+cgop",
+        "-c:a",
+        "aac",
+        "-map",
+        "[v2_out]",
+        "-map",
+        "[a2]",
+        "-c:v:1",
+        "libx264",
+        "-flags",
+        "+cgop",
+        "-c:a:1",
+        "aac",
+        "-f",
+        "hls",
+        "-hls_time",
+        "6",
+        "-hls_list_size",
+        "600",
+        "-hls_flags",
+        "append_list+delete_segments+omit_endlist",
+        "-hls_segment_filename",
+        "/usr/share/ffplayout/public/live/stream_%v-%d.ts",
+        "-master_pl_name",
+        "master.m3u8",
+        "-var_stream_map",
+        "v:0,a:0,name:720p v:1,a:1,name:288p",
+        "/usr/share/ffplayout/public/live/stream_%v.m3u8"
+    ]);
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let enc_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "./assets/with_audio.mp4"
+    ];
+
+    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+
+    let test_cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "./assets/with_audio.mp4",
+        "-filter_complex",
+        "[0:v:0]scale=1024:576,realtime=speed=1,split=2[v1_out][v2];[v2]scale=w=512:h=288[v2_out];[0:a]asplit=2[a1][a2]",
+        "-map",
+        "[v1_out]",
+        "-map",
+        "[a1]",
+        "-c:v",
+        "libx264",
+        "-flags",
+        "+cgop",
+        "-c:a",
+        "aac",
+        "-map",
+        "[v2_out]",
+        "-map",
+        "[a2]",
+        "-c:v:1",
+        "libx264",
+        "-flags",
+        "+cgop",
+        "-c:a:1",
+        "aac",
+        "-f",
+        "hls",
+        "-hls_time",
+        "6",
+        "-hls_list_size",
+        "600",
+        "-hls_flags",
+        "append_list+delete_segments+omit_endlist",
+        "-hls_segment_filename",
+        "/usr/share/ffplayout/public/live/stream_%v-%d.ts",
+        "-master_pl_name",
+        "master.m3u8",
+        "-var_stream_map",
+        "v:0,a:0,name:720p v:1,a:1,name:288p",
+        "/usr/share/ffplayout/public/live/stream_%v.m3u8"
+    ];
+
+    assert_eq!(enc_cmd, test_cmd);
+}
+
+#[test]
+fn multi_video_multi_audio_hls() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = HLS;
+    config.processing.add_logo = false;
+    config.processing.audio_tracks = 2;
+    config.text.add_text = false;
+    config.out.output_count = 2;
+    config.out.output_filter = Some("[0:v]split=2[v1_out][v2];[v2]scale=w=512:h=288[v2_out];[0:a:0]asplit=2[a_0_1][a_0_2];[0:a:1]asplit=2[a_1_1][a_1_2]".to_string());
+    config.out.output_cmd = Some(vec_strings![
+        "-map",
+        "[v1_out]",
+        "-map",
+        "[a_0_1]",
+        "-map",
+        "[a_1_1]",
+        "-c:v",
+        "libx264",
+        "-flags",
+        "+cgop",
+        "-c:a",
+        "aac",
+        "-map",
+        "[v2_out]",
+        "-map",
+        "[a_0_2]",
+        "-map",
+        "[a_1_2]",
+        "-c:v:1",
+        "libx264",
+        "-flags",
+        "+cgop",
+        "-c:a:1",
+        "aac",
+        "-f",
+        "hls",
+        "-hls_time",
+        "6",
+        "-hls_list_size",
+        "600",
+        "-hls_flags",
+        "append_list+delete_segments+omit_endlist",
+        "-hls_segment_filename",
+        "/usr/share/ffplayout/public/live/stream_%v-%d.ts",
+        "-master_pl_name",
+        "master.m3u8",
+        "-var_stream_map",
+        "v:0,a:0,a:1,name:720p v:1,a:2,a:3,name:288p",
+        "/usr/share/ffplayout/public/live/stream_%v.m3u8"
+    ]);
+
+    let media_obj = Media::new(0, "./assets/dual_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    let enc_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "./assets/dual_audio.mp4"
+    ];
+
+    let enc_cmd = prepare_output_cmd(&config, enc_prefix, &media.filter);
+
+    let test_cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "level+error",
+        "-re",
+        "-i",
+        "./assets/dual_audio.mp4",
+        "-filter_complex",
+        "[0:v:0]scale=1024:576,realtime=speed=1,split=2[v1_out][v2];[v2]scale=w=512:h=288[v2_out];[0:a:0]anull,asplit=2[a_0_1][a_0_2];[0:a:1]anull,asplit=2[a_1_1][a_1_2]",
+        "-map",
+        "[v1_out]",
+        "-map",
+        "[a_0_1]",
+        "-map",
+        "[a_1_1]",
+        "-c:v",
+        "libx264",
+        "-flags",
+        "+cgop",
+        "-c:a",
+        "aac",
+        "-map",
+        "[v2_out]",
+        "-map",
+        "[a_0_2]",
+        "-map",
+        "[a_1_2]",
+        "-c:v:1",
+        "libx264",
+        "-flags",
+        "+cgop",
+        "-c:a:1",
+        "aac",
+        "-f",
+        "hls",
+        "-hls_time",
+        "6",
+        "-hls_list_size",
+        "600",
+        "-hls_flags",
+        "append_list+delete_segments+omit_endlist",
+        "-hls_segment_filename",
+        "/usr/share/ffplayout/public/live/stream_%v-%d.ts",
+        "-master_pl_name",
+        "master.m3u8",
+        "-var_stream_map",
+        "v:0,a:0,a:1,name:720p v:1,a:2,a:3,name:288p",
+        "/usr/share/ffplayout/public/live/stream_%v.m3u8"
+    ];
+
+    assert_eq!(enc_cmd, test_cmd);
+}
+
+#[test]
+fn hls_discontinuity_flag() {
+    let cmd = vec_strings![
+        "-c:v",
+        "libx264",
+        "-f",
+        "hls",
+        "-hls_time",
+        "6",
+        "-hls_flags",
+        "append_list+delete_segments+omit_endlist",
+        "/usr/share/ffplayout/public/live/stream.m3u8"
+    ];
+
+    let test_cmd = vec_strings![
+        "-c:v",
+        "libx264",
+        "-f",
+        "hls",
+        "-hls_time",
+        "6",
+        "-hls_flags",
+        "append_list+delete_segments+omit_endlist+discont_start",
+        "/usr/share/ffplayout/public/live/stream.m3u8"
+    ];
+
+    assert_eq!(discontinuity_cmd(cmd), test_cmd);
+
+    let cmd = vec_strings![
+        "-f",
+        "hls",
+        "-hls_time",
+        "6",
+        "/usr/share/ffplayout/public/live/stream.m3u8"
+    ];
+
+    let test_cmd = vec_strings![
+        "-f",
+        "hls",
+        "-hls_flags",
+        "discont_start",
+        "-hls_time",
+        "6",
+        "/usr/share/ffplayout/public/live/stream.m3u8"
+    ];
+
+    assert_eq!(discontinuity_cmd(cmd), test_cmd);
+}
+
+#[test]
+fn ad_break_cue_tags() {
+    let config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+
+    assert_eq!(ad_break_length(&config, 30.0), 30.0);
+    assert_eq!(ad_break_length(&config, 25.0), 30.0);
+
+    let playlist = "#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXTINF:6.000000,
+stream-1.ts
+#EXT-X-DISCONTINUITY
+#EXTINF:6.000000,
+stream-2.ts
+#EXTINF:6.000000,
+stream-3.ts
+#EXT-X-DISCONTINUITY
+#EXTINF:6.000000,
+stream-4.ts
+";
+
+    let marks = vec![CueMark {
+        first: "stream-2.ts".into(),
+        last: "stream-3.ts".into(),
+        duration: 12.0,
+    }];
+
+    let tagged = cue_playlist(playlist, &marks).unwrap();
+
+    assert_eq!(
+        tagged,
+        "#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXTINF:6.000000,
+stream-1.ts
+#EXT-X-DISCONTINUITY
+#EXT-X-CUE-OUT:DURATION=12
+#EXTINF:6.000000,
+stream-2.ts
+#EXTINF:6.000000,
+stream-3.ts
+#EXT-X-CUE-IN
+#EXT-X-DISCONTINUITY
+#EXTINF:6.000000,
+stream-4.ts
+"
+    );
+    assert_eq!(cue_playlist(&tagged, &marks), None);
+}
+
+#[test]
+fn remote_input_reconnect() {
+    // without the field the default is the same as in the example config
+    let yml = fs::read_to_string("../assets/ffplayout.yml")
+        .unwrap()
+        .replace("    input_reconnect: 5\n", "");
+    let path = env::temp_dir().join(format!("ffplayout_reconnect_{}.yml", process::id()));
+    fs::write(&path, yml).unwrap();
+
+    let config = PlayoutConfig::new(Some(path.to_string_lossy().to_string()));
+    fs::remove_file(path).unwrap();
+
+    assert_eq!(config.processing.input_reconnect, 5);
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.processing.input_reconnect = 10;
+
+    assert!(is_remote("https://example.org/clip.mp4"));
+    assert!(!is_remote("./assets/with_audio.mp4"));
+
+    let media_obj = Media::new(0, "https://example.org/clip.mp4", false);
+
+    assert_eq!(
+        seek_and_length(&config, &media_obj),
+        vec_strings![
+            "-reconnect",
+            "1",
+            "-reconnect_streamed",
+            "1",
+            "-reconnect_delay_max",
+            "10",
+            "-i",
+            "https://example.org/clip.mp4"
+        ]
+    );
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", false);
+
+    assert_eq!(
+        seek_and_length(&config, &media_obj),
+        vec_strings!["-i", "./assets/with_audio.mp4"]
+    );
+
+    config.processing.input_reconnect = 0;
+    let media_obj = Media::new(0, "https://example.org/clip.mp4", false);
+
+    assert_eq!(
+        seek_and_length(&config, &media_obj),
+        vec_strings!["-i", "https://example.org/clip.mp4"]
+    );
+}
+
+#[test]
+fn remote_input_headers() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.processing.input_reconnect = 5;
+
+    let mut media_obj = Media::new(0, "https://example.org/private/clip.mp4", false);
+    media_obj.seek = 10.0;
+    media_obj.source_headers = "Authorization: Bearer abc\nX-Channel: main".to_string();
+
+    assert_eq!(
+        seek_and_length(&config, &media_obj),
+        vec_strings![
+            "-ss",
+            "10",
+            "-reconnect",
+            "1",
+            "-reconnect_streamed",
+            "1",
+            "-reconnect_delay_max",
+            "5",
+            "-headers",
+            "Authorization: Bearer abc\r\nX-Channel: main\r\n",
+            "-i",
+            "https://example.org/private/clip.mp4"
+        ]
+    );
+
+    // headers are only for http sources
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", false);
+    media_obj.source_headers = "Authorization: Bearer abc".to_string();
+
+    assert_eq!(
+        seek_and_length(&config, &media_obj),
+        vec_strings!["-i", "./assets/with_audio.mp4"]
+    );
+}
+
+#[test]
+fn source_scheme_resolver() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.storage.resolvers = vec![SourceResolver {
+        scheme: "s3".to_string(),
+        command: "echo https://signed.example.org/?src={source}".to_string(),
+    }];
+
+    assert_eq!(
+        resolve_source(&config, "s3://bucket/clip.mp4"),
+        Ok("https://signed.example.org/?src=s3://bucket/clip.mp4".to_string())
+    );
+    assert_eq!(
+        resolve_source(&config, "./assets/with_audio.mp4"),
+        Ok("./assets/with_audio.mp4".to_string())
+    );
+
+    // a hanging resolver gets stopped
+    config.storage.resolvers[0].command = "sleep 30".to_string();
+    let start = Instant::now();
+
+    assert!(resolve_source(&config, "s3://bucket/clip.mp4").is_err());
+    assert!(start.elapsed() < RESOLVER_TIMEOUT + Duration::from_secs(5));
+}
+
+#[test]
+fn encoder_stall_watchdog() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.processing.stall_timeout_seconds = 2;
+    let proc_control = ProcessControl::new();
+
+    assert_eq!(out_time("out_time_ms=1200000"), Some(1200000));
+    assert_eq!(out_time("out_time=00:00:01.200000"), None);
+
+    assert!(!check_stall(&config, &proc_control));
+
+    *proc_control.last_progress.lock().unwrap() = Instant::now() - Duration::from_secs(5);
+    proc_control.watchdog_pause.store(true, Ordering::SeqCst);
+
+    assert!(!check_stall(&config, &proc_control));
+
+    *proc_control.last_progress.lock().unwrap() = Instant::now() - Duration::from_secs(5);
+    proc_control.watchdog_pause.store(false, Ordering::SeqCst);
+
+    assert!(check_stall(&config, &proc_control));
+    assert!(proc_control.encoder_stalled.load(Ordering::SeqCst));
+}
+
+/// Mpegts packets with a timestamp every 10 ms of media, from `from_ms` until `to_ms`.
+fn media_packets(from_ms: u64, to_ms: u64) -> Vec<u8> {
+    (from_ms..=to_ms)
+        .step_by(10)
+        .flat_map(|ms| pts_packet(ms * 90))
+        .collect()
+}
+
+#[test]
+fn preroll_bursty_source() {
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let mut preroll = Preroll::new(100);
+
+    // the encoder got one second of the clip before
+    assert!(preroll.push(&media_packets(0, 1000), at(0)).is_some());
+
+    preroll.start();
+    assert!(preroll.is_filling());
+
+    // the new source comes in bursts with gaps, it is held back by media time
+    let mut released = vec![];
+
+    for (ms, from, to) in [(300, 0, 20), (302, 30, 50), (340, 60, 90), (345, 100, 110)] {
+        if let Some(data) = preroll.push(&media_packets(from, to), at(ms)) {
+            assert!(to >= 100, "preroll released after {to} ms media");
+            released.extend_from_slice(data);
+        }
+    }
+
+    assert!(!preroll.is_filling());
+
+    let mut clock = MediaClock::new();
+    clock.push(&released);
+
+    assert!(clock.elapsed() >= 0.1);
+
+    // the rest passes through
+    assert!(preroll.push(&media_packets(120, 200), at(350)).is_some());
+
+    // the encoder has nothing left from before, so nothing is held back
+    preroll.start();
+
+    assert!(preroll.push(&media_packets(0, 20), at(5000)).is_some());
+    assert!(!preroll.is_filling());
+
+    // without a consumer the buffer stays bounded
+    let mut preroll = Preroll::new(1000).with_max_bytes(64);
+    preroll.push(&media_packets(0, 5000), start);
+    preroll.start();
+
+    assert!(preroll.push(&[0; 40], start).is_none());
+    assert_eq!(preroll.push(&[0; 40], start).map(|d| d.len()), Some(80));
+
+    // a source, which ends before the depth is reached
+    preroll.start();
+    preroll.push(&[1; 10], start);
+
+    assert_eq!(preroll.flush(), vec![1; 10]);
+    assert!(!preroll.is_filling());
+
+    // depth 0 disables the preroll
+    let mut preroll = Preroll::new(0);
+    preroll.start();
+
+    assert_eq!(preroll.push(&[2; 3], start), Some(&[2u8; 3][..]));
+}
+
+#[test]
+fn video_audio_fit_mode_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+
+    let modes = [
+        (
+            FitMode::Pad,
+            "scale=1024:576:force_original_aspect_ratio=decrease,pad=1024:576:(ow-iw)/2:(oh-ih)/2",
+        ),
+        (
+            FitMode::Crop,
+            "scale=1024:576:force_original_aspect_ratio=increase,crop=1024:576",
+        ),
+    ];
+
+    for (mode, filter) in modes {
+        config.processing.fit_mode = mode;
+
+        let mut media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+        media_obj.probe.as_mut().unwrap().video_streams[0].display_aspect_ratio =
+            Some("4:3".to_string());
+        let media = gen_source(&config, media_obj, &None);
+
+        let test_filter_cmd = vec_strings![
+            "-filter_complex",
+            format!("[0:v:0]{filter},setdar=dar=1.778[vout0];[0:a:0]anull[aout0]")
+        ];
+
+        assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+    }
+
+    // clips with the right aspect are only scaled
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    assert_eq!(
+        media.filter.unwrap().cmd(),
+        vec_strings![
+            "-filter_complex",
+            "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0]"
+        ]
+    );
+}
+
+#[test]
+fn video_audio_color_normalize_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj.clone(), &None);
+
+    assert_eq!(
+        media.filter.unwrap().cmd(),
+        vec_strings![
+            "-filter_complex",
+            "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0]"
+        ]
+    );
+
+    config.processing.color_normalize = true;
+    config.processing.color_range = ColorRange::Full;
+    let media = gen_source(&config, media_obj, &None);
+
+    assert_eq!(
+        media.filter.unwrap().cmd(),
+        vec_strings![
+            "-filter_complex",
+            "[0:v:0]scale=1024:576,scale=in_color_matrix=auto:out_color_matrix=bt709:out_range=full,setrange=full[vout0];[0:a:0]anull[aout0]"
+        ]
+    );
+}
+
+#[test]
+fn video_audio_sync_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.processing.audio_sync = true;
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    assert_eq!(
+        media.filter.unwrap().cmd(),
+        vec_strings![
+            "-filter_complex",
+            "[0:v:0]scale=1024:576[vout0];[0:a:0]aresample=async=1,anull[aout0]"
+        ]
+    );
+}
+
+#[test]
+fn av_start_delta_threshold() {
+    assert!(!av_desync(0.0));
+    assert!(!av_desync(0.1));
+    assert!(!av_desync(-0.05));
+    assert!(av_desync(0.25));
+    assert!(av_desync(-1.5));
+}
+
+/// Start of a video frame in a mpegts packet, with the timestamp.
+fn pts_packet(pts: u64) -> Vec<u8> {
+    let mut packet = vec![0xFF; 188];
+    packet[..13].copy_from_slice(&[0x47, 0x41, 0x00, 0x10, 0, 0, 1, 0xE0, 0, 0, 0x80, 0x80, 5]);
+    packet[13] = 0x21 | ((pts >> 29) & 0x0E) as u8;
+    packet[14] = (pts >> 22) as u8;
+    packet[15] = 0x01 | ((pts >> 14) & 0xFE) as u8;
+    packet[16] = (pts >> 7) as u8;
+    packet[17] = 0x01 | ((pts << 1) & 0xFE) as u8;
+
+    packet
+}
+
+#[test]
+fn media_clock_pts() {
+    assert_eq!(ts_pts(&pts_packet(8589934591)), Some(8589934591));
+    assert_eq!(ts_pts(&pts_packet(900000)), Some(900000));
+
+    // a packet, which continues a frame, has no timestamp
+    let mut packet = pts_packet(900000);
+    packet[1] = 0x01;
+    assert_eq!(ts_pts(&packet), None);
+
+    let mut clock = MediaClock::new();
+    let data = [pts_packet(900000), pts_packet(1080000), pts_packet(990000)].concat();
+
+    // the reads can end in the middle of a packet
+    clock.push(&data[..300]);
+    assert_eq!(clock.elapsed(), 0.0);
+
+    clock.push(&data[300..]);
+    assert_eq!(clock.elapsed(), 2.0);
+
+    clock.reset();
+    assert_eq!(clock.elapsed(), 0.0);
+}
+
+#[test]
+fn concat_next_decoder() {
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", false);
+    media_obj.seek = 5.0;
+    media_obj.out = 15.0;
+
+    let mut clock = MediaClock::new();
+    clock.push(&pts_packet(0));
+    clock.push(&pts_packet(((10.0 - CONCAT_LEAD - 1.0) * 90000.0) as u64));
+
+    assert!(!next_due(&media_obj, &clock));
+
+    // the media time counts, not how long the decoder runs
+    clock.push(&pts_packet(((10.0 - CONCAT_LEAD) * 90000.0) as u64));
+
+    assert!(next_due(&media_obj, &clock));
+}
+
+#[test]
+fn video_audio_pix_fmt_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+
+    assert_eq!(
+        media.filter.unwrap().cmd(),
+        vec_strings![
+            "-filter_complex",
+            "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0]"
+        ]
+    );
+
+    for pix_fmt in ["yuv422p", "yuv420p10le"] {
+        config.processing.pix_fmt = pix_fmt.to_string();
+
+        let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+        let media = gen_source(&config, media_obj, &None);
+
+        assert_eq!(
+            media.filter.unwrap().cmd(),
+            vec_strings![
+                "-filter_complex",
+                format!("[0:v:0]scale=1024:576,format={pix_fmt}[vout0];[0:a:0]anull[aout0]")
+            ]
+        );
+    }
+}
+
+#[test]
+fn processing_pix_fmt_args() {
+    let config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    let cmd = config.processing.cmd.unwrap();
+
+    assert_eq!(
+        cmd[..6],
+        vec_strings!["-pix_fmt", "yuv420p", "-r", "25", "-c:v", "mpeg2video"]
+    );
+    assert_eq!(bit_depth("yuv420p"), 8);
+    assert_eq!(bit_depth("yuv420p10le"), 10);
+    assert_eq!(bit_depth("p010le"), 10);
+    assert_eq!(bit_depth("yuv444p12be"), 12);
+
+    let yaml = fs::read_to_string("../assets/ffplayout.yml")
+        .unwrap()
+        .replace("pix_fmt: yuv420p", "pix_fmt: yuv420p10le");
+    let path = std::env::temp_dir().join("ffplayout_pix_fmt.yml");
+    fs::write(&path, yaml).unwrap();
+
+    let mut config = PlayoutConfig::new(Some(path.to_string_lossy().to_string()));
+    let cmd = config.processing.cmd.clone().unwrap();
+
+    assert_eq!(
+        cmd[..8],
+        vec_strings![
+            "-pix_fmt",
+            "yuv420p10le",
+            "-r",
+            "25",
+            "-c:v",
+            "libx264",
+            "-preset",
+            "ultrafast"
+        ]
+    );
+
+    // 10 bit works with x264, but not with the mpeg2 encoder
+    assert_eq!(eight_bit_encoder(&config), None);
+
+    config.out.output_cmd = Some(vec_strings!["-c:v", "mpeg2video", "-f", "mpegts", "-"]);
+    assert_eq!(eight_bit_encoder(&config), Some("mpeg2video".to_string()));
+
+    config.processing.pix_fmt = "yuv420p".to_string();
+    assert_eq!(eight_bit_encoder(&config), None);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn intermediate_pix_fmt() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+
+    // 4:4:4 is not possible with mpeg2video
+    assert_eq!(intermediate_codec("yuv422p"), Some("mpeg2video"));
+    assert_eq!(intermediate_codec("yuv444p"), Some("libx264"));
+    assert_eq!(intermediate_codec("yuv444p10le"), Some("libx264"));
+
+    let yaml = fs::read_to_string("../assets/ffplayout.yml")
+        .unwrap()
+        .replace("pix_fmt: yuv420p", "pix_fmt: yuv444p");
+    let path = env::temp_dir().join(format!("ffplayout_pix_fmt_{}.yml", process::id()));
+    fs::write(&path, yaml).unwrap();
+
+    let config_444 = PlayoutConfig::new(Some(path.to_string_lossy().to_string()));
+    fs::remove_file(path).unwrap();
+    let cmd = config_444.processing.cmd.clone().unwrap();
+
+    assert!(config_444.validate().is_ok());
+    assert_eq!(
+        cmd[cmd.iter().position(|c| c == "-c:v").unwrap() + 1],
+        "libx264"
+    );
+
+    config.processing.pix_fmt = "yuv444p12le".to_string();
+
+    assert!(config
+        .validate()
+        .unwrap_err()
+        .iter()
+        .any(|e| e.field == "processing.pix_fmt"));
+}
+
+#[test]
+fn print_spawn_commands() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.out.output_cmd = Some(vec_strings![
+        "-c:v",
+        "libx264",
+        "-f",
+        "flv",
+        "rtmp://127.0.0.1/live/stream"
+    ]);
+    config.processing.add_logo = false;
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+    let lines = print_commands(&config, Some(media.clone()));
+
+    let dec_cmd = decoder_cmd(
+        &config,
+        &log_format(&config),
+        media.cmd.clone().unwrap(),
+        media.filter.clone(),
+    );
+    let (program, enc_cmd) = encoder_cmd(&config, &log_format(&config));
+
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("Decoder: ffmpeg -hide_banner -nostats -v level+"));
+    assert!(lines[0].contains(" -i ./assets/with_audio.mp4 -filter_complex '[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0]' "));
+    assert_eq!(program, "ffmpeg");
+
+    // the printed lines split back to the commands, which get spawned
+    let decoder = shlex::split(lines[0].strip_prefix("Decoder: ").unwrap()).unwrap();
+    let encoder = shlex::split(lines[1].strip_prefix("Encoder: ").unwrap()).unwrap();
+
+    assert_eq!(decoder[0], "ffmpeg");
+    assert_eq!(decoder[1..], dec_cmd);
+    assert_eq!(encoder[0], "ffmpeg");
+    assert_eq!(encoder[1..], enc_cmd);
+}
+
+#[test]
+fn print_commands_unprobed() {
+    let dir = env::temp_dir().join(format!("ffplayout_print_{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    // no media file, printing the commands must not probe it
+    fs::write(dir.join("clip.mp4"), "no media").unwrap();
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.processing.mode = ProcessMode::Folder;
+    config.storage.path = dir.display().to_string();
+    config.out.mode = Stream;
+    config.out.output_cmd = Some(vec_strings![
+        "-c:v",
+        "libx264",
+        "-f",
+        "flv",
+        "rtmp://127.0.0.1/live/stream?token=secret"
+    ]);
+
+    let lines = print_commands(&config, None);
+
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains(&format!("-i {}/clip.mp4", dir.display())));
+    assert!(lines[1].ends_with("rtmp://127.0.0.1/live/stream?token=*****"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn ffmpeg_threads_args() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.out.output_cmd = Some(vec_strings![
+        "-c:v",
+        "libx264",
+        "-f",
+        "flv",
+        "rtmp://127.0.0.1/live/stream"
+    ]);
+
+    let input = vec_strings!["-i", "./assets/with_audio.mp4"];
+    let dec_cmd = decoder_cmd(&config, "level+error", input.clone(), None);
+
+    assert_eq!(
+        dec_cmd[..6],
+        vec_strings![
+            "-hide_banner",
+            "-nostats",
+            "-v",
+            "level+error",
+            "-i",
+            "./assets/with_audio.mp4"
+        ]
+    );
+
+    config.processing.ffmpeg_threads = 2;
+    config.processing.filter_threads = 1;
+
+    // decoder threads stand before the input
+    let dec_cmd = decoder_cmd(&config, "level+error", input, None);
+
+    assert_eq!(
+        dec_cmd[..10],
+        vec_strings![
+            "-hide_banner",
+            "-nostats",
+            "-v",
+            "level+error",
+            "-threads",
+            "2",
+            "-filter_threads",
+            "1",
+            "-i",
+            "./assets/with_audio.mp4"
+        ]
+    );
+
+    // encoder threads stand after the input
+    let (_, enc_cmd) = encoder_cmd(&config, "level+error");
+    let pos = enc_cmd.iter().position(|c| c == "pipe:0").unwrap();
+
+    assert_eq!(
+        enc_cmd[pos - 1..pos + 5],
+        vec_strings!["-i", "pipe:0", "-threads", "2", "-filter_threads", "1"]
+    );
+
+    config.processing.ffmpeg_threads = -1;
+    assert_eq!(
+        config.validate().unwrap_err()[0].field,
+        "processing.ffmpeg_threads"
+    );
+}
+
+#[test]
+fn realtime_read_inputs() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.timestamp = TimestampMode::Setpts;
+
+    let input = vec_strings!["-ss", 10, "-i", "./assets/with_audio.mp4", "-t", 20];
+    let dec_cmd = decoder_cmd(&config, "level+error", input.clone(), None);
+
+    assert!(!dec_cmd.contains(&"-re".to_string()));
+
+    config.processing.realtime_read = true;
+
+    // -re belongs to the input, so it stands before its seek
+    let dec_cmd = decoder_cmd(&config, "level+error", input, None);
+
+    assert_eq!(
+        dec_cmd[4..11],
+        vec_strings!["-re", "-ss", 10, "-i", "./assets/with_audio.mp4", "-t", 20]
+    );
+
+    // every file input gets it
+    let input = vec_strings!["-i", "./assets/with_audio.mp4", "-i", "./assets/audio.mp3"];
+    let dec_cmd = decoder_cmd(&config, "level+error", input, None);
+
+    assert_eq!(
+        dec_cmd[4..10],
+        vec_strings![
+            "-re",
+            "-i",
+            "./assets/with_audio.mp4",
+            "-re",
+            "-i",
+            "./assets/audio.mp3"
+        ]
+    );
+
+    // live sources never
+    for source in ["rtmp://127.0.0.1/live/stream", "srt://127.0.0.1:40051"] {
+        let dec_cmd = decoder_cmd(&config, "level+error", vec_strings!["-i", source], None);
+
+        assert_eq!(dec_cmd[4..6], vec_strings!["-i", source]);
+    }
+
+    config.ingest.enable = true;
+    let ingest = ingest_cmd(&config, &None);
+
+    assert!(!ingest.contains(&"-re".to_string()));
+}
+
+#[test]
+fn clip_audio_gain() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.processing.custom_filter = "[0:a]volume=0.2[c_a_out]".to_string();
+
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    media_obj.audio_gain_db = -6.5;
+    let media = gen_source(&config, media_obj, &None);
+
+    // clip gain stands before the global audio filter
+    assert_eq!(
+        media.filter.unwrap().cmd(),
+        vec_strings![
+            "-filter_complex",
+            "[0:v:0]scale=1024:576[vout0];[0:a:0]anull,volume=-6.5dB,volume=0.2[aout0]"
+        ]
+    );
+
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    media_obj.audio_gain_db = 45.0;
+    let media = gen_source(&config, media_obj, &None);
+
+    assert!(media.filter.unwrap().cmd()[1].contains("anull,volume=30dB,volume=0.2[aout0]"));
+
+    // loudnorm would level the gain out, so the gain comes after it
+    config.processing.loudnorm.enable = true;
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    media_obj.audio_gain_db = -6.5;
+    let media = gen_source(&config, media_obj, &None);
+    let filter = media.filter.unwrap().cmd()[1].clone();
+
+    assert!(filter.find("loudnorm=").unwrap() < filter.find("volume=-6.5dB").unwrap());
+
+    let item: Media = serde_json::from_str(
+        r#"{"in": 0, "out": 10, "duration": 10, "source": "clip.mp4", "audio_gain_db": 3}"#,
+    )
+    .unwrap();
+
+    assert_eq!(item.audio_gain_db, 3.0);
+
+    // a changed gain or title is a changed playlist
+    let mut other = item.clone();
+    other.audio_gain_db = 0.0;
+    assert_ne!(item, other);
+
+    let mut other = item.clone();
+    other.title = "News".to_string();
+    assert_ne!(item, other);
+}
+
+#[test]
+fn censor_region_filter() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = true;
+    config.processing.logo = "./assets/logo.png".to_string();
+
+    let blur = CensorRegion {
+        x: 100,
+        y: 50,
+        width: 200,
+        height: 120,
+        start: 5.0,
+        end: 20.0,
+        ..Default::default()
+    };
+    let delogo = CensorRegion {
+        mode: CensorMode::Delogo,
+        x: 900,
+        y: 10,
+        width: 100,
+        height: 40,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        censor_filter(&blur, 0),
+        "split[cb0][cr0];[cr0]crop=200:120:100:50,boxblur=10:1[cs0];[cb0][cs0]overlay=100:50:enable='between(t,5,20)'"
+    );
+    assert_eq!(censor_filter(&delogo, 1), "delogo=x=900:y=10:w=100:h=40");
+
+    config.processing.censor = vec![blur, delogo];
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let media = gen_source(&config, media_obj, &None);
+    let filter = media.filter.unwrap().cmd()[1].clone();
+
+    // the regions get censored before the logo overlay
+    assert!(filter.starts_with("[0:v:0]scale=1024:576,split[cb0][cr0];[cr0]crop=200:120:100:50"));
+    assert!(filter.contains(
+        "overlay=100:50:enable='between(t,5,20)',delogo=x=900:y=10:w=100:h=40,null[v];movie="
+    ));
+
+    assert!(config.validate().is_ok());
+
+    config.processing.censor[1].x = 950;
+    assert_eq!(
+        config.validate().unwrap_err()[0].message,
+        "region 100x40 at 950:10 is outside of the 1024x576 frame"
+    );
+
+    config.processing.censor[1].x = 900;
+    config.processing.censor[0].strength = 40;
+    assert_eq!(
+        config.validate().unwrap_err()[0].message,
+        "strength must be between 1 and 30, a quarter of the region size"
+    );
+
+    // huge values can't overflow the frame check
+    let region = CensorRegion {
+        mode: CensorMode::Delogo,
+        x: u32::MAX,
+        y: u32::MAX,
+        width: 100,
+        height: 40,
+        ..Default::default()
+    };
+
+    assert!(region.check(1024, 576).is_err());
+}
+
+#[test]
+fn encoder_fallback_ladder() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.fallback.slow_seconds = 10;
+    config.out.fallback.cooldown_seconds = 60;
+    let mut ladder = FallbackLadder::new(&config);
+    let start = Instant::now();
+    let at = |sec: u64| start + Duration::from_secs(sec);
+
+    // short spikes and unknown speed change nothing
+    assert_eq!(ladder.update(0.8, at(0)), None);
+    assert_eq!(ladder.update(0.0, at(5)), None);
+    assert_eq!(ladder.update(1.0, at(6)), None);
+    assert_eq!(ladder.update(0.8, at(7)), None);
+    assert_eq!(ladder.update(0.9, at(16)), None);
+
+    assert_eq!(
+        ladder.update(0.9, at(17)),
+        Some(FallbackAction::Downgrade(1))
+    );
+    assert_eq!(ladder.update(0.7, at(18)), None);
+    assert_eq!(
+        ladder.update(0.7, at(28)),
+        Some(FallbackAction::Downgrade(2))
+    );
+
+    // the ladder has only two steps
+    assert_eq!(ladder.update(0.5, at(29)), None);
+    assert_eq!(ladder.update(0.5, at(60)), None);
+    assert_eq!(ladder.level(), 2);
+
+    // restore needs the cooldown in realtime
+    assert_eq!(ladder.update(1.0, at(61)), None);
+    assert_eq!(ladder.update(0.95, at(100)), None);
+    assert_eq!(ladder.update(1.01, at(101)), None);
+    assert_eq!(
+        ladder.update(1.2, at(161)),
+        Some(FallbackAction::Upgrade(1))
+    );
+    assert_eq!(ladder.update(1.0, at(200)), None);
+    assert_eq!(
+        ladder.update(1.0, at(260)),
+        Some(FallbackAction::Upgrade(0))
+    );
+    assert_eq!(ladder.update(1.0, at(500)), None);
+    assert_eq!(ladder.level(), 0);
+}
+
+#[test]
+fn encoder_fallback_cmd() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.output_cmd = Some(vec_strings![
+        "-c:v",
+        "libx264",
+        "-preset",
+        "faster",
+        "-f",
+        "flv",
+        "rtmp://127.0.0.1/live/stream"
+    ]);
+
+    assert_eq!(
+        fallback_config(&config, 0).out.output_cmd,
+        config.out.output_cmd
+    );
+    assert_eq!(
+        fallback_config(&config, 1).out.output_cmd,
+        Some(vec_strings![
+            "-c:v",
+            "libx264",
+            "-preset",
+            "veryfast",
+            "-f",
+            "flv",
+            "-s",
+            "854x480",
+            "rtmp://127.0.0.1/live/stream"
+        ])
+    );
+
+    config.out.mode = Stream;
+    config.out.fallback.enable = true;
+    assert!(config.validate().is_ok());
+
+    config.out.fallback.ladder[1].size = "360p".to_string();
+    assert_eq!(
+        config.validate().unwrap_err()[0].message,
+        "'360p' is no size like 854x480"
+    );
+}
+
+#[test]
+fn timestamp_modes() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+
+    let input = vec_strings!["-i", "./assets/with_audio.mp4"];
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let filter = |config: &PlayoutConfig| {
+        gen_source(config, media_obj.clone(), &None)
+            .filter
+            .unwrap()
+            .cmd()
+    };
+
+    // auto is the default, ffmpeg handles the timestamps like before
+    let dec_cmd = decoder_cmd(&config, "level+error", input.clone(), None);
+
+    assert_eq!(config.processing.timestamp, TimestampMode::Auto);
+    assert_eq!(dec_cmd[4..6], vec_strings!["-i", "./assets/with_audio.mp4"]);
+    assert!(!dec_cmd.contains(&"+genpts".to_string()));
+    assert_eq!(
+        filter(&config)[1],
+        "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0]"
+    );
+
+    // genpts regenerates the timestamps on every input
+    config.processing.timestamp = TimestampMode::Genpts;
+    let dec_cmd = decoder_cmd(&config, "level+error", input.clone(), None);
+
+    assert_eq!(
+        dec_cmd[4..8],
+        vec_strings!["-fflags", "+genpts", "-i", "./assets/with_audio.mp4"]
+    );
+    assert!(!dec_cmd.contains(&"-copyts".to_string()));
+    assert_eq!(
+        filter(&config)[1],
+        "[0:v:0]scale=1024:576[vout0];[0:a:0]anull[aout0]"
+    );
+
+    // copyts keeps the source timestamps
+    config.processing.timestamp = TimestampMode::Copyts;
+    let dec_cmd = decoder_cmd(&config, "level+error", input.clone(), None);
+
+    assert_eq!(
+        dec_cmd[4..7],
+        vec_strings!["-copyts", "-i", "./assets/with_audio.mp4"]
+    );
+    assert!(!dec_cmd.contains(&"+genpts".to_string()));
+
+    // setpts starts video and audio of every clip at zero
+    config.processing.timestamp = TimestampMode::Setpts;
+    let dec_cmd = decoder_cmd(&config, "level+error", input, None);
+
+    assert_eq!(dec_cmd[4..6], vec_strings!["-i", "./assets/with_audio.mp4"]);
+    assert_eq!(
+        filter(&config)[1],
+        "[0:v:0]setpts=PTS-STARTPTS,scale=1024:576[vout0];[0:a:0]asetpts=PTS-STARTPTS,anull[aout0]"
+    );
+}
+
+#[test]
+fn output_metadata_args() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.general.channel = "Channel 1".into();
+
+    let mut node = Media::new(0, "assets/with_audio.mp4", false);
+    node.title = "Morning News".into();
+    let next = Media::new(1, "/media/weather_report.mp4", false);
+
+    assert!(metadata_cmd(&config, Some(&node), Some(&next)).is_empty());
+
+    config.out.metadata.enable = true;
+
+    assert_eq!(
+        metadata_cmd(&config, Some(&node), Some(&next)),
+        vec_strings![
+            "-metadata",
+            "service_name=Channel 1",
+            "-metadata",
+            "title=Morning News - Channel 1",
+            "-metadata",
+            "comment=Next: weather_report"
+        ]
+    );
+
+    // title from file name, without next clip
+    config.out.metadata.title = "{title} | next: {next}".into();
+
+    assert_eq!(
+        metadata_cmd(&config, Some(&next), None),
+        vec_strings![
+            "-metadata",
+            "service_name=Channel 1",
+            "-metadata",
+            "title=weather_report | next: "
+        ]
+    );
+    assert_eq!(
+        metadata_cmd(&config, None, None),
+        vec_strings!["-metadata", "service_name=Channel 1"]
+    );
+
+    // the long running encoder has the channel on every output
+    config.out.mode = Stream;
+    config.out.output_cmd = Some(vec_strings![
+        "-c:v",
+        "libx264",
+        "-f",
+        "flv",
+        "rtmp://127.0.0.1/live/stream",
+        "-c:v",
+        "libx264",
+        "-f",
+        "mpegts",
+        "udp://127.0.0.1:1234"
+    ]);
+
+    let (_, enc_cmd) = encoder_cmd(&config, "level+error");
+    let targets: Vec<usize> = enc_cmd
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.starts_with("rtmp://") || c.starts_with("udp://"))
+        .map(|(i, _)| i)
+        .collect();
+
+    assert_eq!(targets.len(), 2);
+
+    for i in targets {
+        assert_eq!(
+            enc_cmd[i - 2..i],
+            vec_strings!["-metadata", "service_name=Channel 1"]
+        );
+    }
+
+    config.out.mode = HLS;
+    config.out.metadata.program_date_time = true;
+    config.out.output_cmd = Some(vec_strings![
+        "-c:v",
+        "libx264",
+        "-f",
+        "hls",
+        "-hls_flags",
+        "append_list+delete_segments",
+        "/usr/share/ffplayout/public/live/stream.m3u8"
+    ]);
+
+    let cmd = hls_writer_cmd(
+        &config,
+        &node,
+        Some(&next),
+        vec_strings!["-i", node.source],
+        false,
+    );
+    let output = cmd.iter().position(|c| c == "-c:v").unwrap();
+
+    assert_eq!(
+        cmd[output..],
+        vec_strings![
+            "-c:v",
+            "libx264",
+            "-f",
+            "hls",
+            "-hls_flags",
+            "append_list+delete_segments+program_date_time",
+            "-metadata",
+            "service_name=Channel 1",
+            "-metadata",
+            "title=Morning News | next: weather_report",
+            "-metadata",
+            "comment=Next: weather_report",
+            "/usr/share/ffplayout/public/live/stream.m3u8"
+        ]
+    );
+
+    // playlist title survives the serialization
+    let json = serde_json::to_string(&node).unwrap();
+    let media: Media = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(media.title, "Morning News");
+}
+
+#[test]
+fn decode_recovery_retry() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    let proc_control = ProcessControl::new();
+    let ff_log_format = log_format(&config);
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let node = gen_source(&config, media_obj, &None);
+    let input = node.cmd.clone().unwrap();
+
+    // a successful run or a run with delivered data gets no retry
+    assert!(recovery_node(&node, false, false).is_none());
+    assert!(recovery_node(&node, true, true).is_none());
+
+    // first run fails
+    assert_eq!(proc_control.count_clip_failure(&node.source), 1);
+    let retry = recovery_node(&node, true, false).unwrap();
+    let retry_cmd = retry.cmd.clone().unwrap();
+
+    assert_eq!(retry_cmd, recovery_input_cmd(&input));
+    assert_eq!(retry_cmd[..4], RECOVERY_FLAGS.map(|f| f.to_string()));
+    assert_eq!(retry_cmd[4..], input[..]);
+    assert_eq!(retry.source, node.source);
+
+    // the recovery flags come after the general input flags, so they win
+    let dec_cmd = decoder_cmd(&config, &ff_log_format, retry_cmd, retry.filter.clone());
+    let input_pos = dec_cmd.iter().position(|c| c == "-i").unwrap();
+
+    assert_eq!(
+        dec_cmd[input_pos - 4..input_pos],
+        RECOVERY_FLAGS.map(|f| f.to_string())
+    );
+
+    // the retry runs fine, the failure stays in the status
+    assert!(recovery_node(&retry, false, false).is_none());
+    assert_eq!(
+        proc_control
+            .channel_status()
+            .clip_failures
+            .get(&node.source),
+        Some(&1)
+    );
+
+    // a second failed clip is counted separately
+    assert_eq!(
+        proc_control.count_clip_failure("./assets/short_video.mp4"),
+        1
+    );
+    assert_eq!(proc_control.count_clip_failure(&node.source), 2);
+    assert_eq!(proc_control.channel_status().clip_failures.len(), 2);
+}
+
+#[test]
+fn pip_overlay_filter() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.ingest.enable = true;
+    config.ingest.takeover = false;
+    config.ingest.pip.enable = true;
+    config.ingest.pip.width = 320;
+    config.ingest.pip.height = 180;
+    config.ingest.pip.x = "20".into();
+    config.ingest.pip.y = "H-h-20".into();
+
+    assert_eq!(
+        pip_filter(&config, 1),
+        "null[vpip];[1:v:0]scale=320:180,setpts=PTS-STARTPTS[pip];[vpip][pip]overlay=20:H-h-20:eof_action=pass"
+    );
+
+    config.ingest.pip.border = 4;
+    config.ingest.pip.border_color = "black".into();
+
+    assert_eq!(
+        pip_filter(&config, 2),
+        "null[vpip];[2:v:0]scale=320:180,setpts=PTS-STARTPTS,pad=328:188:4:4:color=black[pip];[vpip][pip]overlay=20:H-h-20:eof_action=pass"
+    );
+
+    // only with a live stream
+    assert!(!pip_active(&config, false));
+    assert!(pip_active(&config, true));
+
+    assert!(!config
+        .validate()
+        .err()
+        .unwrap_or_default()
+        .iter()
+        .any(|e| e.field == "ingest.pip.enable"));
+
+    // the output filter would replace the overlay
+    let mut output_filter = config.clone();
+    output_filter.out.output_filter = Some("[0:v]null[v]".into());
+
+    assert!(output_filter
+        .validate()
+        .unwrap_err()
+        .iter()
+        .any(|e| e.field == "ingest.pip.enable"));
+
+    let media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    let mut node = gen_source(&config, media_obj, &None);
+    let (input, mut filter) = pip_cmd(&config, node.cmd.take().unwrap(), node.filter.take());
+    let pip_input = input.iter().rposition(|i| i == "-i").unwrap();
+
+    assert_eq!(
+        input[pip_input - 4..],
+        vec_strings!["-thread_queue_size", "1024", "-f", "mpegts", "-i", "pipe:0"]
+    );
+
+    // the box goes over the processed clip, on the ingest from the second input
+    let filter_cmd = filter.cmd();
+    assert!(filter_cmd[1].contains(",null[vpip];[1:v:0]scale=320:180"));
+    assert!(filter_cmd[1].contains("overlay=20:H-h-20:eof_action=pass[vout0]"));
+
+    // pip and takeover exclude each other
+    config.ingest.takeover = true;
+
+    assert!(config
+        .validate()
+        .unwrap_err()
+        .iter()
+        .any(|e| e.field == "ingest.pip.enable"));
+}
@@ -0,0 +1,434 @@
+use std::{
+    env, fs,
+    io::{Read, Write},
+    net::TcpStream,
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use ffplayout::utils::control::{control_server, seek_position, SeekTarget};
+use ffplayout_lib::utils::{
+    playlist_file, write_playlist, JsonPlaylist, Media, OutputMode, PlayerControl, PlayoutConfig,
+    PlayoutStatus, ProcessControl, ProcessMode,
+};
+
+#[test]
+fn control_endpoints() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.control.enable = true;
+    config.control.address = "127.0.0.1:9601".into();
+    config.control.token = "secret".into();
+    let play_control = PlayerControl::new();
+    let playout_stat = PlayoutStatus::new();
+    let proc_control = ProcessControl::new();
+    let play_stat = playout_stat.clone();
+    let live = config.live.clone();
+
+    thread::spawn(move || control_server(config, play_control, play_stat, proc_control));
+    thread::sleep(Duration::from_millis(500));
+
+    let client = reqwest::blocking::Client::new();
+
+    let unauthorized = client.get("http://127.0.0.1:9601/status").send().unwrap();
+    assert_eq!(unauthorized.status(), 401);
+
+    let wrong_token = client
+        .get("http://127.0.0.1:9601/status")
+        .bearer_auth("wrong")
+        .send()
+        .unwrap();
+    assert_eq!(wrong_token.status(), 401);
+
+    let status: serde_json::Value = client
+        .get("http://127.0.0.1:9601/status")
+        .bearer_auth("secret")
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(status["ingest_runs"], false);
+
+    let reload = client
+        .post("http://127.0.0.1:9601/control/reload")
+        .bearer_auth("secret")
+        .send()
+        .unwrap();
+    assert!(reload.status().is_success());
+    assert!(playout_stat.reload.load(Ordering::SeqCst));
+
+    let ad_break = client
+        .post("http://127.0.0.1:9601/control/adbreak?duration=30")
+        .bearer_auth("secret")
+        .send()
+        .unwrap();
+    assert!(ad_break.status().is_success());
+    assert_eq!(*playout_stat.ad_break.lock().unwrap(), Some(30.0));
+
+    let invalid = client
+        .post("http://127.0.0.1:9601/control/adbreak")
+        .bearer_auth("secret")
+        .send()
+        .unwrap();
+    assert_eq!(invalid.status(), 400);
+
+    playout_stat.list_init.store(false, Ordering::SeqCst);
+
+    let filters = client
+        .post("http://127.0.0.1:9601/control/filters")
+        .bearer_auth("secret")
+        .send()
+        .unwrap();
+    assert!(filters.status().is_success());
+    assert!(playout_stat.list_init.load(Ordering::SeqCst));
+
+    let censor = client
+        .post("http://127.0.0.1:9601/control/censor")
+        .bearer_auth("secret")
+        .body(r#"{"x": 10, "y": 10, "width": 100, "height": 80}"#)
+        .send()
+        .unwrap();
+    assert!(censor.status().is_success());
+    assert_eq!(
+        live.read().unwrap().censor.as_ref().map(|c| c.len()),
+        Some(1)
+    );
+
+    let outside = client
+        .post("http://127.0.0.1:9601/control/censor")
+        .bearer_auth("secret")
+        .body(r#"{"x": 1000, "y": 10, "width": 100, "height": 80}"#)
+        .send()
+        .unwrap();
+    assert_eq!(outside.status(), 400);
+
+    let clear = client
+        .delete("http://127.0.0.1:9601/control/censor")
+        .bearer_auth("secret")
+        .send()
+        .unwrap();
+    assert!(clear.status().is_success());
+    assert!(live.read().unwrap().censor.is_none());
+}
+
+#[test]
+fn control_pause() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.control.enable = true;
+    config.control.address = "127.0.0.1:9605".into();
+    config.control.token = "secret".into();
+    config.out.mode = OutputMode::Stream;
+    config.general.stat_file = env::temp_dir()
+        .join(format!("ffplayout_pause_{}.json", process::id()))
+        .to_string_lossy()
+        .to_string();
+    let stat_file = config.general.stat_file.clone();
+    let playout_stat = PlayoutStatus::new();
+    let play_stat = playout_stat.clone();
+
+    thread::spawn(move || {
+        control_server(
+            config,
+            PlayerControl::new(),
+            play_stat,
+            ProcessControl::new(),
+        )
+    });
+    thread::sleep(Duration::from_millis(500));
+
+    let client = reqwest::blocking::Client::new();
+
+    let not_paused = client
+        .post("http://127.0.0.1:9605/control/resume")
+        .bearer_auth("secret")
+        .send()
+        .unwrap();
+    assert_eq!(not_paused.status(), 400);
+
+    let pause = client
+        .post("http://127.0.0.1:9605/control/pause")
+        .bearer_auth("secret")
+        .send()
+        .unwrap();
+    assert!(pause.status().is_success());
+    assert!(playout_stat.pause.lock().unwrap().is_some());
+
+    let paused_again = client
+        .post("http://127.0.0.1:9605/control/pause")
+        .bearer_auth("secret")
+        .send()
+        .unwrap();
+    assert_eq!(paused_again.status(), 400);
+
+    thread::sleep(Duration::from_millis(200));
+
+    let resume = client
+        .post("http://127.0.0.1:9605/control/resume")
+        .bearer_auth("secret")
+        .send()
+        .unwrap();
+    assert!(resume.status().is_success());
+    assert!(playout_stat.pause.lock().unwrap().is_none());
+    assert!(*playout_stat.time_shift.lock().unwrap() <= -0.2);
+
+    let _ = fs::remove_file(stat_file);
+}
+
+fn raw_request(request: &str) -> String {
+    let mut stream = TcpStream::connect("127.0.0.1:9604").unwrap();
+    let mut response = String::new();
+
+    stream.write_all(request.as_bytes()).unwrap();
+    stream.read_to_string(&mut response).unwrap();
+
+    response
+}
+
+#[test]
+fn control_request_limits() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.control.enable = true;
+    config.control.address = "127.0.0.1:9604".into();
+    config.control.token = "secret".into();
+
+    thread::spawn(move || {
+        control_server(
+            config,
+            PlayerControl::new(),
+            PlayoutStatus::new(),
+            ProcessControl::new(),
+        )
+    });
+    thread::sleep(Duration::from_millis(500));
+
+    // an idle client must not block the others
+    let _idle = TcpStream::connect("127.0.0.1:9604").unwrap();
+
+    let unauthorized =
+        raw_request("POST /control/censor HTTP/1.1\r\nContent-Length: 1000000000\r\n\r\n");
+    assert!(unauthorized.starts_with("HTTP/1.1 401"));
+
+    let too_large = raw_request(
+        "POST /control/censor HTTP/1.1\r\nAuthorization: Bearer secret\r\nContent-Length: 1000000000\r\n\r\n",
+    );
+    assert!(too_large.starts_with("HTTP/1.1 413"));
+
+    let long_header = format!(
+        "GET /status HTTP/1.1\r\nX-Long: {}\r\n\r\n",
+        "a".repeat(20 * 1024)
+    );
+    assert!(raw_request(&long_header).starts_with("HTTP/1.1 413"));
+
+    let status = raw_request("GET /status HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n");
+    assert!(status.starts_with("HTTP/1.1 200"));
+}
+
+#[test]
+fn seek_target_position() {
+    let config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    let mut nodes = vec![];
+    let mut begin = config.playlist.start_sec.unwrap();
+
+    for (i, length) in [3600.0, 1800.0, 30.0].into_iter().enumerate() {
+        let mut node = Media::new(i, "./assets/with_audio.mp4", false);
+        node.begin = Some(begin);
+        node.duration = length;
+        node.out = length;
+        begin += length;
+        nodes.push(node);
+    }
+
+    let target: SeekTarget = serde_json::from_str(r#"{"index": 1}"#).unwrap();
+    assert_eq!(target, SeekTarget::Index { index: 1 });
+    assert_eq!(seek_position(&config, &nodes, &target), Ok((1, 0.0)));
+
+    let target: SeekTarget = serde_json::from_str(r#"{"time": "06:30:00"}"#).unwrap();
+    assert_eq!(
+        target,
+        SeekTarget::Time {
+            time: "06:30:00".into()
+        }
+    );
+    assert_eq!(seek_position(&config, &nodes, &target), Ok((0, 1835.0)));
+
+    let target = SeekTarget::Time {
+        time: "07:00:00".into(),
+    };
+    assert_eq!(seek_position(&config, &nodes, &target), Ok((1, 35.0)));
+
+    assert_eq!(
+        seek_position(&config, &nodes, &SeekTarget::Index { index: 3 }),
+        Err("Index 3 is out of range, playlist has 3 clips".to_string())
+    );
+
+    // after the last clip and before the playlist start, which is the next day
+    for time in ["12:00:00", "05:00:00"] {
+        let target = SeekTarget::Time { time: time.into() };
+        assert_eq!(
+            seek_position(&config, &nodes, &target),
+            Err(format!("Time {time} is not in the playlist"))
+        );
+    }
+
+    for time in ["25:00:00", "07:00", "seven"] {
+        let target = SeekTarget::Time { time: time.into() };
+        assert_eq!(
+            seek_position(&config, &nodes, &target),
+            Err(format!("Time {time} is not like HH:MM:SS"))
+        );
+    }
+}
+
+fn playlist_json(date: &str, sources: &[&str]) -> String {
+    let program: Vec<serde_json::Value> = sources
+        .iter()
+        .map(|s| serde_json::json!({"in": 0.0, "out": 10.0, "duration": 10.0, "source": s}))
+        .collect();
+
+    serde_json::json!({"channel": "Channel 1", "date": date, "program": program}).to_string()
+}
+
+#[test]
+fn playlist_upload() {
+    let playlist_root = env::temp_dir().join(format!("ffplayout_upload_{}", process::id()));
+    fs::create_dir_all(&playlist_root).unwrap();
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.processing.mode = ProcessMode::Playlist;
+    config.playlist.path = playlist_root.display().to_string();
+    config.control.enable = true;
+    config.control.address = "127.0.0.1:9602".into();
+    config.control.token = "secret".into();
+    let playout_stat = PlayoutStatus::new();
+    let play_stat = playout_stat.clone();
+    let server_config = config.clone();
+
+    thread::spawn(move || {
+        control_server(
+            server_config,
+            PlayerControl::new(),
+            play_stat,
+            ProcessControl::new(),
+        )
+    });
+    thread::sleep(Duration::from_millis(500));
+
+    let client = reqwest::blocking::Client::new();
+    let file = playlist_file(&config, "2024-02-01");
+
+    // invalid playlist is rejected and nothing is written
+    let invalid = client
+        .put("http://127.0.0.1:9602/playlist")
+        .bearer_auth("secret")
+        .body(playlist_json("2024-02-01", &["./assets/not_exists.mp4"]))
+        .send()
+        .unwrap();
+    assert_eq!(invalid.status(), 422);
+
+    let body: serde_json::Value = invalid.json().unwrap();
+    assert_eq!(body["errors"][0]["index"], 0);
+    assert_eq!(body["errors"][0]["field"], "source");
+    assert!(!file.exists());
+    assert!(!playout_stat.reload.load(Ordering::SeqCst));
+
+    let broken = client
+        .put("http://127.0.0.1:9602/playlist")
+        .bearer_auth("secret")
+        .body("{\"date\": ")
+        .send()
+        .unwrap();
+    assert_eq!(broken.status(), 422);
+
+    let wrong_date = client
+        .put("http://127.0.0.1:9602/playlist")
+        .bearer_auth("secret")
+        .body(playlist_json("01.02.2024", &["./assets/with_audio.mp4"]))
+        .send()
+        .unwrap();
+    assert_eq!(wrong_date.status(), 422);
+    assert!(!playout_stat.reload.load(Ordering::SeqCst));
+
+    // valid playlist replaces the file and triggers the reload
+    let accepted = client
+        .put("http://127.0.0.1:9602/playlist")
+        .bearer_auth("secret")
+        .body(playlist_json("2024-02-01", &["./assets/with_audio.mp4"]))
+        .send()
+        .unwrap();
+    assert!(accepted.status().is_success());
+    assert!(playout_stat.reload.load(Ordering::SeqCst));
+
+    let written: JsonPlaylist = serde_json::from_str(&fs::read_to_string(&file).unwrap()).unwrap();
+    assert_eq!(written.program[0].source, "./assets/with_audio.mp4");
+    assert_eq!(file, playlist_root.join("2024/02/2024-02-01.json"));
+
+    fs::remove_dir_all(playlist_root).unwrap();
+}
+
+#[test]
+fn playlist_atomic_replace() {
+    let playlist_root = env::temp_dir().join(format!("ffplayout_atomic_{}", process::id()));
+    fs::create_dir_all(&playlist_root).unwrap();
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.playlist.path = playlist_root.display().to_string();
+
+    let short: JsonPlaylist =
+        serde_json::from_str(&playlist_json("2024-02-02", &["./assets/with_audio.mp4"])).unwrap();
+    let long: JsonPlaylist = serde_json::from_str(&playlist_json(
+        "2024-02-02",
+        &["./assets/with_audio.mp4"; 500],
+    ))
+    .unwrap();
+    let file = write_playlist(&config, &short).unwrap();
+    let done = Arc::new(AtomicBool::new(false));
+    let reader_done = done.clone();
+    let reader_file = file.clone();
+
+    // a concurrent reader must always get a complete playlist
+    let reader = thread::spawn(move || {
+        let mut reads = 0;
+
+        while !reader_done.load(Ordering::SeqCst) {
+            let json = fs::read_to_string(&reader_file).unwrap();
+            let playlist: JsonPlaylist = serde_json::from_str(&json).unwrap();
+
+            assert!(playlist.program.len() == 1 || playlist.program.len() == 500);
+            reads += 1;
+        }
+
+        reads
+    });
+
+    // two writers at the same time don't share a temp file
+    let writer_config = config.clone();
+    let writer_long = long.clone();
+    let writer = thread::spawn(move || {
+        for _ in 0..50 {
+            write_playlist(&writer_config, &writer_long).unwrap();
+        }
+    });
+
+    for i in 0..50 {
+        let playlist = if i % 2 == 0 { &long } else { &short };
+        write_playlist(&config, playlist).unwrap();
+    }
+
+    writer.join().unwrap();
+
+    done.store(true, Ordering::SeqCst);
+    assert!(reader.join().unwrap() > 0);
+
+    // no temp file stays behind
+    assert_eq!(fs::read_dir(file.parent().unwrap()).unwrap().count(), 1);
+
+    let mut invalid_date = short.clone();
+    invalid_date.date = "../../etc".into();
+    assert!(write_playlist(&config, &invalid_date).is_err());
+
+    fs::remove_dir_all(playlist_root).unwrap();
+}
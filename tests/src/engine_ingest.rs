@@ -0,0 +1,725 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::bounded;
+
+use ffplayout::{
+    input::{
+        drop_stale, ingest_buffer, ingest_server, read_ingest, reconnect_delay, send_package,
+        IngestIdle, SendError,
+    },
+    output::{ingest_cmd, Switch, Takeover},
+    utils::{
+        ingest_allowed, listen_connection, parse_rtmp_connection, webhook::ingest_hook,
+        ClientCheck, ClientGate,
+    },
+};
+use ffplayout_lib::{
+    utils::{
+        hide_secrets, Backpressure, IngestMode, IngestPackage, LatencyWindow, OutputMode,
+        PlayoutConfig, ProcessControl,
+    },
+    vec_strings,
+};
+
+#[test]
+fn ingest_default_buffer_size() {
+    let config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+
+    assert_eq!(ingest_buffer(&config).len(), 65088);
+}
+
+#[test]
+fn ingest_custom_buffer_size() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.ingest.buffer_size = 188 * 1024;
+
+    assert_eq!(ingest_buffer(&config).len(), 192512);
+}
+
+#[test]
+fn ingest_srt_input_cmd() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.ingest.mode = IngestMode::Srt;
+    config.ingest.srt_address = "0.0.0.0:40077".into();
+    config.ingest.srt_latency = 200000;
+    config.ingest.srt_passphrase = "12345abcde".into();
+
+    let cmd = config.ingest.srt_input_cmd();
+
+    assert_eq!(
+        cmd,
+        vec_strings![
+            "-f",
+            "mpegts",
+            "-i",
+            "srt://0.0.0.0:40077?mode=listener&latency=200000&passphrase=12345abcde"
+        ]
+    );
+    assert_eq!(
+        hide_secrets(&cmd.join(" ")),
+        "-f mpegts -i srt://0.0.0.0:40077?mode=listener&latency=200000&passphrase=*****"
+    );
+    assert_eq!(
+        hide_secrets(
+            "-headers Authorization: Bearer abc\r\nX-Key: 1\r\n -i https://cdn.org/a.mp4?X-Amz-Signature=f00&token=t0k&sig=5 -f null -"
+        ),
+        "-headers ***** -i https://cdn.org/a.mp4?X-Amz-Signature=*****&token=*****&sig=***** -f null -"
+    );
+    assert_eq!(
+        hide_secrets("authorization: Basic dXNlcjpwYXNz"),
+        "authorization: *****"
+    );
+
+    config.ingest.srt_passphrase = "a&b=c d%e".into();
+
+    assert_eq!(
+        config.ingest.srt_input_cmd()[3],
+        "srt://0.0.0.0:40077?mode=listener&latency=200000&passphrase=a%26b%3Dc%20d%25e"
+    );
+}
+
+#[test]
+fn ingest_probe_args() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+
+    // rtmp keeps the ffmpeg defaults
+    assert!(config.ingest.probe_cmd().is_empty());
+    assert_eq!(
+        config.ingest.listen_cmd().unwrap()[..2],
+        vec_strings!["-f", "live_flv"]
+    );
+
+    config.ingest.mode = IngestMode::Srt;
+    config.ingest.input_cmd = config.ingest.listen_cmd();
+
+    let server_cmd = ingest_cmd(&config, &None);
+    let probe = server_cmd.iter().position(|a| a == "-probesize").unwrap();
+    let analyze = server_cmd
+        .iter()
+        .position(|a| a == "-analyzeduration")
+        .unwrap();
+    let input = server_cmd.iter().position(|a| a == "-i").unwrap();
+
+    assert_eq!(server_cmd[probe + 1], "10000000");
+    assert_eq!(server_cmd[analyze + 1], "10000000");
+    assert!(probe < input && analyze < input);
+
+    config.ingest.mode = IngestMode::Rtmp;
+    config.ingest.probesize = Some(32);
+    config.ingest.analyzeduration = Some(500000);
+    config.ingest.input_cmd = config.ingest.listen_cmd();
+
+    let server_cmd = ingest_cmd(&config, &None);
+    let input = server_cmd.iter().position(|a| a == "-i").unwrap();
+    let probe = server_cmd.iter().position(|a| a == "-probesize").unwrap();
+
+    assert_eq!(
+        server_cmd[probe..probe + 6],
+        vec_strings![
+            "-probesize",
+            "32",
+            "-analyzeduration",
+            "500000",
+            "-f",
+            "live_flv"
+        ]
+    );
+    assert!(probe < input);
+}
+
+#[test]
+fn ingest_separate_audio_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = OutputMode::Stream;
+    config.ingest.audio_input_param =
+        "-f live_flv -listen 1 -i rtmp://127.0.0.1:1937/live/audio".into();
+    config.ingest.audio_input_cmd = config.ingest.audio_listen_cmd();
+
+    let server_cmd = ingest_cmd(&config, &None);
+    let inputs: Vec<&String> = server_cmd
+        .iter()
+        .enumerate()
+        .filter(|(_, a)| *a == "-i")
+        .map(|(i, _)| &server_cmd[i + 1])
+        .collect();
+
+    assert_eq!(
+        inputs,
+        vec![
+            "rtmp://127.0.0.1:1936/live/stream",
+            "rtmp://127.0.0.1:1937/live/audio"
+        ]
+    );
+
+    let filter = server_cmd
+        .iter()
+        .position(|a| a == "-filter_complex")
+        .unwrap();
+
+    // a dropped feed is held, so it doesn't end the stream
+    assert!(!server_cmd.contains(&"-shortest".to_string()));
+    assert!(server_cmd[filter + 1].starts_with("[0:v:0]"));
+    assert!(server_cmd[filter + 1].contains("tpad=stop_mode=clone:stop_duration=2"));
+    assert!(server_cmd[filter + 1].contains(";[1:a:0]aformat"));
+    assert!(server_cmd[filter + 1].contains("anull,apad=pad_dur=2,"));
+    assert!(server_cmd.windows(2).any(|w| w == ["-map", "[vout0]"]));
+    assert!(server_cmd.windows(2).any(|w| w == ["-map", "[aout0]"]));
+
+    // a remote audio feed reconnects on its own
+    config.processing.input_reconnect = 5;
+    config.ingest.audio_input_param = "-i http://127.0.0.1:8000/mixer".into();
+    config.ingest.audio_input_cmd = config.ingest.audio_listen_cmd();
+
+    let server_cmd = ingest_cmd(&config, &None);
+    let audio = server_cmd
+        .iter()
+        .position(|a| a == "http://127.0.0.1:8000/mixer")
+        .unwrap();
+
+    assert_eq!(
+        server_cmd[audio - 7..audio],
+        [
+            "-reconnect",
+            "1",
+            "-reconnect_streamed",
+            "1",
+            "-reconnect_delay_max",
+            "5",
+            "-i"
+        ]
+    );
+    assert_eq!(server_cmd.iter().filter(|a| *a == "-reconnect").count(), 1);
+
+    // without the second input, the audio comes from the main stream
+    config.ingest.audio_input_param = String::new();
+    config.ingest.audio_input_cmd = config.ingest.audio_listen_cmd();
+
+    let server_cmd = ingest_cmd(&config, &None);
+    let filter = server_cmd
+        .iter()
+        .position(|a| a == "-filter_complex")
+        .unwrap();
+
+    assert_eq!(server_cmd.iter().filter(|a| *a == "-i").count(), 1);
+    assert!(!server_cmd.contains(&"-shortest".to_string()));
+    assert!(server_cmd[filter + 1].contains(";[0:a:0]aformat"));
+    assert!(!server_cmd[filter + 1].contains("apad"));
+}
+
+#[test]
+fn ingest_drain_and_terminate() {
+    let proc_control = ProcessControl::new();
+    let (sender, receiver) = bounded::<IngestPackage>(96);
+
+    for _ in 0..10 {
+        sender.send((188, vec![0; 188], Instant::now())).unwrap();
+    }
+
+    let consumer = thread::spawn(move || {
+        let mut received = 0;
+
+        for (bytes_len, _, _) in receiver.iter() {
+            if bytes_len == 0 {
+                break;
+            }
+
+            received += bytes_len;
+        }
+
+        received
+    });
+
+    proc_control.drain_and_terminate(&sender, Duration::from_secs(2));
+
+    assert!(proc_control.is_terminated.load(Ordering::SeqCst));
+    assert!(sender.is_empty());
+    assert_eq!(consumer.join().unwrap(), 1880);
+}
+
+#[test]
+fn ingest_latency_percentiles() {
+    let (sender, receiver) = bounded::<IngestPackage>(96);
+    let mut latency = LatencyWindow::default();
+
+    assert_eq!(latency.p50(), None);
+
+    // every tenth package waits longer in the queue
+    for i in 0..40 {
+        let queued = Instant::now() - Duration::from_millis(if i % 10 == 9 { 100 } else { 10 });
+        sender.send((188, vec![0; 188], queued)).unwrap();
+    }
+
+    drop(sender);
+
+    for (_, _, queued) in receiver.iter() {
+        latency.push(queued.elapsed());
+    }
+
+    let p50 = latency.p50().unwrap();
+    let p95 = latency.p95().unwrap();
+
+    assert!(p50 >= Duration::from_millis(10) && p50 < Duration::from_millis(50));
+    assert!(p95 >= Duration::from_millis(100) && p95 < Duration::from_millis(150));
+
+    // the window keeps only the newest samples
+    for _ in 0..1000 {
+        latency.push(Duration::from_millis(1));
+    }
+
+    assert_eq!(latency.p95(), Some(Duration::from_millis(1)));
+
+    // a new ingest connection starts with an empty window
+    let proc_control = ProcessControl::new();
+    *proc_control.ingest_latency.lock().unwrap() = latency;
+
+    proc_control.set_ingest_active(false);
+    assert!(proc_control.ingest_latency.lock().unwrap().p50().is_some());
+
+    proc_control.set_ingest_active(true);
+    assert_eq!(proc_control.ingest_latency.lock().unwrap().p50(), None);
+}
+
+/// Consumer, which reads one package every 20 ms, until the channel is closed.
+fn slow_consumer(
+    receiver: crossbeam_channel::Receiver<IngestPackage>,
+) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut received = vec![];
+
+        while let Ok((_, data, _)) = receiver.recv() {
+            received.push(data[0]);
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        received
+    })
+}
+
+#[test]
+fn ingest_backpressure_policies() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+
+    // block: every package arrives, the producer waits for the consumer
+    let (sender, receiver) = bounded::<IngestPackage>(4);
+    let consumer = slow_consumer(receiver);
+
+    for i in 0..10 {
+        let package = (1, vec![i], Instant::now());
+        assert_eq!(send_package(&config, &sender, package), Ok(0));
+    }
+
+    drop(sender);
+    assert_eq!(consumer.join().unwrap(), (0..10).collect::<Vec<u8>>());
+
+    // drop_oldest: the producer never waits
+    config.ingest.backpressure = Backpressure::DropOldest;
+    let (sender, receiver) = bounded::<IngestPackage>(4);
+    let consumer = slow_consumer(receiver);
+    let start = Instant::now();
+    let mut dropped = 0;
+
+    for i in 0..50 {
+        let package = (1, vec![i], Instant::now());
+        dropped += send_package(&config, &sender, package).unwrap();
+    }
+
+    assert!(start.elapsed() < Duration::from_millis(200));
+    assert!(dropped > 0);
+
+    drop(sender);
+    let received = consumer.join().unwrap();
+
+    assert_eq!(received.len() + dropped, 50);
+
+    // the consumer removes the oldest half of a full channel
+    let (sender, receiver) = bounded::<IngestPackage>(4);
+
+    assert_eq!(drop_stale(&receiver), 0);
+
+    for i in 0..6 {
+        send_package(&config, &sender, (1, vec![i], Instant::now())).unwrap();
+    }
+
+    assert_eq!(drop_stale(&receiver), 2);
+    assert_eq!(
+        receiver.try_iter().map(|p| p.1[0]).collect::<Vec<_>>(),
+        vec![2, 3]
+    );
+
+    // terminate: gives up, when the consumer is blocked
+    config.ingest.backpressure = Backpressure::Terminate;
+    config.ingest.backpressure_timeout_ms = 100;
+    let (sender, receiver) = bounded::<IngestPackage>(4);
+
+    for i in 0..4 {
+        let package = (1, vec![i], Instant::now());
+        assert_eq!(send_package(&config, &sender, package), Ok(0));
+    }
+
+    let start = Instant::now();
+    let package = (1, vec![4], Instant::now());
+
+    assert_eq!(
+        send_package(&config, &sender, package),
+        Err(SendError::Timeout)
+    );
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    drop(receiver);
+    let package = (1, vec![5], Instant::now());
+
+    assert_eq!(
+        send_package(&config, &sender, package),
+        Err(SendError::Disconnected)
+    );
+}
+
+#[test]
+fn ingest_reconnect_delay() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.ingest.reconnect_base_ms = 500;
+    config.ingest.reconnect_max_ms = 5000;
+
+    assert_eq!(reconnect_delay(&config, 0), Duration::ZERO);
+    assert_eq!(reconnect_delay(&config, 1), Duration::from_millis(500));
+    assert_eq!(reconnect_delay(&config, 3), Duration::from_millis(2000));
+    assert_eq!(reconnect_delay(&config, 5), Duration::from_millis(5000));
+    assert_eq!(reconnect_delay(&config, 100), Duration::from_millis(5000));
+}
+
+#[test]
+fn ingest_idle_state() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.ingest.idle_timeout_seconds = 30;
+
+    let start = Instant::now();
+    let mut idle = IngestIdle::new(&config);
+
+    // no timeout before the first disconnect
+    assert!(!idle.is_idle(start + Duration::from_secs(60)));
+
+    idle.disconnected(start);
+    assert!(!idle.is_idle(start + Duration::from_secs(29)));
+    assert!(idle.is_idle(start + Duration::from_secs(30)));
+
+    idle.connected();
+    assert!(!idle.is_idle(start + Duration::from_secs(60)));
+
+    idle.disconnected(start + Duration::from_secs(60));
+    assert!(!idle.is_idle(start + Duration::from_secs(80)));
+    assert!(idle.is_idle(start + Duration::from_secs(90)));
+
+    config.ingest.idle_timeout_seconds = 0;
+    let mut idle = IngestIdle::new(&config);
+    idle.disconnected(start);
+
+    assert!(!idle.is_idle(start + Duration::from_secs(3600)));
+}
+
+/// Reader which returns the given chunk sizes, 0 is a empty read.
+struct ChunkReader(VecDeque<usize>);
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.0.pop_front().unwrap_or(0).min(buf.len());
+        buf[..len].fill(1);
+
+        Ok(len)
+    }
+}
+
+#[test]
+fn ingest_intermittent_empty_reads() {
+    let mut reader = ChunkReader(VecDeque::from([188, 0, 0, 376, 0, 188]));
+    let mut buffer = vec![0; 1024];
+    let mut reads = vec![];
+
+    while let Some(len) = read_ingest(&mut reader, &mut buffer, || false).unwrap() {
+        reads.push(len);
+    }
+
+    assert_eq!(reads, vec![188, 376, 188]);
+
+    // empty read from a closed server is the end of stream
+    let mut reader = ChunkReader(VecDeque::from([0, 188]));
+
+    assert_eq!(
+        read_ingest(&mut reader, &mut buffer, || true).unwrap(),
+        None
+    );
+}
+
+/// Answer the first request with an error and the second with success, return the bodies.
+fn hook_server(listener: TcpListener) -> Vec<String> {
+    let mut bodies = vec![];
+
+    for (i, stream) in listener.incoming().take(2).enumerate() {
+        let mut stream = stream.unwrap();
+        let mut reader = BufReader::new(&stream);
+        let mut length = 0;
+
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+
+            if line.trim().is_empty() {
+                break;
+            }
+
+            if let Some(l) = line.to_lowercase().strip_prefix("content-length:") {
+                length = l.trim().parse().unwrap();
+            }
+        }
+
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        bodies.push(String::from_utf8(body).unwrap());
+
+        let status = if i == 0 {
+            "500 Internal Server Error"
+        } else {
+            "200 OK"
+        };
+
+        stream
+            .write_all(
+                format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                    .as_bytes(),
+            )
+            .unwrap();
+    }
+
+    bodies
+}
+
+#[test]
+fn ingest_connect_webhook() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.ingest.on_connect_url = "http://127.0.0.1:9603/hook".to_string();
+
+    let listener = TcpListener::bind("127.0.0.1:9603").unwrap();
+    let server = thread::spawn(move || hook_server(listener));
+
+    // no url for disconnect, nothing is send
+    ingest_hook(&config, false);
+    ingest_hook(&config, true);
+
+    let bodies = server.join().unwrap();
+
+    // failed request is send again
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(bodies[0], bodies[1]);
+
+    let payload: serde_json::Value = serde_json::from_str(&bodies[0]).unwrap();
+
+    assert_eq!(payload["event"], "ingest_connect");
+    assert_eq!(payload["channel"], config.general.channel);
+    assert_eq!(payload["mode"], "rtmp");
+    assert!(payload["timestamp"].is_string());
+}
+
+#[test]
+fn ingest_takeover_switch() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.ingest.enable = true;
+
+    let mut takeover = Takeover::new(&config);
+
+    assert_eq!(takeover.update(false), None);
+    assert_eq!(takeover.update(true), Some(Switch::ToLive));
+    assert!(takeover.is_live());
+    assert_eq!(takeover.update(true), None);
+    assert_eq!(takeover.update(false), Some(Switch::ToPlaylist));
+    assert!(!takeover.is_live());
+
+    // a new connection takes over again
+    assert_eq!(takeover.update(true), Some(Switch::ToLive));
+
+    config.ingest.takeover = false;
+    let mut takeover = Takeover::new(&config);
+
+    assert_eq!(takeover.update(true), None);
+    assert!(!takeover.is_live());
+
+    // in hls mode the server writes no segments without takeover
+    config.out.mode = OutputMode::HLS;
+    config.out.output_cmd = Some(vec_strings!["-f", "hls", "/tmp/stream.m3u8"]);
+
+    let server_cmd = ingest_cmd(&config, &None);
+
+    assert_eq!(
+        server_cmd[server_cmd.len() - 3..],
+        vec_strings!["-f", "null", "-"]
+    );
+    assert!(!server_cmd.contains(&"/tmp/stream.m3u8".to_string()));
+}
+
+#[test]
+fn ingest_multicast_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.ingest.mode = IngestMode::Multicast;
+    config.ingest.multicast.fifo_size = 100000;
+    config.ingest.input_cmd = config.ingest.listen_cmd();
+
+    let server_cmd = ingest_cmd(&config, &None);
+    let input = server_cmd.iter().position(|a| a == "-i").unwrap();
+
+    assert_eq!(server_cmd[input - 2..input], vec_strings!["-f", "mpegts"]);
+    assert_eq!(
+        server_cmd[input + 1],
+        "udp://239.0.0.1:1234?pkt_size=1316&fifo_size=100000&overrun_nonfatal=1"
+    );
+    assert!(server_cmd.contains(&"-probesize".to_string()));
+
+    config.ingest.enable = true;
+    config.ingest.multicast.address = "239.0.0.1:port".into();
+
+    assert!(config
+        .validate()
+        .unwrap_err()
+        .iter()
+        .any(|e| e.field == "ingest.multicast.address"));
+}
+
+#[test]
+fn ingest_rtmp_allowlist() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    let stream_line =
+        "[rtmp @ 0x55d1c8e0] [warning] Unexpected stream secret_key?, expecting stream";
+    let app_line = "[rtmp @ 0x55d1c8e0] [warning] App field don't match up: other <-> live";
+
+    let input_line = "[info] Input #0, flv, from 'rtmp://127.0.0.1:1936/live/stream':";
+    let check = |config: &PlayoutConfig, lines: &[&str]| {
+        let mut gate = ClientGate::new(config);
+
+        lines
+            .iter()
+            .map(|l| gate.check_line(config, l))
+            .last()
+            .unwrap()
+    };
+
+    let conn = parse_rtmp_connection(stream_line).unwrap();
+
+    assert_eq!(conn.stream.as_deref(), Some("secret_key"));
+    assert_eq!(conn.expected.as_deref(), Some("stream"));
+    assert_eq!(conn.app, None);
+    assert_eq!(conn.address, None);
+    assert!(parse_rtmp_connection("[rtmp @ 0x55d1c8e0] [info] Opening stream").is_none());
+
+    let listen = listen_connection(&config);
+
+    assert_eq!(listen.app.as_deref(), Some("live"));
+    assert_eq!(listen.stream.as_deref(), Some("stream"));
+
+    // without allowlist the name must match the listen url
+    assert!(!ingest_allowed(&config, &conn));
+    assert_eq!(check(&config, &[stream_line]), ClientCheck::Rejected);
+    assert_eq!(check(&config, &[input_line]), ClientCheck::Accepted);
+    assert_eq!(
+        check(
+            &config,
+            &[
+                "[rtmp @ 0x55d1c8e0] [warning] Unexpected stream stream?, expecting stream",
+                input_line
+            ]
+        ),
+        ClientCheck::Accepted
+    );
+
+    // the stream data waits, until the input is opened
+    assert_eq!(
+        check(&config, &["[rtmp @ 0x55d1c8e0] [info] Opening stream"]),
+        ClientCheck::Pending
+    );
+
+    config.ingest.allowed_keys = vec_strings!["secret_key", "backup_key"];
+
+    assert!(ingest_allowed(&config, &conn));
+    assert_eq!(
+        check(&config, &[stream_line, input_line]),
+        ClientCheck::Accepted
+    );
+    assert_eq!(
+        check(
+            &config,
+            &["[rtmp @ 0x55d1c8e0] [warning] Unexpected stream guessed, expecting stream"]
+        ),
+        ClientCheck::Rejected
+    );
+
+    // a key like the listen path is not logged, but it must be in the list too
+    assert_eq!(check(&config, &[input_line]), ClientCheck::Rejected);
+
+    config.ingest.allowed_keys.push("stream".to_string());
+
+    assert_eq!(check(&config, &[input_line]), ClientCheck::Accepted);
+
+    // app mismatch
+    let conn = parse_rtmp_connection(&format!("{app_line} from 10.0.0.5:51234")).unwrap();
+
+    assert_eq!(conn.app.as_deref(), Some("other"));
+    assert_eq!(conn.address.as_deref(), Some("10.0.0.5:51234"));
+    assert!(ingest_allowed(&config, &conn));
+
+    config.ingest.allowed_apps = vec_strings!["live"];
+
+    assert!(!ingest_allowed(&config, &conn));
+    assert_eq!(check(&config, &[app_line]), ClientCheck::Rejected);
+    assert_eq!(check(&config, &[input_line]), ClientCheck::Accepted);
+
+    config.ingest.allowed_apps.push("other".to_string());
+
+    assert_eq!(
+        check(&config, &[app_line, input_line]),
+        ClientCheck::Accepted
+    );
+
+    // allowlist is only for rtmp
+    config.ingest.mode = IngestMode::Srt;
+
+    assert_eq!(ClientGate::new(&config).state, ClientCheck::Accepted);
+
+    assert!(config
+        .validate()
+        .unwrap_err()
+        .iter()
+        .any(|e| e.field == "ingest.allowed_keys"));
+}
+
+#[test]
+fn ingest_missing_ffmpeg() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    config.processing.ffmpeg_path = "/not/existing/ffmpeg".to_string();
+    config.ingest.input_cmd = Some(vec_strings![
+        "-f",
+        "live_flv",
+        "-listen",
+        "1",
+        "-i",
+        format!("rtmp://127.0.0.1:{port}/live/stream")
+    ]);
+
+    let proc_control = ProcessControl::new();
+    let (sender, _receiver) = bounded(96);
+
+    // no panic, the server stops the playout instead
+    assert!(ingest_server(config, sender, proc_control.clone()).is_ok());
+    assert!(proc_control.is_terminated.load(Ordering::SeqCst));
+
+    let last_error = proc_control.channel_status().last_error.unwrap();
+    assert!(last_error.contains("ffmpeg not found on PATH; set processing.ffmpeg_path"));
+    assert!(last_error.contains("/not/existing/ffmpeg"));
+}
@@ -0,0 +1,180 @@
+use std::{
+    io::Cursor,
+    process::Command,
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+
+use ffplayout::utils::metrics::metrics_server;
+use ffplayout_lib::utils::{
+    EncoderStats, FfmpegLines, PlayoutConfig, ProcessControl, ProcessUnit, MAX_LINE_LEN,
+};
+
+#[test]
+fn metrics_endpoint() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.metrics.enable = true;
+    config.metrics.address = "127.0.0.1:9599".into();
+    let proc_control = ProcessControl::new();
+
+    thread::spawn(move || metrics_server(config, proc_control));
+    thread::sleep(Duration::from_millis(500));
+
+    let resp = reqwest::blocking::get("http://127.0.0.1:9599/metrics").unwrap();
+    assert!(resp.status().is_success());
+
+    let body = resp.text().unwrap();
+    let mut names = vec![];
+
+    for line in body.lines() {
+        if line.starts_with("# HELP ") || line.starts_with("# TYPE ") {
+            continue;
+        }
+
+        let (name, value) = line.split_once(' ').unwrap();
+        assert!(value.parse::<f64>().is_ok(), "invalid value in: {line}");
+        names.push(name.to_string());
+    }
+
+    for name in [
+        "ffplayout_ingest_running",
+        "ffplayout_current_clip_seconds_remaining",
+        "ffplayout_decoder_restarts_total",
+        "ffplayout_encoder_restarts_total",
+        "ffplayout_dropped_frames_total",
+        "ffplayout_encoder_fps",
+        "ffplayout_encoder_speed",
+        "ffplayout_encoder_dropped_frames",
+        "ffplayout_encoder_duplicated_frames",
+        "ffplayout_live_children",
+        "ffplayout_ingest_latency_p50_seconds",
+        "ffplayout_ingest_latency_p95_seconds",
+        "ffplayout_channel_uptime_seconds",
+    ] {
+        assert!(names.contains(&name.to_string()), "missing metric: {name}");
+    }
+
+    let not_found = reqwest::blocking::get("http://127.0.0.1:9599/").unwrap();
+    assert_eq!(not_found.status(), 404);
+}
+
+#[test]
+fn health_endpoints() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.metrics.enable = true;
+    config.metrics.address = "127.0.0.1:9597".into();
+    config.metrics.ready_seconds = 5;
+    let proc_control = ProcessControl::new();
+    let proc_ctl = proc_control.clone();
+
+    thread::spawn(move || metrics_server(config, proc_ctl));
+    thread::sleep(Duration::from_millis(500));
+
+    let status = |path: &str| {
+        reqwest::blocking::get(format!("http://127.0.0.1:9597{path}"))
+            .unwrap()
+            .status()
+    };
+
+    // no encoder
+    assert_eq!(status("/healthz"), 503);
+    assert_eq!(status("/readyz"), 503);
+
+    let child = Command::new("sleep").arg("30").spawn().unwrap();
+    *proc_control.encoder_term.lock().unwrap() = Some(child);
+    *proc_control.last_progress.lock().unwrap() = Instant::now();
+
+    assert_eq!(status("/healthz"), 200);
+    assert_eq!(status("/readyz"), 200);
+
+    // running, but no progress
+    *proc_control.last_progress.lock().unwrap() = Instant::now() - Duration::from_secs(10);
+
+    assert_eq!(status("/healthz"), 200);
+    assert_eq!(status("/readyz"), 503);
+
+    if let Some(proc) = proc_control.encoder_term.lock().unwrap().as_mut() {
+        proc.kill().unwrap();
+        proc.wait().unwrap();
+    }
+
+    assert_eq!(status("/healthz"), 503);
+    assert_eq!(status("/readyz"), 503);
+}
+
+#[test]
+fn parse_encoder_stats() {
+    let line = "frame=  250 fps= 25 q=28.0 size=    1024kB time=00:00:10.00 bitrate= 838.9kbits/s dup=2 drop=3 speed=0.98x";
+
+    assert_eq!(
+        EncoderStats::parse(line),
+        Some(EncoderStats {
+            frame: 250,
+            fps: 25.0,
+            drop: 3,
+            dup: 2,
+            speed: 0.98,
+        })
+    );
+
+    // multiple progress updates in one line, only the last counts
+    let line = "frame=   10 fps=0.0 q=-1.0 size=N/A time=00:00:00.40 bitrate=N/A speed=N/A\rframe=   50 fps= 50 q=-1.0 size=N/A time=00:00:02.00 bitrate=N/A speed=1.99x";
+    let stats = EncoderStats::parse(line).unwrap();
+
+    assert_eq!(stats.frame, 50);
+    assert_eq!(stats.speed, 1.99);
+    assert_eq!(stats.drop, 0);
+
+    assert_eq!(EncoderStats::parse("[info] Stream mapping:"), None);
+}
+
+#[test]
+fn count_drops_and_restarts() {
+    let stats = |drop| EncoderStats {
+        drop,
+        ..Default::default()
+    };
+
+    assert_eq!(stats(5).new_drops(&stats(3)), 2);
+    assert_eq!(stats(3).new_drops(&stats(3)), 0);
+    // restarted encoder counts from zero
+    assert_eq!(stats(2).new_drops(&stats(40)), 2);
+
+    let proc_control = ProcessControl::new();
+    let mut child = Command::new("false").spawn().unwrap();
+    child.wait().unwrap();
+    *proc_control.encoder_term.lock().unwrap() = Some(child);
+
+    assert!(proc_control.count_restart(ProcessUnit::Encoder));
+    assert_eq!(proc_control.encoder_restarts.load(Ordering::SeqCst), 1);
+    assert_eq!(proc_control.decoder_restarts.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn split_stderr_lines() {
+    let stderr = "[info] Stream mapping:\nframe=   10 fps=0.0 speed=N/A\rframe=   50 fps= 50 speed=1.99x\r\n[warning] late\n";
+    let lines: Vec<String> = FfmpegLines::new(Cursor::new(stderr))
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(
+        lines,
+        vec![
+            "[info] Stream mapping:",
+            "frame=   10 fps=0.0 speed=N/A",
+            "frame=   50 fps= 50 speed=1.99x",
+            "[warning] late"
+        ]
+    );
+    assert_eq!(EncoderStats::parse(&lines[2]).unwrap().speed, 1.99);
+
+    // a line without end is cut, the next one is complete
+    let long = format!("{}\nframe=1 speed=1.00x", "a".repeat(MAX_LINE_LEN * 2));
+    let lines: Vec<String> = FfmpegLines::new(Cursor::new(long))
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(lines[0].len(), MAX_LINE_LEN);
+    assert_eq!(lines[1], "frame=1 speed=1.00x");
+}
@@ -1,13 +1,31 @@
 use std::{
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     thread::{self, sleep},
     time::Duration,
 };
 
+use rand::{rngs::StdRng, SeedableRng};
 use serial_test::serial;
 use simplelog::*;
 
-use ffplayout::output::player;
-use ffplayout_lib::{utils::*, vec_strings};
+use ffplayout::{
+    input::playlist::realtime_position,
+    output::player,
+    utils::resume::{read_resume, write_resume, ResumeState},
+};
+use ffplayout_lib::{
+    utils::{
+        folder::{self, FolderSource},
+        *,
+    },
+    vec_strings,
+};
 
 fn timed_stop(sec: u64, proc_ctl: ProcessControl) {
     sleep(Duration::from_secs(sec));
@@ -98,3 +116,374 @@ fn playlist_change_at_six() {
 
     assert_eq!(playlist_date, "2023-02-09");
 }
+
+/// Serve a playlist with ETag, and answer with 304 when the client sends it back.
+fn playlist_server(listener: TcpListener, count: usize) -> Vec<String> {
+    let body = fs::read_to_string("assets/playlists/2023/02/2023-02-08.json").unwrap();
+    let mut requests = vec![];
+
+    for stream in listener.incoming().take(count) {
+        let mut stream = stream.unwrap();
+        let mut reader = BufReader::new(&stream);
+        let mut head = String::new();
+
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+
+            if line.trim().is_empty() {
+                break;
+            }
+
+            head.push_str(&line.to_lowercase());
+        }
+
+        let response = if head.contains("if-none-match: \"v1\"") {
+            "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n".to_string()
+        } else {
+            format!(
+                "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        };
+
+        stream.write_all(response.as_bytes()).unwrap();
+        requests.push(head);
+    }
+
+    requests
+}
+
+#[test]
+#[serial]
+fn remote_playlist_cache() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.playlist.cache_dir = std::env::temp_dir()
+        .join("ffplayout_test_cache")
+        .display()
+        .to_string();
+    fs::remove_dir_all(&config.playlist.cache_dir).unwrap_or_default();
+
+    let url = "http://127.0.0.1:9602/2023-02-08.json".to_string();
+    let is_terminated = Arc::new(AtomicBool::new(true));
+    let listener = TcpListener::bind("127.0.0.1:9602").unwrap();
+    let server = thread::spawn(move || playlist_server(listener, 2));
+
+    let first = read_json(
+        &config,
+        Some(url.clone()),
+        is_terminated.clone(),
+        false,
+        0.0,
+    );
+
+    assert_eq!(first.modified, Some("\"v1\"".to_string()));
+    assert!(remote_cache(&config, &url).is_file());
+
+    // not modified, the cached playlist gets used
+    let second = read_json(
+        &config,
+        Some(url.clone()),
+        is_terminated.clone(),
+        false,
+        0.0,
+    );
+
+    assert_eq!(second.modified, first.modified);
+    assert_eq!(second.program, first.program);
+
+    let requests = server.join().unwrap();
+
+    assert!(requests[1].contains("if-none-match: \"v1\""));
+
+    // server is gone, fallback to the last cached playlist
+    let third = read_json(&config, Some(url), is_terminated, false, 0.0);
+
+    assert_eq!(third.program, first.program);
+}
+
+#[test]
+fn stdin_playlist_reader() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.playlist.path = STDIN_PLAYLIST.to_string();
+    let is_terminated = Arc::new(AtomicBool::new(true));
+    let data = fs::read("assets/playlists/playlist_short.json").unwrap();
+
+    let playlist = playlist_from_reader(
+        &config,
+        data.as_slice(),
+        is_terminated.clone(),
+        "2022-11-01".to_string(),
+    );
+
+    assert_eq!(playlist.date, "2022-11-01");
+    assert_eq!(playlist.program.len(), 8);
+    assert_eq!(playlist.program[0].source, "tests/assets/av_sync.mp4");
+
+    // broken json gives the dummy playlist
+    let dummy = playlist_from_reader(
+        &config,
+        "{\"program\": [".as_bytes(),
+        is_terminated,
+        "2022-11-01".to_string(),
+    );
+
+    assert_eq!(dummy.program.len(), 1);
+    assert_eq!(dummy.program[0].source, "");
+
+    // stdin can't be replaced over the control api
+    assert!(write_playlist(&config, &playlist).is_err());
+}
+
+#[test]
+fn resume_state_max_age() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.general.resume_file = std::env::temp_dir()
+        .join("ffp_test_resume")
+        .display()
+        .to_string();
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let mut state = ResumeState {
+        index: 3,
+        source: "assets/with_audio.mp4".to_string(),
+        elapsed: 12.5,
+        timestamp: now,
+    };
+
+    write_resume(&config, &state);
+
+    assert_eq!(read_resume(&config), Some(state.clone()));
+
+    state.timestamp = now - config.general.resume_max_age - 10;
+    write_resume(&config, &state);
+
+    assert_eq!(read_resume(&config), None);
+}
+
+#[test]
+fn realtime_mid_clip_position() {
+    let mut nodes = vec![];
+
+    for i in 0..3 {
+        let mut media = Media::new(i, "assets/with_audio.mp4", false);
+        media.begin = Some(21600.0 + i as f64 * 30.0);
+        media.duration = 30.0;
+        media.out = 30.0;
+
+        nodes.push(media);
+    }
+
+    assert_eq!(realtime_position(&nodes, 21500.0), Some((0, 0.0)));
+    assert_eq!(realtime_position(&nodes, 21600.0), Some((0, 0.0)));
+    assert_eq!(realtime_position(&nodes, 21645.0), Some((1, 15.0)));
+    assert_eq!(realtime_position(&nodes, 21689.5), Some((2, 29.5)));
+    assert_eq!(realtime_position(&nodes, 21690.0), None);
+}
+
+#[test]
+fn dry_run_failed_clips() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.processing.add_logo = false;
+    config.text.add_text = false;
+    config.playlist.start_sec = Some(0.0);
+
+    let mut valid = Media::new(0, "assets/short_video.mp4", false);
+    valid.duration = 10.0;
+    valid.out = 2.0;
+
+    let mut missing = Media::new(1, "assets/missing.mp4", false);
+    missing.duration = 10.0;
+    missing.out = 10.0;
+
+    let playlist = JsonPlaylist {
+        channel: "Test 1".into(),
+        date: "2023-02-08".into(),
+        start_sec: Some(0.0),
+        current_file: None,
+        modified: None,
+        program: vec![valid.clone()],
+    };
+
+    assert_eq!(analyze_playlist(&playlist, config.clone()), 0);
+
+    let playlist = JsonPlaylist {
+        program: vec![valid, missing],
+        ..playlist
+    };
+
+    assert_eq!(analyze_playlist(&playlist, config), 1);
+}
+
+#[test]
+fn loop_mode_single_file() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.processing.mode = ProcessMode::Loop;
+    config.processing.add_logo = false;
+    config.text.add_text = false;
+    config.storage.path = "assets/short_video.mp4".into();
+
+    let current_list = Arc::new(Mutex::new(vec![]));
+    let index = Arc::new(AtomicUsize::new(0));
+    let mut source = FolderSource::new(&config, None, current_list.clone(), index.clone());
+
+    {
+        let list = current_list.lock().unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].source, "assets/short_video.mp4");
+        assert_eq!(list[0].index, Some(0));
+    }
+
+    for _ in 0..3 {
+        let node = source.next().unwrap();
+
+        assert_eq!(node.source, "assets/short_video.mp4");
+        assert_eq!(node.index, Some(0));
+        assert_eq!(index.load(Ordering::SeqCst), 1);
+    }
+}
+
+#[test]
+fn loop_mode_sorted_folder() {
+    let dir = env::temp_dir().join(format!("ffplayout_loop_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::copy("assets/short_video.mp4", dir.join("b.mp4")).unwrap();
+    fs::copy("assets/short_video.mp4", dir.join("a.mp4")).unwrap();
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.processing.mode = ProcessMode::Loop;
+    config.processing.add_logo = false;
+    config.text.add_text = false;
+    config.storage.path = dir.display().to_string();
+    config.storage.shuffle = false;
+
+    let index = Arc::new(AtomicUsize::new(0));
+    let source = FolderSource::new(&config, None, Arc::new(Mutex::new(vec![])), index);
+    let names = source
+        .take(5)
+        .map(|node| node.source.rsplit('/').next().unwrap().to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(names, vec!["a.mp4", "b.mp4", "a.mp4", "b.mp4", "a.mp4"]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+fn weighted_config() -> PlayoutConfig {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.storage.path = "/media".into();
+    config.storage.shuffle = true;
+    config.storage.weights = vec![
+        FolderWeight {
+            path: "promo".into(),
+            weight: 3,
+        },
+        FolderWeight {
+            path: "promo/old.mp4".into(),
+            weight: 1,
+        },
+    ];
+
+    config
+}
+
+fn weighted_nodes() -> Vec<Media> {
+    ["a.mp4", "b.mp4", "promo/new.mp4", "promo/old.mp4"]
+        .iter()
+        .enumerate()
+        .map(|(i, name)| Media::new(i, &format!("/media/{name}"), false))
+        .collect()
+}
+
+fn sources(nodes: &[Media]) -> Vec<String> {
+    nodes.iter().map(|n| n.source.clone()).collect()
+}
+
+#[test]
+fn shuffle_seeded_order() {
+    let config = weighted_config();
+
+    assert_eq!(folder::file_weight(&config, "/media/a.mp4"), 1);
+    assert_eq!(folder::file_weight(&config, "/media/promo/new.mp4"), 3);
+    assert_eq!(folder::file_weight(&config, "/media/promo/old.mp4"), 1);
+
+    let mut first = weighted_nodes();
+    let mut second = weighted_nodes();
+    second.reverse();
+
+    folder::shuffle_nodes(&config, &mut first, &mut StdRng::seed_from_u64(7), None);
+    folder::shuffle_nodes(&config, &mut second, &mut StdRng::seed_from_u64(7), None);
+
+    assert_eq!(first.len(), 6);
+    assert_eq!(sources(&first), sources(&second));
+    assert_eq!(
+        first.iter().map(|n| n.index).collect::<Vec<_>>(),
+        (0..6).map(Some).collect::<Vec<_>>()
+    );
+
+    // a new round starts again from the unique files
+    folder::shuffle_nodes(&config, &mut first, &mut StdRng::seed_from_u64(7), None);
+    assert_eq!(sources(&first), sources(&second));
+
+    let dir = env::temp_dir().join(format!("ffplayout_seed_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    for name in ["a.mp4", "b.mp4", "c.mp4", "d.mp4"] {
+        fs::copy("assets/short_video.mp4", dir.join(name)).unwrap();
+    }
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.processing.mode = ProcessMode::Folder;
+    config.storage.path = dir.display().to_string();
+    config.storage.shuffle = true;
+    config.storage.seed = Some(42);
+
+    let orders = (0..2)
+        .map(|_| {
+            let list = Arc::new(Mutex::new(vec![]));
+            FolderSource::new(&config, None, list.clone(), Arc::new(AtomicUsize::new(0)));
+            let nodes = list.lock().unwrap();
+
+            sources(&nodes)
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(orders[0].len(), 4);
+    assert_eq!(orders[0], orders[1]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn shuffle_no_immediate_repeat() {
+    let config = weighted_config();
+
+    for seed in 0..200 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut nodes = weighted_nodes();
+        let mut last = None;
+
+        for _ in 0..3 {
+            folder::shuffle_nodes(&config, &mut nodes, &mut rng, last.as_deref());
+
+            assert_eq!(
+                nodes
+                    .iter()
+                    .filter(|n| n.source == "/media/promo/new.mp4")
+                    .count(),
+                3
+            );
+            assert_ne!(Some(nodes[0].source.clone()), last, "seed {seed}");
+
+            for pair in nodes.windows(2) {
+                assert_ne!(pair[0].source, pair[1].source, "seed {seed}");
+            }
+
+            last = nodes.last().map(|n| n.source.clone());
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    env, fs,
+    process::{self, Command},
+    sync::atomic::Ordering,
+    thread::{self, sleep},
+    time::Duration,
+};
+
+use ffplayout::{output::ingest_cmd, utils::reload::reload_handler};
+use ffplayout_lib::utils::{
+    CensorRegion, LiveSettings, PlayoutConfig, PlayoutStatus, ProcessControl,
+};
+
+fn send_sighup() {
+    let status = Command::new("kill")
+        .args(["-HUP", &process::id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    sleep(Duration::from_millis(1000));
+}
+
+#[test]
+fn sighup_reloads_live_settings() {
+    let cfg_path = env::temp_dir().join(format!("ffplayout_reload_{}.yml", process::id()));
+    let original = fs::read_to_string("../assets/ffplayout.yml").unwrap();
+    fs::write(&cfg_path, &original).unwrap();
+
+    let config = PlayoutConfig::new(Some(cfg_path.display().to_string()));
+    let proc_control = ProcessControl::new();
+
+    assert_eq!(config.processing.volume, 1.0);
+
+    reload_handler(config.clone(), PlayoutStatus::new(), proc_control.clone());
+
+    // live change
+    let changed = original.replace("    volume: 1\n", "    volume: 0.5\n");
+    fs::write(&cfg_path, &changed).unwrap();
+    send_sighup();
+
+    assert_eq!(config.live.read().unwrap().volume, Some(0.5));
+    assert_eq!(config.live_config().processing.volume, 0.5);
+
+    // broken config keeps the running settings
+    fs::write(
+        &cfg_path,
+        changed.replace("    volume: 0.5\n", "    volume: loud\n"),
+    )
+    .unwrap();
+    send_sighup();
+
+    assert_eq!(config.live_config().processing.volume, 0.5);
+
+    // restart only changes are not applied
+    let resized = changed.replace("    width: 1024\n", "    width: 1280\n");
+    assert_ne!(resized, changed);
+    fs::write(&cfg_path, resized).unwrap();
+    send_sighup();
+
+    assert_eq!(config.live_config().processing.width, 1024);
+    assert_eq!(config.live_config().processing.volume, 0.5);
+
+    // censor regions from the file are merged with the ones from the control api
+    let api_region = CensorRegion {
+        x: 10,
+        ..Default::default()
+    };
+    config.live.write().unwrap().censor = Some(vec![api_region.clone()]);
+
+    let censored = changed.replace(
+        "    censor: []\n",
+        "    censor:\n      - { mode: blur, x: 0, y: 0, width: 100, height: 50 }\n",
+    );
+    assert_ne!(censored, changed);
+    fs::write(&cfg_path, censored).unwrap();
+    send_sighup();
+
+    let regions = config.live_config().processing.censor.clone();
+    assert_eq!(regions.len(), 2);
+    assert_eq!(regions[0].width, 100);
+    assert_eq!(regions[1], api_region);
+
+    proc_control.is_terminated.store(true, Ordering::SeqCst);
+    fs::remove_file(cfg_path).unwrap();
+}
+
+#[test]
+fn live_settings_snapshots() {
+    let config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    let settings = |nr: usize| LiveSettings {
+        volume: Some(0.25 + nr as f64 * 0.5),
+        logo: Some(format!("logo_{nr}.png")),
+        ..Default::default()
+    };
+
+    let writer_config = config.clone();
+    let writer = thread::spawn(move || {
+        for i in 0..2000 {
+            *writer_config.live.write().unwrap() = settings(i % 2);
+        }
+    });
+
+    // a snapshot has always volume and logo from the same swap
+    while !writer.is_finished() {
+        let snapshot = config.live_config();
+        let p = &snapshot.processing;
+
+        match p.logo.as_str() {
+            "logo_0.png" => assert_eq!(p.volume, 0.25),
+            "logo_1.png" => assert_eq!(p.volume, 0.75),
+            _ => assert_eq!(p.volume, 1.0),
+        }
+    }
+
+    writer.join().unwrap();
+
+    // the ingest server builds its command from the current settings
+    *config.live.write().unwrap() = settings(1);
+
+    assert!(ingest_cmd(&config, &None)
+        .iter()
+        .any(|arg| arg.contains("volume=0.75")));
+}
@@ -0,0 +1,55 @@
+use std::{
+    path::Path,
+    process::{self, Child, Command},
+    sync::{atomic::Ordering, Mutex},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use ffplayout::utils::channel::set_shutdown_handler;
+use ffplayout_lib::utils::ProcessControl;
+
+/// Long running mock child, which is stored in the process control.
+fn mock_child(term: &Mutex<Option<Child>>) -> u32 {
+    let child = Command::new("sleep").arg("60").spawn().unwrap();
+    let pid = child.id();
+    *term.lock().unwrap() = Some(child);
+
+    pid
+}
+
+/// Reaped children have no proc entry, zombies would still have one.
+fn is_reaped(pid: u32) -> bool {
+    !Path::new(&format!("/proc/{pid}")).exists()
+}
+
+#[test]
+fn sigterm_reaps_children() {
+    let proc_control = ProcessControl::new();
+    let pids = [
+        mock_child(&proc_control.decoder_term),
+        mock_child(&proc_control.encoder_term),
+        mock_child(&proc_control.server_term),
+    ];
+
+    set_shutdown_handler(vec![proc_control.clone()]);
+
+    let status = Command::new("kill")
+        .args(["-TERM", &process::id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let start = Instant::now();
+
+    while !pids.iter().all(|p| is_reaped(*p)) && start.elapsed() < Duration::from_secs(5) {
+        sleep(Duration::from_millis(10));
+    }
+
+    assert!(proc_control.is_terminated.load(Ordering::SeqCst));
+    assert!(!proc_control.is_alive.load(Ordering::SeqCst));
+
+    for pid in pids {
+        assert!(is_reaped(pid), "child {pid} is not reaped");
+    }
+}
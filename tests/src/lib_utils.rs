@@ -4,6 +4,9 @@ use chrono::prelude::*;
 #[cfg(test)]
 use ffplayout_lib::utils::*;
 
+#[cfg(test)]
+use ffplayout_lib::vec_strings;
+
 #[test]
 fn mock_date_time() {
     let time_str = "2022-05-20T06:00:00";
@@ -50,3 +53,1372 @@ fn test_delta() {
 
     assert!(delta < 2.0);
 }
+
+#[test]
+fn json_log_line() {
+    let line = json_line(
+        &log::Record::builder()
+            .args(format_args!(
+                "Start ingest server, listening on: <b><magenta>rtmp://127.0.0.1:1936/live/stream</></b>"
+            ))
+            .level(log::Level::Info)
+            .target("ffplayout::input::ingest")
+            .build(),
+        "channel1",
+        false,
+    );
+
+    let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+
+    assert_eq!(value["level"], "INFO");
+    assert_eq!(value["target"], "ffplayout::input::ingest");
+    assert_eq!(value["channel"], "channel1");
+    assert_eq!(
+        value["msg"],
+        "Start ingest server, listening on: rtmp://127.0.0.1:1936/live/stream"
+    );
+    assert!(value["ts"].as_str().unwrap().ends_with("+00:00"));
+}
+
+#[test]
+fn process_failures_window() {
+    let mut failures = ProcessFailures::default();
+    let window = std::time::Duration::from_secs(60);
+
+    assert_eq!(failures.push(Decoder, window), 1);
+    assert_eq!(failures.push(Decoder, window), 2);
+    assert_eq!(failures.push(Ingest, window), 1);
+}
+
+#[test]
+fn process_last_errors() {
+    let proc_control = ProcessControl::new();
+
+    for i in 0..60 {
+        proc_control.push_line(Decoder, &format!("[error] line {i}"));
+    }
+
+    proc_control.push_line(Ingest, "[error] ingest line");
+
+    let errors = proc_control.last_errors(Decoder);
+
+    assert_eq!(errors.len(), 50);
+    assert_eq!(errors[0], "[error] line 10");
+    assert_eq!(errors[49], "[error] line 59");
+    assert!(proc_control.last_errors(Encoder).is_empty());
+
+    proc_control.clear_lines(Decoder);
+
+    assert!(proc_control.last_errors(Decoder).is_empty());
+    assert_eq!(proc_control.last_errors(Ingest).len(), 1);
+}
+
+/// A zombie has still a proc entry, with the state Z.
+fn is_zombie(pid: u32) -> bool {
+    std::fs::read_to_string(format!("/proc/{pid}/stat"))
+        .ok()
+        .and_then(|stat| {
+            stat.rsplit_once(')')
+                .and_then(|(_, rest)| rest.split_whitespace().next().map(|s| s == "Z"))
+        })
+        .unwrap_or(false)
+}
+
+#[test]
+fn replaced_children_reaped() {
+    let proc_control = ProcessControl::new();
+    let mut pids = vec![];
+
+    for i in 0..50 {
+        // every second process is still running, when it gets replaced
+        let time = if i % 2 == 0 { "0" } else { "10" };
+        let child = std::process::Command::new("sleep")
+            .arg(time)
+            .spawn()
+            .unwrap();
+        pids.push(child.id());
+
+        proc_control.set_child(Decoder, child);
+    }
+
+    assert_eq!(proc_control.live_children(), 1);
+
+    proc_control.stop(Decoder).unwrap();
+
+    assert_eq!(proc_control.live_children(), 0);
+
+    for pid in pids {
+        assert!(!is_zombie(pid), "zombie process {pid}");
+    }
+}
+
+#[test]
+fn channel_status_snapshot() {
+    let empty = StatusState::default().snapshot(100.0, 5.0);
+
+    assert_eq!(empty.source, None);
+    assert_eq!(empty.elapsed, 0.0);
+    assert_eq!(empty.remaining, 0.0);
+    assert_eq!(empty.uptime, 5.0);
+
+    let proc_control = ProcessControl::new();
+    let writer_ctl = proc_control.clone();
+
+    let writer = std::thread::spawn(move || {
+        for i in 0..2000 {
+            let mut node = Media::new(i, &format!("clip_{i}"), false);
+            node.begin = Some(100.0);
+            node.out = 30.0;
+
+            writer_ctl.set_clip(&node);
+        }
+    });
+
+    while !writer.is_finished() {
+        let status = proc_control.channel_status();
+
+        if let (Some(source), Some(index)) = (status.source, status.index) {
+            assert_eq!(source, format!("clip_{index}"));
+        }
+    }
+
+    writer.join().unwrap();
+
+    proc_control.set_ingest_active(true);
+    proc_control.set_last_error(Decoder, "broken input");
+
+    let status = proc_control.channel_status();
+
+    assert_eq!(status.index, Some(1999));
+    assert!(status.ingest_active);
+    assert!(proc_control
+        .server_is_running
+        .load(std::sync::atomic::Ordering::SeqCst));
+    assert_eq!(
+        status.last_error,
+        Some("[Decoder] broken input".to_string())
+    );
+}
+
+#[test]
+fn config_validate() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+
+    assert!(config.validate().is_ok());
+
+    config.ingest.enable = true;
+    config.ingest.input_cmd = None;
+    config.processing.fps = 0.0;
+    config.processing.logo_opacity = 1.5;
+    config.processing.loudnorm.enable = true;
+    config.processing.loudnorm.integrated = 0.0;
+
+    let fields: Vec<String> = config
+        .validate()
+        .unwrap_err()
+        .into_iter()
+        .map(|e| e.field)
+        .collect();
+
+    assert_eq!(
+        fields,
+        vec![
+            "ingest.input_param",
+            "processing.fps",
+            "processing.logo_opacity",
+            "processing.loudnorm.integrated"
+        ]
+    );
+}
+
+#[test]
+fn logo_filter_validate() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+
+    assert!(check_logo_filter("").is_ok());
+    assert!(check_logo_filter("overlay=W-w-12:12").is_ok());
+    assert!(check_logo_filter("overlay=x=(main_w-overlay_w)/2:y=12").is_ok());
+
+    assert!(check_logo_filter("scale=100:-1").is_err());
+    assert_eq!(
+        check_logo_filter("overlay=12:12[out];[0:v]null").unwrap_err(),
+        "invalid characters in overlay expression: '[' ']' ';'"
+    );
+    assert!(check_logo_filter("overlay=12:12,drawtext=text='x'").is_err());
+    assert!(check_logo_filter("overlay=12:$(id)").is_err());
+
+    config.processing.logo_opacity = -0.1;
+    config.processing.logo_filter = "overlay=12:12;movie=/etc/passwd".into();
+
+    let fields: Vec<String> = config
+        .validate()
+        .unwrap_err()
+        .into_iter()
+        .map(|e| e.field)
+        .collect();
+
+    assert_eq!(
+        fields,
+        vec!["processing.logo_opacity", "processing.logo_filter"]
+    );
+
+    config.processing.logo_opacity = 1.0;
+    config.processing.logo_filter = "overlay=W-w-12:12".into();
+
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn typed_bitrate_args() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    let cmd = vec_strings![
+        "-c:v",
+        "libx264",
+        "-maxrate",
+        "1300k",
+        "-bufsize",
+        "2600k",
+        "-c:a",
+        "aac",
+        "-b:a",
+        "128k",
+        "-f",
+        "flv",
+        "rtmp://localhost/live/stream"
+    ];
+
+    assert_eq!(bitrate_output_cmd(cmd.clone(), &config.processing), cmd);
+
+    config.processing.video_bitrate = "2M".into();
+    config.processing.audio_bitrate = "192k".into();
+    config.processing.maxrate = "2500k".into();
+    config.processing.bufsize = "5000k".into();
+
+    assert_eq!(
+        bitrate_output_cmd(cmd, &config.processing),
+        vec_strings![
+            "-b:v",
+            "2M",
+            "-b:a",
+            "192k",
+            "-maxrate",
+            "2500k",
+            "-bufsize",
+            "5000k",
+            "-c:v",
+            "libx264",
+            "-c:a",
+            "aac",
+            "-f",
+            "flv",
+            "rtmp://localhost/live/stream"
+        ]
+    );
+    assert!(config.validate().is_ok());
+
+    assert_eq!(bitrate_value("1300k"), Some(1_300_000.0));
+    assert_eq!(bitrate_value("2.5M"), Some(2_500_000.0));
+    assert_eq!(bitrate_value("fast"), None);
+
+    config.processing.maxrate = "1M".into();
+    config.processing.bufsize = "0".into();
+
+    let fields: Vec<String> = config
+        .validate()
+        .unwrap_err()
+        .into_iter()
+        .map(|e| e.field)
+        .collect();
+
+    assert_eq!(fields, vec!["processing.bufsize", "processing.maxrate"]);
+}
+
+#[test]
+fn playlist_item_errors() {
+    let json = r#"{
+        "channel": "Test 1",
+        "date": "2024-02-01",
+        "program": [
+            {"in": 0, "out": 30.0, "duration": 30.0, "source": "./assets/with_audio.mp4"},
+            {"in": 0, "out": 10.0, "duration": 10.0, "source": ""},
+            {"in": 20.0, "out": 10.0, "duration": 0, "source": "./assets/not_exists.mp4"},
+            {"in": -1.0, "out": 5.0, "duration": 5.0, "source": "https://example.org/live.m3u8"}
+        ]
+    }"#;
+
+    let errors: Vec<String> = parse_playlist(json, true)
+        .unwrap_err()
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+
+    assert_eq!(
+        errors,
+        vec![
+            "program[1].source: source is missing",
+            "program[2].source: file ./assets/not_exists.mp4 not exists",
+            "program[2].duration: duration must be positive",
+            "program[2].out: out-point 10 is before in-point 20",
+            "program[3].in: in-point must not be negative",
+        ]
+    );
+
+    // without file check the missing file is fine
+    assert_eq!(parse_playlist(json, false).unwrap_err().len(), 4);
+
+    let broken = "{\n    \"date\": \"2024-02-01\",\n    \"program\": [{\"in\": 0}]\n}";
+    let errors = parse_playlist(broken, false).unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].index, None);
+    assert_eq!(errors[0].field, "json");
+    assert!(errors[0].message.contains("line 3"));
+
+    let valid = r#"{
+        "date": "2024-02-01",
+        "program": [{"in": 0, "out": 30.0, "duration": 30.0, "source": "./assets/with_audio.mp4"}]
+    }"#;
+
+    assert!(parse_playlist(valid, true).is_ok());
+}
+
+#[test]
+fn hls_gop_size() {
+    let config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    let cmd = config.out.output_cmd.unwrap();
+
+    assert_eq!(hls_time(&cmd), Some(6.0));
+    assert_eq!(gop_size(25.0, 6.0), 150);
+
+    // the keyint of the x264 parameters stays
+    assert_eq!(keyframe_interval(&cmd), Some(50));
+    assert!(!cmd.contains(&"-g".to_string()));
+
+    let yml = std::fs::read_to_string("../assets/ffplayout.yml")
+        .unwrap()
+        .replace("-x264-params keyint=50:min-keyint=25:scenecut=-1", "");
+    let path = std::env::temp_dir().join(format!("ffplayout_gop_{}.yml", std::process::id()));
+    std::fs::write(&path, yml).unwrap();
+
+    let config = PlayoutConfig::new(Some(path.to_string_lossy().to_string()));
+    let cmd = config.out.output_cmd.unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(keyframe_interval(&cmd), Some(150));
+    assert_eq!(
+        cmd[..6],
+        ["-g", "150", "-keyint_min", "150", "-sc_threshold", "0"]
+    );
+}
+
+#[test]
+fn tee_output_target() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.tee = vec![
+        TeeOutput {
+            format: "flv".into(),
+            ignore_failure: true,
+            target: "rtmp://127.0.0.1/live/stream".into(),
+            ..Default::default()
+        },
+        TeeOutput {
+            format: "hls".into(),
+            options: "hls_time=6:hls_list_size=600".into(),
+            target: "/var/www/live/stream.m3u8".into(),
+            ..Default::default()
+        },
+        TeeOutput {
+            format: "mp4".into(),
+            options: "movflags=+faststart".into(),
+            ignore_failure: true,
+            target: "/var/lib/ffplayout/record.mp4".into(),
+        },
+    ];
+
+    assert_eq!(
+        tee_target(&config.out.tee),
+        r"[f=flv:onfail=ignore]rtmp\://127.0.0.1/live/stream|[f=hls:hls_time=6:hls_list_size=600]/var/www/live/stream.m3u8|[f=mp4:movflags=+faststart:onfail=ignore]/var/lib/ffplayout/record.mp4"
+    );
+    assert!(config.validate().is_ok());
+
+    let output = TeeOutput {
+        format: "mpegts".into(),
+        target: "srt://127.0.0.1:9000?streamid=a|b[1]".into(),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        tee_target(std::slice::from_ref(&output)),
+        r"[f=mpegts]srt\://127.0.0.1\:9000?streamid=a\|b\[1\]"
+    );
+
+    // flv and mp4 need global headers, the other flags stay
+    let cmd = vec_strings!["-c:v", "libx264", "-flags", "+cgop"];
+
+    assert_eq!(
+        global_header_cmd(cmd.clone(), &config.out.tee),
+        vec_strings!["-c:v", "libx264", "-flags", "+cgop+global_header"]
+    );
+    assert_eq!(global_header_cmd(cmd.clone(), &[output]), cmd);
+
+    config.out.tee[1].ignore_failure = true;
+
+    assert_eq!(
+        config.validate().unwrap_err()[0].field,
+        "out.tee.ignore_failure"
+    );
+}
+
+#[test]
+fn ts_packet_chunks() {
+    let mut packets = TsPackets::new();
+
+    assert!(packets.push(&[0x47; 100]).is_empty());
+    assert_eq!(packets.push(&[0x47; 300]).len(), 2 * TS_PACKET_SIZE);
+    assert_eq!(packets.push(&[0x47; 164]).len(), TS_PACKET_SIZE);
+
+    packets.push(&[0x47; 10]);
+    packets.reset();
+
+    assert_eq!(packets.push(&[0x47; 188]).len(), TS_PACKET_SIZE);
+}
+
+#[test]
+fn preview_only_in_stream_mode() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.preview = true;
+    config.out.mode = OutputMode::HLS;
+
+    assert_eq!(config.validate().unwrap_err()[0].field, "out.preview");
+
+    config.out.mode = OutputMode::Stream;
+
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn config_formats_round_trip() {
+    let path = std::path::Path::new("../assets/ffplayout.yml");
+    let config = PlayoutConfig::from_path(path).unwrap();
+    let expected = serde_json::to_value(&config).unwrap();
+    let dir = std::env::temp_dir();
+
+    let formats = [
+        ("yaml", serde_yaml::to_string(&config).unwrap()),
+        ("toml", toml::to_string(&config).unwrap()),
+        ("json", serde_json::to_string(&config).unwrap()),
+    ];
+
+    for (ext, data) in formats {
+        let file = dir.join(format!("ffplayout_round_trip.{ext}"));
+        std::fs::write(&file, data).unwrap();
+
+        let loaded = PlayoutConfig::from_path(&file).unwrap();
+
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), expected, "{ext}");
+    }
+}
+
+#[test]
+fn config_env_overrides() {
+    let config = PlayoutConfig::from_path(std::path::Path::new("../assets/ffplayout.yml")).unwrap();
+    let vars = vec![
+        ("FFPLAYOUT_PROCESSING_WIDTH".to_string(), "1280".to_string()),
+        (
+            "FFPLAYOUT_INGEST_SRT_ADDRESS".to_string(),
+            "127.0.0.1:40052".to_string(),
+        ),
+        (
+            "FFPLAYOUT_PROCESSING_LOUDNORM_INTEGRATED".to_string(),
+            "-23".to_string(),
+        ),
+        ("HOME".to_string(), "/root".to_string()),
+    ];
+
+    let config = config.apply_env(vars).unwrap();
+
+    assert_eq!(config.processing.width, 1280);
+    assert_eq!(config.ingest.srt_address, "127.0.0.1:40052");
+    assert_eq!(config.processing.loudnorm.integrated, -23.0);
+
+    let vars = vec![
+        ("FFPLAYOUT_PROCESSING_WID".to_string(), "640".to_string()),
+        (
+            "FFPLAYOUT_PROCESSING_WIDTH_X".to_string(),
+            "640".to_string(),
+        ),
+    ];
+    let config = config.apply_env(vars).unwrap();
+
+    assert_eq!(config.processing.width, 1280);
+
+    let vars = vec![("FFPLAYOUT_PROCESSING_WIDTH".to_string(), "wide".to_string())];
+
+    assert_eq!(
+        config.apply_env(vars).unwrap_err().field,
+        "FFPLAYOUT_PROCESSING_WIDTH"
+    );
+}
+
+#[test]
+fn bumper_schedule() {
+    let clip = |src: &str, length: f64| {
+        let mut media = Media::new(0, src, false);
+        media.out = length;
+        media.duration = length;
+        media
+    };
+
+    let intro = clip("intro.mp4", 10.0);
+    let program = vec![
+        clip("a.mp4", 300.0),
+        clip("b.mp4", 400.0),
+        clip("c.mp4", 300.0),
+    ];
+
+    // start at 00:50:00, top of hour is in 600 seconds
+    let list = splice_bumpers(program, 3000.0, 3600.0, Some(&intro), None);
+    let sources: Vec<&str> = list.iter().map(|m| m.source.as_str()).collect();
+    let begins: Vec<f64> = list.iter().map(|m| m.begin.unwrap()).collect();
+
+    assert_eq!(sources, vec!["a.mp4", "b.mp4", "intro.mp4", "c.mp4"]);
+    assert_eq!(begins, vec![3000.0, 3300.0, 3600.0, 3610.0]);
+    // overflowing clip gets trimmed at the hour
+    assert_eq!(list[1].out, 300.0);
+    assert_eq!(list[3].index, Some(3));
+}
+
+#[test]
+fn ffmpeg_line_levels() {
+    let lines = [
+        (
+            "[fatal] Invalid argument",
+            log::Level::Error,
+            "Invalid argument",
+        ),
+        (
+            "[h264 @ 0x55] [error] decode_slice_header error",
+            log::Level::Error,
+            "[h264 @ 0x55] decode_slice_header error",
+        ),
+        (
+            "[warning] Non-monotonous DTS",
+            log::Level::Warn,
+            "Non-monotonous DTS",
+        ),
+        (
+            "[info] Stream #0:0: Video: h264",
+            log::Level::Info,
+            "Stream #0:0: Video: h264",
+        ),
+        ("[verbose] Opening file", log::Level::Debug, "Opening file"),
+        ("untagged line", log::Level::Debug, "untagged line"),
+    ];
+
+    for (line, level, msg) in lines {
+        assert_eq!(ffmpeg_level(line), (level, msg.to_string()));
+    }
+}
+
+#[test]
+fn log_file_size_rotation() {
+    use std::io::Write;
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.logging.rotate.max_size_mb = 1;
+    config.logging.rotate.max_files = 2;
+
+    let dir = std::env::temp_dir().join("ffplayout_test_log_rotate");
+    std::fs::remove_dir_all(&dir).unwrap_or_default();
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("ffplayout.log");
+    let mut writer = log_file(&path.display().to_string(), &config.logging);
+    let line = format!("{}\n", "x".repeat(1023));
+
+    for _ in 0..1024 * 3 {
+        writer.write_all(line.as_bytes()).unwrap();
+    }
+
+    writer.flush().unwrap();
+
+    assert!(path.is_file());
+    assert!(dir.join("ffplayout.log.1").is_file());
+    assert!(dir.join("ffplayout.log.2").is_file());
+    // old files over the limit are pruned
+    assert!(!dir.join("ffplayout.log.3").exists());
+
+    // backups over the age limit are pruned too
+    config.logging.rotate.max_files = 5;
+    config.logging.rotate.max_age_days = 1;
+
+    let old = std::fs::File::options()
+        .append(true)
+        .open(dir.join("ffplayout.log.2"))
+        .unwrap();
+    old.set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(3 * 86400))
+        .unwrap();
+
+    writer = log_file(&path.display().to_string(), &config.logging);
+
+    for _ in 0..1024 * 2 {
+        writer.write_all(line.as_bytes()).unwrap();
+    }
+
+    writer.flush().unwrap();
+
+    assert!(dir.join("ffplayout.log.1").is_file());
+    assert!(dir.join("ffplayout.log.2").is_file());
+
+    for file in std::fs::read_dir(&dir).unwrap() {
+        let age = file.unwrap().metadata().unwrap().modified().unwrap();
+
+        assert!(age.elapsed().unwrap() < std::time::Duration::from_secs(86400));
+    }
+}
+
+#[derive(Clone, Default)]
+struct Capture(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn log_channel_tag() {
+    use log::Log;
+
+    let capture = Capture::default();
+    let logger = ChannelTag::new(simplelog::WriteLogger::new(
+        log::LevelFilter::Info,
+        simplelog::Config::default(),
+        capture.clone(),
+    ));
+
+    let record = |msg| {
+        logger.log(
+            &log::Record::builder()
+                .args(format_args!("{msg}"))
+                .level(log::Level::Info)
+                .target("ffplayout::input::ingest")
+                .build(),
+        )
+    };
+
+    record("untagged message");
+
+    // other threads inherit the tag from the spawning thread
+    set_log_channel("channel_a");
+    spawn_tagged(move || assert_eq!(log_channel(), Some("channel_a".to_string())))
+        .join()
+        .unwrap();
+
+    record("Start ingest server");
+
+    let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+
+    assert!(!lines[0].contains("[channel_a]"));
+    assert!(lines[1].contains("[channel_a]"));
+    assert!(lines[1].contains("Start ingest server"));
+
+    assert_eq!(
+        channel_log_path("/var/log/ffplayout/ffplayout.log", "Channel 1"),
+        "/var/log/ffplayout/channel_1.log"
+    );
+}
+
+#[test]
+fn hls_output_args() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    let cmd = config.out.output_cmd.clone().unwrap();
+    let pos = cmd
+        .iter()
+        .position(|c| c == "-hls_segment_filename")
+        .unwrap();
+
+    assert_eq!(
+        cmd[pos - 6..pos].to_vec(),
+        vec![
+            "-hls_time",
+            "6",
+            "-hls_list_size",
+            "600",
+            "-hls_flags",
+            "append_list+delete_segments+omit_endlist"
+        ]
+    );
+
+    config.out.hls_segment_seconds = Some(4.0);
+    config.out.hls_list_size = Some(10);
+    config.out.hls_flags = Some("delete_segments+append_list".into());
+
+    let cmd: Vec<String> = [
+        "-c:v",
+        "libx264",
+        "-f",
+        "hls",
+        "-hls_time",
+        "10",
+        "/live/stream.m3u8",
+    ]
+    .iter()
+    .map(|c| c.to_string())
+    .collect();
+
+    assert_eq!(
+        hls_output_cmd(cmd, &config.out),
+        vec![
+            "-c:v",
+            "libx264",
+            "-f",
+            "hls",
+            "-hls_time",
+            "4",
+            "-hls_list_size",
+            "10",
+            "-hls_flags",
+            "delete_segments+append_list",
+            "/live/stream.m3u8"
+        ]
+    );
+
+    config.out.hls_list_size = Some(0);
+
+    assert_eq!(
+        config.validate().unwrap_err()[0].field,
+        "out.hls_list_size".to_string()
+    );
+}
+
+#[test]
+fn output_extra_args() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.out.extra_args = vec_strings!["-muxdelay", "0", "-max_muxing_queue_size", "1024"];
+
+    assert!(denied_extra_args(&config.out).is_empty());
+    assert!(config.validate().is_ok());
+
+    let cmd = vec_strings![
+        "-c:v",
+        "libx264",
+        "-f",
+        "flv",
+        "rtmp://127.0.0.1/live/stream"
+    ];
+
+    assert_eq!(
+        extra_args_cmd(cmd, &config.out),
+        vec_strings![
+            "-c:v",
+            "libx264",
+            "-f",
+            "flv",
+            "-muxdelay",
+            "0",
+            "-max_muxing_queue_size",
+            "1024",
+            "rtmp://127.0.0.1/live/stream"
+        ]
+    );
+
+    config.out.extra_args = vec_strings!["-map", "0:v", "-f", "mpegts", "-muxdelay", "0"];
+
+    assert_eq!(denied_extra_args(&config.out), vec_strings!["-map", "-f"]);
+
+    let errors = config.validate().unwrap_err();
+
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].field, "out.extra_args");
+
+    config.out.extra_args = vec_strings![
+        "-vf",
+        "null",
+        "-af",
+        "anull",
+        "-map:0",
+        "0:v",
+        "-filter:a",
+        "anull"
+    ];
+
+    assert_eq!(
+        denied_extra_args(&config.out),
+        vec_strings!["-vf", "-af", "-map:0", "-filter:a"]
+    );
+
+    // desktop output has no format
+    config.out.mode = Desktop;
+    config.out.extra_args = vec_strings!["-f", "sdl2"];
+
+    assert!(denied_extra_args(&config.out).is_empty());
+}
+
+#[test]
+fn dash_output_args() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = OutputMode::Dash;
+    config.out.dash.segment_seconds = 2.0;
+    config.out.dash.adaptation_sets = "id=0,streams=v id=1,streams=a".into();
+
+    let cmd: Vec<String> = [
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-f",
+        "mp4",
+        "/var/www/live/stream.mpd",
+    ]
+    .iter()
+    .map(|c| c.to_string())
+    .collect();
+
+    assert_eq!(
+        dash_output_cmd(cmd, &config.out),
+        vec![
+            "-c:v",
+            "libx264",
+            "-c:a",
+            "aac",
+            "-f",
+            "dash",
+            "-seg_duration",
+            "2",
+            "-use_template",
+            "1",
+            "-adaptation_sets",
+            "id=0,streams=v id=1,streams=a",
+            "/var/www/live/stream.mpd"
+        ]
+    );
+
+    // manifest folder must exist
+    config.out.output_cmd = Some(vec!["/not/existing/folder/stream.mpd".to_string()]);
+
+    assert_eq!(
+        config.validate().unwrap_err()[0].field,
+        "out.output_param".to_string()
+    );
+
+    // the check creates no files in the folder
+    let dir = std::env::temp_dir().join(format!("ffplayout_dash_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    config.out.output_cmd = Some(vec![dir.join("stream.mpd").display().to_string()]);
+
+    assert!(config
+        .validate()
+        .err()
+        .unwrap_or_default()
+        .iter()
+        .all(|e| e.field != "out.output_param"));
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn archive_segment_output() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.archive.dir = "/var/lib/ffplayout/archive".into();
+    config.out.archive.segment_seconds = 1800;
+
+    let output = archive_output(&config.out.archive);
+
+    assert_eq!(
+        tee_target(&[output]),
+        "[f=segment:segment_time=1800:segment_format=matroska:strftime=1:reset_timestamps=1:onfail=ignore]/var/lib/ffplayout/archive/%Y-%m-%d_%H-%M-%S.mkv"
+    );
+
+    // without the archive section the defaults are used
+    let yml = std::fs::read_to_string("../assets/ffplayout.yml")
+        .unwrap()
+        .replace(
+            "    archive:\n        dir:\n        segment_seconds: 3600\n        max_age_days: 0\n",
+            "",
+        );
+    let path = std::env::temp_dir().join(format!("ffplayout_archive_{}.yml", std::process::id()));
+    std::fs::write(&path, &yml).unwrap();
+
+    let config = PlayoutConfig::new(Some(path.to_string_lossy().to_string()));
+
+    assert!(config.out.archive.dir.is_empty());
+    assert_eq!(config.out.archive.segment_seconds, 3600);
+
+    // a stream output with a muxer option at the end has no target, which can be moved
+    let yml = yml
+        .replace("    mode: hls\n", "    mode: stream\n")
+        .replace(
+            "        -hls_segment_filename /usr/share/ffplayout/public/live/stream-%d.ts\n        /usr/share/ffplayout/public/live/stream.m3u8\n",
+            "        -muxdelay 0\n",
+        )
+        .replace(
+            "    multicast:\n",
+            "    archive:\n        dir: /var/lib/ffplayout/archive\n    multicast:\n",
+        );
+    std::fs::write(&path, yml).unwrap();
+
+    let config = PlayoutConfig::new(Some(path.to_string_lossy().to_string()));
+    std::fs::remove_file(path).unwrap();
+    let cmd = config.out.output_cmd.unwrap();
+
+    assert_eq!(config.out.tee.len(), 1);
+    assert_eq!(
+        cmd[cmd.iter().position(|c| c == "-muxdelay").unwrap() + 1],
+        "0"
+    );
+}
+
+#[test]
+fn multicast_url_options() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.multicast.address = "239.0.0.1:1234".into();
+
+    assert_eq!(
+        config.out.multicast.url(true),
+        "udp://239.0.0.1:1234?ttl=16&pkt_size=1316"
+    );
+
+    let output = config.out.multicast.tee_output();
+
+    assert_eq!(
+        tee_target(&[output]),
+        r"[f=mpegts]udp\://239.0.0.1\:1234?ttl=16&pkt_size=1316"
+    );
+
+    config.out.multicast.protocol = MulticastProtocol::Rtp;
+    config.out.multicast.ttl = 4;
+    config.out.multicast.pkt_size = 0;
+
+    assert_eq!(config.out.multicast.url(true), "rtp://239.0.0.1:1234?ttl=4");
+    assert_eq!(config.out.multicast.tee_output().format, "rtp_mpegts");
+
+    // the fifo is only for udp inputs
+    config.out.multicast.fifo_size = 50000;
+
+    assert_eq!(config.out.multicast.url(false), "rtp://239.0.0.1:1234");
+
+    config.out.multicast.protocol = MulticastProtocol::Udp;
+    config.out.multicast.pkt_size = 1316;
+
+    assert_eq!(
+        config.out.multicast.url(false),
+        "udp://239.0.0.1:1234?pkt_size=1316&fifo_size=50000&overrun_nonfatal=1"
+    );
+
+    assert_eq!(config.out.multicast.check_address(), Ok(true));
+
+    config.out.multicast.address = "10.0.0.1:1234".into();
+
+    assert_eq!(config.out.multicast.check_address(), Ok(false));
+
+    config.out.mode = Stream;
+    config.out.multicast.address = "239.0.0.1".into();
+
+    assert!(config
+        .validate()
+        .unwrap_err()
+        .iter()
+        .any(|e| e.field == "out.multicast.address"));
+}
+
+#[test]
+fn ffmpeg_capability_parse() {
+    let buildconf = "  configuration:\n    --prefix=/usr\n    --enable-gpl\n    --enable-libx264\n    --enable-libzmq\n";
+    let filters = "Filters:\n  T.. = Timeline support\n  | = Source or sink filter\n ... anull             A->A       Pass the source unchanged to the output.\n T.C overlay           VV->V      Overlay a video source on top of the input.\n ... movie             |->N       Read audio or video from a movie source.\n";
+    let protocols =
+        "Supported file protocols:\nInput:\n  file\n  rtmp\n  tcp\nOutput:\n  file\n  srt\n";
+
+    let caps = FfmpegCapabilities::parse(buildconf, filters, protocols);
+
+    assert_eq!(caps.libs, vec!["libx264", "libzmq"]);
+    assert_eq!(caps.filters, vec!["anull", "overlay", "movie"]);
+    assert_eq!(caps.input_protocols, vec!["file", "rtmp", "tcp"]);
+    assert_eq!(caps.output_protocols, vec!["file", "srt"]);
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.processing.add_logo = true;
+    config.processing.audio_only = false;
+    config.processing.subtitle = String::new();
+    config.processing.ticker.enable = false;
+    config.processing.loudnorm.enable = false;
+    config.text.add_text = false;
+    config.ingest.enable = true;
+    config.ingest.mode = IngestMode::Rtmp;
+
+    assert!(missing_capabilities(&config, &caps).is_empty());
+
+    config.processing.subtitle = "./assets/subtitle.vtt".to_string();
+    config.processing.ticker.enable = true;
+    config.ingest.mode = IngestMode::Srt;
+
+    assert_eq!(
+        missing_capabilities(&config, &caps),
+        vec![
+            "filter 'subtitles' for subtitles",
+            "filter 'drawtext' for ticker",
+            "protocol 'srt' for srt ingest"
+        ]
+    );
+}
+
+#[test]
+fn ffmpeg_version_check() {
+    let release = "ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023 the FFmpeg developers\nbuilt with gcc 13 (Ubuntu 13.2.0-23ubuntu3)\nlibavutil      58. 29.100 / 58. 29.100\n";
+    let tagged = "ffmpeg version n4.4.2 Copyright (c) 2000-2021 the FFmpeg developers\n";
+    let old = "ffmpeg version 3.4.11-0ubuntu0.1 Copyright (c) 2000-2022 the FFmpeg developers\n";
+    let git = "ffmpeg version N-112345-gabcdef0123 Copyright (c) 2000-2024 the FFmpeg developers\n";
+
+    assert_eq!(parse_ffmpeg_version(release), Some((6, 1)));
+    assert_eq!(parse_ffmpeg_version(tagged), Some((4, 4)));
+    assert_eq!(parse_ffmpeg_version(old), Some((3, 4)));
+    assert_eq!(parse_ffmpeg_version(git), None);
+    assert_eq!(parse_ffmpeg_version(""), None);
+
+    assert!(check_ffmpeg_version(release).is_ok());
+    assert!(check_ffmpeg_version(tagged).is_ok());
+    assert!(check_ffmpeg_version(git).is_ok());
+    assert_eq!(
+        check_ffmpeg_version(old),
+        Err("ffmpeg version 3.4 is to old, at least 4.0 is needed!".to_string())
+    );
+
+    let config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+
+    assert_eq!(config.processing.ffmpeg_path, "ffmpeg");
+    assert_eq!(config.processing.ffprobe_path, "ffprobe");
+
+    // every channel thread probes with its own binary, also in the threads it spawns
+    let channel = |path: &'static str| {
+        std::thread::spawn(move || {
+            set_ffprobe_path(path);
+            spawn_tagged(ffprobe_path).join().unwrap()
+        })
+    };
+    let (one, two) = (channel("/opt/ffmpeg/ffprobe"), channel("ffprobe-7"));
+
+    assert_eq!(one.join().unwrap(), "/opt/ffmpeg/ffprobe");
+    assert_eq!(two.join().unwrap(), "ffprobe-7");
+    assert_eq!(std::thread::spawn(ffprobe_path).join().unwrap(), "ffprobe");
+}
+
+#[test]
+fn output_container_check() {
+    for (target, container) in [
+        ("rtmp://127.0.0.1/live/stream", Container::Flv),
+        ("srt://127.0.0.1:40051", Container::Mpegts),
+        ("udp://239.0.0.1:1234?pkt_size=1316", Container::Mpegts),
+        ("rtsp://127.0.0.1:8554/live", Container::Rtsp),
+        ("/var/www/live/stream.m3u8", Container::Hls),
+        ("/archive/record.mkv", Container::Mkv),
+    ] {
+        assert_eq!(Container::for_target(target), Some(&[container][..]));
+    }
+
+    assert_eq!(Container::for_target("pipe:1"), None);
+    assert_eq!(Container::Mkv.muxer(), "matroska");
+
+    assert_eq!(
+        container_output_cmd(
+            vec_strings![
+                "-c:v",
+                "libx264",
+                "-f",
+                "mpegts",
+                "rtmp://127.0.0.1/live/stream"
+            ],
+            Container::Flv
+        ),
+        vec_strings![
+            "-c:v",
+            "libx264",
+            "-f",
+            "flv",
+            "rtmp://127.0.0.1/live/stream"
+        ]
+    );
+    assert_eq!(
+        container_output_cmd(
+            vec_strings!["-c:v", "libx264", "/archive/record.mkv"],
+            Container::Mkv
+        ),
+        vec_strings!["-c:v", "libx264", "-f", "matroska", "/archive/record.mkv"]
+    );
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = OutputMode::Stream;
+    config.out.tee = vec![];
+    config.out.container = Some(Container::Flv);
+    config.out.output_cmd = Some(vec_strings![
+        "-c:v",
+        "libx264",
+        "-f",
+        "flv",
+        "rtmp://127.0.0.1/live/stream"
+    ]);
+
+    assert!(config.validate().is_ok());
+
+    config.out.container = Some(Container::Mpegts);
+
+    let errors = config.validate().unwrap_err();
+    assert_eq!(errors[0].field, "out.container");
+    assert_eq!(
+        errors[0].message,
+        "container mpegts doesn't work with the output target, use: flv"
+    );
+
+    config.out.mode = OutputMode::HLS;
+    assert_eq!(
+        config.validate().unwrap_err()[0].message,
+        "hls mode needs the hls container"
+    );
+}
+
+#[test]
+fn keyframe_snap_points() {
+    let points = [0.0, 2.0, 4.0, 6.0, 8.0, 10.0];
+
+    // nearest keyframe before and after the cut
+    assert_eq!(snap_to_keyframe(&points, 2.9, 7.2, 12.0), (2.0, 8.0));
+    assert_eq!(snap_to_keyframe(&points, 3.1, 6.9, 12.0), (4.0, 6.0));
+
+    // clip begin and end stay
+    assert_eq!(snap_to_keyframe(&points, 0.0, 12.0, 12.0), (0.0, 12.0));
+
+    // out-point must stay after the in-point
+    assert_eq!(snap_to_keyframe(&points, 3.9, 4.2, 12.0), (4.0, 6.0));
+    assert_eq!(snap_to_keyframe(&points, 9.5, 10.2, 12.0), (10.0, 10.2));
+
+    // without keyframes nothing changes
+    assert_eq!(snap_to_keyframe(&[], 2.9, 7.2, 12.0), (2.9, 7.2));
+
+    let frames = keyframes("./assets/with_audio.mp4");
+
+    assert!(!frames.is_empty());
+    assert!(frames.windows(2).all(|w| w[0] <= w[1]));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn keyframe_scan_cache() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("ffplayout_keyframes_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let clip = dir.join("clip.mp4");
+    let runs = dir.join("runs");
+    let probe = dir.join("ffprobe");
+    std::fs::write(&clip, "clip").unwrap();
+    // fake ffprobe, which counts its runs
+    std::fs::write(
+        &probe,
+        format!(
+            "#!/bin/sh\necho run >> {}\nprintf '2.000000,__\\n0.000000,K_\\n4.000000,K_\\n'\n",
+            runs.display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&probe, std::fs::Permissions::from_mode(0o755)).unwrap();
+    // the ffprobe path is per thread, so only this test uses it
+    set_ffprobe_path(&probe.display().to_string());
+
+    let source = clip.display().to_string();
+    let scans = || {
+        std::fs::read_to_string(&runs)
+            .unwrap_or_default()
+            .lines()
+            .count()
+    };
+
+    assert_eq!(*keyframes(&source), vec![0.0, 4.0]);
+    assert_eq!(*keyframes(&source), vec![0.0, 4.0]);
+    assert_eq!(scans(), 1);
+
+    // a changed file gets scanned again
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    std::fs::write(&clip, "new clip").unwrap();
+    keyframes(&source);
+
+    assert_eq!(scans(), 2);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn process_nice_level() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    // nice without arguments prints the level of its process
+    config.processing.ffmpeg_path = "nice".to_string();
+
+    let level = |config: &PlayoutConfig| {
+        let output = ffmpeg_command(config).output().unwrap();
+
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse::<i32>()
+            .unwrap()
+    };
+
+    config.processing.nice = 19;
+    assert_eq!(level(&config), 19);
+
+    // a missing cgroup doesn't stop the process
+    config.processing.cgroup = "/not/existing/cgroup".to_string();
+    assert_eq!(
+        cgroup_procs(&config),
+        Some(std::path::PathBuf::from(
+            "/not/existing/cgroup/cgroup.procs"
+        ))
+    );
+    assert_eq!(level(&config), 19);
+
+    config.processing.nice = 25;
+    assert!(config
+        .validate()
+        .unwrap_err()
+        .iter()
+        .any(|e| e.field == "processing.nice"));
+}
+
+#[test]
+fn output_target_names() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = OutputMode::Stream;
+    config.out.tee = vec![];
+    config.out.rtmp_flashver = "FMLE/3.0".to_string();
+    config.out.tcurl = "rtmp://live.example.com/app".to_string();
+    config.out.service_name = "Channel 1".to_string();
+    config.out.service_provider = "ffplayout".to_string();
+
+    // rtmp target gets only the connection flags
+    let rtmp_cmd = target_output_cmd(
+        vec_strings![
+            "-c:v",
+            "libx264",
+            "-f",
+            "flv",
+            "rtmp://127.0.0.1/live/stream"
+        ],
+        &config.out,
+    );
+
+    assert_eq!(
+        rtmp_cmd,
+        vec_strings![
+            "-c:v",
+            "libx264",
+            "-f",
+            "flv",
+            "-rtmp_flashver",
+            "FMLE/3.0",
+            "-rtmp_tcurl",
+            "rtmp://live.example.com/app",
+            "rtmp://127.0.0.1/live/stream"
+        ]
+    );
+
+    // mpegts output gets only the service names
+    let ts_cmd = target_output_cmd(
+        vec_strings!["-c:v", "libx264", "-f", "mpegts", "srt://127.0.0.1:40051"],
+        &config.out,
+    );
+
+    assert_eq!(
+        ts_cmd,
+        vec_strings![
+            "-c:v",
+            "libx264",
+            "-f",
+            "mpegts",
+            "-metadata",
+            "service_name=Channel 1",
+            "-metadata",
+            "service_provider=ffplayout",
+            "srt://127.0.0.1:40051"
+        ]
+    );
+    assert_eq!(
+        target_args(
+            &config.out,
+            &vec_strings!["-c:v", "libx264", "udp://239.0.0.1:1234"]
+        ),
+        vec_strings![
+            "-metadata",
+            "service_name=Channel 1",
+            "-metadata",
+            "service_provider=ffplayout"
+        ]
+    );
+
+    // every setting must fit to the output
+    config.out.output_cmd = Some(rtmp_cmd);
+
+    let fields = config
+        .validate()
+        .unwrap_err()
+        .into_iter()
+        .map(|e| e.field)
+        .collect::<Vec<_>>();
+
+    assert!(fields.contains(&"out.service_name".to_string()));
+    assert!(fields.contains(&"out.service_provider".to_string()));
+    assert!(!fields.contains(&"out.rtmp_flashver".to_string()));
+    assert!(!fields.contains(&"out.tcurl".to_string()));
+
+    config.out.output_cmd = Some(ts_cmd);
+
+    let fields = config
+        .validate()
+        .unwrap_err()
+        .into_iter()
+        .map(|e| e.field)
+        .collect::<Vec<_>>();
+
+    assert!(fields.contains(&"out.rtmp_flashver".to_string()));
+    assert!(fields.contains(&"out.tcurl".to_string()));
+    assert!(!fields.contains(&"out.service_name".to_string()));
+}
+
+#[test]
+fn logo_enable_window() {
+    // the default keeps the logo always on
+    assert_eq!(LogoEnable::default().expr(), "");
+
+    let window = LogoEnable {
+        start: 5.0,
+        end: 60.0,
+        period: 0.0,
+    };
+    assert_eq!(window.expr(), "between(t,5,60)");
+
+    let recurring = LogoEnable {
+        start: 0.0,
+        end: 600.0,
+        period: 1800.0,
+    };
+    assert_eq!(recurring.expr(), "between(mod(t,1800),0,600)");
+    assert!(recurring.check().is_ok());
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.processing.logo_enable = LogoEnable {
+        start: 0.0,
+        end: 2000.0,
+        period: 1800.0,
+    };
+
+    assert!(config
+        .validate()
+        .unwrap_err()
+        .iter()
+        .any(|e| e.field == "processing.logo_enable"));
+}